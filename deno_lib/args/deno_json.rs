@@ -1,12 +1,20 @@
 // Copyright 2018-2024 the Deno authors. All rights reserved. MIT license.
 
+use std::collections::BTreeMap;
 use std::collections::HashSet;
 
+use deno_config::deno_json::ConfigFile;
 use deno_config::deno_json::TsConfigForEmit;
+use deno_core::anyhow::Context;
+use deno_core::error::AnyError;
 use deno_core::serde_json;
 use deno_semver::jsr::JsrDepPackageReq;
 use deno_semver::jsr::JsrPackageReqReference;
 use deno_semver::npm::NpmPackageReqReference;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+
+use super::ClientCertConfig;
 
 #[cfg(test)] // happens to only be used by the tests at the moment
 pub struct DenoConfigFsAdapter<'a>(
@@ -64,6 +72,111 @@ impl<'a> deno_config::fs::DenoConfigFs for DenoConfigFsAdapter<'a> {
   }
 }
 
+/// The `compile` section of a deno.json, which deno_config doesn't know
+/// about. Paths are relative to the config file.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct CompileConfig {
+  pub include: Vec<String>,
+  pub exclude: Vec<String>,
+}
+
+pub fn read_compile_config(
+  config_file: &ConfigFile,
+) -> Result<Option<CompileConfig>, AnyError> {
+  read_config_section(config_file, "compile")
+}
+
+/// An entry of the `clientCerts` section of a deno.json, which maps hosts
+/// to the client certificates to present to them. Paths are relative to
+/// the config file.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq)]
+#[serde(deny_unknown_fields)]
+struct ClientCertEntry {
+  cert: String,
+  #[serde(default)]
+  key: Option<String>,
+}
+
+pub fn read_client_certs_config(
+  config_file: &ConfigFile,
+) -> Result<Vec<ClientCertConfig>, AnyError> {
+  let Some(entries) = read_config_section::<BTreeMap<String, ClientCertEntry>>(
+    config_file,
+    "clientCerts",
+  )?
+  else {
+    return Ok(Vec::new());
+  };
+  let dir_path = config_file.dir_path();
+  Ok(
+    entries
+      .into_iter()
+      .map(|(host, entry)| ClientCertConfig {
+        host,
+        cert_file: dir_path.join(entry.cert),
+        key_file: entry.key.map(|key| dir_path.join(key)),
+      })
+      .collect(),
+  )
+}
+
+/// The `download` section of a deno.json, which configures how modules and
+/// npm packages are downloaded.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq)]
+#[serde(default, deny_unknown_fields, rename_all = "camelCase")]
+pub struct DownloadConfig {
+  pub max_attempts: Option<u32>,
+  pub initial_backoff_ms: Option<u64>,
+  pub max_backoff_ms: Option<u64>,
+  pub max_concurrent_requests_per_host: Option<usize>,
+  pub timeout_ms: Option<u64>,
+}
+
+pub fn read_download_config(
+  config_file: &ConfigFile,
+) -> Result<Option<DownloadConfig>, AnyError> {
+  read_config_section(config_file, "download")
+}
+
+/// Reads the `mirrors` section of a deno.json, which maps the URL prefixes of
+/// remote modules to the prefixes of the mirrors they are downloaded from.
+pub fn read_mirrors_config(
+  config_file: &ConfigFile,
+) -> Result<BTreeMap<String, String>, AnyError> {
+  Ok(read_config_section(config_file, "mirrors")?.unwrap_or_default())
+}
+
+/// Reads a top level section of a deno.json that deno_config doesn't know
+/// about.
+fn read_config_section<T: DeserializeOwned>(
+  config_file: &ConfigFile,
+  key: &str,
+) -> Result<Option<T>, AnyError> {
+  let Ok(path) = config_file.specifier.to_file_path() else {
+    return Ok(None);
+  };
+  let text = std::fs::read_to_string(&path)
+    .with_context(|| format!("Failed reading '{}'", path.display()))?;
+  parse_config_section(&text, key).with_context(|| {
+    format!("Invalid \"{}\" configuration in '{}'", key, path.display())
+  })
+}
+
+fn parse_config_section<T: DeserializeOwned>(
+  text: &str,
+  key: &str,
+) -> Result<Option<T>, AnyError> {
+  let value = jsonc_parser::parse_to_serde_value(text, &Default::default())?;
+  let Some(serde_json::Value::Object(mut value)) = value else {
+    return Ok(None);
+  };
+  match value.remove(key) {
+    Some(section) => Ok(Some(serde_json::from_value(section)?)),
+    None => Ok(None),
+  }
+}
+
 pub fn deno_json_deps(
   config: &deno_config::deno_json::ConfigFile,
 ) -> HashSet<JsrDepPackageReq> {
@@ -121,3 +234,118 @@ pub fn check_warn_tsconfig(ts_config: &TsConfigForEmit) {
     );
   }
 }
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  fn parse_compile_config(
+    text: &str,
+  ) -> Result<Option<CompileConfig>, AnyError> {
+    parse_config_section(text, "compile")
+  }
+
+  #[test]
+  fn parses_compile_config() {
+    assert_eq!(parse_compile_config("{}").unwrap(), None);
+    assert_eq!(
+      parse_compile_config(
+        r#"{
+          // comments are allowed
+          "compile": {
+            "include": ["assets/"],
+            "exclude": ["**/*.map"]
+          }
+        }"#
+      )
+      .unwrap(),
+      Some(CompileConfig {
+        include: vec!["assets/".to_string()],
+        exclude: vec!["**/*.map".to_string()],
+      })
+    );
+    assert!(
+      parse_compile_config(r#"{ "compile": { "includes": [] } }"#).is_err()
+    );
+  }
+
+  #[test]
+  fn parses_client_certs_config() {
+    let entries = parse_config_section::<BTreeMap<String, ClientCertEntry>>(
+      r#"{
+        "clientCerts": {
+          "registry.internal:8443": { "cert": "certs/client.pem" },
+          "npm.internal": { "cert": "client.crt", "key": "client.key" }
+        }
+      }"#,
+      "clientCerts",
+    )
+    .unwrap()
+    .unwrap();
+    assert_eq!(
+      entries.into_iter().collect::<Vec<_>>(),
+      vec![
+        (
+          "npm.internal".to_string(),
+          ClientCertEntry {
+            cert: "client.crt".to_string(),
+            key: Some("client.key".to_string()),
+          }
+        ),
+        (
+          "registry.internal:8443".to_string(),
+          ClientCertEntry {
+            cert: "certs/client.pem".to_string(),
+            key: None,
+          }
+        ),
+      ]
+    );
+    assert!(parse_config_section::<BTreeMap<String, ClientCertEntry>>(
+      r#"{ "clientCerts": { "npm.internal": { "certificate": "a.pem" } } }"#,
+      "clientCerts",
+    )
+    .is_err());
+  }
+
+  #[test]
+  fn parses_download_config() {
+    assert_eq!(
+      parse_config_section::<DownloadConfig>(
+        r#"{ "download": { "maxAttempts": 6, "timeoutMs": 30000 } }"#,
+        "download",
+      )
+      .unwrap(),
+      Some(DownloadConfig {
+        max_attempts: Some(6),
+        timeout_ms: Some(30000),
+        ..Default::default()
+      })
+    );
+    assert!(parse_config_section::<DownloadConfig>(
+      r#"{ "download": { "retries": 6 } }"#,
+      "download",
+    )
+    .is_err());
+  }
+
+  #[test]
+  fn parses_mirrors_config() {
+    assert_eq!(
+      parse_config_section::<BTreeMap<String, String>>(
+        r#"{ "mirrors": { "https://deno.land/": "https://mirror.internal/deno/" } }"#,
+        "mirrors",
+      )
+      .unwrap(),
+      Some(BTreeMap::from([(
+        "https://deno.land/".to_string(),
+        "https://mirror.internal/deno/".to_string()
+      )]))
+    );
+    assert!(parse_config_section::<BTreeMap<String, String>>(
+      r#"{ "mirrors": ["https://mirror.internal/"] }"#,
+      "mirrors",
+    )
+    .is_err());
+  }
+}
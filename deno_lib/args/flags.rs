@@ -107,6 +107,51 @@ pub struct BenchFlags {
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct CacheFlags {
   pub files: Vec<String>,
+  /// Write the cache entries the files need to this archive.
+  pub export: Option<String>,
+  /// Merge this archive into the cache instead of caching files.
+  pub import: Option<String>,
+  /// Serve the cache to other machines on this address instead of caching
+  /// files.
+  pub serve: Option<String>,
+}
+
+/// A part of the cache that `deno clean` can remove on its own.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, PartialOrd, Ord)]
+pub enum CleanCategory {
+  /// The npm packages.
+  Npm,
+  /// The HTTP cache of remote and jsr modules.
+  Remote,
+  /// The emitted JavaScript, the V8 code cache and the type checking cache.
+  Emit,
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct CleanFlags {
+  /// Only remove these parts of the cache, all of it when empty.
+  pub categories: Vec<CleanCategory>,
+  /// Only remove entries that weren't used in this many days.
+  pub older_than_days: Option<u64>,
+  /// Keep the npm packages and remote modules that the lockfiles in these
+  /// directories reference.
+  pub keep_locked_in: Vec<String>,
+  /// Only remove this npm or jsr package, for example `npm:chalk@5.3.0` or
+  /// `jsr:@std/path` for all of its versions.
+  pub package: Option<String>,
+  /// Report what would be removed without removing it.
+  pub dry_run: bool,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct VendorFlags {
+  pub files: Vec<String>,
+  /// Directory to write the modules to, `vendor` by default.
+  pub output: Option<String>,
+  /// Verify the directory instead of writing to it.
+  pub check: bool,
+  /// Replace the contents of a non-empty directory.
+  pub force: bool,
 }
 
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -116,6 +161,16 @@ pub struct CheckFlags {
   pub doc_only: bool,
 }
 
+/// Where a compiled program keeps the changes it makes to its embedded files.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum VfsOverlayMode {
+  /// A per-user directory, so changes survive restarts.
+  Persistent,
+  /// A temporary directory that is removed when the program exits. There
+  /// is no in-memory mode, since the overlay needs real files to hand out.
+  TempDir,
+}
+
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct CompileFlags {
   pub source_file: String,
@@ -125,6 +180,63 @@ pub struct CompileFlags {
   pub no_terminal: bool,
   pub icon: Option<String>,
   pub include: Vec<String>,
+  pub exclude: Vec<String>,
+  /// Additional entrypoints, dispatched to by the executable's name or its
+  /// first argument.
+  pub entrypoints: Vec<CompileEntrypoint>,
+  /// zstd level used to compress the embedded files, if any.
+  pub compress: Option<i32>,
+  /// Embed the V8 code cache of the compiled modules.
+  pub code_cache: bool,
+  /// Embed the code cache recorded by a run of the executable instead of
+  /// creating it.
+  pub code_cache_from: Option<String>,
+  /// Let the program modify its embedded files through an overlay.
+  pub vfs_overlay: Option<VfsOverlayMode>,
+  /// Path to the ed25519 private key the embedded data is signed with.
+  pub sign_key: Option<String>,
+  /// Produce the same bytes for the same sources on any machine.
+  pub reproducible: bool,
+  /// sha256 the base binary must have.
+  pub base_binary_sha256: Option<String>,
+  /// Rebuild and compare against this executable instead of writing one.
+  pub verify_build: Option<String>,
+  /// Base binaries to use instead of downloading one.
+  pub base_binaries: Vec<CompileBaseBinary>,
+  /// Directory or zip archive laid out like the download server, to find
+  /// base binaries in instead of downloading them.
+  pub base_binary_dir: Option<String>,
+  /// JSON file with the sha256 of the base binary per version and target.
+  pub base_binary_manifest: Option<String>,
+  /// Audit the executable at `source_file` instead of creating one.
+  pub inspect: Option<CompileInspectFlags>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CompileEntrypoint {
+  pub name: String,
+  pub source_file: String,
+  /// Permissions used instead of the ones of the main entrypoint.
+  pub permissions: Option<PermissionFlags>,
+  pub args: Vec<String>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CompileBaseBinary {
+  /// The target this base binary is for, or any target if `None`.
+  pub target: Option<String>,
+  /// Path to a denort executable or the zip archive it's released in.
+  pub path: String,
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct CompileInspectFlags {
+  pub verify: bool,
+  /// Directory to extract the embedded files to.
+  pub extract: Option<String>,
+  /// The only file or directory to extract.
+  pub entry: Option<String>,
+  pub json: bool,
 }
 
 impl CompileFlags {
@@ -229,6 +341,8 @@ pub struct InitFlags {
 pub struct InfoFlags {
   pub json: bool,
   pub file: Option<String>,
+  /// Report how the cache directory is used instead.
+  pub cache_usage: bool,
 }
 
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -441,7 +555,7 @@ pub enum DenoSubcommand {
   Bundle,
   Cache(CacheFlags),
   Check(CheckFlags),
-  Clean,
+  Clean(CleanFlags),
   Compile(CompileFlags),
   Completions(CompletionsFlags),
   Coverage(CoverageFlags),
@@ -463,7 +577,7 @@ pub enum DenoSubcommand {
   Test(TestFlags),
   Types,
   Upgrade(UpgradeFlags),
-  Vendor,
+  Vendor(VendorFlags),
   Publish(PublishFlags),
   Help(HelpFlags),
 }
@@ -542,6 +656,17 @@ pub enum CaData {
   Bytes(Vec<u8>),
 }
 
+/// A client certificate to present to a host that asks for one (mutual TLS).
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ClientCertConfig {
+  /// The host, optionally with a port, matched like in `DENO_AUTH_TOKENS`.
+  pub host: String,
+  /// A PEM encoded certificate chain.
+  pub cert_file: PathBuf,
+  /// A PEM encoded private key. Read from `cert_file` when not set.
+  pub key_file: Option<PathBuf>,
+}
+
 // Info needed to run NPM lifecycle scripts
 #[derive(Clone, Debug, Eq, PartialEq, Default)]
 pub struct LifecycleScriptsConfig {
@@ -561,6 +686,137 @@ pub enum PackagesAllowedScripts {
   None,
 }
 
+/// Parses `<NAME>=<PATH>`. The path is taken as is, so it may contain
+/// spaces.
+fn parse_compile_entrypoint(s: &str) -> Result<CompileEntrypoint, AnyError> {
+  let Some((name, source_file)) = s.split_once('=') else {
+    bail!("Invalid entrypoint '{}'. Expected <NAME>=<PATH>", s);
+  };
+  if name.is_empty()
+    || source_file.is_empty()
+    || name.starts_with('-')
+    || name.contains(['/', '\\'])
+  {
+    bail!("Invalid entrypoint '{}'. Expected <NAME>=<PATH>", s);
+  }
+  Ok(CompileEntrypoint {
+    name: name.to_string(),
+    source_file: source_file.to_string(),
+    permissions: None,
+    args: vec![],
+  })
+}
+
+/// Parses `<NAME>=<ARG>`, with the argument taken as is.
+fn parse_compile_entrypoint_arg(s: &str) -> Result<(String, String), AnyError> {
+  match s.split_once('=') {
+    Some((name, arg)) if !name.is_empty() => {
+      Ok((name.to_string(), arg.to_string()))
+    }
+    _ => bail!("Invalid entrypoint argument '{}'. Expected <NAME>=<ARG>", s),
+  }
+}
+
+/// Applies the `--entrypoint-arg` values of each entrypoint, which are
+/// permission flags followed by the default arguments after a `--`.
+fn compile_entrypoint_args_parse(
+  entrypoints: &mut [CompileEntrypoint],
+  entrypoint_args: Vec<(String, String)>,
+) -> clap::error::Result<()> {
+  for (name, _) in &entrypoint_args {
+    if !entrypoints
+      .iter()
+      .any(|entrypoint| entrypoint.name == *name)
+    {
+      return Err(clap::Error::raw(
+        clap::error::ErrorKind::InvalidValue,
+        format!("--entrypoint-arg refers to unknown entrypoint '{}'\n", name),
+      ));
+    }
+  }
+  for entrypoint in entrypoints {
+    let words = entrypoint_args
+      .iter()
+      .filter(|(name, _)| *name == entrypoint.name)
+      .map(|(_, arg)| arg.as_str())
+      .collect::<Vec<_>>();
+    if words.is_empty() {
+      continue;
+    }
+    let app = permission_args(Command::new("entrypoint"), None)
+      .no_binary_name(true)
+      .arg(Arg::new("args").num_args(0..).trailing_var_arg(true));
+    let mut matches = app.try_get_matches_from(words)?;
+    let mut flags = Flags::default();
+    permission_args_parse(&mut flags, &mut matches)?;
+    if flags.permissions != PermissionFlags::default() {
+      entrypoint.permissions = Some(flags.permissions);
+    }
+    entrypoint.args = matches
+      .remove_many::<String>("args")
+      .map(|args| args.collect())
+      .unwrap_or_default();
+  }
+  Ok(())
+}
+
+/// The targets `deno compile` can compile for.
+const COMPILE_TARGETS: [&str; 5] = [
+  "x86_64-unknown-linux-gnu",
+  "aarch64-unknown-linux-gnu",
+  "x86_64-pc-windows-msvc",
+  "x86_64-apple-darwin",
+  "aarch64-apple-darwin",
+];
+
+fn parse_compile_base_binary(s: &str) -> Result<CompileBaseBinary, AnyError> {
+  let (target, path) = match s.split_once('=') {
+    Some((target, path)) if COMPILE_TARGETS.contains(&target) => {
+      (Some(target.to_string()), path)
+    }
+    _ => (None, s),
+  };
+  if path.is_empty() {
+    bail!("Invalid base binary '{}'. Expected [<TARGET>=]<PATH>", s);
+  }
+  Ok(CompileBaseBinary {
+    target,
+    path: path.to_string(),
+  })
+}
+
+/// Parses `<HOST>=<CERT_FILE>[,<KEY_FILE>]`.
+pub fn parse_client_cert(s: &str) -> Result<ClientCertConfig, AnyError> {
+  let Some((host, files)) = s.split_once('=') else {
+    bail!(
+      "Invalid client certificate '{}'. Expected <HOST>=<CERT_FILE>[,<KEY_FILE>]",
+      s
+    );
+  };
+  let (cert_file, key_file) = match files.split_once(',') {
+    Some((cert_file, key_file)) => (cert_file, Some(key_file)),
+    None => (files, None),
+  };
+  if host.is_empty() || cert_file.is_empty() || key_file == Some("") {
+    bail!(
+      "Invalid client certificate '{}'. Expected <HOST>=<CERT_FILE>[,<KEY_FILE>]",
+      s
+    );
+  }
+  Ok(ClientCertConfig {
+    host: host.to_string(),
+    cert_file: PathBuf::from(cert_file),
+    key_file: key_file.map(PathBuf::from),
+  })
+}
+
+fn parse_sha256_hex(s: &str) -> Result<String, AnyError> {
+  if s.len() != 64 || !s.chars().all(|c| c.is_ascii_hexdigit()) {
+    bail!("Invalid sha256 hash '{}'. Expected 64 hex characters", s);
+  }
+  Ok(s.to_ascii_lowercase())
+}
+
 fn parse_packages_allowed_scripts(s: &str) -> Result<String, AnyError> {
   if !s.starts_with("npm:") {
     bail!("Invalid package for --allow-scripts: '{}'. An 'npm:' specifier is required", s);
@@ -600,6 +856,7 @@ pub struct Flags {
   pub frozen_lockfile: Option<bool>,
   pub ca_stores: Option<Vec<String>>,
   pub ca_data: Option<CaData>,
+  pub client_certs: Vec<ClientCertConfig>,
   pub cache_blocklist: Vec<String>,
   pub cached_only: bool,
   pub type_check_mode: TypeCheckMode,
@@ -624,6 +881,7 @@ pub struct Flags {
   pub no_lock: bool,
   pub no_npm: bool,
   pub reload: bool,
+  pub revalidate: bool,
   pub seed: Option<u64>,
   pub strace_ops: Option<Vec<String>>,
   pub unstable_config: UnstableConfig,
@@ -1141,8 +1399,19 @@ static ENV_VARIABLES_HELP: &str = cstr!(
   <g>DENO_AUTH_TOKENS</>      A semi-colon separated list of bearer tokens and hostnames
                         to use when fetching remote modules from private repositories
                          <p(245)>(e.g. "abcde12345@deno.land;54321edcba@github.com")</>
+  <g>DENO_CACHE_SERVER</>     The URL of a "deno cache --serve" server to download the remote
+                        modules and npm packages the lockfile pins from before their origin
+                         <p(245)>(e.g. "http://cache.internal:4507")</>
+  <g>DENO_CACHE_SERVER_TOKEN</>
+                        The token a cache server requires from its clients
   <g>DENO_CERT</>             Load certificate authorities from PEM encoded file
+  <g>DENO_CLIENT_CERTS</>     A semi-colon separated list of client certificates to present
+                        to hosts for mutual TLS
+                         <p(245)>(e.g. "registry.internal=client.pem,client.key")</>
   <g>DENO_DIR</>              Set the cache directory
+  <g>DENO_DOWNLOAD_*</>       Retry, backoff, per-host concurrency and timeout policy for
+                        downloads, overriding the "download" section of deno.json
+                         <p(245)>(e.g. DENO_DOWNLOAD_MAX_ATTEMPTS=6, DENO_DOWNLOAD_TIMEOUT_MS=60000)</>
   <g>DENO_INSTALL_ROOT</>     Set deno install's output directory
                          <p(245)>(defaults to $HOME/.deno/bin)</>
   <g>DENO_NO_PACKAGE_JSON</>  Disables auto-resolution of package.json
@@ -1182,6 +1451,8 @@ static DENO_HELP: &str = cstr!(
     <g>install</>      Installs dependencies either in the local project or globally to a bin directory
     <g>uninstall</>    Uninstalls a dependency or an executable script in the installation root's bin directory
     <g>remove</>       Remove dependencies from the configuration file
+    <g>vendor</>       Copy the remote modules of a program into a directory with an import map
+                  <p(245)>deno vendor main.ts  |  deno vendor --check main.ts</>
 
   <y>Tooling:</>
     <g>bench</>        Run benchmarks
@@ -1371,7 +1642,7 @@ pub fn flags_from_vec(args: Vec<OsString>) -> clap::error::Result<Flags> {
       "types" => types_parse(&mut flags, &mut m),
       "uninstall" => uninstall_parse(&mut flags, &mut m),
       "upgrade" => upgrade_parse(&mut flags, &mut m),
-      "vendor" => vendor_parse(&mut flags, &mut m),
+      "vendor" => vendor_parse(&mut flags, &mut m)?,
       "publish" => publish_parse(&mut flags, &mut m),
       _ => unreachable!(),
     }
@@ -1781,6 +2052,13 @@ Download and compile a module with all of its static dependencies and save them
 
 Future runs of this module will trigger no downloads or compilation unless --reload is specified
 
+Export the cache entries a module needs to an archive, and merge it into the cache of another machine:
+  <p(245)>deno cache --export=cache.zip main.ts</>
+  <p(245)>deno cache --import=cache.zip</>
+
+Serve the cache to other machines, which use it with <c>DENO_CACHE_SERVER=http://<<ADDRESS>></> and the same <c>DENO_CACHE_SERVER_TOKEN</>:
+  <p(245)>DENO_CACHE_SERVER_TOKEN=<<TOKEN>> deno cache --serve=10.0.0.5:4507</>
+
 <y>Read more:</> <c>https://docs.deno.com/go/cache</>"),
     UnstableArgsConfig::ResolutionOnly,
 )
@@ -1791,9 +2069,34 @@ Future runs of this module will trigger no downloads or compilation unless --rel
       .arg(
         Arg::new("file")
           .num_args(1..)
-          .required_unless_present("help")
+          .required_unless_present_any(["help", "import", "serve"])
+          .value_hint(ValueHint::FilePath),
+      )
+      .arg(
+        Arg::new("export")
+          .long("export")
+          .help("Write the cache entries the modules need to an archive")
+          .value_name("ARCHIVE")
+          .value_hint(ValueHint::FilePath),
+      )
+      .arg(
+        Arg::new("import")
+          .long("import")
+          .help("Merge an archive written with --export into the cache, checking it against the lockfile")
+          .value_name("ARCHIVE")
+          .conflicts_with_all(["export", "file"])
           .value_hint(ValueHint::FilePath),
       )
+      .arg(
+        Arg::new("serve")
+          .long("serve")
+          .help("Serve the cached remote modules, JSR metadata and npm packages over HTTP to machines that set DENO_CACHE_SERVER, downloading what isn't cached yet from the JSR and npm registries. Requires DENO_CACHE_SERVER_TOKEN unless bound to a loopback address")
+          .value_name("ADDRESS")
+          .num_args(0..=1)
+          .require_equals(true)
+          .default_missing_value("127.0.0.1:4507")
+          .conflicts_with_all(["export", "import", "file"]),
+      )
       .arg(frozen_lockfile_arg())
       .arg(allow_scripts_arg())
       .arg(allow_import_arg())
@@ -1803,9 +2106,66 @@ Future runs of this module will trigger no downloads or compilation unless --rel
 fn clean_subcommand() -> Command {
   command(
     "clean",
-    cstr!("Remove the cache directory (<c>$DENO_DIR</>)"),
+    cstr!("Remove the cache directory (<c>$DENO_DIR</>)
+
+Remove only some of it, for example the npm packages not used in 30 days that no lockfile in the projects directory references:
+  <p(245)>deno clean --npm --older-than=30 --keep-locked-in=projects</>
+
+See what would be removed with --dry-run.
+
+Remove a single package:
+  <p(245)>deno clean --package=npm:chalk@5.3.0</>"),
     UnstableArgsConfig::None,
   )
+  .defer(|cmd| {
+    cmd
+      .arg(
+        Arg::new("npm")
+          .long("npm")
+          .help("Remove the npm packages")
+          .action(ArgAction::SetTrue),
+      )
+      .arg(
+        Arg::new("remote")
+          .long("remote")
+          .help("Remove the remote and jsr modules")
+          .action(ArgAction::SetTrue),
+      )
+      .arg(
+        Arg::new("emit")
+          .long("emit")
+          .help("Remove the emitted JavaScript and the code and type checking caches")
+          .action(ArgAction::SetTrue),
+      )
+      .arg(
+        Arg::new("older-than")
+          .long("older-than")
+          .help("Only remove entries that weren't used in this many days")
+          .value_name("DAYS")
+          .value_parser(value_parser!(u64)),
+      )
+      .arg(
+        Arg::new("keep-locked-in")
+          .long("keep-locked-in")
+          .help("Keep the npm packages and remote modules that a deno.lock in these directories references")
+          .value_name("DIR")
+          .action(ArgAction::Append)
+          .value_delimiter(',')
+          .value_hint(ValueHint::DirPath),
+      )
+      .arg(
+        Arg::new("package")
+          .long("package")
+          .help("Only remove this package, for example npm:chalk@5.3.0 or jsr:@std/path for all of its versions")
+          .value_name("PACKAGE"),
+      )
+      .arg(
+        Arg::new("dry-run")
+          .long("dry-run")
+          .help("Report what would be removed without removing it")
+          .action(ArgAction::SetTrue),
+      )
+  })
 }
 
 fn check_subcommand() -> Command {
@@ -1890,13 +2250,49 @@ On the first invocation with deno will download the proper binary and cache it i
         Arg::new("include")
           .long("include")
           .help(
-            cstr!("Includes an additional module in the compiled executable's module graph.
+            cstr!("Includes an additional module, directory or glob of files in the compiled executable.
   <p(245)>Use this flag if a dynamically imported module or a web worker main module
-  fails to load in the executable. This flag can be passed multiple times,
-  to include multiple additional modules.</>",
+  fails to load in the executable. JavaScript and TypeScript files matched by a
+  directory or glob are added to the module graph, other files are embedded as is.
+  This flag can be passed multiple times. Also settable in the \"compile\" section of deno.json.</>",
           ))
           .action(ArgAction::Append)
-          .value_hint(ValueHint::FilePath)
+          .value_hint(ValueHint::AnyPath)
+          .help_heading(COMPILE_HEADING),
+      )
+      .arg(
+        Arg::new("exclude")
+          .long("exclude")
+          .value_name("PATTERN")
+          .help(
+            cstr!("Leaves the files matching a path or glob out of the compiled executable
+  <p(245)>Use npm:<<PACKAGE>>/<<SUBPATH>> to leave out files of an npm package (ex. npm:lodash/fp/**).
+  This flag can be passed multiple times. Also settable in the \"compile\" section of deno.json.</>",
+          ))
+          .action(ArgAction::Append)
+          .help_heading(COMPILE_HEADING),
+      )
+      .arg(
+        Arg::new("entrypoint")
+          .long("entrypoint")
+          .value_name("NAME=PATH")
+          .help(cstr!("Adds a named entrypoint to the executable, sharing its files and modules
+  <p(245)>It runs when the executable is invoked under that name (ex. through a symlink) or with the name as first argument.</>"))
+          .value_parser(parse_compile_entrypoint)
+          .action(ArgAction::Append)
+          .help_heading(COMPILE_HEADING),
+      )
+      .arg(
+        Arg::new("entrypoint-arg")
+          .long("entrypoint-arg")
+          .value_name("NAME=ARG")
+          .help(cstr!("Adds a permission flag or, after a --, a default argument to a named entrypoint
+  <p(245)>Each flag or argument is passed separately:
+  deno compile --entrypoint lint=./lint.ts --entrypoint-arg lint=--allow-read --entrypoint-arg lint=-- --entrypoint-arg lint=--fix main.ts</>"))
+          .value_parser(parse_compile_entrypoint_arg)
+          .requires("entrypoint")
+          .allow_hyphen_values(true)
+          .action(ArgAction::Append)
           .help_heading(COMPILE_HEADING),
       )
       .arg(
@@ -1912,13 +2308,7 @@ On the first invocation with deno will download the proper binary and cache it i
         Arg::new("target")
           .long("target")
           .help("Target OS architecture")
-          .value_parser([
-            "x86_64-unknown-linux-gnu",
-            "aarch64-unknown-linux-gnu",
-            "x86_64-pc-windows-msvc",
-            "x86_64-apple-darwin",
-            "aarch64-apple-darwin",
-          ])
+          .value_parser(COMPILE_TARGETS)
           .help_heading(COMPILE_HEADING),
       )
       .arg(
@@ -1935,6 +2325,157 @@ On the first invocation with deno will download the proper binary and cache it i
           .value_parser(value_parser!(String))
           .help_heading(COMPILE_HEADING),
       )
+      .arg(
+        Arg::new("compress")
+          .long("compress")
+          .num_args(0..=1)
+          .require_equals(true)
+          .value_name("LEVEL")
+          .value_parser(value_parser!(i32).range(1..=22))
+          .default_missing_value("3")
+          .help(cstr!("Compress the files embedded in the executable with zstd
+  <p(245)>Optionally takes a compression level from 1 to 22 (defaults to 3).</>"))
+          .help_heading(COMPILE_HEADING),
+      )
+      .arg(
+        Arg::new("code-cache")
+          .long("code-cache")
+          .help(cstr!("Embed the V8 code cache of the modules in the executable
+  <p(245)>Speeds up startup at the cost of a larger executable.</>"))
+          .action(ArgAction::SetTrue)
+          .help_heading(COMPILE_HEADING),
+      )
+      .arg(
+        Arg::new("code-cache-from")
+          .long("code-cache-from")
+          .value_name("FILE")
+          .help(cstr!("Embed the V8 code cache recorded by running the executable with DENO_COMPILE_RECORD_CODE_CACHE=FILE
+  <p(245)>Only the modules loaded by that run are cached. Implies --code-cache.</>"))
+          .value_parser(value_parser!(String))
+          .value_hint(ValueHint::FilePath)
+          .help_heading(COMPILE_HEADING),
+      )
+      .arg(
+        Arg::new("vfs-overlay")
+          .long("vfs-overlay")
+          .value_name("MODE")
+          .value_parser(["persistent", "temp-dir"])
+          .help(cstr!("Let the executable modify its embedded files through a copy-on-write overlay
+  <p(245)>persistent keeps the changes in a per-user directory, temp-dir keeps them in a temporary directory that is removed on exit.
+  The overlay isn't covered by the integrity check, so embedded modules and npm packages stay read-only.</>"))
+          .help_heading(COMPILE_HEADING),
+      )
+      .arg(
+        Arg::new("sign-key")
+          .long("sign-key")
+          .value_name("PATH")
+          .help(cstr!("Sign the embedded data with an ed25519 private key in PKCS#8 format
+  <p(245)>Set DENO_COMPILE_PUBLIC_KEY when running the executable to require a signature from that key.</>"))
+          .value_parser(value_parser!(String))
+          .value_hint(ValueHint::FilePath)
+          .help_heading(COMPILE_HEADING),
+      )
+      .arg(
+        Arg::new("reproducible")
+          .long("reproducible")
+          .help(cstr!("Produce a byte-identical executable for the same sources and lockfile on any machine
+  <p(245)>Records the sha256 of the base binary. Can't be combined with --code-cache.</>"))
+          .action(ArgAction::SetTrue)
+          .conflicts_with_all(["code-cache", "code-cache-from"])
+          .help_heading(COMPILE_HEADING),
+      )
+      .arg(
+        Arg::new("base-binary-sha256")
+          .long("base-binary-sha256")
+          .value_name("HASH")
+          .help("Fail unless the base binary has this sha256 hash")
+          .value_parser(parse_sha256_hex)
+          .help_heading(COMPILE_HEADING),
+      )
+      .arg(
+        Arg::new("verify-build")
+          .long("verify-build")
+          .value_name("EXECUTABLE")
+          .help(cstr!("Rebuild the executable and report the parts that differ from the given one
+  <p(245)>deno compile --reproducible --verify-build ./my_app main.ts</>"))
+          .value_parser(value_parser!(String))
+          .value_hint(ValueHint::FilePath)
+          .requires("reproducible")
+          .help_heading(COMPILE_HEADING),
+      )
+      .arg(
+        Arg::new("base-binary")
+          .long("base-binary")
+          .value_name("[TARGET=]PATH")
+          .help(cstr!("Use a local denort executable or release zip as the base binary instead of downloading one
+  <p(245)>Prefix the path with a target to only use it for that target. Can be repeated.</>"))
+          .action(ArgAction::Append)
+          .value_parser(parse_compile_base_binary)
+          .help_heading(COMPILE_HEADING),
+      )
+      .arg(
+        Arg::new("base-binary-dir")
+          .long("base-binary-dir")
+          .value_name("DIR")
+          .help(cstr!("Look up base binaries in a directory or zip archive instead of downloading them
+  <p(245)>Laid out like dl.deno.land, for example release/v2.0.6/denort-x86_64-unknown-linux-gnu.zip</>"))
+          .value_parser(value_parser!(String))
+          .value_hint(ValueHint::AnyPath)
+          .help_heading(COMPILE_HEADING),
+      )
+      .arg(
+        Arg::new("base-binary-manifest")
+          .long("base-binary-manifest")
+          .value_name("PATH")
+          .help(cstr!("Verify the base binary against a JSON file of sha256 hashes keyed by version and target
+  <p(245)>{ \"2.0.6\": { \"x86_64-unknown-linux-gnu\": \"<sha256>\" } }</>"))
+          .value_parser(value_parser!(String))
+          .value_hint(ValueHint::FilePath)
+          .help_heading(COMPILE_HEADING),
+      )
+      .arg(
+        Arg::new("inspect")
+          .long("inspect")
+          .help(cstr!("Print the contents of an executable created by deno compile instead of compiling a script
+  <p(245)>deno compile --inspect ./my_app</>"))
+          .action(ArgAction::SetTrue)
+          .help_heading(COMPILE_HEADING),
+      )
+      .arg(
+        Arg::new("verify")
+          .long("verify")
+          .help("Check that every embedded file and module can be read")
+          .action(ArgAction::SetTrue)
+          .requires("inspect")
+          .help_heading(COMPILE_HEADING),
+      )
+      .arg(
+        Arg::new("extract")
+          .long("extract")
+          .value_name("DIR")
+          .help("Extract the embedded files to a directory")
+          .value_parser(value_parser!(String))
+          .value_hint(ValueHint::DirPath)
+          .requires("inspect")
+          .help_heading(COMPILE_HEADING),
+      )
+      .arg(
+        Arg::new("entry")
+          .long("entry")
+          .value_name("PATH")
+          .help("Only extract the embedded file or directory at this path")
+          .value_parser(value_parser!(String))
+          .requires("extract")
+          .help_heading(COMPILE_HEADING),
+      )
+      .arg(
+        Arg::new("json")
+          .long("json")
+          .help("Print the contents as JSON")
+          .action(ArgAction::SetTrue)
+          .requires("inspect")
+          .help_heading(COMPILE_HEADING),
+      )
       .arg(executable_ext_arg())
       .arg(env_file_arg())
       .arg(
@@ -2423,6 +2964,9 @@ fn info_subcommand() -> Command {
 Get information about a module:
   <p(245)>deno info jsr:@std/http/file-server</>
 
+Show how much space the cache uses and what uses it:
+  <p(245)>deno info --cache-usage</>
+
 The following information is shown:
   local: Local path of the file
   type: JavaScript, TypeScript, or JSON
@@ -2435,7 +2979,9 @@ The following information is shown:
     .defer(|cmd| cmd
       .arg(Arg::new("file").value_hint(ValueHint::FilePath))
       .arg(reload_arg().requires("file"))
+      .arg(revalidate_arg().requires("file"))
       .arg(ca_file_arg())
+      .arg(client_cert_arg())
       .arg(unsafely_ignore_certificate_errors_arg())
       .arg(
         location_arg()
@@ -2452,6 +2998,13 @@ The following information is shown:
       .arg(import_map_arg())
       .arg(node_modules_dir_arg())
       .arg(vendor_arg())
+      .arg(
+        Arg::new("cache-usage")
+          .long("cache-usage")
+          .help("Show the size of the cache by category, the largest remote origins and npm packages, the npm packages the lockfile doesn't use and the health of the cache databases")
+          .conflicts_with("file")
+          .action(ArgAction::SetTrue),
+      )
       .arg(
         Arg::new("json")
           .long("json")
@@ -3185,12 +3738,51 @@ different location, use the <c>--output</> flag:
 
 fn vendor_subcommand() -> Command {
   command("vendor",
-      "⚠️ `deno vendor` was removed in Deno 2.
+      cstr!("Copy the remote modules of a program into a directory.
+
+Every http(s) and jsr module imported by the given modules is written to the directory along with an import map that resolves them to their copies:
+  <p(245)>deno vendor main.ts</>
+
+Use the import map by setting <c>\"importMap\": \"./vendor/import_map.json\"</> in the configuration file.
 
-See the Deno 1.x to 2.x Migration Guide for migration instructions: https://docs.deno.com/runtime/manual/advanced/migrate_deprecations",
+Verify the directory still matches the sources and the lockfile:
+  <p(245)>deno vendor --check main.ts</>"),
       UnstableArgsConfig::ResolutionOnly
     )
-    .hide(true)
+    .defer(|cmd| {
+      compile_args_without_check_args(cmd)
+        .arg(
+          Arg::new("file")
+            .num_args(1..)
+            .required_unless_present("help")
+            .value_hint(ValueHint::FilePath),
+        )
+        .arg(
+          Arg::new("output")
+            .long("output")
+            .short('o')
+            .value_name("DIR")
+            .help("Directory to write the modules to [default: vendor]")
+            .value_parser(value_parser!(String))
+            .value_hint(ValueHint::DirPath),
+        )
+        .arg(
+          Arg::new("check")
+            .long("check")
+            .help("Verify the directory matches the sources and the lockfile instead of writing to it")
+            .action(ArgAction::SetTrue)
+            .conflicts_with("force"),
+        )
+        .arg(
+          Arg::new("force")
+            .long("force")
+            .short('f')
+            .help("Replace the contents of the directory if it isn't empty")
+            .action(ArgAction::SetTrue),
+        )
+        .arg(frozen_lockfile_arg())
+        .arg(allow_import_arg())
+    })
 }
 
 fn publish_subcommand() -> Command {
@@ -3252,9 +3844,11 @@ fn compile_args_without_check_args(app: Command) -> Command {
     .arg(config_arg())
     .arg(no_config_arg())
     .arg(reload_arg())
+    .arg(revalidate_arg())
     .arg(lock_arg())
     .arg(no_lock_arg())
     .arg(ca_file_arg())
+    .arg(client_cert_arg())
     .arg(unsafely_ignore_certificate_errors_arg())
 }
 
@@ -3784,6 +4378,16 @@ fn reload_arg() -> Arg {
     .help_heading(DEPENDENCY_MANAGEMENT_HEADING)
 }
 
+fn revalidate_arg() -> Arg {
+  Arg::new("revalidate")
+    .long("revalidate")
+    .action(ArgAction::SetTrue)
+    .conflicts_with("reload")
+    .help(cstr!("Revalidate stale remote modules with the server, only downloading the ones that changed
+  <p(245)>Uses the ETag and Last-Modified headers of the cached responses</>"))
+    .help_heading(DEPENDENCY_MANAGEMENT_HEADING)
+}
+
 fn ca_file_arg() -> Arg {
   Arg::new("cert")
     .long("cert")
@@ -3792,6 +4396,17 @@ fn ca_file_arg() -> Arg {
     .value_hint(ValueHint::FilePath)
 }
 
+fn client_cert_arg() -> Arg {
+  Arg::new("client-cert")
+    .long("client-cert")
+    .value_name("HOST=CERT_FILE[,KEY_FILE]")
+    .action(ArgAction::Append)
+    .value_parser(parse_client_cert)
+    .help(cstr!("Present a PEM encoded client certificate to a host for mutual TLS
+  <p(245)>The private key is read from the certificate file when no key file is given</>"))
+    .value_hint(ValueHint::FilePath)
+}
+
 fn cached_only_arg() -> Arg {
   Arg::new("cached-only")
     .long("cached-only")
@@ -4361,9 +4976,20 @@ fn cache_parse(
   frozen_lockfile_arg_parse(flags, matches);
   allow_scripts_arg_parse(flags, matches)?;
   allow_import_parse(flags, matches);
-  let files = matches.remove_many::<String>("file").unwrap().collect();
-  flags.subcommand = DenoSubcommand::Cache(CacheFlags { files });
-  Ok(())
+  let files = matches
+    .remove_many::<String>("file")
+    .map(|files| files.collect())
+    .unwrap_or_default();
+  let export = matches.remove_one::<String>("export");
+  let import = matches.remove_one::<String>("import");
+  let serve = matches.remove_one::<String>("serve");
+  flags.subcommand = DenoSubcommand::Cache(CacheFlags {
+    files,
+    export,
+    import,
+    serve,
+  });
+  Ok(())
 }
 
 fn check_parse(
@@ -4387,8 +5013,26 @@ fn check_parse(
   Ok(())
 }
 
-fn clean_parse(flags: &mut Flags, _matches: &mut ArgMatches) {
-  flags.subcommand = DenoSubcommand::Clean;
+fn clean_parse(flags: &mut Flags, matches: &mut ArgMatches) {
+  let categories = [
+    ("npm", CleanCategory::Npm),
+    ("remote", CleanCategory::Remote),
+    ("emit", CleanCategory::Emit),
+  ]
+  .into_iter()
+  .filter(|(name, _)| matches.get_flag(name))
+  .map(|(_, category)| category)
+  .collect();
+  flags.subcommand = DenoSubcommand::Clean(CleanFlags {
+    categories,
+    older_than_days: matches.remove_one::<u64>("older-than"),
+    keep_locked_in: matches
+      .remove_many::<String>("keep-locked-in")
+      .map(|dirs| dirs.collect())
+      .unwrap_or_default(),
+    package: matches.remove_one::<String>("package"),
+    dry_run: matches.get_flag("dry-run"),
+  });
 }
 
 fn compile_parse(
@@ -4409,6 +5053,53 @@ fn compile_parse(
     Some(f) => f.collect(),
     None => vec![],
   };
+  let exclude = match matches.remove_many::<String>("exclude") {
+    Some(f) => f.collect(),
+    None => vec![],
+  };
+  let mut entrypoints =
+    match matches.remove_many::<CompileEntrypoint>("entrypoint") {
+      Some(f) => f.collect::<Vec<_>>(),
+      None => vec![],
+    };
+  let entrypoint_args =
+    match matches.remove_many::<(String, String)>("entrypoint-arg") {
+      Some(f) => f.collect(),
+      None => vec![],
+    };
+  compile_entrypoint_args_parse(&mut entrypoints, entrypoint_args)?;
+  let compress = matches.remove_one::<i32>("compress");
+  let code_cache_from = matches.remove_one::<String>("code-cache-from");
+  let code_cache = matches.get_flag("code-cache") || code_cache_from.is_some();
+  let sign_key = matches.remove_one::<String>("sign-key");
+  let reproducible = matches.get_flag("reproducible");
+  let base_binary_sha256 = matches.remove_one::<String>("base-binary-sha256");
+  let verify_build = matches.remove_one::<String>("verify-build");
+  let base_binaries =
+    match matches.remove_many::<CompileBaseBinary>("base-binary") {
+      Some(f) => f.collect(),
+      None => vec![],
+    };
+  let base_binary_dir = matches.remove_one::<String>("base-binary-dir");
+  let base_binary_manifest =
+    matches.remove_one::<String>("base-binary-manifest");
+  let inspect = if matches.get_flag("inspect") {
+    Some(CompileInspectFlags {
+      verify: matches.get_flag("verify"),
+      extract: matches.remove_one::<String>("extract"),
+      entry: matches.remove_one::<String>("entry"),
+      json: matches.get_flag("json"),
+    })
+  } else {
+    None
+  };
+  let vfs_overlay = matches.remove_one::<String>("vfs-overlay").map(|mode| {
+    match mode.as_str() {
+      "persistent" => VfsOverlayMode::Persistent,
+      "temp-dir" => VfsOverlayMode::TempDir,
+      _ => unreachable!(),
+    }
+  });
   ext_arg_parse(flags, matches);
 
   flags.subcommand = DenoSubcommand::Compile(CompileFlags {
@@ -4419,6 +5110,20 @@ fn compile_parse(
     no_terminal,
     icon,
     include,
+    exclude,
+    entrypoints,
+    compress,
+    code_cache,
+    code_cache_from,
+    vfs_overlay,
+    sign_key,
+    reproducible,
+    base_binary_sha256,
+    verify_build,
+    base_binaries,
+    base_binary_dir,
+    base_binary_manifest,
+    inspect,
   });
 
   Ok(())
@@ -4641,10 +5346,12 @@ fn info_parse(
 ) -> clap::error::Result<()> {
   unstable_args_parse(flags, matches, UnstableArgsConfig::ResolutionOnly);
   reload_arg_parse(flags, matches)?;
+  revalidate_arg_parse(flags, matches);
   config_args_parse(flags, matches);
   import_map_arg_parse(flags, matches);
   location_arg_parse(flags, matches);
   ca_file_arg_parse(flags, matches);
+  client_cert_arg_parse(flags, matches);
   unsafely_ignore_certificate_errors_parse(flags, matches);
   node_modules_and_vendor_dir_arg_parse(flags, matches);
   lock_args_parse(flags, matches);
@@ -4655,6 +5362,7 @@ fn info_parse(
   flags.subcommand = DenoSubcommand::Info(InfoFlags {
     file: matches.remove_one::<String>("file"),
     json,
+    cache_usage: matches.get_flag("cache-usage"),
   });
 
   Ok(())
@@ -5191,8 +5899,22 @@ fn upgrade_parse(flags: &mut Flags, matches: &mut ArgMatches) {
   });
 }
 
-fn vendor_parse(flags: &mut Flags, _matches: &mut ArgMatches) {
-  flags.subcommand = DenoSubcommand::Vendor
+fn vendor_parse(
+  flags: &mut Flags,
+  matches: &mut ArgMatches,
+) -> clap::error::Result<()> {
+  compile_args_without_check_parse(flags, matches)?;
+  unstable_args_parse(flags, matches, UnstableArgsConfig::ResolutionOnly);
+  frozen_lockfile_arg_parse(flags, matches);
+  allow_import_parse(flags, matches);
+  let files = matches.remove_many::<String>("file").unwrap().collect();
+  flags.subcommand = DenoSubcommand::Vendor(VendorFlags {
+    files,
+    output: matches.remove_one::<String>("output"),
+    check: matches.get_flag("check"),
+    force: matches.get_flag("force"),
+  });
+  Ok(())
 }
 
 fn publish_parse(flags: &mut Flags, matches: &mut ArgMatches) {
@@ -5231,8 +5953,10 @@ fn compile_args_without_check_parse(
   node_modules_and_vendor_dir_arg_parse(flags, matches);
   config_args_parse(flags, matches);
   reload_arg_parse(flags, matches)?;
+  revalidate_arg_parse(flags, matches);
   lock_args_parse(flags, matches);
   ca_file_arg_parse(flags, matches);
+  client_cert_arg_parse(flags, matches);
   unsafely_ignore_certificate_errors_parse(flags, matches);
   Ok(())
 }
@@ -5493,10 +6217,22 @@ fn reload_arg_parse(
   Ok(())
 }
 
+fn revalidate_arg_parse(flags: &mut Flags, matches: &mut ArgMatches) {
+  if matches.get_flag("revalidate") {
+    flags.revalidate = true;
+  }
+}
+
 fn ca_file_arg_parse(flags: &mut Flags, matches: &mut ArgMatches) {
   flags.ca_data = matches.remove_one::<String>("cert").map(CaData::File);
 }
 
+fn client_cert_arg_parse(flags: &mut Flags, matches: &mut ArgMatches) {
+  if let Some(client_certs) = matches.remove_many("client-cert") {
+    flags.client_certs = client_certs.collect();
+  }
+}
+
 fn enable_testing_features_arg_parse(
   flags: &mut Flags,
   matches: &mut ArgMatches,
@@ -7121,10 +7857,167 @@ mod tests {
       Flags {
         subcommand: DenoSubcommand::Cache(CacheFlags {
           files: svec!["script.ts"],
+          export: None,
+          import: None,
+          serve: None,
+        }),
+        ..Flags::default()
+      }
+    );
+  }
+
+  #[test]
+  fn cache_export_import() {
+    let r =
+      flags_from_vec(svec!["deno", "cache", "--export=cache.zip", "main.ts"]);
+    assert_eq!(
+      r.unwrap(),
+      Flags {
+        subcommand: DenoSubcommand::Cache(CacheFlags {
+          files: svec!["main.ts"],
+          export: Some("cache.zip".to_string()),
+          import: None,
+          serve: None,
+        }),
+        ..Flags::default()
+      }
+    );
+
+    let r = flags_from_vec(svec!["deno", "cache", "--import=cache.zip"]);
+    assert_eq!(
+      r.unwrap(),
+      Flags {
+        subcommand: DenoSubcommand::Cache(CacheFlags {
+          files: vec![],
+          export: None,
+          import: Some("cache.zip".to_string()),
+          serve: None,
+        }),
+        ..Flags::default()
+      }
+    );
+
+    let r = flags_from_vec(svec![
+      "deno",
+      "cache",
+      "--import=cache.zip",
+      "--export=other.zip"
+    ]);
+    assert!(r.is_err());
+    let r = flags_from_vec(svec!["deno", "cache", "--export=cache.zip"]);
+    assert!(r.is_err());
+  }
+
+  #[test]
+  fn cache_serve() {
+    let r = flags_from_vec(svec!["deno", "cache", "--serve"]);
+    assert_eq!(
+      r.unwrap(),
+      Flags {
+        subcommand: DenoSubcommand::Cache(CacheFlags {
+          files: vec![],
+          export: None,
+          import: None,
+          serve: Some("127.0.0.1:4507".to_string()),
+        }),
+        ..Flags::default()
+      }
+    );
+
+    let r = flags_from_vec(svec!["deno", "cache", "--serve=0.0.0.0:8080"]);
+    assert_eq!(
+      r.unwrap(),
+      Flags {
+        subcommand: DenoSubcommand::Cache(CacheFlags {
+          files: vec![],
+          export: None,
+          import: None,
+          serve: Some("0.0.0.0:8080".to_string()),
+        }),
+        ..Flags::default()
+      }
+    );
+
+    let r = flags_from_vec(svec!["deno", "cache", "--serve", "main.ts"]);
+    assert!(r.is_err());
+  }
+
+  #[test]
+  fn clean() {
+    let r = flags_from_vec(svec!["deno", "clean"]);
+    assert_eq!(
+      r.unwrap(),
+      Flags {
+        subcommand: DenoSubcommand::Clean(CleanFlags::default()),
+        ..Flags::default()
+      }
+    );
+
+    let r = flags_from_vec(svec![
+      "deno",
+      "clean",
+      "--npm",
+      "--emit",
+      "--older-than=30",
+      "--keep-locked-in=a,b",
+      "--keep-locked-in=c",
+      "--package=npm:chalk",
+      "--dry-run"
+    ]);
+    assert_eq!(
+      r.unwrap(),
+      Flags {
+        subcommand: DenoSubcommand::Clean(CleanFlags {
+          categories: vec![CleanCategory::Npm, CleanCategory::Emit],
+          older_than_days: Some(30),
+          keep_locked_in: svec!["a", "b", "c"],
+          package: Some("npm:chalk".to_string()),
+          dry_run: true,
+        }),
+        ..Flags::default()
+      }
+    );
+  }
+
+  #[test]
+  fn vendor() {
+    let r = flags_from_vec(svec!["deno", "vendor", "main.ts", "other.ts"]);
+    assert_eq!(
+      r.unwrap(),
+      Flags {
+        subcommand: DenoSubcommand::Vendor(VendorFlags {
+          files: svec!["main.ts", "other.ts"],
+          output: None,
+          check: false,
+          force: false,
         }),
         ..Flags::default()
       }
     );
+
+    let r = flags_from_vec(svec![
+      "deno",
+      "vendor",
+      "--output",
+      "deps",
+      "--check",
+      "--lock=lock.json",
+      "main.ts"
+    ]);
+    let flags = r.unwrap();
+    assert_eq!(
+      flags.subcommand,
+      DenoSubcommand::Vendor(VendorFlags {
+        files: svec!["main.ts"],
+        output: Some("deps".to_string()),
+        check: true,
+        force: false,
+      })
+    );
+    assert_eq!(flags.lock, Some("lock.json".to_string()));
+
+    let r = flags_from_vec(svec!["deno", "vendor", "--check", "-f", "main.ts"]);
+    assert!(r.is_err());
   }
 
   #[test]
@@ -7222,6 +8115,7 @@ mod tests {
         subcommand: DenoSubcommand::Info(InfoFlags {
           json: false,
           file: Some("script.ts".to_string()),
+          cache_usage: false,
         }),
         ..Flags::default()
       }
@@ -7234,6 +8128,7 @@ mod tests {
         subcommand: DenoSubcommand::Info(InfoFlags {
           json: false,
           file: Some("script.ts".to_string()),
+          cache_usage: false,
         }),
         reload: true,
         ..Flags::default()
@@ -7247,6 +8142,7 @@ mod tests {
         subcommand: DenoSubcommand::Info(InfoFlags {
           json: true,
           file: Some("script.ts".to_string()),
+          cache_usage: false,
         }),
         ..Flags::default()
       }
@@ -7258,7 +8154,8 @@ mod tests {
       Flags {
         subcommand: DenoSubcommand::Info(InfoFlags {
           json: false,
-          file: None
+          file: None,
+          cache_usage: false,
         }),
         ..Flags::default()
       }
@@ -7270,7 +8167,8 @@ mod tests {
       Flags {
         subcommand: DenoSubcommand::Info(InfoFlags {
           json: true,
-          file: None
+          file: None,
+          cache_usage: false,
         }),
         ..Flags::default()
       }
@@ -7289,7 +8187,8 @@ mod tests {
       Flags {
         subcommand: DenoSubcommand::Info(InfoFlags {
           json: false,
-          file: None
+          file: None,
+          cache_usage: false,
         }),
         config_flag: ConfigFlag::Path("tsconfig.json".to_owned()),
         no_npm: true,
@@ -7297,6 +8196,22 @@ mod tests {
         ..Flags::default()
       }
     );
+
+    let r = flags_from_vec(svec!["deno", "info", "--cache-usage", "--json"]);
+    assert_eq!(
+      r.unwrap(),
+      Flags {
+        subcommand: DenoSubcommand::Info(InfoFlags {
+          json: true,
+          file: None,
+          cache_usage: true,
+        }),
+        ..Flags::default()
+      }
+    );
+
+    let r = flags_from_vec(svec!["deno", "info", "--cache-usage", "script.ts"]);
+    assert!(r.is_err());
   }
 
   #[test]
@@ -8087,6 +9002,7 @@ mod tests {
         subcommand: DenoSubcommand::Info(InfoFlags {
           file: Some("script.ts".to_string()),
           json: false,
+          cache_usage: false,
         }),
         import_map_path: Some("import_map.json".to_owned()),
         ..Flags::default()
@@ -8107,6 +9023,9 @@ mod tests {
       Flags {
         subcommand: DenoSubcommand::Cache(CacheFlags {
           files: svec!["script.ts"],
+          export: None,
+          import: None,
+          serve: None,
         }),
         import_map_path: Some("import_map.json".to_owned()),
         ..Flags::default()
@@ -8234,6 +9153,9 @@ mod tests {
       Flags {
         subcommand: DenoSubcommand::Cache(CacheFlags {
           files: svec!["script.ts", "script_two.ts"],
+          export: None,
+          import: None,
+          serve: None,
         }),
         ..Flags::default()
       }
@@ -9028,6 +9950,76 @@ mod tests {
     );
   }
 
+  #[test]
+  fn run_with_revalidate() {
+    let r = flags_from_vec(svec!["deno", "run", "--revalidate", "script.ts"]);
+    assert_eq!(
+      r.unwrap(),
+      Flags {
+        subcommand: DenoSubcommand::Run(RunFlags::new_default(
+          "script.ts".to_string(),
+        )),
+        revalidate: true,
+        code_cache_enabled: true,
+        ..Flags::default()
+      }
+    );
+
+    let r = flags_from_vec(svec![
+      "deno",
+      "run",
+      "--revalidate",
+      "--reload",
+      "script.ts"
+    ]);
+    assert!(r.is_err());
+  }
+
+  #[test]
+  fn run_with_client_cert() {
+    let r = flags_from_vec(svec![
+      "deno",
+      "run",
+      "--client-cert",
+      "registry.internal=client.pem",
+      "--client-cert=localhost:8443=client.crt,client.key",
+      "script.ts"
+    ]);
+    assert_eq!(
+      r.unwrap(),
+      Flags {
+        subcommand: DenoSubcommand::Run(RunFlags::new_default(
+          "script.ts".to_string(),
+        )),
+        client_certs: vec![
+          ClientCertConfig {
+            host: "registry.internal".to_string(),
+            cert_file: PathBuf::from("client.pem"),
+            key_file: None,
+          },
+          ClientCertConfig {
+            host: "localhost:8443".to_string(),
+            cert_file: PathBuf::from("client.crt"),
+            key_file: Some(PathBuf::from("client.key")),
+          },
+        ],
+        code_cache_enabled: true,
+        ..Flags::default()
+      }
+    );
+
+    for invalid in ["client.pem", "=client.pem", "localhost=", "a=b,"] {
+      let r = flags_from_vec(svec![
+        "deno",
+        "run",
+        "--client-cert",
+        invalid,
+        "script.ts"
+      ]);
+      assert!(r.is_err(), "{invalid}");
+    }
+  }
+
   #[test]
   fn run_with_enable_testing_features() {
     let r = flags_from_vec(svec![
@@ -9645,6 +10637,9 @@ mod tests {
       Flags {
         subcommand: DenoSubcommand::Cache(CacheFlags {
           files: svec!["script.ts", "script_two.ts"],
+          export: None,
+          import: None,
+          serve: None,
         }),
         ca_data: Some(CaData::File("example.crt".to_owned())),
         ..Flags::default()
@@ -9667,6 +10662,7 @@ mod tests {
         subcommand: DenoSubcommand::Info(InfoFlags {
           json: false,
           file: Some("https://example.com".to_string()),
+          cache_usage: false,
         }),
         ca_data: Some(CaData::File("example.crt".to_owned())),
         ..Flags::default()
@@ -9994,7 +10990,21 @@ mod tests {
           target: None,
           no_terminal: false,
           icon: None,
-          include: vec![]
+          include: vec![],
+          exclude: vec![],
+          entrypoints: vec![],
+          compress: None,
+          code_cache: false,
+          code_cache_from: None,
+          vfs_overlay: None,
+          sign_key: None,
+          reproducible: false,
+          base_binary_sha256: None,
+          verify_build: None,
+          base_binaries: vec![],
+          base_binary_dir: None,
+          base_binary_manifest: None,
+          inspect: None,
         }),
         type_check_mode: TypeCheckMode::Local,
         ..Flags::default()
@@ -10017,7 +11027,21 @@ mod tests {
           target: None,
           no_terminal: true,
           icon: Some(String::from("favicon.ico")),
-          include: vec![]
+          include: vec![],
+          exclude: vec![],
+          entrypoints: vec![],
+          compress: None,
+          code_cache: false,
+          code_cache_from: None,
+          vfs_overlay: None,
+          sign_key: None,
+          reproducible: false,
+          base_binary_sha256: None,
+          verify_build: None,
+          base_binaries: vec![],
+          base_binary_dir: None,
+          base_binary_manifest: None,
+          inspect: None,
         }),
         import_map_path: Some("import_map.json".to_string()),
         no_remote: true,
@@ -10042,6 +11066,559 @@ mod tests {
     );
   }
 
+  #[test]
+  fn compile_compress() {
+    let r = flags_from_vec(svec!["deno", "compile", "--compress", "main.ts"]);
+    assert_eq!(
+      r.unwrap(),
+      Flags {
+        subcommand: DenoSubcommand::Compile(CompileFlags {
+          source_file: "main.ts".to_string(),
+          output: None,
+          args: vec![],
+          target: None,
+          no_terminal: false,
+          icon: None,
+          include: vec![],
+          exclude: vec![],
+          entrypoints: vec![],
+          compress: Some(3),
+          code_cache: false,
+          code_cache_from: None,
+          vfs_overlay: None,
+          sign_key: None,
+          reproducible: false,
+          base_binary_sha256: None,
+          verify_build: None,
+          base_binaries: vec![],
+          base_binary_dir: None,
+          base_binary_manifest: None,
+          inspect: None,
+        }),
+        type_check_mode: TypeCheckMode::Local,
+        ..Flags::default()
+      }
+    );
+
+    let r =
+      flags_from_vec(svec!["deno", "compile", "--compress=19", "main.ts"]);
+    assert_eq!(
+      r.unwrap(),
+      Flags {
+        subcommand: DenoSubcommand::Compile(CompileFlags {
+          source_file: "main.ts".to_string(),
+          output: None,
+          args: vec![],
+          target: None,
+          no_terminal: false,
+          icon: None,
+          include: vec![],
+          exclude: vec![],
+          entrypoints: vec![],
+          compress: Some(19),
+          code_cache: false,
+          code_cache_from: None,
+          vfs_overlay: None,
+          sign_key: None,
+          reproducible: false,
+          base_binary_sha256: None,
+          verify_build: None,
+          base_binaries: vec![],
+          base_binary_dir: None,
+          base_binary_manifest: None,
+          inspect: None,
+        }),
+        type_check_mode: TypeCheckMode::Local,
+        ..Flags::default()
+      }
+    );
+
+    let r =
+      flags_from_vec(svec!["deno", "compile", "--compress=23", "main.ts"]);
+    assert!(r.is_err());
+  }
+
+  #[test]
+  fn compile_code_cache() {
+    let r = flags_from_vec(svec!["deno", "compile", "--code-cache", "main.ts"]);
+    assert_eq!(
+      r.unwrap(),
+      Flags {
+        subcommand: DenoSubcommand::Compile(CompileFlags {
+          source_file: "main.ts".to_string(),
+          output: None,
+          args: vec![],
+          target: None,
+          no_terminal: false,
+          icon: None,
+          include: vec![],
+          exclude: vec![],
+          entrypoints: vec![],
+          compress: None,
+          code_cache: true,
+          code_cache_from: None,
+          vfs_overlay: None,
+          sign_key: None,
+          reproducible: false,
+          base_binary_sha256: None,
+          verify_build: None,
+          base_binaries: vec![],
+          base_binary_dir: None,
+          base_binary_manifest: None,
+          inspect: None,
+        }),
+        type_check_mode: TypeCheckMode::Local,
+        ..Flags::default()
+      }
+    );
+
+    let r = flags_from_vec(svec![
+      "deno",
+      "compile",
+      "--code-cache-from",
+      "code_cache.bin",
+      "main.ts"
+    ]);
+    let DenoSubcommand::Compile(compile_flags) = r.unwrap().subcommand else {
+      unreachable!();
+    };
+    assert!(compile_flags.code_cache);
+    assert_eq!(
+      compile_flags.code_cache_from,
+      Some("code_cache.bin".to_string())
+    );
+
+    let r = flags_from_vec(svec![
+      "deno",
+      "compile",
+      "--code-cache-from",
+      "code_cache.bin",
+      "--reproducible",
+      "main.ts"
+    ]);
+    assert!(r.is_err());
+  }
+
+  #[test]
+  fn compile_inspect() {
+    let r = flags_from_vec(svec![
+      "deno",
+      "compile",
+      "--inspect",
+      "--verify",
+      "--extract",
+      "out",
+      "--entry",
+      "src/main.ts",
+      "my_app"
+    ]);
+    assert_eq!(
+      r.unwrap(),
+      Flags {
+        subcommand: DenoSubcommand::Compile(CompileFlags {
+          source_file: "my_app".to_string(),
+          output: None,
+          args: vec![],
+          target: None,
+          no_terminal: false,
+          icon: None,
+          include: vec![],
+          exclude: vec![],
+          entrypoints: vec![],
+          compress: None,
+          code_cache: false,
+          code_cache_from: None,
+          vfs_overlay: None,
+          sign_key: None,
+          reproducible: false,
+          base_binary_sha256: None,
+          verify_build: None,
+          base_binaries: vec![],
+          base_binary_dir: None,
+          base_binary_manifest: None,
+          inspect: Some(CompileInspectFlags {
+            verify: true,
+            extract: Some("out".to_string()),
+            entry: Some("src/main.ts".to_string()),
+            json: false,
+          }),
+        }),
+        type_check_mode: TypeCheckMode::Local,
+        ..Flags::default()
+      }
+    );
+
+    let r = flags_from_vec(svec!["deno", "compile", "--verify", "my_app"]);
+    assert!(r.is_err());
+  }
+
+  #[test]
+  fn compile_vfs_overlay() {
+    let r = flags_from_vec(svec![
+      "deno",
+      "compile",
+      "--vfs-overlay",
+      "persistent",
+      "main.ts"
+    ]);
+    assert_eq!(
+      r.unwrap(),
+      Flags {
+        subcommand: DenoSubcommand::Compile(CompileFlags {
+          source_file: "main.ts".to_string(),
+          output: None,
+          args: vec![],
+          target: None,
+          no_terminal: false,
+          icon: None,
+          include: vec![],
+          exclude: vec![],
+          entrypoints: vec![],
+          compress: None,
+          code_cache: false,
+          code_cache_from: None,
+          vfs_overlay: Some(VfsOverlayMode::Persistent),
+          sign_key: None,
+          reproducible: false,
+          base_binary_sha256: None,
+          verify_build: None,
+          base_binaries: vec![],
+          base_binary_dir: None,
+          base_binary_manifest: None,
+          inspect: None,
+        }),
+        type_check_mode: TypeCheckMode::Local,
+        ..Flags::default()
+      }
+    );
+
+    let r = flags_from_vec(svec![
+      "deno",
+      "compile",
+      "--vfs-overlay",
+      "tmp",
+      "main.ts"
+    ]);
+    assert!(r.is_err());
+  }
+
+  #[test]
+  fn compile_include_exclude() {
+    let r = flags_from_vec(svec![
+      "deno",
+      "compile",
+      "--include",
+      "assets/**",
+      "--exclude",
+      "**/*.map",
+      "--exclude",
+      "npm:lodash/fp/**",
+      "main.ts"
+    ]);
+    assert_eq!(
+      r.unwrap(),
+      Flags {
+        subcommand: DenoSubcommand::Compile(CompileFlags {
+          source_file: "main.ts".to_string(),
+          output: None,
+          args: vec![],
+          target: None,
+          no_terminal: false,
+          icon: None,
+          include: svec!["assets/**"],
+          exclude: svec!["**/*.map", "npm:lodash/fp/**"],
+          entrypoints: vec![],
+          compress: None,
+          code_cache: false,
+          code_cache_from: None,
+          vfs_overlay: None,
+          sign_key: None,
+          reproducible: false,
+          base_binary_sha256: None,
+          verify_build: None,
+          base_binaries: vec![],
+          base_binary_dir: None,
+          base_binary_manifest: None,
+          inspect: None,
+        }),
+        type_check_mode: TypeCheckMode::Local,
+        ..Flags::default()
+      }
+    );
+  }
+
+  #[test]
+  fn compile_entrypoints() {
+    let r = flags_from_vec(svec![
+      "deno",
+      "compile",
+      "--entrypoint",
+      "fmt=./fmt.ts",
+      "--entrypoint",
+      "lint=./my lint.ts",
+      "--entrypoint-arg",
+      "lint=--allow-read=.",
+      "--entrypoint-arg",
+      "lint=--",
+      "--entrypoint-arg",
+      "lint=--fix",
+      "--entrypoint-arg",
+      "lint=a b",
+      "main.ts"
+    ]);
+    assert_eq!(
+      r.unwrap(),
+      Flags {
+        subcommand: DenoSubcommand::Compile(CompileFlags {
+          source_file: "main.ts".to_string(),
+          output: None,
+          args: vec![],
+          target: None,
+          no_terminal: false,
+          icon: None,
+          include: vec![],
+          exclude: vec![],
+          entrypoints: vec![
+            CompileEntrypoint {
+              name: "fmt".to_string(),
+              source_file: "./fmt.ts".to_string(),
+              permissions: None,
+              args: vec![],
+            },
+            CompileEntrypoint {
+              name: "lint".to_string(),
+              source_file: "./my lint.ts".to_string(),
+              permissions: Some(PermissionFlags {
+                allow_read: Some(svec!["."]),
+                ..Default::default()
+              }),
+              args: svec!["--fix", "a b"],
+            },
+          ],
+          compress: None,
+          code_cache: false,
+          code_cache_from: None,
+          vfs_overlay: None,
+          sign_key: None,
+          reproducible: false,
+          base_binary_sha256: None,
+          verify_build: None,
+          base_binaries: vec![],
+          base_binary_dir: None,
+          base_binary_manifest: None,
+          inspect: None,
+        }),
+        type_check_mode: TypeCheckMode::Local,
+        ..Flags::default()
+      }
+    );
+
+    for value in ["fmt", "=./fmt.ts", "a/b=./fmt.ts"] {
+      let r = flags_from_vec(svec![
+        "deno",
+        "compile",
+        "--entrypoint",
+        value,
+        "main.ts"
+      ]);
+      assert!(r.is_err(), "{}", value);
+    }
+    for arg in ["fmt=--unknown", "lint=--allow-read", "=--allow-read"] {
+      let r = flags_from_vec(svec![
+        "deno",
+        "compile",
+        "--entrypoint",
+        "fmt=./fmt.ts",
+        "--entrypoint-arg",
+        arg,
+        "main.ts"
+      ]);
+      assert!(r.is_err(), "{}", arg);
+    }
+  }
+
+  #[test]
+  fn compile_reproducible() {
+    let hash = "AB".repeat(32);
+    let r = flags_from_vec(svec![
+      "deno",
+      "compile",
+      "--reproducible",
+      "--base-binary-sha256",
+      hash,
+      "--verify-build",
+      "./my_app",
+      "main.ts"
+    ]);
+    assert_eq!(
+      r.unwrap(),
+      Flags {
+        subcommand: DenoSubcommand::Compile(CompileFlags {
+          source_file: "main.ts".to_string(),
+          output: None,
+          args: vec![],
+          target: None,
+          no_terminal: false,
+          icon: None,
+          include: vec![],
+          exclude: vec![],
+          entrypoints: vec![],
+          compress: None,
+          code_cache: false,
+          code_cache_from: None,
+          vfs_overlay: None,
+          sign_key: None,
+          reproducible: true,
+          base_binary_sha256: Some("ab".repeat(32)),
+          verify_build: Some("./my_app".to_string()),
+          base_binaries: vec![],
+          base_binary_dir: None,
+          base_binary_manifest: None,
+          inspect: None,
+        }),
+        type_check_mode: TypeCheckMode::Local,
+        ..Flags::default()
+      }
+    );
+
+    for args in [
+      svec![
+        "deno",
+        "compile",
+        "--reproducible",
+        "--code-cache",
+        "main.ts"
+      ],
+      svec!["deno", "compile", "--verify-build", "./my_app", "main.ts"],
+      svec!["deno", "compile", "--base-binary-sha256", "abc", "main.ts"],
+    ] {
+      assert!(flags_from_vec(args).is_err());
+    }
+  }
+
+  #[test]
+  fn compile_base_binary() {
+    let r = flags_from_vec(svec![
+      "deno",
+      "compile",
+      "--base-binary",
+      "./denort",
+      "--base-binary",
+      "x86_64-pc-windows-msvc=C:\\denort.zip",
+      "--base-binary-dir",
+      "./denort-builds.zip",
+      "--base-binary-manifest",
+      "./manifest.json",
+      "main.ts"
+    ]);
+    assert_eq!(
+      r.unwrap(),
+      Flags {
+        subcommand: DenoSubcommand::Compile(CompileFlags {
+          source_file: "main.ts".to_string(),
+          output: None,
+          args: vec![],
+          target: None,
+          no_terminal: false,
+          icon: None,
+          include: vec![],
+          exclude: vec![],
+          entrypoints: vec![],
+          compress: None,
+          code_cache: false,
+          code_cache_from: None,
+          vfs_overlay: None,
+          sign_key: None,
+          reproducible: false,
+          base_binary_sha256: None,
+          verify_build: None,
+          base_binaries: vec![
+            CompileBaseBinary {
+              target: None,
+              path: "./denort".to_string(),
+            },
+            CompileBaseBinary {
+              target: Some("x86_64-pc-windows-msvc".to_string()),
+              path: "C:\\denort.zip".to_string(),
+            },
+          ],
+          base_binary_dir: Some("./denort-builds.zip".to_string()),
+          base_binary_manifest: Some("./manifest.json".to_string()),
+          inspect: None,
+        }),
+        type_check_mode: TypeCheckMode::Local,
+        ..Flags::default()
+      }
+    );
+
+    // only known targets are split off, so equals signs in paths aren't
+    // mistaken for a target
+    assert_eq!(
+      parse_compile_base_binary("./a=b").unwrap(),
+      CompileBaseBinary {
+        target: None,
+        path: "./a=b".to_string(),
+      }
+    );
+    assert_eq!(
+      parse_compile_base_binary("a=b").unwrap(),
+      CompileBaseBinary {
+        target: None,
+        path: "a=b".to_string(),
+      }
+    );
+    assert_eq!(
+      parse_compile_base_binary("=./denort").unwrap(),
+      CompileBaseBinary {
+        target: None,
+        path: "=./denort".to_string(),
+      }
+    );
+    assert!(parse_compile_base_binary("x86_64-apple-darwin=").is_err());
+    assert!(parse_compile_base_binary("").is_err());
+  }
+
+  #[test]
+  fn compile_sign_key() {
+    let r = flags_from_vec(svec![
+      "deno",
+      "compile",
+      "--sign-key",
+      "key.pem",
+      "main.ts"
+    ]);
+    assert_eq!(
+      r.unwrap(),
+      Flags {
+        subcommand: DenoSubcommand::Compile(CompileFlags {
+          source_file: "main.ts".to_string(),
+          output: None,
+          args: vec![],
+          target: None,
+          no_terminal: false,
+          icon: None,
+          include: vec![],
+          exclude: vec![],
+          entrypoints: vec![],
+          compress: None,
+          code_cache: false,
+          code_cache_from: None,
+          vfs_overlay: None,
+          sign_key: Some("key.pem".to_string()),
+          reproducible: false,
+          base_binary_sha256: None,
+          verify_build: None,
+          base_binaries: vec![],
+          base_binary_dir: None,
+          base_binary_manifest: None,
+          inspect: None,
+        }),
+        type_check_mode: TypeCheckMode::Local,
+        ..Flags::default()
+      }
+    );
+  }
+
   #[test]
   fn coverage() {
     let r = flags_from_vec(svec!["deno", "coverage", "foo.json"]);
@@ -10852,6 +12429,9 @@ mod tests {
             Flags {
               subcommand: DenoSubcommand::Cache(CacheFlags {
                 files: svec!["script.ts"],
+                export: None,
+                import: None,
+                serve: None,
               }),
               allow_scripts: value,
               ..Flags::default()
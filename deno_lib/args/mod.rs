@@ -42,6 +42,7 @@ pub use deno_config::deno_json::TsConfigType;
 pub use deno_config::deno_json::TsTypeLib;
 pub use deno_config::glob::FilePatterns;
 pub use deno_json::check_warn_tsconfig;
+pub use deno_json::CompileConfig;
 pub use flags::*;
 pub use lockfile::CliLockfile;
 pub use lockfile::CliLockfileReadFromPathOptions;
@@ -77,13 +78,20 @@ use std::net::SocketAddr;
 use std::num::NonZeroUsize;
 use std::path::Path;
 use std::path::PathBuf;
+use std::str::FromStr;
 use std::sync::Arc;
+use std::time::Duration;
 use thiserror::Error;
 
 use crate::cache;
 use crate::cache::DenoDirProvider;
 use crate::file_fetcher::FileFetcher;
+use crate::file_fetcher::Mirrors;
+use crate::http_util::CacheServer;
+use crate::http_util::DownloadPolicy;
+use crate::http_util::CACHE_SERVER_TOKEN_ENV_VAR_NAME;
 use crate::util::fs::canonicalize_path_maybe_not_exists;
+use crate::util::retry::RetryPolicy;
 use crate::version;
 
 use deno_config::deno_json::FmtConfig;
@@ -736,6 +744,138 @@ pub fn get_root_cert_store(
   Ok(root_cert_store)
 }
 
+fn resolve_client_certs(
+  initial_cwd: &Path,
+  flags: &Flags,
+  start_dir: &WorkspaceDirectory,
+) -> Result<Vec<ClientCertConfig>, AnyError> {
+  let mut client_certs = flags.client_certs.clone();
+  if let Ok(env_client_certs) = env::var("DENO_CLIENT_CERTS") {
+    for entry in env_client_certs.split(';').map(|s| s.trim()) {
+      if !entry.is_empty() {
+        client_certs
+          .push(parse_client_cert(entry).context("Invalid DENO_CLIENT_CERTS")?);
+      }
+    }
+  }
+  for client_cert in &mut client_certs {
+    client_cert.cert_file = initial_cwd.join(&client_cert.cert_file);
+    client_cert.key_file =
+      client_cert.key_file.as_ref().map(|p| initial_cwd.join(p));
+  }
+  let root_folder = start_dir.workspace.root_folder_configs();
+  if let Some(deno_json) = &root_folder.deno_json {
+    client_certs.extend(deno_json::read_client_certs_config(deno_json)?);
+  }
+  Ok(client_certs)
+}
+
+/// Resolves the download policy, where the `DENO_DOWNLOAD_*` environment
+/// variables take precedence over the `download` section of the root
+/// deno.json.
+fn resolve_download_policy(
+  start_dir: &WorkspaceDirectory,
+) -> Result<DownloadPolicy, AnyError> {
+  fn env_var<T: FromStr>(name: &str) -> Result<Option<T>, AnyError>
+  where
+    T::Err: std::fmt::Display,
+  {
+    match env::var(name) {
+      Ok(value) => match value.trim().parse() {
+        Ok(value) => Ok(Some(value)),
+        Err(err) => bail!("Invalid {}: {}", name, err),
+      },
+      Err(_) => Ok(None),
+    }
+  }
+
+  let root_folder = start_dir.workspace.root_folder_configs();
+  let config = match &root_folder.deno_json {
+    Some(deno_json) => {
+      deno_json::read_download_config(deno_json)?.unwrap_or_default()
+    }
+    None => Default::default(),
+  };
+  let mut policy = DownloadPolicy::default();
+  let max_attempts =
+    env_var("DENO_DOWNLOAD_MAX_ATTEMPTS")?.or(config.max_attempts);
+  let initial_backoff_ms =
+    env_var("DENO_DOWNLOAD_INITIAL_BACKOFF_MS")?.or(config.initial_backoff_ms);
+  let max_backoff_ms =
+    env_var("DENO_DOWNLOAD_MAX_BACKOFF_MS")?.or(config.max_backoff_ms);
+  // downloads keep their own retries unless a retry setting is given
+  if max_attempts.is_some()
+    || initial_backoff_ms.is_some()
+    || max_backoff_ms.is_some()
+  {
+    let mut retry = RetryPolicy::default();
+    if let Some(max_attempts) = max_attempts {
+      if max_attempts == 0 {
+        bail!("The maximum number of download attempts must be at least 1.");
+      }
+      retry.max_attempts = max_attempts;
+    }
+    if let Some(ms) = initial_backoff_ms {
+      retry.initial_backoff = Duration::from_millis(ms);
+    }
+    if let Some(ms) = max_backoff_ms {
+      retry.max_backoff = Duration::from_millis(ms);
+    }
+    policy.retry = Some(retry);
+  }
+  policy.max_concurrent_requests_per_host =
+    env_var("DENO_DOWNLOAD_MAX_CONCURRENT_REQUESTS_PER_HOST")?
+      .or(config.max_concurrent_requests_per_host)
+      .filter(|max| *max > 0);
+  policy.timeout = env_var("DENO_DOWNLOAD_TIMEOUT_MS")?
+    .or(config.timeout_ms)
+    .filter(|ms| *ms > 0)
+    .map(Duration::from_millis);
+  Ok(policy)
+}
+
+/// Resolves the cache server from the `DENO_CACHE_SERVER` environment
+/// variable, and the token it requires from `DENO_CACHE_SERVER_TOKEN`.
+fn resolve_cache_server() -> Result<Option<CacheServer>, AnyError> {
+  match env::var("DENO_CACHE_SERVER") {
+    Ok(url) if !url.trim().is_empty() => {
+      let url = Url::parse(url.trim()).context("Invalid DENO_CACHE_SERVER")?;
+      Ok(Some(
+        CacheServer::new(url)?.with_token(resolve_cache_server_token()),
+      ))
+    }
+    _ => Ok(None),
+  }
+}
+
+/// The token of the `DENO_CACHE_SERVER_TOKEN` environment variable, which
+/// clients send to the cache server and the cache server requires.
+pub fn resolve_cache_server_token() -> Option<String> {
+  env::var(CACHE_SERVER_TOKEN_ENV_VAR_NAME)
+    .ok()
+    .map(|token| token.trim().to_string())
+    .filter(|token| !token.is_empty())
+}
+
+/// Resolves the mirrors that remote modules are downloaded from, from the
+/// `mirrors` section of the root deno.json.
+fn resolve_mirrors(
+  start_dir: &WorkspaceDirectory,
+) -> Result<Mirrors, AnyError> {
+  let root_folder = start_dir.workspace.root_folder_configs();
+  let Some(deno_json) = &root_folder.deno_json else {
+    return Ok(Mirrors::default());
+  };
+  let parse_url = |text: &str| {
+    Url::parse(text).with_context(|| format!("Invalid mirror URL '{}'", text))
+  };
+  let rules = deno_json::read_mirrors_config(deno_json)?
+    .iter()
+    .map(|(original, mirror)| Ok((parse_url(original)?, parse_url(mirror)?)))
+    .collect::<Result<Vec<_>, AnyError>>()?;
+  Mirrors::new(rules)
+}
+
 /// State provided to the process via an environment variable.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct NpmProcessState {
@@ -799,6 +939,10 @@ pub struct CliOptions {
   npmrc: Arc<ResolvedNpmRc>,
   maybe_lockfile: Option<Arc<CliLockfile>>,
   overrides: CliOptionOverrides,
+  client_certs: Vec<ClientCertConfig>,
+  download_policy: DownloadPolicy,
+  mirrors: Mirrors,
+  cache_server: Option<CacheServer>,
   pub start_dir: Arc<WorkspaceDirectory>,
   pub deno_dir_provider: Arc<DenoDirProvider>,
 }
@@ -842,9 +986,20 @@ impl CliOptions {
 
     load_env_variables_from_env_file(flags.env_file.as_ref());
 
+    let client_certs = resolve_client_certs(&initial_cwd, &flags, &start_dir)
+      .context("Resolving client certificates.")?;
+    let download_policy = resolve_download_policy(&start_dir)
+      .context("Resolving download policy.")?;
+    let mirrors = resolve_mirrors(&start_dir).context("Resolving mirrors.")?;
+    let cache_server = resolve_cache_server()?;
+
     Ok(Self {
       flags,
       initial_cwd,
+      client_certs,
+      download_policy,
+      mirrors,
+      cache_server,
       maybe_lockfile,
       npmrc,
       maybe_node_modules_folder,
@@ -989,6 +1144,8 @@ impl CliOptions {
       CacheSetting::ReloadSome(self.flags.cache_blocklist.clone())
     } else if self.flags.reload {
       CacheSetting::ReloadAll
+    } else if self.flags.revalidate {
+      CacheSetting::RespectHeaders
     } else {
       CacheSetting::Use
     }
@@ -1305,6 +1462,22 @@ impl CliOptions {
     Ok(result)
   }
 
+  /// Reads the `compile` section of the root deno.json, returning it with
+  /// the directory its paths are relative to.
+  pub fn resolve_compile_config(
+    &self,
+  ) -> Result<Option<(PathBuf, CompileConfig)>, AnyError> {
+    let root_folder = self.workspace().root_folder_configs();
+    let Some(deno_json) = &root_folder.deno_json else {
+      return Ok(None);
+    };
+    let Some(config) = deno_json::read_compile_config(deno_json)? else {
+      return Ok(None);
+    };
+    let dir_path = deno_json.dir_path();
+    Ok(Some((dir_path, config)))
+  }
+
   pub fn resolve_config_unstable_fmt_options(&self) -> UnstableFmtOptions {
     let workspace = self.workspace();
     UnstableFmtOptions {
@@ -1413,6 +1586,28 @@ impl CliOptions {
     &self.flags.ca_data
   }
 
+  /// The client certificates from the flags, `DENO_CLIENT_CERTS` and the
+  /// root deno.json, in that order of precedence.
+  pub fn client_certs(&self) -> &[ClientCertConfig] {
+    &self.client_certs
+  }
+
+  /// How downloads are retried, throttled and timed out, from the
+  /// environment or the root deno.json.
+  pub fn download_policy(&self) -> &DownloadPolicy {
+    &self.download_policy
+  }
+
+  /// The mirrors remote modules are downloaded from, from the root deno.json.
+  pub fn mirrors(&self) -> &Mirrors {
+    &self.mirrors
+  }
+
+  /// The `deno cache --serve` server to download from before the origin.
+  pub fn cache_server(&self) -> Option<&CacheServer> {
+    self.cache_server.as_ref()
+  }
+
   pub fn ca_stores(&self) -> &Option<Vec<String>> {
     &self.flags.ca_stores
   }
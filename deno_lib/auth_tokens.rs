@@ -2,16 +2,40 @@
 
 use base64::prelude::BASE64_STANDARD;
 use base64::Engine;
+use deno_core::anyhow::bail;
+use deno_core::anyhow::Context;
+use deno_core::error::AnyError;
+use deno_core::parking_lot::Mutex;
+use deno_core::serde_json;
 use deno_core::ModuleSpecifier;
 use log::debug;
 use log::error;
+use serde::Deserialize;
 use std::borrow::Cow;
+use std::collections::HashMap;
+use std::env;
 use std::fmt;
 use std::net::IpAddr;
 use std::net::Ipv4Addr;
 use std::net::Ipv6Addr;
 use std::net::SocketAddr;
+use std::process::Stdio;
 use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
+use tokio::io::AsyncWriteExt;
+
+/// Path to a netrc file to read credentials from.
+pub const NETRC_ENV_VAR_NAME: &str = "DENO_NETRC";
+/// Command that is run to get the credentials of a host. Arguments are split
+/// like a shell would, so quote paths with spaces.
+pub const CREDENTIAL_HELPER_ENV_VAR_NAME: &str = "DENO_AUTH_CREDENTIAL_HELPER";
+
+/// How long the result of the credential helper is used when it doesn't
+/// say when the credentials expire.
+const DEFAULT_CREDENTIAL_TTL: Duration = Duration::from_secs(5 * 60);
+const CREDENTIAL_HELPER_TIMEOUT: Duration = Duration::from_secs(30);
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum AuthTokenData {
@@ -40,8 +64,15 @@ impl fmt::Display for AuthToken {
 /// A structure which contains bearer tokens that can be used when sending
 /// requests to websites, intended to authorize access to private resources
 /// such as remote modules.
-#[derive(Debug, Clone)]
-pub struct AuthTokens(Vec<AuthToken>);
+///
+/// Tokens are looked up in `DENO_AUTH_TOKENS` first, then in the netrc file
+/// and finally by asking the credential helper.
+#[derive(Debug, Clone, Default)]
+pub struct AuthTokens {
+  tokens: Vec<AuthToken>,
+  netrc: Vec<NetrcEntry>,
+  credential_helper: Option<Arc<CredentialHelper>>,
+}
 
 /// An authorization domain, either an exact or suffix match.
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -121,51 +152,416 @@ impl AuthTokens {
   /// parsed for token values.  The string is expected to be a semi-colon
   /// separated string, where each value is `{token}@{hostname}`.
   pub fn new(maybe_tokens_str: Option<String>) -> Self {
-    let mut tokens = Vec::new();
-    if let Some(tokens_str) = maybe_tokens_str {
-      for token_str in tokens_str.trim().split(';') {
-        if token_str.contains('@') {
-          let mut iter = token_str.rsplitn(2, '@');
-          let host = AuthDomain::from(iter.next().unwrap());
-          let token = iter.next().unwrap();
-          if token.contains(':') {
-            let mut iter = token.rsplitn(2, ':');
-            let password = iter.next().unwrap().to_owned();
-            let username = iter.next().unwrap().to_owned();
-            tokens.push(AuthToken {
-              host,
-              token: AuthTokenData::Basic { username, password },
-            });
-          } else {
-            tokens.push(AuthToken {
-              host,
-              token: AuthTokenData::Bearer(token.to_string()),
-            });
-          }
-        } else {
-          error!("Badly formed auth token discarded.");
+    Self {
+      tokens: parse_tokens(maybe_tokens_str),
+      ..Default::default()
+    }
+  }
+
+  /// Creates the tokens from `DENO_AUTH_TOKENS`, `DENO_NETRC` and
+  /// `DENO_AUTH_CREDENTIAL_HELPER`.
+  pub fn from_env() -> Self {
+    let mut auth_tokens = Self::new(env::var("DENO_AUTH_TOKENS").ok());
+    if let Some(path) = env::var_os(NETRC_ENV_VAR_NAME) {
+      match std::fs::read_to_string(&path) {
+        Ok(text) => auth_tokens = auth_tokens.with_netrc(&text),
+        Err(err) => error!(
+          "Failed reading netrc file '{}': {}",
+          path.to_string_lossy(),
+          err
+        ),
+      }
+    }
+    if let Ok(command) = env::var(CREDENTIAL_HELPER_ENV_VAR_NAME) {
+      match split_command(&command) {
+        Ok(command) if command.is_empty() => {}
+        Ok(command) => {
+          auth_tokens = auth_tokens.with_credential_helper(command);
+        }
+        Err(err) => {
+          error!("Invalid {}: {:#}", CREDENTIAL_HELPER_ENV_VAR_NAME, err)
         }
       }
-      debug!("Parsed {} auth token(s).", tokens.len());
     }
+    auth_tokens
+  }
+
+  /// Adds the credentials of a netrc file, used for hosts without a token.
+  pub fn with_netrc(mut self, text: &str) -> Self {
+    self.netrc = parse_netrc(text);
+    debug!("Parsed {} netrc entries.", self.netrc.len());
+    self
+  }
 
-    Self(tokens)
+  /// Runs `command` with the `get` argument to get the credentials of
+  /// hosts without a token or netrc entry. The command receives a JSON
+  /// object with the `protocol`, `host` and `url` of the request on stdin
+  /// and writes one with either a `token` or a `username` and `password`,
+  /// and optionally `expiresIn` seconds, to stdout. It's run with `erase`
+  /// when the server rejects the credentials.
+  pub fn with_credential_helper(mut self, command: Vec<String>) -> Self {
+    self.credential_helper = Some(Arc::new(CredentialHelper {
+      command,
+      cache: Default::default(),
+    }));
+    self
   }
 
   /// Attempt to match the provided specifier to the tokens in the set.  The
   /// matching occurs from the right of the hostname plus port, irrespective of
   /// scheme.  For example `https://www.deno.land:8080/` would match a token
   /// with a host value of `deno.land:8080` but not match `www.deno.land`.  The
-  /// matching is case insensitive.
+  /// matching is case insensitive. Netrc entries match the hostname exactly.
   pub fn get(&self, specifier: &ModuleSpecifier) -> Option<AuthToken> {
-    self.0.iter().find_map(|t| {
-      if t.host.matches(specifier) {
-        Some(t.clone())
+    self
+      .tokens
+      .iter()
+      .find(|t| t.host.matches(specifier))
+      .cloned()
+      .or_else(|| {
+        let host = specifier.host_str()?;
+        let entry = self
+          .netrc
+          .iter()
+          .find(|e| {
+            e.machine
+              .as_ref()
+              .is_some_and(|machine| machine.eq_ignore_ascii_case(host))
+          })
+          .or_else(|| self.netrc.iter().find(|e| e.machine.is_none()))?;
+        Some(AuthToken {
+          host: AuthDomain::from(host),
+          token: entry.token.clone(),
+        })
+      })
+  }
+
+  /// Like `get`, but also asks the credential helper, whose answers are
+  /// cached until they expire.
+  pub async fn resolve(
+    &self,
+    specifier: &ModuleSpecifier,
+  ) -> Option<AuthToken> {
+    if let Some(token) = self.get(specifier) {
+      return Some(token);
+    }
+    self.credential_helper.as_ref()?.get(specifier).await
+  }
+
+  /// Asks the credential helper for new credentials after the server
+  /// rejected `rejected`, returning them if they differ. Tokens from
+  /// `DENO_AUTH_TOKENS` and the netrc file can't be refreshed.
+  pub async fn refresh(
+    &self,
+    specifier: &ModuleSpecifier,
+    rejected: &AuthToken,
+  ) -> Option<AuthToken> {
+    if self.get(specifier).is_some() {
+      return None;
+    }
+    let helper = self.credential_helper.as_ref()?;
+    helper.erase(specifier).await;
+    helper
+      .get(specifier)
+      .await
+      .filter(|token| token.token != rejected.token)
+  }
+}
+
+fn parse_tokens(maybe_tokens_str: Option<String>) -> Vec<AuthToken> {
+  let mut tokens = Vec::new();
+  if let Some(tokens_str) = maybe_tokens_str {
+    for token_str in tokens_str.trim().split(';') {
+      if token_str.contains('@') {
+        let mut iter = token_str.rsplitn(2, '@');
+        let host = AuthDomain::from(iter.next().unwrap());
+        let token = iter.next().unwrap();
+        if token.contains(':') {
+          let mut iter = token.rsplitn(2, ':');
+          let password = iter.next().unwrap().to_owned();
+          let username = iter.next().unwrap().to_owned();
+          tokens.push(AuthToken {
+            host,
+            token: AuthTokenData::Basic { username, password },
+          });
+        } else {
+          tokens.push(AuthToken {
+            host,
+            token: AuthTokenData::Bearer(token.to_string()),
+          });
+        }
       } else {
-        None
+        error!("Badly formed auth token discarded.");
+      }
+    }
+    debug!("Parsed {} auth token(s).", tokens.len());
+  }
+  tokens
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct NetrcEntry {
+  /// The host, or `None` for the `default` entry.
+  machine: Option<String>,
+  token: AuthTokenData,
+}
+
+/// Parses the entries of a netrc file. Entries with a `login` use basic
+/// authentication and ones with only a `password` use it as a bearer token.
+fn parse_netrc(text: &str) -> Vec<NetrcEntry> {
+  fn push_entry(
+    entries: &mut Vec<NetrcEntry>,
+    machine: Option<Option<String>>,
+    login: Option<String>,
+    password: Option<String>,
+  ) {
+    let (Some(machine), Some(password)) = (machine, password) else {
+      return;
+    };
+    let token = match login {
+      Some(username) => AuthTokenData::Basic { username, password },
+      None => AuthTokenData::Bearer(password),
+    };
+    entries.push(NetrcEntry { machine, token });
+  }
+
+  let mut entries = Vec::new();
+  let mut machine = None;
+  let mut login = None;
+  let mut password = None;
+  let mut lines = text.lines();
+  while let Some(line) = lines.next() {
+    let mut words = line.split_whitespace();
+    while let Some(word) = words.next() {
+      match word {
+        "machine" | "default" => {
+          push_entry(
+            &mut entries,
+            machine.take(),
+            login.take(),
+            password.take(),
+          );
+          machine = if word == "default" {
+            Some(None)
+          } else {
+            words.next().map(|name| Some(name.to_string()))
+          };
+        }
+        "login" => login = words.next().map(ToString::to_string),
+        "password" => password = words.next().map(ToString::to_string),
+        "account" => {
+          words.next();
+        }
+        "macdef" => {
+          // the macro definition ends at the next empty line
+          for line in lines.by_ref() {
+            if line.trim().is_empty() {
+              break;
+            }
+          }
+          break;
+        }
+        _ if word.starts_with('#') => break,
+        _ => {}
+      }
+    }
+  }
+  push_entry(&mut entries, machine, login, password);
+  entries
+}
+
+#[derive(Debug)]
+struct CachedCredential {
+  token: Option<AuthTokenData>,
+  expires_at: Instant,
+}
+
+#[derive(Debug)]
+struct CredentialHelper {
+  command: Vec<String>,
+  /// Keyed by the scheme, host and port. Each entry is locked while the
+  /// command runs for its host, so concurrent requests to a host only run it
+  /// once without waiting on the helper for other hosts.
+  cache: Mutex<HashMap<String, CredentialSlot>>,
+}
+
+type CredentialSlot = Arc<tokio::sync::Mutex<Option<CachedCredential>>>;
+
+#[derive(Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+struct CredentialHelperOutput {
+  token: Option<String>,
+  username: Option<String>,
+  password: Option<String>,
+  expires_in: Option<u64>,
+}
+
+impl CredentialHelperOutput {
+  fn parse(stdout: &[u8]) -> Result<Self, AnyError> {
+    if stdout.iter().all(|b| b.is_ascii_whitespace()) {
+      return Ok(Self::default());
+    }
+    Ok(serde_json::from_slice(stdout)?)
+  }
+
+  fn into_token(self) -> Option<AuthTokenData> {
+    match (self.token, self.username, self.password) {
+      (Some(token), _, _) => Some(AuthTokenData::Bearer(token)),
+      (None, Some(username), Some(password)) => {
+        Some(AuthTokenData::Basic { username, password })
+      }
+      _ => None,
+    }
+  }
+}
+
+impl CredentialHelper {
+  async fn get(&self, specifier: &ModuleSpecifier) -> Option<AuthToken> {
+    let host = host_with_port(specifier)?;
+    let slot = self.slot(specifier, &host);
+    let mut cached = slot.lock().await;
+    let token = match &*cached {
+      Some(entry) if entry.expires_at > Instant::now() => entry.token.clone(),
+      _ => {
+        let (token, ttl) = match self.run("get", specifier, &host).await {
+          Ok(output) => {
+            let ttl = output
+              .expires_in
+              .map(Duration::from_secs)
+              .unwrap_or(DEFAULT_CREDENTIAL_TTL);
+            (output.into_token(), ttl)
+          }
+          Err(err) => {
+            error!("Credential helper failed for {}: {:#}", host, err);
+            (None, DEFAULT_CREDENTIAL_TTL)
+          }
+        };
+        *cached = Some(CachedCredential {
+          token: token.clone(),
+          expires_at: Instant::now() + ttl,
+        });
+        token
       }
+    };
+    Some(AuthToken {
+      host: AuthDomain::from(host),
+      token: token?,
     })
   }
+
+  async fn erase(&self, specifier: &ModuleSpecifier) {
+    let Some(host) = host_with_port(specifier) else {
+      return;
+    };
+    let slot = self.slot(specifier, &host);
+    let mut cached = slot.lock().await;
+    *cached = None;
+    if let Err(err) = self.run("erase", specifier, &host).await {
+      debug!("Credential helper failed erasing {}: {:#}", host, err);
+    }
+  }
+
+  fn slot(&self, specifier: &ModuleSpecifier, host: &str) -> CredentialSlot {
+    let key = format!("{}://{}", specifier.scheme(), host);
+    self.cache.lock().entry(key).or_default().clone()
+  }
+
+  async fn run(
+    &self,
+    action: &str,
+    specifier: &ModuleSpecifier,
+    host: &str,
+  ) -> Result<CredentialHelperOutput, AnyError> {
+    let input = serde_json::json!({
+      "protocol": specifier.scheme(),
+      "host": host,
+      "url": specifier.as_str(),
+    });
+    let mut child = tokio::process::Command::new(&self.command[0])
+      .args(&self.command[1..])
+      .arg(action)
+      .stdin(Stdio::piped())
+      .stdout(Stdio::piped())
+      .stderr(Stdio::inherit())
+      .kill_on_drop(true)
+      .spawn()
+      .with_context(|| format!("Failed running '{}'", self.command[0]))?;
+    let mut stdin = child.stdin.take().unwrap();
+    stdin.write_all(input.to_string().as_bytes()).await?;
+    drop(stdin);
+    let output =
+      tokio::time::timeout(CREDENTIAL_HELPER_TIMEOUT, child.wait_with_output())
+        .await
+        .context("Timed out")??;
+    if !output.status.success() {
+      bail!("Exited with {}", output.status);
+    }
+    CredentialHelperOutput::parse(&output.stdout)
+      .context("Failed parsing the output")
+  }
+}
+
+/// Splits a command line into its arguments like a POSIX shell, so paths
+/// with spaces can be quoted or escaped. Backslashes outside of quotes are
+/// kept on Windows, where they separate path components.
+fn split_command(command: &str) -> Result<Vec<String>, AnyError> {
+  let mut args = Vec::new();
+  let mut current: Option<String> = None;
+  let mut chars = command.chars();
+  while let Some(c) = chars.next() {
+    match c {
+      '\'' => {
+        let arg = current.get_or_insert_with(String::new);
+        loop {
+          match chars.next() {
+            Some('\'') => break,
+            Some(c) => arg.push(c),
+            None => bail!("Unterminated single quote"),
+          }
+        }
+      }
+      '"' => {
+        let arg = current.get_or_insert_with(String::new);
+        loop {
+          match chars.next() {
+            Some('"') => break,
+            Some('\\') => match chars.next() {
+              Some(c @ ('"' | '\\' | '$' | '`')) => arg.push(c),
+              Some('\n') => {}
+              Some(c) => {
+                arg.push('\\');
+                arg.push(c);
+              }
+              None => bail!("Unterminated double quote"),
+            },
+            Some(c) => arg.push(c),
+            None => bail!("Unterminated double quote"),
+          }
+        }
+      }
+      '\\' if !cfg!(windows) => match chars.next() {
+        Some('\n') => {}
+        Some(c) => current.get_or_insert_with(String::new).push(c),
+        None => bail!("Trailing backslash"),
+      },
+      c if c.is_whitespace() => {
+        if let Some(arg) = current.take() {
+          args.push(arg);
+        }
+      }
+      c => current.get_or_insert_with(String::new).push(c),
+    }
+  }
+  args.extend(current);
+  Ok(args)
+}
+
+fn host_with_port(specifier: &ModuleSpecifier) -> Option<String> {
+  let host = specifier.host_str()?;
+  Some(match specifier.port() {
+    Some(port) => format!("{}:{}", host, port),
+    None => host.to_string(),
+  })
 }
 
 #[cfg(test)]
@@ -366,4 +762,126 @@ mod tests {
       assert_eq!(actual, expected);
     }
   }
+
+  #[test]
+  fn test_netrc() {
+    let netrc = r#"
+# private hosts
+machine modules.example.com
+  login deploy
+  password s3cret
+machine tokens.example.com password abc123 account ignored
+
+macdef init
+machine ignored.example.com password nope
+
+default login anonymous password guest
+"#;
+    assert_eq!(
+      parse_netrc(netrc),
+      vec![
+        NetrcEntry {
+          machine: Some("modules.example.com".to_string()),
+          token: AuthTokenData::Basic {
+            username: "deploy".to_string(),
+            password: "s3cret".to_string(),
+          },
+        },
+        NetrcEntry {
+          machine: Some("tokens.example.com".to_string()),
+          token: AuthTokenData::Bearer("abc123".to_string()),
+        },
+        NetrcEntry {
+          machine: None,
+          token: AuthTokenData::Basic {
+            username: "anonymous".to_string(),
+            password: "guest".to_string(),
+          },
+        },
+      ]
+    );
+
+    let auth_tokens = AuthTokens::new(Some("xyz@tokens.example.com".into()))
+      .with_netrc("machine modules.example.com login a password b\nmachine tokens.example.com password c");
+    let fixture = resolve_url("https://MODULES.example.com/mod.ts").unwrap();
+    assert_eq!(
+      auth_tokens.get(&fixture).unwrap().to_string(),
+      format!("Basic {}", BASE64_STANDARD.encode("a:b"))
+    );
+    // DENO_AUTH_TOKENS takes precedence
+    let fixture = resolve_url("https://tokens.example.com/mod.ts").unwrap();
+    assert_eq!(auth_tokens.get(&fixture).unwrap().to_string(), "Bearer xyz");
+    // netrc entries don't match subdomains
+    let fixture = resolve_url("https://www.modules.example.com/").unwrap();
+    assert_eq!(auth_tokens.get(&fixture), None);
+  }
+
+  #[test]
+  fn test_credential_helper_output() {
+    let output =
+      CredentialHelperOutput::parse(br#"{"token":"abc","expiresIn":60}"#)
+        .unwrap();
+    assert_eq!(output.expires_in, Some(60));
+    assert_eq!(
+      output.into_token(),
+      Some(AuthTokenData::Bearer("abc".to_string()))
+    );
+    let output =
+      CredentialHelperOutput::parse(br#"{"username":"a","password":"b"}"#)
+        .unwrap();
+    assert_eq!(
+      output.into_token(),
+      Some(AuthTokenData::Basic {
+        username: "a".to_string(),
+        password: "b".to_string(),
+      })
+    );
+    let output = CredentialHelperOutput::parse(b" \n").unwrap();
+    assert_eq!(output, CredentialHelperOutput::default());
+    assert_eq!(output.into_token(), None);
+    assert!(CredentialHelperOutput::parse(b"token=abc").is_err());
+  }
+
+  #[tokio::test]
+  async fn test_credential_helper_locks_per_host() {
+    let helper = CredentialHelper {
+      command: vec!["helper".to_string()],
+      cache: Default::default(),
+    };
+    let a = resolve_url("https://a.example/mod.ts").unwrap();
+    let b = resolve_url("https://b.example/mod.ts").unwrap();
+    let a_slot = helper.slot(&a, "a.example");
+    let _guard = a_slot.lock().await;
+    // a helper running for one host doesn't hold up the others
+    assert!(helper.slot(&b, "b.example").try_lock().is_ok());
+    assert!(helper.slot(&a, "a.example").try_lock().is_err());
+  }
+
+  #[test]
+  fn test_split_command() {
+    assert_eq!(
+      split_command("helper --flag value").unwrap(),
+      vec!["helper", "--flag", "value"]
+    );
+    assert_eq!(
+      split_command(r#"  "/opt/my tools/helper" 'a b' "" "#).unwrap(),
+      vec!["/opt/my tools/helper", "a b", ""]
+    );
+    #[cfg(not(windows))]
+    assert_eq!(split_command(r"c\ d").unwrap(), vec!["c d"]);
+    #[cfg(windows)]
+    assert_eq!(
+      split_command(r"C:\tools\helper.exe").unwrap(),
+      vec![r"C:\tools\helper.exe"]
+    );
+    assert_eq!(
+      split_command(r#""say \"hi\"" 'it\s' a"b"'c'"#).unwrap(),
+      vec![r#"say "hi""#, r"it\s", "abc"]
+    );
+    assert_eq!(split_command(" \t\n").unwrap(), Vec::<String>::new());
+    assert!(split_command("'unterminated").is_err());
+    assert!(split_command("\"unterminated").is_err());
+    #[cfg(not(windows))]
+    assert!(split_command("trailing\\").is_err());
+  }
 }
@@ -149,6 +149,96 @@ const RESULT_KEYS: &[&str] =
 fn run_exec_time(
   deno_exe: &Path,
   target_dir: &PathRef,
+) -> Result<HashMap<String, HashMap<String, f64>>> {
+  let commands = EXEC_TIME_BENCHMARKS
+    .iter()
+    .map(|(name, args, return_code)| {
+      let ret_code_test = if let Some(code) = return_code {
+        // Bash test which asserts the return code value of the previous command
+        // $? contains the return code of the previous command
+        format!("; test $? -eq {code}")
+      } else {
+        "".to_string()
+      };
+      let command = format!(
+        "{} {} {}",
+        deno_exe.to_str().unwrap(),
+        args.join(" "),
+        ret_code_test
+      );
+      (name.to_string(), command)
+    })
+    .collect::<Vec<_>>();
+  run_hyperfine(&commands, target_dir)
+}
+
+/// The list of the tuples of the benchmark name and `deno compile` arguments
+/// of the binaries whose startup time is measured.
+const COMPILE_STARTUP_BENCHMARKS: &[(&str, &[&str])] = &[
+  ("compiled_hello", &[]),
+  ("compiled_hello_code_cache", &["--code-cache"]),
+];
+
+/// Measures the startup time of binaries created by `deno compile`, which
+/// includes verifying the integrity of the embedded data and loading the
+/// code cache.
+fn run_compile_startup(
+  deno_exe: &Path,
+  target_dir: &PathRef,
+) -> Result<HashMap<String, HashMap<String, f64>>> {
+  let mut commands = Vec::new();
+  for (name, args) in COMPILE_STARTUP_BENCHMARKS {
+    let output = target_dir.join(format!("bench_{name}"));
+    let output_str = output.to_string();
+    let mut command = vec![
+      deno_exe.to_str().unwrap(),
+      "compile",
+      "--output",
+      output_str.as_str(),
+    ];
+    command.extend(args.iter());
+    command.push("tests/testdata/run/002_hello.ts");
+    test_util::run(&command, None, None, None, true);
+    commands.push((name.to_string(), output_str));
+  }
+
+  // the integrity of the embedded files is checked as they're read, so
+  // files that are never read shouldn't slow down the startup
+  let include_dir = target_dir.join("bench_compile_include");
+  include_dir.create_dir_all();
+  for i in 0..64 {
+    include_dir
+      .join(format!("file_{i}.bin"))
+      .write(vec![i as u8; 1024 * 1024]);
+  }
+  let include_dir_str = include_dir.to_string();
+  let output = target_dir.join("bench_compiled_hello_large_include");
+  let output_str = output.to_string();
+  test_util::run(
+    &[
+      deno_exe.to_str().unwrap(),
+      "compile",
+      "--output",
+      output_str.as_str(),
+      "--include",
+      include_dir_str.as_str(),
+      "tests/testdata/run/002_hello.ts",
+    ],
+    None,
+    None,
+    None,
+    true,
+  );
+  commands.push(("compiled_hello_large_include".to_string(), output_str));
+
+  run_hyperfine(&commands, target_dir)
+}
+
+/// Runs each command of the `(name, command)` pairs with hyperfine and
+/// returns the timings by name.
+fn run_hyperfine(
+  commands: &[(String, String)],
+  target_dir: &PathRef,
 ) -> Result<HashMap<String, HashMap<String, f64>>> {
   let hyperfine_exe = test_util::prebuilt_tool_path("hyperfine").to_string();
 
@@ -165,22 +255,7 @@ fn run_exec_time(
   .iter()
   .map(|s| s.to_string())
   .collect::<Vec<_>>();
-
-  for (_, args, return_code) in EXEC_TIME_BENCHMARKS {
-    let ret_code_test = if let Some(code) = return_code {
-      // Bash test which asserts the return code value of the previous command
-      // $? contains the return code of the previous command
-      format!("; test $? -eq {code}")
-    } else {
-      "".to_string()
-    };
-    command.push(format!(
-      "{} {} {}",
-      deno_exe.to_str().unwrap(),
-      args.join(" "),
-      ret_code_test
-    ));
-  }
+  command.extend(commands.iter().map(|(_, command)| command.clone()));
 
   test_util::run(
     &command.iter().map(|s| s.as_ref()).collect::<Vec<_>>(),
@@ -192,7 +267,7 @@ fn run_exec_time(
 
   let mut results = HashMap::<String, HashMap<String, f64>>::new();
   let hyperfine_results = read_json(benchmark_file.as_path())?;
-  for ((name, _, _), data) in EXEC_TIME_BENCHMARKS.iter().zip(
+  for ((name, _), data) in commands.iter().zip(
     hyperfine_results
       .as_object()
       .unwrap()
@@ -360,6 +435,7 @@ async fn main() -> Result<()> {
 
   let mut benchmarks = vec![
     "exec_time",
+    "compile_startup",
     "binary_size",
     "cargo_deps",
     "lsp",
@@ -412,6 +488,11 @@ async fn main() -> Result<()> {
     new_data.benchmark = exec_times;
   }
 
+  if benchmarks.contains(&"compile_startup") {
+    let startup_times = run_compile_startup(&deno_exe, &target_dir)?;
+    new_data.benchmark.extend(startup_times);
+  }
+
   if benchmarks.contains(&"binary_size") {
     let binary_sizes = get_binary_sizes(target_dir.as_path())?;
     new_data.binary_size = binary_sizes;
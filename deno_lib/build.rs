@@ -5,6 +5,9 @@ use std::path::PathBuf;
 
 use deno_runtime::*;
 
+// Only the TypeScript version is needed when the "type-check" feature is
+// disabled, the compiler snapshot is skipped.
+#[cfg_attr(not(feature = "type-check"), allow(dead_code))]
 mod ts {
     use std::env;
 
@@ -397,11 +400,14 @@ fn main() {
     println!("cargo:rustc-env=TARGET={}", env::var("TARGET").unwrap());
     println!("cargo:rustc-env=PROFILE={}", env::var("PROFILE").unwrap());
 
-    let c = PathBuf::from(env::var_os("CARGO_MANIFEST_DIR").unwrap());
     let o = PathBuf::from(env::var_os("OUT_DIR").unwrap());
 
-    let compiler_snapshot_path = o.join("COMPILER_SNAPSHOT.bin");
-    ts::create_compiler_snapshot(compiler_snapshot_path, &c);
+    #[cfg(feature = "type-check")]
+    {
+        let c = PathBuf::from(env::var_os("CARGO_MANIFEST_DIR").unwrap());
+        let compiler_snapshot_path = o.join("COMPILER_SNAPSHOT.bin");
+        ts::create_compiler_snapshot(compiler_snapshot_path, &c);
+    }
 
     #[cfg(not(feature = "hmr"))]
     {
@@ -6,9 +6,11 @@ use deno_core::parking_lot::MutexGuard;
 use deno_core::unsync::spawn_blocking;
 use deno_runtime::deno_webstorage::rusqlite;
 use deno_runtime::deno_webstorage::rusqlite::Connection;
+use deno_runtime::deno_webstorage::rusqlite::OpenFlags;
 use deno_runtime::deno_webstorage::rusqlite::OptionalExtension;
 use deno_runtime::deno_webstorage::rusqlite::Params;
 use once_cell::sync::OnceCell;
+use serde::Serialize;
 use std::io::IsTerminal;
 use std::path::Path;
 use std::path::PathBuf;
@@ -99,6 +101,23 @@ impl CacheDBConfiguration {
   }
 }
 
+/// The state of a cache database file, found without initializing or
+/// repairing it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase", tag = "status")]
+pub enum CacheDBHealth {
+  /// The database hasn't been created yet.
+  Missing,
+  /// The database is intact and was written by this version of Deno.
+  Ok,
+  /// The database was written by another version of Deno, so it is cleared
+  /// the next time it is used.
+  Outdated { version: String },
+  /// The database can't be read, so it is deleted and recreated the next
+  /// time it is used.
+  Corrupt { message: String },
+}
+
 #[derive(Debug)]
 enum ConnectionState {
   Connected(Connection),
@@ -215,6 +234,42 @@ impl CacheDB {
     });
   }
 
+  /// Checks the database file at `path` the way it would be opened by
+  /// `version`, without modifying it.
+  pub fn check_health(path: &Path, version: &str) -> CacheDBHealth {
+    if !path.exists() {
+      return CacheDBHealth::Missing;
+    }
+    let check = || -> Result<CacheDBHealth, rusqlite::Error> {
+      let conn = Connection::open_with_flags(
+        path,
+        OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+      )?;
+      let result = conn
+        .query_row("PRAGMA quick_check", [], |row| row.get::<_, String>(0))?;
+      if result != "ok" {
+        return Ok(CacheDBHealth::Corrupt { message: result });
+      }
+      let existing_version = conn
+        .query_row(
+          "SELECT value FROM info WHERE key='CLI_VERSION' LIMIT 1",
+          [],
+          |row| row.get::<_, String>(0),
+        )
+        .optional()?
+        .unwrap_or_default();
+      if existing_version != version {
+        return Ok(CacheDBHealth::Outdated {
+          version: existing_version,
+        });
+      }
+      Ok(CacheDBHealth::Ok)
+    };
+    check().unwrap_or_else(|err| CacheDBHealth::Corrupt {
+      message: err.to_string(),
+    })
+  }
+
   /// Open the connection in memory or on disk.
   fn actually_open_connection(
     &self,
@@ -507,6 +562,36 @@ mod tests {
     assert_eq!(res, Some("1".into()));
   }
 
+  #[test]
+  fn check_health() {
+    let temp_dir = TempDir::new();
+    let path = temp_dir.path().join("data");
+    assert_eq!(
+      CacheDB::check_health(path.as_path(), "1.0"),
+      CacheDBHealth::Missing
+    );
+
+    let conn = Connection::open(&path).unwrap();
+    CacheDB::initialize_connection(&TEST_DB, &conn, "1.0").unwrap();
+    drop(conn);
+    assert_eq!(
+      CacheDB::check_health(path.as_path(), "1.0"),
+      CacheDBHealth::Ok
+    );
+    assert_eq!(
+      CacheDB::check_health(path.as_path(), "2.0"),
+      CacheDBHealth::Outdated {
+        version: "1.0".to_string()
+      }
+    );
+
+    path.write("not a database");
+    assert!(matches!(
+      CacheDB::check_health(path.as_path(), "1.0"),
+      CacheDBHealth::Corrupt { .. }
+    ));
+  }
+
   #[tokio::test]
   async fn bad_sql() {
     let db = CacheDB::in_memory(&BAD_SQL_TEST_DB, "1.0");
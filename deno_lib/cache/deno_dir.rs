@@ -131,6 +131,11 @@ impl DenoDir {
     self.root.join("remote")
   }
 
+  /// Folder of the npm packuments and tarballs stored by `deno cache --serve`.
+  pub fn cache_server_folder_path(&self) -> PathBuf {
+    self.root.join("cache_server")
+  }
+
   /// Path to the origin data cache folder.
   pub fn origin_data_folder_path(&self) -> PathBuf {
     // TODO(@crowlKats): change to origin_data for 2.0
@@ -43,7 +43,9 @@ mod module_info;
 mod node;
 mod parsed_source;
 
+pub use cache_db::CacheDB;
 pub use cache_db::CacheDBHash;
+pub use cache_db::CacheDBHealth;
 pub use caches::Caches;
 pub use check::TypeCheckCache;
 pub use code_cache::CodeCache;
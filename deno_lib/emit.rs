@@ -183,11 +183,21 @@ impl Emitter {
     specifier: &ModuleSpecifier,
     module_kind: deno_ast::ModuleKind,
   ) -> Result<String, AnyError> {
-    let media_type = MediaType::from_specifier(specifier);
     let source_code = tokio::fs::read_to_string(
       ModuleSpecifier::to_file_path(specifier).unwrap(),
     )
     .await?;
+    self.emit_for_hmr(specifier, source_code, module_kind)
+  }
+
+  /// Emits the provided source for hot replacing the module at `specifier`.
+  pub fn emit_for_hmr(
+    &self,
+    specifier: &ModuleSpecifier,
+    source_code: String,
+    module_kind: deno_ast::ModuleKind,
+  ) -> Result<String, AnyError> {
+    let media_type = MediaType::from_specifier(specifier);
     match media_type {
       MediaType::TypeScript
       | MediaType::Mts
@@ -50,10 +50,12 @@ use crate::resolver::CliNodeResolver;
 use crate::resolver::CliSloppyImportsResolver;
 use crate::resolver::NpmModuleLoader;
 use crate::resolver::SloppyImportsCachedFs;
+#[cfg(feature = "compile")]
 use crate::standalone::DenoCompileBinaryWriter;
 use crate::tools::check::TypeChecker;
 use crate::tools::coverage::CoverageCollector;
 use crate::tools::lint::LintRuleProvider;
+use crate::tools::run::hmr::HmrModuleReplacer;
 use crate::tools::run::hmr::HmrRunner;
 use crate::tsc::TypeCheckingCjsTracker;
 use crate::util::file_watcher::WatcherCommunicator;
@@ -336,11 +338,19 @@ impl CliFactory {
     })
   }
 
-  pub fn http_client_provider(&self) -> &Arc<HttpClientProvider> {
-    self.services.http_client_provider.get_or_init(|| {
-      Arc::new(HttpClientProvider::new(
-        Some(self.root_cert_store_provider().clone()),
-        self.flags.unsafely_ignore_certificate_errors.clone(),
+  pub fn http_client_provider(
+    &self,
+  ) -> Result<&Arc<HttpClientProvider>, AnyError> {
+    self.services.http_client_provider.get_or_try_init(|| {
+      let cli_options = self.cli_options()?;
+      Ok(Arc::new(
+        HttpClientProvider::new(
+          Some(self.root_cert_store_provider().clone()),
+          self.flags.unsafely_ignore_certificate_errors.clone(),
+        )
+        .with_client_certs(cli_options.client_certs().to_vec())
+        .with_download_policy(cli_options.download_policy().clone())
+        .with_cache_server(cli_options.cache_server().cloned()),
       ))
     })
   }
@@ -348,14 +358,17 @@ impl CliFactory {
   pub fn file_fetcher(&self) -> Result<&Arc<FileFetcher>, AnyError> {
     self.services.file_fetcher.get_or_try_init(|| {
       let cli_options = self.cli_options()?;
-      Ok(Arc::new(FileFetcher::new(
+      let mut file_fetcher = FileFetcher::new(
         self.http_cache()?.clone(),
         cli_options.cache_setting(),
         !cli_options.no_remote(),
-        self.http_client_provider().clone(),
+        self.http_client_provider()?.clone(),
         self.blob_store().clone(),
         Some(self.text_only_progress_bar().clone()),
-      )))
+      );
+      file_fetcher.enable_revalidation_summary();
+      file_fetcher.set_mirrors(cli_options.mirrors().clone());
+      Ok(Arc::new(file_fetcher))
     })
   }
 
@@ -447,7 +460,7 @@ impl CliFactory {
                 },
                 maybe_lockfile: cli_options.maybe_lockfile().cloned(),
                 fs: fs.clone(),
-                http_client_provider: self.http_client_provider().clone(),
+                http_client_provider: self.http_client_provider()?.clone(),
                 npm_cache_dir: self.npm_cache_dir()?.clone(),
                 cache_setting: cli_options.cache_setting(),
                 text_only_progress_bar: self.text_only_progress_bar().clone(),
@@ -844,6 +857,7 @@ impl CliFactory {
     })
   }
 
+  #[cfg(feature = "compile")]
   pub async fn create_compile_binary_writer(
     &self,
   ) -> Result<DenoCompileBinaryWriter, AnyError> {
@@ -853,7 +867,7 @@ impl CliFactory {
       self.deno_dir()?,
       self.emitter()?,
       self.file_fetcher()?,
-      self.http_client_provider(),
+      self.http_client_provider()?,
       self.npm_resolver().await?.as_ref(),
       self.workspace_resolver().await?.as_ref(),
       cli_options.npm_system_info(),
@@ -962,6 +976,19 @@ impl CliFactory {
     } else {
       None
     };
+    let create_module_replacer = {
+      let emitter = self.emitter()?.clone();
+      let cjs_tracker = self.cjs_tracker()?.clone();
+      let fn_: crate::worker::CreateModuleReplacerCb =
+        Box::new(move |session| {
+          Box::new(HmrModuleReplacer::new(
+            cjs_tracker.clone(),
+            emitter.clone(),
+            session,
+          ))
+        });
+      Some(fn_)
+    };
     let create_coverage_collector =
       if let Some(coverage_dir) = cli_options.coverage_dir() {
         let coverage_dir = PathBuf::from(coverage_dir);
@@ -1002,6 +1029,7 @@ impl CliFactory {
         .unsafely_ignore_certificate_errors()
         .clone(),
       create_hmr_runner,
+      create_module_replacer,
       create_coverage_collector,
       node_ipc: cli_options.node_ipc_fd(),
       serve_port: cli_options.serve_port(),
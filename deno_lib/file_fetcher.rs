@@ -4,13 +4,16 @@ use crate::args::CacheSetting;
 use crate::auth_tokens::AuthTokens;
 use crate::cache::HttpCache;
 use crate::colors;
+use crate::http_util::update_not_modified_headers;
 use crate::http_util::CacheSemantics;
 use crate::http_util::FetchOnceArgs;
 use crate::http_util::FetchOnceResult;
 use crate::http_util::HttpClientProvider;
 use crate::util::progress_bar::ProgressBar;
+use crate::util::retry::RetryPolicy;
 
 use deno_ast::MediaType;
+use deno_core::anyhow::bail;
 use deno_core::anyhow::Context;
 use deno_core::error::custom_error;
 use deno_core::error::generic_error;
@@ -25,10 +28,10 @@ use deno_path_util::url_to_file_path;
 use deno_runtime::deno_permissions::PermissionsContainer;
 use deno_runtime::deno_web::BlobStore;
 use http::header;
+use http::StatusCode;
 use log::debug;
 use std::borrow::Cow;
 use std::collections::HashMap;
-use std::env;
 use std::fs;
 use std::path::PathBuf;
 use std::sync::Arc;
@@ -194,6 +197,94 @@ pub struct FetchNoFollowOptions<'a> {
   pub maybe_checksum: Option<&'a LoaderChecksum>,
 }
 
+/// What happened to the stale cache entries that were revalidated with
+/// `CacheSetting::RespectHeaders`.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct RevalidationSummary {
+  /// Entries the server confirmed are unchanged.
+  pub unchanged: Vec<ModuleSpecifier>,
+  /// Entries that were downloaded again because they changed.
+  pub updated: Vec<ModuleSpecifier>,
+}
+
+impl RevalidationSummary {
+  pub fn is_empty(&self) -> bool {
+    self.unchanged.is_empty() && self.updated.is_empty()
+  }
+}
+
+/// Rewrite rules from the URL prefixes of remote modules to the prefixes of
+/// mirrors that serve the same files. Only the request goes to the mirror: the
+/// module keeps its original specifier, which is also what the cache and the
+/// lockfile use, so they are the same with and without mirrors.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Mirrors {
+  rules: Vec<(Url, Url)>,
+}
+
+impl Mirrors {
+  /// Creates the rules from `(original prefix, mirror prefix)` pairs.
+  pub fn new(
+    rules: impl IntoIterator<Item = (Url, Url)>,
+  ) -> Result<Self, AnyError> {
+    let rules = rules.into_iter().collect::<Vec<_>>();
+    for url in rules
+      .iter()
+      .flat_map(|(original, mirror)| [original, mirror])
+    {
+      if !matches!(url.scheme(), "http" | "https") {
+        bail!("Mirror URL '{}' must be an http or https URL.", url);
+      }
+      // otherwise https://deno.land/x would also match https://deno.land/xyz
+      if !url.path().ends_with('/')
+        || url.query().is_some()
+        || url.fragment().is_some()
+      {
+        bail!("Mirror URL '{}' must end with a slash.", url);
+      }
+    }
+    Ok(Self { rules })
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.rules.is_empty()
+  }
+
+  /// The URL to download `url` from, when a mirror serves it. The longest
+  /// matching prefix wins.
+  pub fn rewrite(&self, url: &Url) -> Option<Url> {
+    Self::replace_prefix(
+      url,
+      self
+        .rules
+        .iter()
+        .map(|(original, mirror)| (original, mirror)),
+    )
+  }
+
+  /// The original URL of `url` on a mirror, so that redirects of the mirror
+  /// are followed as redirects of the original URLs.
+  pub fn restore(&self, url: &Url) -> Option<Url> {
+    Self::replace_prefix(
+      url,
+      self
+        .rules
+        .iter()
+        .map(|(original, mirror)| (mirror, original)),
+    )
+  }
+
+  fn replace_prefix<'a>(
+    url: &Url,
+    rules: impl Iterator<Item = (&'a Url, &'a Url)>,
+  ) -> Option<Url> {
+    let (from, to) = rules
+      .filter(|(from, _)| url.as_str().starts_with(from.as_str()))
+      .max_by_key(|(from, _)| from.as_str().len())?;
+    Url::parse(&format!("{}{}", to, &url.as_str()[from.as_str().len()..])).ok()
+  }
+}
+
 /// A structure for resolving, fetching and caching source files.
 #[derive(Debug)]
 pub struct FileFetcher {
@@ -206,6 +297,8 @@ pub struct FileFetcher {
   blob_store: Arc<BlobStore>,
   download_log_level: log::Level,
   progress_bar: Option<ProgressBar>,
+  maybe_revalidation_summary: Option<Mutex<RevalidationSummary>>,
+  mirrors: Mirrors,
 }
 
 impl FileFetcher {
@@ -218,7 +311,7 @@ impl FileFetcher {
     progress_bar: Option<ProgressBar>,
   ) -> Self {
     Self {
-      auth_tokens: AuthTokens::new(env::var("DENO_AUTH_TOKENS").ok()),
+      auth_tokens: AuthTokens::from_env(),
       allow_remote,
       memory_files: Default::default(),
       cache_setting,
@@ -227,6 +320,8 @@ impl FileFetcher {
       blob_store,
       download_log_level: log::Level::Info,
       progress_bar,
+      maybe_revalidation_summary: None,
+      mirrors: Default::default(),
     }
   }
 
@@ -234,6 +329,31 @@ impl FileFetcher {
     &self.cache_setting
   }
 
+  /// Starts keeping track of the cache entries that are revalidated, so
+  /// that a summary of them can be taken.
+  pub fn enable_revalidation_summary(&mut self) {
+    self.maybe_revalidation_summary = Some(Default::default());
+  }
+
+  /// Takes the summary of the cache entries revalidated since it was last
+  /// taken.
+  pub fn take_revalidation_summary(&self) -> RevalidationSummary {
+    match &self.maybe_revalidation_summary {
+      Some(summary) => std::mem::take(&mut *summary.lock()),
+      None => Default::default(),
+    }
+  }
+
+  /// Sets the credentials sent to the hosts of remote modules.
+  pub fn set_auth_tokens(&mut self, auth_tokens: AuthTokens) {
+    self.auth_tokens = auth_tokens;
+  }
+
+  /// Sets the mirrors remote modules are downloaded from.
+  pub fn set_mirrors(&mut self, mirrors: Mirrors) {
+    self.mirrors = mirrors;
+  }
+
   /// Sets the log level to use when outputting the download message.
   pub fn set_download_log_level(&mut self, level: log::Level) {
     self.download_log_level = level;
@@ -388,7 +508,9 @@ impl FileFetcher {
       );
     }
 
-    let maybe_etag_cache_entry = self
+    // the cached entry, if any, is revalidated with the server instead of
+    // being downloaded again
+    let mut maybe_cache_entry = self
       .http_cache
       .cache_item_key(specifier)
       .ok()
@@ -403,27 +525,53 @@ impl FileFetcher {
           )
           .ok()
           .flatten()
-      })
-      .and_then(|cache_entry| {
-        cache_entry
-          .metadata
-          .headers
-          .get("etag")
-          .cloned()
-          .map(|etag| (cache_entry, etag))
       });
-    let maybe_auth_token = self.auth_tokens.get(specifier);
+    let maybe_etag = maybe_cache_entry
+      .as_ref()
+      .and_then(|entry| entry.metadata.headers.get("etag").cloned());
+    let maybe_last_modified = maybe_cache_entry
+      .as_ref()
+      .and_then(|entry| entry.metadata.headers.get("last-modified").cloned());
+    let maybe_revalidation_summary =
+      self.maybe_revalidation_summary.as_ref().filter(|_| {
+        *cache_setting == CacheSetting::RespectHeaders
+          && maybe_cache_entry.is_some()
+      });
+    // a cache server is the first tier for what isn't cached at all
+    if maybe_cache_entry.is_none() && *cache_setting != CacheSetting::ReloadAll
+    {
+      if let Some(file_or_redirect) = self
+        .fetch_from_cache_server(specifier, maybe_checksum)
+        .await
+      {
+        return Ok(file_or_redirect);
+      }
+    }
+
+    // the request may go to a mirror, while the file keeps its specifier
+    let maybe_mirror_url = self.mirrors.rewrite(specifier);
+    if let Some(mirror_url) = &maybe_mirror_url {
+      debug!("Fetching '{}' from mirror '{}'", specifier, mirror_url);
+    }
+    let request_url = maybe_mirror_url.as_ref().unwrap_or(specifier);
+    let mut maybe_auth_token = self.auth_tokens.resolve(request_url).await;
 
     async fn handle_request_or_server_error(
-      retried: &mut bool,
+      attempt: &mut u32,
+      maybe_retry_policy: &Option<RetryPolicy>,
       specifier: &Url,
       err_str: String,
     ) -> Result<(), AnyError> {
-      // Retry once, and bail otherwise.
-      if !*retried {
-        *retried = true;
+      // Retry once after 50ms, unless configured otherwise, and bail when
+      // out of attempts.
+      let (max_attempts, wait) = match maybe_retry_policy {
+        Some(policy) => (policy.max_attempts, policy.backoff(*attempt)),
+        None => (2, std::time::Duration::from_millis(50)),
+      };
+      if *attempt < max_attempts {
         log::debug!("Import '{}' failed: {}. Retrying...", specifier, err_str);
-        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        tokio::time::sleep(wait).await;
+        *attempt += 1;
         Ok(())
       } else {
         Err(generic_error(format!(
@@ -433,28 +581,49 @@ impl FileFetcher {
       }
     }
 
-    let mut retried = false; // retry intermittent failures
+    // retry intermittent failures
+    let retry_policy = self.http_client_provider.download_policy().retry;
+    let mut attempt = 1;
+    let mut refreshed_auth = false; // retry once with refreshed credentials
     let result = loop {
       let result = match self
         .http_client_provider
-        .get_or_create()?
+        .get_or_create_for_url(request_url)?
         .fetch_no_follow(FetchOnceArgs {
-          url: specifier.clone(),
+          url: request_url.clone(),
           maybe_accept: maybe_accept.map(ToOwned::to_owned),
-          maybe_etag: maybe_etag_cache_entry
-            .as_ref()
-            .map(|(_, etag)| etag.clone()),
+          maybe_etag: maybe_etag.clone(),
+          maybe_last_modified: maybe_last_modified.clone(),
           maybe_auth_token: maybe_auth_token.clone(),
           maybe_auth: maybe_auth.clone(),
           maybe_progress_guard: maybe_progress_guard.as_ref(),
         })
         .await?
       {
-        FetchOnceResult::NotModified => {
-          let (cache_entry, _) = maybe_etag_cache_entry.unwrap();
+        FetchOnceResult::NotModified(headers) => {
+          let mut cache_entry = maybe_cache_entry.take().unwrap();
+          update_not_modified_headers(
+            &mut cache_entry.metadata.headers,
+            headers,
+          );
+          // also resets the download time the entry's freshness is based on
+          self.http_cache.set(
+            specifier,
+            cache_entry.metadata.headers.clone(),
+            &cache_entry.content,
+          )?;
+          if let Some(summary) = maybe_revalidation_summary {
+            summary.lock().unchanged.push(specifier.clone());
+          }
           FileOrRedirect::from_deno_cache_entry(specifier, cache_entry)
         }
-        FetchOnceResult::Redirect(redirect_url, headers) => {
+        FetchOnceResult::Redirect(mut redirect_url, mut headers) => {
+          if maybe_mirror_url.is_some() {
+            redirect_url =
+              self.mirrors.restore(&redirect_url).unwrap_or(redirect_url);
+            // the cached location is resolved against the original specifier
+            headers.insert("location".to_string(), redirect_url.to_string());
+          }
           self.http_cache.set(specifier, headers, &[])?;
           Ok(FileOrRedirect::Redirect(redirect_url))
         }
@@ -463,6 +632,15 @@ impl FileFetcher {
           if let Some(checksum) = &maybe_checksum {
             checksum.check_source(&bytes)?;
           }
+          if let Some(summary) = maybe_revalidation_summary {
+            let cache_entry = maybe_cache_entry.as_ref().unwrap();
+            let mut summary = summary.lock();
+            if *cache_entry.content == *bytes {
+              summary.unchanged.push(specifier.clone());
+            } else {
+              summary.updated.push(specifier.clone());
+            }
+          }
           Ok(FileOrRedirect::File(File {
             specifier: specifier.clone(),
             maybe_headers: Some(headers),
@@ -470,18 +648,47 @@ impl FileFetcher {
           }))
         }
         FetchOnceResult::RequestError(err) => {
-          handle_request_or_server_error(&mut retried, specifier, err).await?;
+          handle_request_or_server_error(
+            &mut attempt,
+            &retry_policy,
+            specifier,
+            err,
+          )
+          .await?;
           continue;
         }
         FetchOnceResult::ServerError(status) => {
           handle_request_or_server_error(
-            &mut retried,
+            &mut attempt,
+            &retry_policy,
             specifier,
             status.to_string(),
           )
           .await?;
           continue;
         }
+        FetchOnceResult::Unauthorized => {
+          if !refreshed_auth {
+            refreshed_auth = true;
+            if let Some(rejected) = &maybe_auth_token {
+              let maybe_refreshed =
+                self.auth_tokens.refresh(request_url, rejected).await;
+              if maybe_refreshed.is_some() {
+                log::debug!(
+                  "Import '{}' was unauthorized. Retrying with refreshed credentials...",
+                  specifier
+                );
+                maybe_auth_token = maybe_refreshed;
+                continue;
+              }
+            }
+          }
+          Err(generic_error(format!(
+            "Import '{}' failed: {}",
+            specifier,
+            StatusCode::UNAUTHORIZED
+          )))
+        }
       };
       break result;
     };
@@ -490,6 +697,44 @@ impl FileFetcher {
     result
   }
 
+  /// Fetches the cache entry of `specifier` from the cache server, when it
+  /// has one that matches the lockfile checksum, and stores it in the local
+  /// cache.
+  async fn fetch_from_cache_server(
+    &self,
+    specifier: &ModuleSpecifier,
+    maybe_checksum: Option<&LoaderChecksum>,
+  ) -> Option<FileOrRedirect> {
+    // only the lockfile vouches for what the cache server has, so a module
+    // the lockfile has no checksum of and a redirect come from the origin
+    let checksum = maybe_checksum?;
+    let (headers, content) = self
+      .http_client_provider
+      .fetch_remote_from_cache_server(specifier)
+      .await?;
+    if headers.contains_key("location") {
+      return None;
+    }
+    if let Err(err) = checksum.check_source(&content) {
+      log::warn!(
+        "{} The cache server has a different '{}' than the lockfile, so it is downloaded from its origin instead.\n\n{:#}",
+        colors::yellow("Warning"),
+        specifier,
+        err
+      );
+      return None;
+    }
+    let file = File {
+      specifier: specifier.clone(),
+      maybe_headers: Some(headers.clone()),
+      source: Arc::from(content.as_slice()),
+    };
+    if let Err(err) = self.http_cache.set(specifier, headers, &content) {
+      debug!("Failed caching '{}': {:#}", specifier, err);
+    }
+    Some(FileOrRedirect::File(file))
+  }
+
   /// Returns if the cache should be used for a given specifier.
   fn should_use_cache(
     &self,
@@ -1129,6 +1374,87 @@ mod tests {
     );
   }
 
+  #[test]
+  fn test_mirrors() {
+    let url = |text: &str| Url::parse(text).unwrap();
+    let mirrors = Mirrors::new([
+      (
+        url("https://deno.land/"),
+        url("https://mirror.internal/deno/"),
+      ),
+      (
+        url("https://deno.land/x/"),
+        url("https://mirror.internal/deno_x/"),
+      ),
+    ])
+    .unwrap();
+    assert_eq!(
+      mirrors.rewrite(&url("https://deno.land/std/path/mod.ts")),
+      Some(url("https://mirror.internal/deno/std/path/mod.ts"))
+    );
+    assert_eq!(
+      mirrors.rewrite(&url("https://deno.land/x/oak/mod.ts")),
+      Some(url("https://mirror.internal/deno_x/oak/mod.ts"))
+    );
+    assert_eq!(mirrors.rewrite(&url("https://jsr.io/@std/path")), None);
+    assert_eq!(
+      mirrors.restore(&url("https://mirror.internal/deno_x/oak/mod.ts")),
+      Some(url("https://deno.land/x/oak/mod.ts"))
+    );
+    assert_eq!(mirrors.restore(&url("https://mirror.internal/other")), None);
+
+    assert!(Mirrors::new([(
+      url("https://deno.land/x"),
+      url("https://mirror.internal/x/")
+    )])
+    .is_err());
+    assert!(Mirrors::new([(
+      url("https://deno.land/"),
+      url("file:///mirror/")
+    )])
+    .is_err());
+  }
+
+  #[tokio::test]
+  async fn test_fetch_from_mirror() {
+    let _http_server_guard = test_util::http_server();
+    let (mut file_fetcher, _) = setup(CacheSetting::Use, None);
+    file_fetcher.set_mirrors(
+      Mirrors::new([
+        (
+          resolve_url("http://mirrored.invalid/").unwrap(),
+          resolve_url("http://localhost:4545/").unwrap(),
+        ),
+        (
+          resolve_url("http://redirecting.invalid/").unwrap(),
+          resolve_url("http://localhost:4546/").unwrap(),
+        ),
+      ])
+      .unwrap(),
+    );
+    let specifier =
+      resolve_url("http://redirecting.invalid/subdir/redirects/redirect1.js")
+        .unwrap();
+    let redirected_specifier =
+      resolve_url("http://mirrored.invalid/subdir/redirects/redirect1.js")
+        .unwrap();
+
+    let file = file_fetcher
+      .fetch_bypass_permissions(&specifier)
+      .await
+      .unwrap();
+    // the redirect of the mirror is followed as a redirect of the original
+    assert_eq!(file.specifier, redirected_specifier);
+    assert_eq!(
+      get_location_header_from_cache(&file_fetcher, &specifier),
+      Some(redirected_specifier.to_string()),
+    );
+    assert_eq!(
+      get_text_from_cache(&file_fetcher, &redirected_specifier),
+      "export const redirect = 1;\n"
+    );
+  }
+
   #[tokio::test]
   async fn test_fetch_multiple_redirects() {
     let _http_server_guard = test_util::http_server();
@@ -1475,6 +1801,124 @@ mod tests {
     assert_eq!(first, second);
   }
 
+  #[tokio::test]
+  async fn test_respect_cache_revalidation_summary() {
+    let _g = test_util::http_server();
+    let temp_dir = TempDir::new();
+    let (mut file_fetcher, _) =
+      setup(CacheSetting::RespectHeaders, Some(temp_dir.clone()));
+    file_fetcher.enable_revalidation_summary();
+    let etag_specifier =
+      ModuleSpecifier::parse("http://127.0.0.1:4545/etag_script.ts").unwrap();
+    let dynamic_specifier =
+      ModuleSpecifier::parse("http://localhost:4545/dynamic").unwrap();
+    for specifier in [&etag_specifier, &dynamic_specifier] {
+      file_fetcher
+        .fetch_bypass_permissions(specifier)
+        .await
+        .unwrap();
+    }
+    // nothing was cached yet
+    assert!(file_fetcher.take_revalidation_summary().is_empty());
+
+    let (mut file_fetcher, _) =
+      setup(CacheSetting::RespectHeaders, Some(temp_dir.clone()));
+    file_fetcher.enable_revalidation_summary();
+    for specifier in [&etag_specifier, &dynamic_specifier] {
+      file_fetcher
+        .fetch_bypass_permissions(specifier)
+        .await
+        .unwrap();
+    }
+    assert_eq!(
+      file_fetcher.take_revalidation_summary(),
+      RevalidationSummary {
+        unchanged: vec![etag_specifier],
+        updated: vec![dynamic_specifier],
+      }
+    );
+    assert!(file_fetcher.take_revalidation_summary().is_empty());
+  }
+
+  #[cfg(unix)]
+  #[tokio::test]
+  async fn test_fetch_retries_with_refreshed_credentials() {
+    use tokio::io::AsyncReadExt;
+    use tokio::io::AsyncWriteExt;
+
+    // answers with 401 unless the refreshed token is sent
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let seen_tokens = Arc::new(Mutex::new(Vec::new()));
+    deno_core::unsync::spawn({
+      let seen_tokens = seen_tokens.clone();
+      async move {
+        loop {
+          let (mut stream, _) = listener.accept().await.unwrap();
+          let mut request = Vec::new();
+          let mut buf = [0; 1024];
+          while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+            let read = stream.read(&mut buf).await.unwrap();
+            if read == 0 {
+              break;
+            }
+            request.extend_from_slice(&buf[..read]);
+          }
+          let request = String::from_utf8(request).unwrap();
+          let token = request.lines().find_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            name
+              .eq_ignore_ascii_case("authorization")
+              .then(|| value.trim().to_string())
+          });
+          let response = if token.as_deref() == Some("Bearer new") {
+            "HTTP/1.1 200 OK\r\ncontent-type: application/javascript\r\ncontent-length: 20\r\nconnection: close\r\n\r\nexport const a = 1;\n"
+          } else {
+            "HTTP/1.1 401 Unauthorized\r\ncontent-length: 0\r\nconnection: close\r\n\r\n"
+          };
+          seen_tokens.lock().push(token);
+          stream.write_all(response.as_bytes()).await.unwrap();
+        }
+      }
+    });
+
+    // hands out "old" until its credentials are erased
+    let temp_dir = TempDir::new();
+    let script = temp_dir.path().join("helper.sh");
+    script.write(
+      r#"cat > /dev/null
+case "$2" in
+  get) if [ -f "$1" ]; then echo '{"token":"new"}'; else echo '{"token":"old"}'; fi ;;
+  erase) touch "$1" ;;
+esac
+"#,
+    );
+    let (mut file_fetcher, _) =
+      setup(CacheSetting::ReloadAll, Some(temp_dir.clone()));
+    file_fetcher.auth_tokens =
+      AuthTokens::new(None).with_credential_helper(vec![
+        "sh".to_string(),
+        script.to_string_lossy().to_string(),
+        temp_dir.path().join("erased").to_string_lossy().to_string(),
+      ]);
+
+    let specifier =
+      ModuleSpecifier::parse(&format!("http://127.0.0.1:{port}/mod.js"))
+        .unwrap();
+    let file = file_fetcher
+      .fetch_bypass_permissions(&specifier)
+      .await
+      .unwrap();
+    assert_eq!(file.source.as_ref(), b"export const a = 1;\n");
+    assert_eq!(
+      *seen_tokens.lock(),
+      vec![
+        Some("Bearer old".to_string()),
+        Some("Bearer new".to_string())
+      ]
+    );
+  }
+
   #[tokio::test]
   async fn test_fetch_local_utf_16be() {
     let expected =
@@ -0,0 +1,564 @@
+// Copyright 2018-2024 the Deno authors. All rights reserved. MIT license.
+
+//! A typed, public representation of a module graph.
+//!
+//! This mirrors the information `deno info --json` outputs (see
+//! `schemas/module-graph.json`), but as Rust data structures so that embedders
+//! can inspect dependencies without parsing JSON.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use deno_ast::MediaType;
+use deno_ast::ModuleSpecifier;
+use deno_core::error::AnyError;
+use deno_core::resolve_url_or_path;
+use deno_core::serde::Serialize;
+use deno_core::url::Url;
+use deno_graph::Dependency;
+use deno_graph::GraphKind;
+use deno_graph::Module;
+use deno_graph::ModuleGraph;
+use deno_graph::Resolution;
+use deno_npm::resolution::NpmResolutionSnapshot;
+use deno_npm::NpmPackageId;
+use deno_semver::npm::NpmPackageReqReference;
+
+use crate::args::ConfigFlag;
+use crate::args::Flags;
+use crate::factory::CliFactory;
+use crate::npm::CliNpmResolver;
+
+/// Options for [`module_graph`](crate::module_graph).
+#[derive(Debug, Default, Clone)]
+pub struct ModuleGraphOptions {
+  /// Path to the configuration file. When not set, a `deno.json` is
+  /// discovered from the current working directory.
+  pub config_path: Option<String>,
+  /// Path to an import map that is used instead of the configuration file's.
+  pub import_map_path: Option<String>,
+  /// Only use modules that are already cached.
+  pub cached_only: bool,
+  /// Skip type-only dependencies (ex. `@deno-types` and `import type`).
+  pub code_only: bool,
+}
+
+/// A zero-based position in a source file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SourcePosition {
+  pub line: usize,
+  pub character: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SourceRange {
+  pub specifier: ModuleSpecifier,
+  pub start: SourcePosition,
+  pub end: SourcePosition,
+}
+
+impl From<&deno_graph::Range> for SourceRange {
+  fn from(range: &deno_graph::Range) -> Self {
+    Self {
+      specifier: range.specifier.clone(),
+      start: SourcePosition {
+        line: range.start.line,
+        character: range.start.character,
+      },
+      end: SourcePosition {
+        line: range.end.line,
+        character: range.end.character,
+      },
+    }
+  }
+}
+
+/// The resolved side of a dependency, either its code or its types.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase", tag = "kind")]
+pub enum ResolvedDependencyInfo {
+  Ok {
+    specifier: ModuleSpecifier,
+    range: SourceRange,
+  },
+  Err {
+    error: String,
+    range: SourceRange,
+  },
+}
+
+impl ResolvedDependencyInfo {
+  fn from_resolution(resolution: &Resolution) -> Option<Self> {
+    match resolution {
+      Resolution::None => None,
+      Resolution::Ok(resolved) => Some(Self::Ok {
+        specifier: resolved.specifier.clone(),
+        range: SourceRange::from(&resolved.range),
+      }),
+      Resolution::Err(err) => Some(Self::Err {
+        error: err.to_string(),
+        range: SourceRange::from(err.range()),
+      }),
+    }
+  }
+
+  pub fn specifier(&self) -> Option<&ModuleSpecifier> {
+    match self {
+      Self::Ok { specifier, .. } => Some(specifier),
+      Self::Err { .. } => None,
+    }
+  }
+}
+
+/// An edge from a module to one of its dependencies.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DependencyInfo {
+  /// The specifier as written in the source.
+  pub specifier: String,
+  pub code: Option<ResolvedDependencyInfo>,
+  pub types: Option<ResolvedDependencyInfo>,
+  pub is_dynamic: bool,
+  /// The resolved npm package id for `npm:` dependencies.
+  pub npm_package: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ModuleKind {
+  Esm,
+  Json,
+  Npm,
+  Node,
+  External,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModuleInfo {
+  pub specifier: ModuleSpecifier,
+  pub kind: ModuleKind,
+  pub media_type: Option<MediaType>,
+  /// Size of the source in bytes.
+  pub size: Option<u64>,
+  pub dependencies: Vec<DependencyInfo>,
+  /// The types dependency supplied by a `X-TypeScript-Types` header or a
+  /// `@ts-self-types` directive.
+  pub types_dependency: Option<DependencyInfo>,
+  /// The resolved npm package id for npm modules.
+  pub npm_package: Option<String>,
+}
+
+/// A module that failed to load or parse.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModuleErrorInfo {
+  pub specifier: ModuleSpecifier,
+  pub error: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NpmPackageInfo {
+  /// The serialized package id (ex. `chalk@5.0.0`).
+  pub id: String,
+  pub name: String,
+  pub version: String,
+  /// Ids of the packages this package depends on.
+  pub dependencies: Vec<String>,
+  /// Size of the package folder in bytes, when it's been downloaded.
+  pub size: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModuleGraphInfo {
+  pub roots: Vec<ModuleSpecifier>,
+  /// Modules sorted by specifier.
+  pub modules: Vec<ModuleInfo>,
+  pub errors: Vec<ModuleErrorInfo>,
+  pub redirects: BTreeMap<ModuleSpecifier, ModuleSpecifier>,
+  /// Npm packages sorted by id.
+  pub npm_packages: Vec<NpmPackageInfo>,
+}
+
+impl ModuleGraphInfo {
+  pub fn new(graph: &ModuleGraph, npm_resolver: &dyn CliNpmResolver) -> Self {
+    match npm_resolver.as_managed() {
+      Some(npm_resolver) => {
+        let snapshot = npm_resolver.snapshot();
+        Self::from_graph(graph, Some(&snapshot), |id| {
+          npm_resolver.package_size(id).ok()
+        })
+      }
+      None => Self::from_graph(graph, None, |_| None),
+    }
+  }
+
+  fn from_graph(
+    graph: &ModuleGraph,
+    snapshot: Option<&NpmResolutionSnapshot>,
+    package_size: impl Fn(&NpmPackageId) -> Option<u64>,
+  ) -> Self {
+    // uses what the dependency resolved to rather than its text, which may be
+    // an alias or a bare specifier mapped to npm
+    let resolve_npm_package = |dep: &Dependency| {
+      let snapshot = snapshot?;
+      let specifier = dep
+        .maybe_code
+        .maybe_specifier()
+        .or_else(|| dep.maybe_type.maybe_specifier())?;
+      let req_ref = NpmPackageReqReference::from_specifier(specifier).ok()?;
+      snapshot
+        .resolve_pkg_from_pkg_req(req_ref.req())
+        .ok()
+        .map(|pkg| pkg.id.as_serialized())
+    };
+    let dependency_info = |specifier: &str, dep: &Dependency| DependencyInfo {
+      specifier: specifier.to_string(),
+      code: ResolvedDependencyInfo::from_resolution(&dep.maybe_code),
+      types: ResolvedDependencyInfo::from_resolution(&dep.maybe_type),
+      is_dynamic: dep.is_dynamic,
+      npm_package: resolve_npm_package(dep),
+    };
+
+    let mut modules = graph
+      .modules()
+      .map(|module| match module {
+        Module::Js(module) => ModuleInfo {
+          specifier: module.specifier.clone(),
+          kind: ModuleKind::Esm,
+          media_type: Some(module.media_type),
+          size: Some(module.size() as u64),
+          dependencies: module
+            .dependencies
+            .iter()
+            .map(|(specifier, dep)| dependency_info(specifier, dep))
+            .collect(),
+          types_dependency: module.maybe_types_dependency.as_ref().map(
+            |types_dep| DependencyInfo {
+              specifier: types_dep.specifier.clone(),
+              code: ResolvedDependencyInfo::from_resolution(
+                &types_dep.dependency,
+              ),
+              types: None,
+              is_dynamic: false,
+              npm_package: None,
+            },
+          ),
+          npm_package: None,
+        },
+        Module::Json(module) => ModuleInfo {
+          specifier: module.specifier.clone(),
+          kind: ModuleKind::Json,
+          media_type: Some(module.media_type),
+          size: Some(module.size() as u64),
+          dependencies: Vec::new(),
+          types_dependency: None,
+          npm_package: None,
+        },
+        Module::Npm(module) => ModuleInfo {
+          specifier: module.specifier.clone(),
+          kind: ModuleKind::Npm,
+          media_type: None,
+          size: None,
+          dependencies: Vec::new(),
+          types_dependency: None,
+          npm_package: snapshot.and_then(|snapshot| {
+            snapshot
+              .resolve_package_from_deno_module(module.nv_reference.nv())
+              .ok()
+              .map(|pkg| pkg.id.as_serialized())
+          }),
+        },
+        Module::Node(module) => ModuleInfo {
+          specifier: module.specifier.clone(),
+          kind: ModuleKind::Node,
+          media_type: None,
+          size: None,
+          dependencies: Vec::new(),
+          types_dependency: None,
+          npm_package: None,
+        },
+        Module::External(module) => ModuleInfo {
+          specifier: module.specifier.clone(),
+          kind: ModuleKind::External,
+          media_type: None,
+          size: None,
+          dependencies: Vec::new(),
+          types_dependency: None,
+          npm_package: None,
+        },
+      })
+      .collect::<Vec<_>>();
+    modules.sort_by(|a, b| a.specifier.cmp(&b.specifier));
+
+    let mut errors = graph
+      .module_errors()
+      .map(|err| ModuleErrorInfo {
+        specifier: err.specifier().clone(),
+        error: err.to_string(),
+      })
+      .collect::<Vec<_>>();
+    errors.sort_by(|a, b| a.specifier.cmp(&b.specifier));
+
+    let npm_packages = match snapshot {
+      Some(snapshot) => {
+        let mut packages = snapshot
+          .all_packages_for_every_system()
+          .map(|pkg| {
+            let mut dependencies = pkg
+              .dependencies
+              .values()
+              .map(|id| id.as_serialized())
+              .collect::<Vec<_>>();
+            dependencies.sort();
+            NpmPackageInfo {
+              id: pkg.id.as_serialized(),
+              name: pkg.id.nv.name.clone(),
+              version: pkg.id.nv.version.to_string(),
+              dependencies,
+              size: package_size(&pkg.id),
+            }
+          })
+          .collect::<Vec<_>>();
+        packages.sort_by(|a, b| a.id.cmp(&b.id));
+        packages
+      }
+      None => Vec::new(),
+    };
+
+    Self {
+      roots: graph.roots.iter().cloned().collect(),
+      modules,
+      errors,
+      redirects: graph.redirects.clone(),
+      npm_packages,
+    }
+  }
+
+  /// Total size in bytes of the modules in the graph, excluding npm packages.
+  pub fn modules_size(&self) -> u64 {
+    self.modules.iter().filter_map(|m| m.size).sum()
+  }
+
+  pub fn get(&self, specifier: &ModuleSpecifier) -> Option<&ModuleInfo> {
+    let specifier = self.redirects.get(specifier).unwrap_or(specifier);
+    self
+      .modules
+      .binary_search_by(|m| m.specifier.cmp(specifier))
+      .ok()
+      .map(|index| &self.modules[index])
+  }
+
+  /// Returns the specifiers of the modules that depend on `specifier`, either
+  /// directly or transitively.
+  pub fn dependents(
+    &self,
+    specifier: &ModuleSpecifier,
+  ) -> Vec<&ModuleSpecifier> {
+    let resolve = |s: &ModuleSpecifier| -> ModuleSpecifier {
+      self.redirects.get(s).unwrap_or(s).clone()
+    };
+    let mut reverse_edges: BTreeMap<ModuleSpecifier, Vec<&ModuleSpecifier>> =
+      BTreeMap::new();
+    for module in &self.modules {
+      let deps = module
+        .dependencies
+        .iter()
+        .chain(module.types_dependency.iter())
+        .flat_map(|dep| {
+          [dep.code.as_ref(), dep.types.as_ref()]
+            .into_iter()
+            .flatten()
+            .filter_map(|resolved| resolved.specifier())
+        });
+      for dep in deps {
+        reverse_edges
+          .entry(resolve(dep))
+          .or_default()
+          .push(&module.specifier);
+      }
+    }
+
+    let specifier = resolve(specifier);
+    let mut seen = std::collections::BTreeSet::new();
+    let mut pending = vec![specifier.clone()];
+    while let Some(current) = pending.pop() {
+      if let Some(dependents) = reverse_edges.get(&current) {
+        for dependent in dependents {
+          if seen.insert(*dependent) {
+            pending.push((*dependent).clone());
+          }
+        }
+      }
+    }
+    // a module in a cycle reaches itself
+    seen
+      .into_iter()
+      .filter(|dependent| **dependent != specifier)
+      .collect()
+  }
+}
+
+/// Resolves a root the same way `deno info` does, by trying the import map
+/// first and falling back to a URL or path relative to `cwd`.
+pub fn resolve_root_specifier(
+  specifier: &str,
+  maybe_import_map: Option<&import_map::ImportMap>,
+  cwd: &std::path::Path,
+) -> Result<ModuleSpecifier, AnyError> {
+  let cwd_url = Url::from_directory_path(cwd).unwrap();
+  if let Some(import_map) = maybe_import_map {
+    if let Ok(specifier) = import_map.resolve(specifier, &cwd_url) {
+      return Ok(specifier);
+    }
+  }
+  Ok(resolve_url_or_path(specifier, cwd)?)
+}
+
+pub async fn build_module_graph_info(
+  roots: &[String],
+  options: ModuleGraphOptions,
+) -> Result<ModuleGraphInfo, AnyError> {
+  let flags = Flags {
+    config_flag: match options.config_path {
+      Some(path) => ConfigFlag::Path(path),
+      None => ConfigFlag::Discover,
+    },
+    import_map_path: options.import_map_path,
+    cached_only: options.cached_only,
+    ..Default::default()
+  };
+  let factory = CliFactory::from_flags(Arc::new(flags));
+  let cli_options = factory.cli_options()?;
+  let resolver = factory.workspace_resolver().await?;
+  let roots = roots
+    .iter()
+    .map(|root| {
+      resolve_root_specifier(
+        root,
+        resolver.maybe_import_map(),
+        cli_options.initial_cwd(),
+      )
+    })
+    .collect::<Result<Vec<_>, _>>()?;
+  let graph_kind = if options.code_only {
+    GraphKind::CodeOnly
+  } else {
+    GraphKind::All
+  };
+  let graph = factory
+    .module_graph_creator()
+    .await?
+    .create_graph(graph_kind, roots)
+    .await?;
+  let npm_resolver = factory.npm_resolver().await?;
+  Ok(ModuleGraphInfo::new(&graph, npm_resolver.as_ref()))
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  fn specifier(s: &str) -> ModuleSpecifier {
+    ModuleSpecifier::parse(s).unwrap()
+  }
+
+  fn module(s: &str, deps: &[&str]) -> ModuleInfo {
+    let range = SourceRange {
+      specifier: specifier(s),
+      start: SourcePosition {
+        line: 0,
+        character: 0,
+      },
+      end: SourcePosition {
+        line: 0,
+        character: 1,
+      },
+    };
+    ModuleInfo {
+      specifier: specifier(s),
+      kind: ModuleKind::Esm,
+      media_type: Some(MediaType::TypeScript),
+      size: Some(10),
+      dependencies: deps
+        .iter()
+        .map(|dep| DependencyInfo {
+          specifier: dep.to_string(),
+          code: Some(ResolvedDependencyInfo::Ok {
+            specifier: specifier(dep),
+            range: range.clone(),
+          }),
+          types: None,
+          is_dynamic: false,
+          npm_package: None,
+        })
+        .collect(),
+      types_dependency: None,
+      npm_package: None,
+    }
+  }
+
+  #[test]
+  fn dependents_are_transitive() {
+    let mut modules = vec![
+      module("file:///a.ts", &["file:///b.ts"]),
+      module("file:///b.ts", &["https://example.com/c.ts"]),
+      module("https://example.com/c2.ts", &[]),
+      module("file:///d.ts", &[]),
+    ];
+    modules.sort_by(|a, b| a.specifier.cmp(&b.specifier));
+    let info = ModuleGraphInfo {
+      roots: vec![specifier("file:///a.ts"), specifier("file:///d.ts")],
+      modules,
+      errors: vec![],
+      redirects: BTreeMap::from([(
+        specifier("https://example.com/c.ts"),
+        specifier("https://example.com/c2.ts"),
+      )]),
+      npm_packages: vec![],
+    };
+    assert_eq!(
+      info.dependents(&specifier("https://example.com/c2.ts")),
+      vec![&specifier("file:///a.ts"), &specifier("file:///b.ts")]
+    );
+    assert_eq!(
+      info.dependents(&specifier("https://example.com/c.ts")),
+      vec![&specifier("file:///a.ts"), &specifier("file:///b.ts")]
+    );
+    assert!(info.dependents(&specifier("file:///d.ts")).is_empty());
+    assert_eq!(
+      info
+        .get(&specifier("https://example.com/c.ts"))
+        .unwrap()
+        .specifier,
+      specifier("https://example.com/c2.ts")
+    );
+    assert_eq!(info.modules_size(), 40);
+  }
+
+  #[test]
+  fn dependents_exclude_self_in_cycle() {
+    let info = ModuleGraphInfo {
+      roots: vec![specifier("file:///a.ts")],
+      modules: vec![
+        module("file:///a.ts", &["file:///b.ts"]),
+        module("file:///b.ts", &["file:///a.ts"]),
+      ],
+      errors: vec![],
+      redirects: BTreeMap::new(),
+      npm_packages: vec![],
+    };
+    assert_eq!(
+      info.dependents(&specifier("file:///a.ts")),
+      vec![&specifier("file:///b.ts")]
+    );
+    assert_eq!(
+      info.dependents(&specifier("file:///b.ts")),
+      vec![&specifier("file:///a.ts")]
+    );
+  }
+}
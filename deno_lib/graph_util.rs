@@ -12,6 +12,7 @@ use crate::cache::ParsedSourceCache;
 use crate::colors;
 use crate::errors::get_error_class_name;
 use crate::file_fetcher::FileFetcher;
+use crate::file_fetcher::RevalidationSummary;
 use crate::npm::CliNpmResolver;
 use crate::resolver::CliGraphResolver;
 use crate::resolver::CliSloppyImportsResolver;
@@ -528,7 +529,7 @@ impl ModuleGraphBuilder {
       .lockfile
       .as_ref()
       .map(|lockfile| LockfileLocker(lockfile));
-    self
+    let result = self
       .build_graph_with_npm_resolution_and_build_options(
         graph,
         options.roots,
@@ -547,7 +548,9 @@ impl ModuleGraphBuilder {
           locker: locker.as_mut().map(|l| l as _),
         },
       )
-      .await
+      .await;
+    log_revalidation_summary(&self.file_fetcher.take_revalidation_summary());
+    result
   }
 
   async fn build_graph_with_npm_resolution_and_build_options<'a>(
@@ -769,6 +772,23 @@ pub fn enhanced_resolution_error_message(error: &ResolutionError) -> String {
   message
 }
 
+/// Logs how many stale remote modules were revalidated and which changed.
+fn log_revalidation_summary(summary: &RevalidationSummary) {
+  if summary.is_empty() {
+    return;
+  }
+  log::info!(
+    "{} {} stale remote modules: {} updated, {} unchanged",
+    colors::green("Revalidated"),
+    summary.updated.len() + summary.unchanged.len(),
+    summary.updated.len(),
+    summary.unchanged.len(),
+  );
+  for specifier in &summary.updated {
+    log::info!("  {} {}", colors::gray("updated"), specifier);
+  }
+}
+
 fn enhanced_sloppy_imports_error_message(
   fs: &Arc<dyn FileSystem>,
   error: &ModuleError,
@@ -1,12 +1,20 @@
 // Copyright 2018-2024 the Deno authors. All rights reserved. MIT license.
 
+use crate::args::ClientCertConfig;
+use crate::auth_tokens::AuthDomain;
 use crate::auth_tokens::AuthToken;
 use crate::util::progress_bar::UpdateGuard;
+use crate::util::retry::RetryPolicy;
+use crate::util::sync::AtomicFlag;
 use crate::version;
 
+use base64::prelude::BASE64_STANDARD;
+use base64::Engine;
 use cache_control::Cachability;
 use cache_control::CacheControl;
 use chrono::DateTime;
+use deno_core::anyhow::bail;
+use deno_core::anyhow::Context;
 use deno_core::error::custom_error;
 use deno_core::error::generic_error;
 use deno_core::error::AnyError;
@@ -18,30 +26,57 @@ use deno_core::url::Url;
 use deno_runtime::deno_fetch;
 use deno_runtime::deno_fetch::create_http_client;
 use deno_runtime::deno_fetch::CreateHttpClientOptions;
+use deno_runtime::deno_tls::rustls_pemfile;
 use deno_runtime::deno_tls::RootCertStoreProvider;
+use deno_runtime::deno_tls::TlsKey;
+use deno_runtime::deno_tls::TlsKeys;
 use http::header;
 use http::header::HeaderName;
 use http::header::HeaderValue;
 use http::header::ACCEPT;
 use http::header::AUTHORIZATION;
 use http::header::CONTENT_LENGTH;
+use http::header::IF_MODIFIED_SINCE;
 use http::header::IF_NONE_MATCH;
 use http::header::LOCATION;
 use http::StatusCode;
 use http_body_util::BodyExt;
+use once_cell::sync::OnceCell;
 
 use std::collections::HashMap;
+use std::future::Future;
+use std::io::BufReader;
 use std::sync::Arc;
 use std::thread::ThreadId;
 use std::time::Duration;
 use std::time::SystemTime;
 use thiserror::Error;
+use tokio::sync::OwnedSemaphorePermit;
+use tokio::sync::Semaphore;
 
 // TODO(ry) HTTP headers are not unique key, value pairs. There may be more than
 // one header line with the same key. This should be changed to something like
 // Vec<(String, String)>
 pub type HeadersMap = HashMap<String, String>;
 
+/// Updates the headers of a cached response with those of a 304 Not Modified
+/// response revalidating it (RFC 9111, section 4.3.4).
+pub fn update_not_modified_headers(
+  cached_headers: &mut HeadersMap,
+  headers: HeadersMap,
+) {
+  for (key, value) in headers {
+    // these describe the empty 304 response rather than the cached content
+    if matches!(
+      key.as_str(),
+      "content-length" | "content-encoding" | "transfer-encoding"
+    ) {
+      continue;
+    }
+    cached_headers.insert(key, value);
+  }
+}
+
 /// A structure used to determine if a entity in the http cache can be used.
 ///
 /// This is heavily influenced by
@@ -193,10 +228,15 @@ impl CacheSemantics {
 #[derive(Debug, Eq, PartialEq)]
 pub enum FetchOnceResult {
   Code(Vec<u8>, HeadersMap),
-  NotModified,
+  /// The cached response is still valid. Holds the headers of the 304
+  /// response, which update the cached ones.
+  NotModified(HeadersMap),
   Redirect(Url, HeadersMap),
   RequestError(String),
   ServerError(StatusCode),
+  /// The server responded with 401 Unauthorized, so the credentials sent
+  /// (if any) may need to be refreshed.
+  Unauthorized,
 }
 
 #[derive(Debug)]
@@ -204,24 +244,112 @@ pub struct FetchOnceArgs<'a> {
   pub url: Url,
   pub maybe_accept: Option<String>,
   pub maybe_etag: Option<String>,
+  pub maybe_last_modified: Option<String>,
   pub maybe_auth_token: Option<AuthToken>,
   pub maybe_auth: Option<(header::HeaderName, header::HeaderValue)>,
   pub maybe_progress_guard: Option<&'a UpdateGuard>,
 }
 
+/// The header of a cache server response that holds the cached headers of a
+/// remote module, as base64 encoded JSON.
+pub const CACHE_SERVER_HEADERS_HEADER: &str = "x-deno-cached-headers";
+
+/// The environment variable with the token that cache server clients send
+/// and that the cache server requires.
+pub const CACHE_SERVER_TOKEN_ENV_VAR_NAME: &str = "DENO_CACHE_SERVER_TOKEN";
+
+/// A `deno cache --serve` server of another machine, which remote modules,
+/// JSR metadata and npm packages are downloaded from before their origin.
+#[derive(Clone, PartialEq, Eq)]
+pub struct CacheServer {
+  url: Url,
+  maybe_token: Option<String>,
+}
+
+impl std::fmt::Debug for CacheServer {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("CacheServer")
+      .field("url", &self.url)
+      .field("has_token", &self.maybe_token.is_some())
+      .finish()
+  }
+}
+
+impl CacheServer {
+  pub fn new(mut url: Url) -> Result<Self, AnyError> {
+    if !matches!(url.scheme(), "http" | "https") {
+      bail!(
+        "The cache server URL '{}' must be an http or https URL.",
+        url
+      );
+    }
+    if !url.path().ends_with('/') {
+      url.set_path(&format!("{}/", url.path()));
+    }
+    Ok(Self {
+      url,
+      maybe_token: None,
+    })
+  }
+
+  /// Sends the token the cache server requires with every request.
+  pub fn with_token(mut self, maybe_token: Option<String>) -> Self {
+    self.maybe_token = maybe_token;
+    self
+  }
+
+  pub fn url(&self) -> &Url {
+    &self.url
+  }
+
+  /// The authorization header of the requests to the cache server.
+  pub fn maybe_auth_header(
+    &self,
+  ) -> Result<Option<(HeaderName, HeaderValue)>, AnyError> {
+    let Some(token) = &self.maybe_token else {
+      return Ok(None);
+    };
+    let value = HeaderValue::from_str(&format!("Bearer {}", token))
+      .with_context(|| {
+        format!("Invalid {}.", CACHE_SERVER_TOKEN_ENV_VAR_NAME)
+      })?;
+    Ok(Some((AUTHORIZATION, value)))
+  }
+
+  /// The endpoint that serves the cache entry of a remote module, which
+  /// includes the JSR metadata.
+  pub fn remote_url(&self, url: &Url) -> Url {
+    self.endpoint("remote", url)
+  }
+
+  /// The endpoint that serves an npm packument or tarball.
+  pub fn npm_url(&self, url: &Url) -> Url {
+    self.endpoint("npm", url)
+  }
+
+  fn endpoint(&self, path: &str, url: &Url) -> Url {
+    let mut endpoint = self.url.join(path).unwrap();
+    endpoint.query_pairs_mut().append_pair("url", url.as_str());
+    endpoint
+  }
+}
+
 pub struct HttpClientProvider {
-  options: CreateHttpClientOptions,
-  root_cert_store_provider: Option<Arc<dyn RootCertStoreProvider>>,
-  // it's not safe to share a reqwest::Client across tokio runtimes,
-  // so we store these Clients keyed by thread id
-  // https://github.com/seanmonstar/reqwest/issues/1148#issuecomment-910868788
-  clients_by_thread_id: Mutex<HashMap<ThreadId, deno_fetch::Client>>,
+  factory: Arc<HttpClientFactory>,
+  download_limiter: Arc<DownloadLimiter>,
+  cache_server: Option<CacheServer>,
+  /// Set once the cache server failed, after which everything is downloaded
+  /// from its origin.
+  cache_server_failed: AtomicFlag,
 }
 
 impl std::fmt::Debug for HttpClientProvider {
   fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
     f.debug_struct("HttpClient")
-      .field("options", &self.options)
+      .field("options", &self.factory.options)
+      .field("client_certs", &self.factory.client_certs)
+      .field("download_policy", &self.download_limiter.policy)
+      .field("cache_server", &self.cache_server)
       .finish()
   }
 }
@@ -232,23 +360,212 @@ impl HttpClientProvider {
     unsafely_ignore_certificate_errors: Option<Vec<String>>,
   ) -> Self {
     Self {
-      options: CreateHttpClientOptions {
-        unsafely_ignore_certificate_errors,
-        ..Default::default()
-      },
-      root_cert_store_provider,
+      factory: Arc::new(HttpClientFactory {
+        options: CreateHttpClientOptions {
+          unsafely_ignore_certificate_errors,
+          ..Default::default()
+        },
+        root_cert_store_provider,
+        client_certs: Vec::new(),
+        clients_by_thread_id: Default::default(),
+      }),
+      download_limiter: Default::default(),
+      cache_server: None,
+      cache_server_failed: Default::default(),
+    }
+  }
+
+  /// Retries, throttles and times out the requests of the clients according
+  /// to the policy.
+  pub fn with_download_policy(mut self, policy: DownloadPolicy) -> Self {
+    self.download_limiter = Arc::new(DownloadLimiter::new(policy));
+    self
+  }
+
+  pub fn download_policy(&self) -> &DownloadPolicy {
+    &self.download_limiter.policy
+  }
+
+  /// Presents the client certificates to the hosts they're configured for.
+  /// When several match a host, the first one wins.
+  pub fn with_client_certs(mut self, configs: Vec<ClientCertConfig>) -> Self {
+    self.factory = Arc::new(HttpClientFactory {
+      options: self.factory.options.clone(),
+      root_cert_store_provider: self.factory.root_cert_store_provider.clone(),
+      client_certs: configs
+        .into_iter()
+        .map(|config| ClientCert {
+          domain: AuthDomain::from(&config.host),
+          config,
+          keys: OnceCell::new(),
+        })
+        .collect(),
       clients_by_thread_id: Default::default(),
+    });
+    self
+  }
+
+  /// Downloads from the cache server before the origin.
+  pub fn with_cache_server(
+    mut self,
+    cache_server: Option<CacheServer>,
+  ) -> Self {
+    self.cache_server = cache_server;
+    self
+  }
+
+  /// The cache server, unless there is none or it failed.
+  pub fn cache_server(&self) -> Option<&CacheServer> {
+    if self.cache_server_failed.is_raised() {
+      return None;
+    }
+    self.cache_server.as_ref()
+  }
+
+  /// Fetches the cached headers and content of the remote module at `url`
+  /// from the cache server, when it has them.
+  pub async fn fetch_remote_from_cache_server(
+    &self,
+    url: &Url,
+  ) -> Option<(HeadersMap, Vec<u8>)> {
+    let cache_server = self.cache_server()?;
+    let endpoint = cache_server.remote_url(url);
+    let result = match (
+      self.get_or_create_for_url(&endpoint),
+      cache_server.maybe_auth_header(),
+    ) {
+      (Ok(client), Ok(maybe_header)) => {
+        client
+          .fetch_cache_server_entry(endpoint, maybe_header)
+          .await
+      }
+      (Err(err), _) | (_, Err(err)) => Err(err),
+    };
+    self.handle_cache_server_result(url, result)
+  }
+
+  /// Downloads the npm packument or tarball at `url` from the cache server,
+  /// when it has it. The server is only tried once, since the origin is the
+  /// fallback.
+  pub async fn download_npm_from_cache_server(
+    &self,
+    url: &Url,
+  ) -> Option<Vec<u8>> {
+    let cache_server = self.cache_server()?;
+    let endpoint = cache_server.npm_url(url);
+    let result = match (
+      self.get_or_create_for_url(&endpoint),
+      cache_server.maybe_auth_header(),
+    ) {
+      (Ok(client), Ok(maybe_header)) => client
+        .download_inner(endpoint, maybe_header, None)
+        .await
+        .map_err(AnyError::from),
+      (Err(err), _) | (_, Err(err)) => Err(err),
+    };
+    self.handle_cache_server_result(url, result)
+  }
+
+  fn handle_cache_server_result<T>(
+    &self,
+    url: &Url,
+    result: Result<Option<T>, AnyError>,
+  ) -> Option<T> {
+    match result {
+      Ok(Some(value)) => {
+        log::debug!("Downloaded '{}' from the cache server.", url);
+        Some(value)
+      }
+      Ok(None) => None,
+      Err(err) => {
+        if self.cache_server_failed.raise() {
+          log::warn!(
+            "{} Failed downloading '{}' from the cache server, so it is no longer used: {:#}",
+            crate::colors::yellow("Warning"),
+            url,
+            err
+          );
+        }
+        None
+      }
     }
   }
 
+  /// Gets a client that doesn't present a client certificate.
   pub fn get_or_create(&self) -> Result<HttpClient, AnyError> {
+    let client = self.factory.get_or_create(None)?;
+    Ok(
+      HttpClient::new(client)
+        .with_download_limiter(self.download_limiter.clone()),
+    )
+  }
+
+  /// Gets a client that presents the client certificate configured for the
+  /// host of `url`, if any. Redirects to other hosts are followed with the
+  /// certificate configured for them.
+  pub fn get_or_create_for_url(
+    &self,
+    url: &Url,
+  ) -> Result<HttpClient, AnyError> {
+    let client = self.factory.get_or_create_for_url(url)?;
+    Ok(
+      HttpClient::new(client)
+        .with_download_limiter(self.download_limiter.clone())
+        .with_redirect_clients(self.factory.clone()),
+    )
+  }
+}
+
+/// Creates the clients of a provider. The clients that present client
+/// certificates share it to pick the client of each redirect hop.
+struct HttpClientFactory {
+  options: CreateHttpClientOptions,
+  root_cert_store_provider: Option<Arc<dyn RootCertStoreProvider>>,
+  client_certs: Vec<ClientCert>,
+  // it's not safe to share a reqwest::Client across tokio runtimes,
+  // so we store these Clients keyed by thread id and the index of the
+  // client certificate they present
+  // https://github.com/seanmonstar/reqwest/issues/1148#issuecomment-910868788
+  clients_by_thread_id:
+    Mutex<HashMap<(ThreadId, Option<usize>), deno_fetch::Client>>,
+}
+
+impl std::fmt::Debug for HttpClientFactory {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("HttpClientFactory")
+      .field("options", &self.options)
+      .field("client_certs", &self.client_certs)
+      .finish()
+  }
+}
+
+impl HttpClientFactory {
+  fn get_or_create_for_url(
+    &self,
+    url: &Url,
+  ) -> Result<deno_fetch::Client, AnyError> {
+    let maybe_index = self
+      .client_certs
+      .iter()
+      .position(|cert| cert.domain.matches(url));
+    self.get_or_create(maybe_index)
+  }
+
+  fn get_or_create(
+    &self,
+    maybe_client_cert_index: Option<usize>,
+  ) -> Result<deno_fetch::Client, AnyError> {
     use std::collections::hash_map::Entry;
     let thread_id = std::thread::current().id();
     let mut clients = self.clients_by_thread_id.lock();
-    let entry = clients.entry(thread_id);
+    let entry = clients.entry((thread_id, maybe_client_cert_index));
     match entry {
-      Entry::Occupied(entry) => Ok(HttpClient::new(entry.get().clone())),
+      Entry::Occupied(entry) => Ok(entry.get().clone()),
       Entry::Vacant(entry) => {
+        let client_cert_chain_and_key = match maybe_client_cert_index {
+          Some(index) => self.client_certs[index].keys()?.clone(),
+          None => TlsKeys::Null,
+        };
         let client = create_http_client(
           version::DENO_VERSION_INFO.user_agent,
           CreateHttpClientOptions {
@@ -256,16 +573,133 @@ impl HttpClientProvider {
               Some(provider) => Some(provider.get_or_try_init()?.clone()),
               None => None,
             },
+            client_cert_chain_and_key,
             ..self.options.clone()
           },
         )?;
         entry.insert(client.clone());
-        Ok(HttpClient::new(client))
+        Ok(client)
       }
     }
   }
 }
 
+#[derive(Debug)]
+struct ClientCert {
+  domain: AuthDomain,
+  config: ClientCertConfig,
+  /// Loaded the first time a client for the host is created.
+  keys: OnceCell<TlsKeys>,
+}
+
+impl ClientCert {
+  fn keys(&self) -> Result<&TlsKeys, AnyError> {
+    self.keys.get_or_try_init(|| {
+      let key = load_client_cert(&self.config).with_context(|| {
+        format!(
+          "Failed loading the client certificate for '{}'",
+          self.config.host
+        )
+      })?;
+      Ok(TlsKeys::Static(key))
+    })
+  }
+}
+
+/// Reads a PEM encoded certificate chain and private key. The key is read
+/// from the certificate file when no key file is configured.
+fn load_client_cert(config: &ClientCertConfig) -> Result<TlsKey, AnyError> {
+  let cert_data = std::fs::read(&config.cert_file).with_context(|| {
+    format!("Failed reading '{}'", config.cert_file.display())
+  })?;
+  let key_data = match &config.key_file {
+    Some(key_file) => std::fs::read(key_file)
+      .with_context(|| format!("Failed reading '{}'", key_file.display()))?,
+    None => cert_data.clone(),
+  };
+  parse_client_cert(&cert_data, &key_data)
+}
+
+fn parse_client_cert(
+  cert_data: &[u8],
+  key_data: &[u8],
+) -> Result<TlsKey, AnyError> {
+  let certs = rustls_pemfile::certs(&mut BufReader::new(cert_data))
+    .collect::<Result<Vec<_>, _>>()?;
+  if certs.is_empty() {
+    bail!("No certificates found");
+  }
+  let Some(key) = rustls_pemfile::private_key(&mut BufReader::new(key_data))?
+  else {
+    bail!("No private key found");
+  };
+  Ok(TlsKey(certs, key))
+}
+
+/// How downloads are retried, throttled and timed out.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DownloadPolicy {
+  /// Replaces the retries of every kind of download when configured.
+  pub retry: Option<RetryPolicy>,
+  /// The number of requests that may be in flight to a single host.
+  pub max_concurrent_requests_per_host: Option<usize>,
+  /// How long a single request, including reading its body, may take.
+  pub timeout: Option<Duration>,
+}
+
+#[derive(Debug, Default)]
+struct DownloadLimiter {
+  policy: DownloadPolicy,
+  semaphores_by_host: Mutex<HashMap<String, Arc<Semaphore>>>,
+}
+
+impl DownloadLimiter {
+  fn new(policy: DownloadPolicy) -> Self {
+    Self {
+      policy,
+      semaphores_by_host: Default::default(),
+    }
+  }
+
+  async fn acquire(&self, url: &Url) -> Option<OwnedSemaphorePermit> {
+    let max_requests = self.policy.max_concurrent_requests_per_host?;
+    let host = format!(
+      "{}:{}",
+      url.host_str()?,
+      url.port_or_known_default().unwrap_or_default()
+    );
+    let semaphore = self
+      .semaphores_by_host
+      .lock()
+      .entry(host)
+      .or_insert_with(|| Arc::new(Semaphore::new(max_requests)))
+      .clone();
+    semaphore.acquire_owned().await.ok()
+  }
+
+  /// Waits for the host of `url` to have capacity for another request, then
+  /// runs it, giving up after the timeout.
+  async fn run<T>(
+    &self,
+    url: &Url,
+    request: impl Future<Output = T>,
+  ) -> Result<T, RequestTimeoutError> {
+    let _permit = self.acquire(url).await;
+    match self.policy.timeout {
+      Some(timeout) => tokio::time::timeout(timeout, request)
+        .await
+        .map_err(|_| RequestTimeoutError { timeout }),
+      None => Ok(request.await),
+    }
+  }
+}
+
+#[derive(Debug, Error)]
+#[error("Timed out after {}ms", .timeout.as_millis())]
+pub struct RequestTimeoutError {
+  pub timeout: Duration,
+}
+
 #[derive(Debug, Error)]
 #[error("Bad response: {:?}{}", .status_code, .response_text.as_ref().map(|s| format!("\n\n{}", s)).unwrap_or_else(String::new))]
 pub struct BadResponseError {
@@ -291,11 +725,20 @@ pub enum DownloadError {
   TooManyRedirects,
   #[error(transparent)]
   BadResponse(#[from] BadResponseError),
+  #[error("Download of '{}' failed: {}", .url, .source)]
+  Timeout {
+    url: Url,
+    source: RequestTimeoutError,
+  },
 }
 
 #[derive(Debug)]
 pub struct HttpClient {
   client: deno_fetch::Client,
+  download_limiter: Arc<DownloadLimiter>,
+  /// Picks the client of each redirect hop, so hosts are only presented
+  /// the client certificate configured for them.
+  maybe_redirect_clients: Option<Arc<HttpClientFactory>>,
   // don't allow sending this across threads because then
   // it might be shared accidentally across tokio runtimes
   // which will cause issues
@@ -309,10 +752,34 @@ impl HttpClient {
   fn new(client: deno_fetch::Client) -> Self {
     Self {
       client,
+      download_limiter: Default::default(),
+      maybe_redirect_clients: None,
       _unsend_marker: deno_core::unsync::UnsendMarker::default(),
     }
   }
 
+  fn with_download_limiter(mut self, limiter: Arc<DownloadLimiter>) -> Self {
+    self.download_limiter = limiter;
+    self
+  }
+
+  fn with_redirect_clients(mut self, factory: Arc<HttpClientFactory>) -> Self {
+    self.maybe_redirect_clients = Some(factory);
+    self
+  }
+
+  fn client_for_redirect(
+    &self,
+    url: &Url,
+  ) -> Result<deno_fetch::Client, DownloadError> {
+    match &self.maybe_redirect_clients {
+      Some(factory) => factory
+        .get_or_create_for_url(url)
+        .map_err(DownloadError::Fetch),
+      None => Ok(self.client.clone()),
+    }
+  }
+
   pub fn get(&self, url: Url) -> Result<RequestBuilder, http::Error> {
     let body = http_body_util::Empty::new()
       .map_err(|never| match never {})
@@ -366,6 +833,21 @@ impl HttpClient {
   pub async fn fetch_no_follow<'a>(
     &self,
     args: FetchOnceArgs<'a>,
+  ) -> Result<FetchOnceResult, AnyError> {
+    let url = args.url.clone();
+    match self
+      .download_limiter
+      .run(&url, self.fetch_no_follow_inner(args))
+      .await
+    {
+      Ok(result) => result,
+      Err(err) => Ok(FetchOnceResult::RequestError(err.to_string())),
+    }
+  }
+
+  async fn fetch_no_follow_inner<'a>(
+    &self,
+    args: FetchOnceArgs<'a>,
   ) -> Result<FetchOnceResult, AnyError> {
     let body = http_body_util::Empty::new()
       .map_err(|never| match never {})
@@ -379,6 +861,12 @@ impl HttpClient {
         .headers_mut()
         .insert(IF_NONE_MATCH, if_none_match_val);
     }
+    if let Some(last_modified) = args.maybe_last_modified {
+      let if_modified_since_val = HeaderValue::from_str(&last_modified)?;
+      request
+        .headers_mut()
+        .insert(IF_MODIFIED_SINCE, if_modified_since_val);
+    }
     if let Some(auth_token) = args.maybe_auth_token {
       let authorization_val = HeaderValue::from_str(&auth_token.to_string())?;
       request
@@ -401,10 +889,6 @@ impl HttpClient {
       }
     };
 
-    if response.status() == StatusCode::NOT_MODIFIED {
-      return Ok(FetchOnceResult::NotModified);
-    }
-
     let mut result_headers = HashMap::new();
     let response_headers = response.headers();
 
@@ -427,6 +911,10 @@ impl HttpClient {
       result_headers.insert(key_str, values_str);
     }
 
+    if response.status() == StatusCode::NOT_MODIFIED {
+      return Ok(FetchOnceResult::NotModified(result_headers));
+    }
+
     if response.status().is_redirection() {
       let new_url = resolve_redirect_from_response(&args.url, &response)?;
       return Ok(FetchOnceResult::Redirect(new_url, result_headers));
@@ -438,6 +926,10 @@ impl HttpClient {
       return Ok(FetchOnceResult::ServerError(status));
     }
 
+    if status == StatusCode::UNAUTHORIZED {
+      return Ok(FetchOnceResult::Unauthorized);
+    }
+
     if status.is_client_error() {
       let err = if response.status() == StatusCode::NOT_FOUND {
         custom_error(
@@ -481,6 +973,7 @@ impl HttpClient {
     progress_guard: &UpdateGuard,
   ) -> Result<Option<Vec<u8>>, DownloadError> {
     crate::util::retry::retry(
+      self.download_limiter.policy.retry,
       || {
         self.download_inner(
           url.clone(),
@@ -488,11 +981,58 @@ impl HttpClient {
           Some(progress_guard),
         )
       },
-      |e| matches!(e, DownloadError::BadResponse(_) | DownloadError::Fetch(_)),
+      |e| {
+        matches!(
+          e,
+          DownloadError::BadResponse(_)
+            | DownloadError::Fetch(_)
+            | DownloadError::Timeout { .. }
+        )
+      },
     )
     .await
   }
 
+  /// Fetches the cached headers and content of a remote module from the
+  /// endpoint of a cache server.
+  async fn fetch_cache_server_entry(
+    &self,
+    endpoint: Url,
+    maybe_header: Option<(HeaderName, HeaderValue)>,
+  ) -> Result<Option<(HeadersMap, Vec<u8>)>, AnyError> {
+    let request =
+      self.fetch_cache_server_entry_unlimited(endpoint.clone(), maybe_header);
+    self.download_limiter.run(&endpoint, request).await?
+  }
+
+  async fn fetch_cache_server_entry_unlimited(
+    &self,
+    endpoint: Url,
+    maybe_header: Option<(HeaderName, HeaderValue)>,
+  ) -> Result<Option<(HeadersMap, Vec<u8>)>, AnyError> {
+    let mut request = self.get(endpoint)?;
+    if let Some((name, value)) = maybe_header {
+      request = request.header(name, value);
+    }
+    let response = request.send().await?;
+    let status = response.status();
+    if status == StatusCode::NOT_FOUND {
+      return Ok(None);
+    } else if !status.is_success() {
+      bail!("Bad response: {}", status);
+    }
+    let headers = response
+      .headers()
+      .get(CACHE_SERVER_HEADERS_HEADER)
+      .with_context(|| {
+        format!("Missing {} header.", CACHE_SERVER_HEADERS_HEADER)
+      })?
+      .to_str()?;
+    let headers = serde_json::from_slice(&BASE64_STANDARD.decode(headers)?)?;
+    let content = response.collect().await?.to_bytes();
+    Ok(Some((headers, content.into())))
+  }
+
   pub async fn get_redirected_url(
     &self,
     url: Url,
@@ -507,6 +1047,20 @@ impl HttpClient {
     url: Url,
     maybe_header: Option<(HeaderName, HeaderValue)>,
     progress_guard: Option<&UpdateGuard>,
+  ) -> Result<Option<Vec<u8>>, DownloadError> {
+    let request =
+      self.download_inner_unlimited(url.clone(), maybe_header, progress_guard);
+    match self.download_limiter.run(&url, request).await {
+      Ok(result) => result,
+      Err(source) => Err(DownloadError::Timeout { url, source }),
+    }
+  }
+
+  async fn download_inner_unlimited(
+    &self,
+    url: Url,
+    maybe_header: Option<(HeaderName, HeaderValue)>,
+    progress_guard: Option<&UpdateGuard>,
   ) -> Result<Option<Vec<u8>>, DownloadError> {
     let (response, _) = self.get_redirected_response(url, maybe_header).await?;
 
@@ -559,8 +1113,7 @@ impl HttpClient {
         }
 
         let new_response = self
-          .client
-          .clone()
+          .client_for_redirect(&new_url)?
           .send(req)
           .await
           .map_err(|e| DownloadError::Fetch(e.into()))?;
@@ -704,6 +1257,7 @@ impl RequestBuilder {
 mod test {
   use std::collections::HashSet;
   use std::hash::RandomState;
+  use std::path::PathBuf;
 
   use deno_runtime::deno_tls::rustls::RootCertStore;
 
@@ -711,6 +1265,30 @@ mod test {
 
   use super::*;
 
+  #[test]
+  fn test_cache_server_urls() {
+    let cache_server =
+      CacheServer::new(Url::parse("http://cache.internal:4507/deno").unwrap())
+        .unwrap();
+    assert_eq!(
+      cache_server.url().as_str(),
+      "http://cache.internal:4507/deno/"
+    );
+    assert_eq!(
+      cache_server
+        .remote_url(&Url::parse("https://jsr.io/@std/fs/meta.json").unwrap())
+        .as_str(),
+      "http://cache.internal:4507/deno/remote?url=https%3A%2F%2Fjsr.io%2F%40std%2Ffs%2Fmeta.json"
+    );
+    assert_eq!(
+      cache_server
+        .npm_url(&Url::parse("https://registry.npmjs.org/chalk").unwrap())
+        .as_str(),
+      "http://cache.internal:4507/deno/npm?url=https%3A%2F%2Fregistry.npmjs.org%2Fchalk"
+    );
+    assert!(CacheServer::new(Url::parse("file:///cache/").unwrap()).is_err());
+  }
+
   #[tokio::test]
   async fn test_http_client_download_redirect() {
     let _http_server_guard = test_util::http_server();
@@ -794,6 +1372,7 @@ mod test {
         url,
         maybe_accept: None,
         maybe_etag: None,
+        maybe_last_modified: None,
         maybe_auth_token: None,
         maybe_progress_guard: None,
         maybe_auth: None,
@@ -821,6 +1400,7 @@ mod test {
         url,
         maybe_accept: None,
         maybe_etag: None,
+        maybe_last_modified: None,
         maybe_auth_token: None,
         maybe_progress_guard: None,
         maybe_auth: None,
@@ -849,6 +1429,7 @@ mod test {
         url: url.clone(),
         maybe_accept: None,
         maybe_etag: None,
+        maybe_last_modified: None,
         maybe_auth_token: None,
         maybe_progress_guard: None,
         maybe_auth: None,
@@ -871,12 +1452,13 @@ mod test {
         url,
         maybe_accept: None,
         maybe_etag: Some("33a64df551425fcc55e".to_string()),
+        maybe_last_modified: None,
         maybe_auth_token: None,
         maybe_progress_guard: None,
         maybe_auth: None,
       })
       .await;
-    assert_eq!(res.unwrap(), FetchOnceResult::NotModified);
+    assert!(matches!(res.unwrap(), FetchOnceResult::NotModified(_)));
   }
 
   #[tokio::test]
@@ -891,6 +1473,7 @@ mod test {
         url,
         maybe_accept: None,
         maybe_etag: None,
+        maybe_last_modified: None,
         maybe_auth_token: None,
         maybe_progress_guard: None,
         maybe_auth: None,
@@ -921,6 +1504,7 @@ mod test {
         url,
         maybe_accept: Some("application/json".to_string()),
         maybe_etag: None,
+        maybe_last_modified: None,
         maybe_auth_token: None,
         maybe_progress_guard: None,
         maybe_auth: None,
@@ -947,6 +1531,7 @@ mod test {
         url,
         maybe_accept: None,
         maybe_etag: None,
+        maybe_last_modified: None,
         maybe_auth_token: None,
         maybe_progress_guard: None,
         maybe_auth: None,
@@ -983,6 +1568,7 @@ mod test {
         url,
         maybe_accept: None,
         maybe_etag: None,
+        maybe_last_modified: None,
         maybe_auth_token: None,
         maybe_progress_guard: None,
         maybe_auth: None,
@@ -1031,6 +1617,7 @@ mod test {
           url,
           maybe_accept: None,
           maybe_etag: None,
+          maybe_last_modified: None,
           maybe_auth_token: None,
           maybe_progress_guard: None,
           maybe_auth: None,
@@ -1044,11 +1631,13 @@ mod test {
         }
         Ok(
           FetchOnceResult::Code(..)
-          | FetchOnceResult::NotModified
+          | FetchOnceResult::NotModified(_)
           | FetchOnceResult::Redirect(..),
         ) => return,
         Ok(
-          FetchOnceResult::RequestError(_) | FetchOnceResult::ServerError(_),
+          FetchOnceResult::RequestError(_)
+          | FetchOnceResult::ServerError(_)
+          | FetchOnceResult::Unauthorized,
         ) => {
           eprintln!("HTTP error: {result:?}");
           continue;
@@ -1094,6 +1683,7 @@ mod test {
         url,
         maybe_accept: None,
         maybe_etag: None,
+        maybe_last_modified: None,
         maybe_auth_token: None,
         maybe_progress_guard: None,
         maybe_auth: None,
@@ -1107,13 +1697,15 @@ mod test {
       }
       Ok(
         FetchOnceResult::Code(..)
-        | FetchOnceResult::NotModified
+        | FetchOnceResult::NotModified(_)
         | FetchOnceResult::Redirect(..),
       ) => {
         panic!("Should not have successfully fetched a URL");
       }
       Ok(
-        FetchOnceResult::RequestError(_) | FetchOnceResult::ServerError(_),
+        FetchOnceResult::RequestError(_)
+        | FetchOnceResult::ServerError(_)
+        | FetchOnceResult::Unauthorized,
       ) => {
         eprintln!("HTTP error (expected): {result:?}");
         return;
@@ -1148,6 +1740,7 @@ mod test {
         url,
         maybe_accept: None,
         maybe_etag: None,
+        maybe_last_modified: None,
         maybe_auth_token: None,
         maybe_progress_guard: None,
         maybe_auth: None,
@@ -1190,6 +1783,7 @@ mod test {
         url: url.clone(),
         maybe_accept: None,
         maybe_etag: None,
+        maybe_last_modified: None,
         maybe_auth_token: None,
         maybe_progress_guard: None,
         maybe_auth: None,
@@ -1213,12 +1807,13 @@ mod test {
         url,
         maybe_accept: None,
         maybe_etag: Some("33a64df551425fcc55e".to_string()),
+        maybe_last_modified: None,
         maybe_auth_token: None,
         maybe_progress_guard: None,
         maybe_auth: None,
       })
       .await;
-    assert_eq!(res.unwrap(), FetchOnceResult::NotModified);
+    assert!(matches!(res.unwrap(), FetchOnceResult::NotModified(_)));
   }
 
   #[tokio::test]
@@ -1248,6 +1843,7 @@ mod test {
         url,
         maybe_accept: None,
         maybe_etag: None,
+        maybe_last_modified: None,
         maybe_auth_token: None,
         maybe_progress_guard: None,
         maybe_auth: None,
@@ -1278,6 +1874,7 @@ mod test {
         url,
         maybe_accept: None,
         maybe_etag: None,
+        maybe_last_modified: None,
         maybe_auth_token: None,
         maybe_progress_guard: None,
         maybe_auth: None,
@@ -1300,6 +1897,7 @@ mod test {
         url,
         maybe_accept: None,
         maybe_etag: None,
+        maybe_last_modified: None,
         maybe_auth_token: None,
         maybe_progress_guard: None,
         maybe_auth: None,
@@ -1324,6 +1922,7 @@ mod test {
         url,
         maybe_accept: None,
         maybe_etag: None,
+        maybe_last_modified: None,
         maybe_auth_token: None,
         maybe_progress_guard: None,
         maybe_auth: None,
@@ -1332,4 +1931,158 @@ mod test {
 
     assert!(matches!(result, Ok(FetchOnceResult::RequestError(_))));
   }
+
+  #[test]
+  fn updates_not_modified_headers() {
+    let mut cached_headers = HeadersMap::from([
+      (
+        "content-type".to_string(),
+        "application/typescript".to_string(),
+      ),
+      ("content-length".to_string(), "42".to_string()),
+      ("etag".to_string(), "\"1\"".to_string()),
+      (
+        "date".to_string(),
+        "Mon, 01 Jan 2024 00:00:00 GMT".to_string(),
+      ),
+    ]);
+    update_not_modified_headers(
+      &mut cached_headers,
+      HeadersMap::from([
+        ("content-length".to_string(), "0".to_string()),
+        ("etag".to_string(), "\"1\"".to_string()),
+        (
+          "date".to_string(),
+          "Tue, 02 Jan 2024 00:00:00 GMT".to_string(),
+        ),
+        ("cache-control".to_string(), "max-age=60".to_string()),
+      ]),
+    );
+    assert_eq!(
+      cached_headers,
+      HeadersMap::from([
+        (
+          "content-type".to_string(),
+          "application/typescript".to_string()
+        ),
+        ("content-length".to_string(), "42".to_string()),
+        ("etag".to_string(), "\"1\"".to_string()),
+        (
+          "date".to_string(),
+          "Tue, 02 Jan 2024 00:00:00 GMT".to_string()
+        ),
+        ("cache-control".to_string(), "max-age=60".to_string()),
+      ])
+    );
+  }
+
+  #[tokio::test]
+  async fn limits_downloads_per_host() {
+    let limiter = DownloadLimiter::new(DownloadPolicy {
+      max_concurrent_requests_per_host: Some(1),
+      timeout: Some(Duration::from_millis(10)),
+      ..Default::default()
+    });
+    let url = Url::parse("https://deno.land/x/mod.ts").unwrap();
+    let permit = limiter.acquire(&url).await.unwrap();
+    // the host is at capacity
+    let same_host = Url::parse("https://deno.land:443/std/mod.ts").unwrap();
+    assert!(tokio::time::timeout(
+      Duration::from_millis(10),
+      limiter.acquire(&same_host)
+    )
+    .await
+    .is_err());
+    // other hosts aren't affected
+    let other_host = Url::parse("https://jsr.io/@std/fs/meta.json").unwrap();
+    assert!(limiter.acquire(&other_host).await.is_some());
+    drop(permit);
+    assert!(limiter.acquire(&same_host).await.is_some());
+
+    let err = limiter
+      .run(&url, std::future::pending::<()>())
+      .await
+      .unwrap_err();
+    assert_eq!(err.to_string(), "Timed out after 10ms");
+    assert_eq!(limiter.run(&url, async { 1 }).await.unwrap(), 1);
+  }
+
+  #[test]
+  fn loads_client_cert() {
+    let tls_path = test_util::testdata_path().join("tls");
+    let config = ClientCertConfig {
+      host: "localhost".to_string(),
+      cert_file: tls_path.join("localhost.crt").to_path_buf(),
+      key_file: Some(tls_path.join("localhost.key").to_path_buf()),
+    };
+    let TlsKey(certs, _) = load_client_cert(&config).unwrap();
+    assert!(!certs.is_empty());
+
+    // the key is read from the certificate file when there's no key file
+    let config = ClientCertConfig {
+      key_file: None,
+      ..config
+    };
+    let err = load_client_cert(&config).err().unwrap();
+    assert_eq!(err.to_string(), "No private key found");
+  }
+
+  #[test]
+  fn selects_client_cert_by_host() {
+    let provider = HttpClientProvider::new(None, None).with_client_certs(vec![
+      ClientCertConfig {
+        host: "registry.internal:8443".to_string(),
+        cert_file: PathBuf::from("missing.pem"),
+        key_file: None,
+      },
+    ]);
+    // certificates are only loaded for the hosts they're configured for
+    assert!(provider
+      .get_or_create_for_url(&Url::parse("https://deno.land/x/").unwrap())
+      .is_ok());
+    let err = provider
+      .get_or_create_for_url(
+        &Url::parse("https://registry.internal:8443/mod.ts").unwrap(),
+      )
+      .err()
+      .unwrap();
+    assert_eq!(
+      err.to_string(),
+      "Failed loading the client certificate for 'registry.internal:8443'"
+    );
+  }
+
+  #[tokio::test]
+  async fn selects_client_cert_per_redirect_hop() {
+    use tokio::io::AsyncReadExt;
+    use tokio::io::AsyncWriteExt;
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    deno_core::unsync::spawn(async move {
+      let (mut stream, _) = listener.accept().await.unwrap();
+      let mut buf = [0; 1024];
+      let _ = stream.read(&mut buf).await.unwrap();
+      let response = format!(
+        "HTTP/1.1 302 Found\r\nlocation: http://localhost:{port}/mod.ts\r\ncontent-length: 0\r\nconnection: close\r\n\r\n"
+      );
+      stream.write_all(response.as_bytes()).await.unwrap();
+    });
+
+    let provider = HttpClientProvider::new(None, None).with_client_certs(vec![
+      ClientCertConfig {
+        host: format!("localhost:{port}"),
+        cert_file: PathBuf::from("missing.pem"),
+        key_file: None,
+      },
+    ]);
+    let url = Url::parse(&format!("http://127.0.0.1:{port}/mod.ts")).unwrap();
+    let client = provider.get_or_create_for_url(&url).unwrap();
+    // the redirect to the host of the certificate needs to load it
+    let err = client.download(url).await.err().unwrap();
+    assert_eq!(
+      err.to_string(),
+      format!("Failed loading the client certificate for 'localhost:{port}'")
+    );
+  }
 }
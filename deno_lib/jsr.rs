@@ -12,6 +12,9 @@ use std::sync::Arc;
 
 /// This is similar to a subset of `JsrCacheResolver` which fetches rather than
 /// just reads the cache. Keep in sync!
+///
+/// The metadata is requested by its `jsr_url()` URL, so the mirrors of the
+/// file fetcher apply to it while the cache keeps using the registry URL.
 #[derive(Debug)]
 pub struct JsrFetchResolver {
   nv_by_req: DashMap<PackageReq, Option<PackageNv>>,
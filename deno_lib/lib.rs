@@ -7,10 +7,12 @@ mod errors;
 mod factory;
 mod file_fetcher;
 mod graph_container;
+mod graph_info;
 mod graph_util;
 mod http_util;
 mod js;
 mod jsr;
+#[cfg(feature = "lsp")]
 mod lsp;
 mod module_loader;
 mod node;
@@ -18,6 +20,7 @@ mod npm;
 mod ops;
 mod resolver;
 mod shared;
+#[cfg(feature = "compile")]
 mod standalone;
 mod task_runner;
 mod tools;
@@ -48,13 +51,53 @@ use deno_runtime::fmt_errors::format_js_error;
 use deno_runtime::tokio_util::create_and_run_current_thread_with_maybe_metrics;
 use deno_terminal::colors;
 use factory::CliFactory;
-use standalone::MODULE_NOT_FOUND;
-use standalone::UNSUPPORTED_SCHEME;
+use module_loader::MODULE_NOT_FOUND;
+use module_loader::UNSUPPORTED_SCHEME;
 use std::future::Future;
 use std::ops::Deref;
 use std::sync::Arc;
 
+pub use cache::CacheDBHealth;
 pub use deno_runtime;
+pub use graph_info::DependencyInfo;
+pub use graph_info::ModuleErrorInfo;
+pub use graph_info::ModuleGraphInfo;
+pub use graph_info::ModuleGraphOptions;
+pub use graph_info::ModuleInfo;
+pub use graph_info::ModuleKind;
+pub use graph_info::NpmPackageInfo;
+pub use graph_info::ResolvedDependencyInfo;
+pub use graph_info::SourcePosition;
+pub use graph_info::SourceRange;
+#[cfg(feature = "compile")]
+pub use standalone::RemoteModuleInfo;
+#[cfg(feature = "compile")]
+pub use standalone::StandaloneBinaryContents;
+#[cfg(feature = "compile")]
+pub use standalone::StandaloneBinaryInfo;
+#[cfg(feature = "compile")]
+pub use standalone::VfsEntryInfo;
+pub use tools::cache_usage::CacheCategory;
+pub use tools::cache_usage::CacheCategoryUsage;
+pub use tools::cache_usage::CacheConsumerUsage;
+pub use tools::cache_usage::CacheDatabaseUsage;
+pub use tools::cache_usage::CacheUsageReport;
+pub use tools::cache_usage::NpmPackageUsage;
+pub use tools::run::WatchSession;
+pub use tools::vendor::VendorOptions;
+pub use tools::vendor::VendorReport;
+pub use util::file_watcher::WatcherChangeAction;
+pub use util::file_watcher::WatcherEvent;
+pub use util::op_trace::to_chrome_trace;
+pub use util::op_trace::OpCallKind;
+pub use util::op_trace::OpMetricsEntry;
+pub use util::op_trace::OpMetricsReport;
+pub use util::op_trace::OpTraceRecorder;
+pub use util::op_trace::OpTraceResult;
+pub use util::op_trace::OpTraceSpan;
+pub use worker::CliMainWorker;
+pub use worker::HmrRejectReason;
+pub use worker::ReplaceModuleError;
 
 pub fn run(cmd: &str) -> String {
     let args: Vec<_> = vec!["deno", "run", cmd]
@@ -178,6 +221,121 @@ pub async fn run_script(flags: Arc<Flags>) -> Result<i32, AnyError> {
     handle.await?
 }
 
+/// Builds the module graph for `roots` using the resolver of the workspace
+/// found in the current working directory, the same way `deno info` does.
+pub async fn module_graph(
+    roots: &[String],
+    options: ModuleGraphOptions,
+) -> Result<ModuleGraphInfo, AnyError> {
+    graph_info::build_module_graph_info(roots, options).await
+}
+
+/// Copies the remote modules of `roots` into a directory along with an
+/// import map that resolves them to their copies, the same way
+/// `deno vendor` does.
+pub async fn vendor(
+    roots: &[String],
+    options: VendorOptions,
+) -> Result<VendorReport, AnyError> {
+    tools::vendor::vendor_modules(roots, options).await
+}
+
+/// Compiles a script into a self-contained executable, the same way
+/// `deno compile <args>` does. When `base_binary` is provided, it's used as
+/// the denort executable the script is embedded in instead of downloading
+/// or looking up one according to the flags.
+#[cfg(feature = "compile")]
+pub async fn compile(args: &[&str], base_binary: Option<Vec<u8>>) -> Result<(), AnyError> {
+    let args: Vec<_> = ["deno", "compile"]
+        .iter()
+        .chain(args)
+        .map(std::ffi::OsString::from)
+        .collect();
+    let flags = flags_from_vec(args)?;
+    let DenoSubcommand::Compile(compile_flags) = flags.subcommand.clone() else {
+        unreachable!();
+    };
+    tools::compile::compile_with_base_binary(Arc::new(flags), compile_flags, base_binary).await
+}
+
+/// Summarizes how the cache directory is used: the size of each category,
+/// the largest remote origins and npm packages, the npm packages that the
+/// lockfile of the project in the current working directory doesn't use and
+/// the health of the cache databases.
+pub fn cache_usage() -> Result<CacheUsageReport, AnyError> {
+    let factory = CliFactory::from_flags(Arc::new(Flags::default()));
+    tools::cache_usage::cache_usage_report(&factory)
+}
+
+/// Runs `script` in watch mode under `session` until the session is stopped.
+/// With `hmr` set, changed modules are hot-replaced where possible instead of
+/// restarting the script.
+pub async fn run_script_with_watch(
+    script: &str,
+    hmr: bool,
+    session: &WatchSession,
+) -> Result<(), AnyError> {
+    let args: Vec<_> = vec![
+        "deno",
+        "run",
+        if hmr { "--watch-hmr" } else { "--watch" },
+        "--no-clear-screen",
+        script,
+    ]
+    .into_iter()
+    .map(std::ffi::OsString::from)
+    .collect();
+    let flags = flags_from_vec(args)?;
+    let DenoSubcommand::Run(run_flags) = &flags.subcommand else {
+        unreachable!();
+    };
+    let watch_flags = run_flags.watch.clone().unwrap();
+    tools::run::run_with_watch_session(
+        WorkerExecutionMode::Run,
+        Arc::new(flags),
+        watch_flags,
+        session,
+    )
+    .await
+}
+
+/// Creates a worker for `script` with the permissions and configuration of
+/// `deno run <script>`. Call [`CliMainWorker::execute_main_module`] to load
+/// the script; the worker stays alive afterwards, so its modules can be
+/// hot-replaced with [`CliMainWorker::replace_module`].
+pub async fn create_main_worker(script: &str) -> Result<CliMainWorker, AnyError> {
+    create_main_worker_with_flags(run_flags(script)?).await
+}
+
+/// Like [`create_main_worker`], but the runtime counts the op calls of the
+/// worker, which [`CliMainWorker::op_metrics_report`] returns.
+pub async fn create_main_worker_with_op_metrics(
+    script: &str,
+) -> Result<CliMainWorker, AnyError> {
+    let mut flags = run_flags(script)?;
+    flags.enable_op_summary_metrics = true;
+    create_main_worker_with_flags(flags).await
+}
+
+fn run_flags(script: &str) -> Result<Flags, AnyError> {
+    let args: Vec<_> = vec!["deno", "run", script]
+        .into_iter()
+        .map(std::ffi::OsString::from)
+        .collect();
+    Ok(flags_from_vec(args)?)
+}
+
+async fn create_main_worker_with_flags(flags: Flags) -> Result<CliMainWorker, AnyError> {
+    let factory = CliFactory::from_flags(Arc::new(flags));
+    let main_module = factory.cli_options()?.resolve_main_module()?;
+    tools::run::maybe_npm_install(&factory).await?;
+    factory
+        .create_cli_main_worker_factory()
+        .await?
+        .create_main_worker(WorkerExecutionMode::Run, main_module)
+        .await
+}
+
 fn resolve_flags_and_init(args: Vec<std::ffi::OsString>) -> Result<Flags, AnyError> {
     let flags = match flags_from_vec(args) {
         Ok(flags) => flags,
@@ -108,7 +108,9 @@ use crate::lsp::tsc::file_text_changes_to_workspace_edit;
 use crate::lsp::urls::LspUrlKind;
 use crate::tools::fmt::format_file;
 use crate::tools::fmt::format_parsed_source;
+#[cfg(feature = "upgrade")]
 use crate::tools::upgrade::check_for_upgrades_for_lsp;
+#[cfg(feature = "upgrade")]
 use crate::tools::upgrade::upgrade_check_enabled;
 use crate::util::fs::remove_dir_all_if_exists;
 use crate::util::path::is_importable_ext;
@@ -3082,6 +3084,7 @@ impl tower_lsp::LanguageServer for LanguageServer {
       }
     }
 
+    #[cfg(feature = "upgrade")]
     if upgrade_check_enabled() {
       let client = self.client.clone();
       // spawn to avoid lsp send/sync requirement, but also just
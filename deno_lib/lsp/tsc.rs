@@ -409,6 +409,8 @@ impl TsServer {
       }
       None => None,
     };
+    let startup_snapshot = tsc::compiler_snapshot()
+      .context("Failed starting the TypeScript language server.")?;
     self
       .inspector_server
       .lock()
@@ -424,6 +426,7 @@ impl TsServer {
         performance,
         specifier_map,
         maybe_inspector_server,
+        startup_snapshot,
       )
     });
     Ok(())
@@ -4754,6 +4757,7 @@ fn run_tsc_thread(
   performance: Arc<Performance>,
   specifier_map: Arc<TscSpecifierMap>,
   maybe_inspector_server: Option<Arc<InspectorServer>>,
+  startup_snapshot: &'static [u8],
 ) {
   let has_inspector_server = maybe_inspector_server.is_some();
   // Create and setup a JsRuntime based on a snapshot. It is expected that the
@@ -4765,7 +4769,7 @@ fn run_tsc_thread(
       specifier_map,
       request_rx,
     )],
-    startup_snapshot: Some(tsc::compiler_snapshot()),
+    startup_snapshot: Some(startup_snapshot),
     inspector: has_inspector_server,
     ..Default::default()
   });
@@ -9,10 +9,12 @@ mod errors;
 mod factory;
 mod file_fetcher;
 mod graph_container;
+mod graph_info;
 mod graph_util;
 mod http_util;
 mod js;
 mod jsr;
+#[cfg(feature = "lsp")]
 mod lsp;
 mod module_loader;
 mod node;
@@ -20,6 +22,7 @@ mod npm;
 mod ops;
 mod resolver;
 mod shared;
+#[cfg(feature = "compile")]
 mod standalone;
 mod task_runner;
 mod tools;
@@ -51,8 +54,8 @@ use deno_runtime::tokio_util::create_and_run_current_thread_with_maybe_metrics;
 use deno_terminal::colors;
 use factory::CliFactory;
 use npm::ResolvePkgFolderFromDenoReqError;
-use standalone::MODULE_NOT_FOUND;
-use standalone::UNSUPPORTED_SCHEME;
+use module_loader::MODULE_NOT_FOUND;
+use module_loader::UNSUPPORTED_SCHEME;
 use std::env;
 use std::future::Future;
 use std::io::IsTerminal;
@@ -123,17 +126,31 @@ async fn run_subcommand(flags: Arc<Flags>) -> Result<i32, AnyError> {
       tools::run::eval_command(flags, eval_flags).await
     }),
     DenoSubcommand::Cache(cache_flags) => spawn_subcommand(async move {
-      tools::installer::install_from_entrypoints(flags, &cache_flags.files).await
+      if let Some(address) = &cache_flags.serve {
+        tools::cache_server::serve(flags, address).await
+      } else if let Some(archive) = &cache_flags.import {
+        tools::cache_bundle::import(flags, archive).await
+      } else if let Some(archive) = &cache_flags.export {
+        tools::cache_bundle::export(flags, &cache_flags.files, archive).await
+      } else {
+        tools::installer::install_from_entrypoints(flags, &cache_flags.files).await
+      }
     }),
     DenoSubcommand::Check(check_flags) => spawn_subcommand(async move {
       tools::check::check(flags, check_flags).await
     }),
-    DenoSubcommand::Clean => spawn_subcommand(async move {
-      tools::clean::clean()
+    DenoSubcommand::Clean(clean_flags) => spawn_subcommand(async move {
+      tools::clean::clean(clean_flags)
     }),
+    #[cfg(feature = "compile")]
     DenoSubcommand::Compile(compile_flags) => spawn_subcommand(async {
       tools::compile::compile(flags, compile_flags).await
     }),
+    #[cfg(not(feature = "compile"))]
+    DenoSubcommand::Compile(_) => exit_with_message(
+      "This deno was built without the \"compile\" feature.",
+      1,
+    ),
     DenoSubcommand::Coverage(coverage_flags) => spawn_subcommand(async {
       tools::coverage::cover_files(flags, coverage_flags)
     }),
@@ -158,12 +175,19 @@ async fn run_subcommand(flags: Arc<Flags>) -> Result<i32, AnyError> {
     DenoSubcommand::JSONReference(json_reference) => spawn_subcommand(async move {
       display::write_to_stdout_ignore_sigpipe(&deno_core::serde_json::to_vec_pretty(&json_reference.json).unwrap())
     }),
+    #[cfg(feature = "jupyter")]
     DenoSubcommand::Jupyter(jupyter_flags) => spawn_subcommand(async {
       tools::jupyter::kernel(flags, jupyter_flags).await
     }),
+    #[cfg(not(feature = "jupyter"))]
+    DenoSubcommand::Jupyter(_) => exit_with_message(
+      "This deno was built without the \"jupyter\" feature.",
+      1,
+    ),
     DenoSubcommand::Uninstall(uninstall_flags) => spawn_subcommand(async {
       tools::installer::uninstall(flags, uninstall_flags).await
     }),
+    #[cfg(feature = "lsp")]
     DenoSubcommand::Lsp => spawn_subcommand(async {
       if std::io::stderr().is_terminal() {
         log::warn!(
@@ -177,6 +201,11 @@ async fn run_subcommand(flags: Arc<Flags>) -> Result<i32, AnyError> {
       }
       lsp::start().await
     }),
+    #[cfg(not(feature = "lsp"))]
+    DenoSubcommand::Lsp => exit_with_message(
+      "This deno was built without the \"lsp\" feature.",
+      1,
+    ),
     DenoSubcommand::Lint(lint_flags) => spawn_subcommand(async {
       if lint_flags.rules {
         tools::lint::print_rules_list(
@@ -188,9 +217,15 @@ async fn run_subcommand(flags: Arc<Flags>) -> Result<i32, AnyError> {
         tools::lint::lint(flags, lint_flags).await
       }
     }),
+    #[cfg(feature = "repl")]
     DenoSubcommand::Repl(repl_flags) => {
       spawn_subcommand(async move { tools::repl::run(flags, repl_flags).await })
     }
+    #[cfg(not(feature = "repl"))]
+    DenoSubcommand::Repl(_) => exit_with_message(
+      "This deno was built without the \"repl\" feature.",
+      1,
+    ),
     DenoSubcommand::Run(run_flags) => spawn_subcommand(async move {
       if run_flags.is_stdin() {
         tools::run::run_from_stdin(flags.clone()).await
@@ -291,8 +326,9 @@ async fn run_subcommand(flags: Arc<Flags>) -> Result<i32, AnyError> {
       })
     }
     DenoSubcommand::Types => spawn_subcommand(async move {
-      let types = tsc::get_types_declaration_file_text();
-      display::write_to_stdout_ignore_sigpipe(types.as_bytes())
+      let types = tsc::get_types_declaration_file_text()?;
+      display::write_to_stdout_ignore_sigpipe(types.as_bytes())?;
+      Ok::<(), AnyError>(())
     }),
     #[cfg(feature = "upgrade")]
     DenoSubcommand::Upgrade(upgrade_flags) => spawn_subcommand(async {
@@ -303,10 +339,18 @@ async fn run_subcommand(flags: Arc<Flags>) -> Result<i32, AnyError> {
       "This deno was built without the \"upgrade\" feature. Please upgrade using the installation method originally used to install Deno.",
       1,
     ),
-    DenoSubcommand::Vendor => exit_with_message("⚠️ `deno vendor` was removed in Deno 2.\n\nSee the Deno 1.x to 2.x Migration Guide for migration instructions: https://docs.deno.com/runtime/manual/advanced/migrate_deprecations", 1),
+    DenoSubcommand::Vendor(vendor_flags) => spawn_subcommand(async {
+      tools::vendor::vendor(flags, vendor_flags).await
+    }),
+    #[cfg(feature = "publish")]
     DenoSubcommand::Publish(publish_flags) => spawn_subcommand(async {
       tools::registry::publish(flags, publish_flags).await
     }),
+    #[cfg(not(feature = "publish"))]
+    DenoSubcommand::Publish(_) => exit_with_message(
+      "This deno was built without the \"publish\" feature.",
+      1,
+    ),
     DenoSubcommand::Help(help_flags) => spawn_subcommand(async move {
       use std::io::Write;
 
@@ -76,6 +76,9 @@ use deno_semver::npm::NpmPackageReqReference;
 use node_resolver::InNpmPackageChecker;
 use node_resolver::NodeResolutionMode;
 
+pub const MODULE_NOT_FOUND: &str = "Module not found";
+pub const UNSUPPORTED_SCHEME: &str = "Unsupported scheme";
+
 pub struct ModuleLoadPreparer {
   options: Arc<CliOptions>,
   lockfile: Option<Arc<CliLockfile>>,
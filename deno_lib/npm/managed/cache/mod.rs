@@ -30,8 +30,12 @@ pub mod registry_info;
 mod tarball;
 mod tarball_extract;
 
+pub use registry_info::locked_versions;
+pub use registry_info::verify_package_info_integrity;
 pub use registry_info::RegistryInfoDownloader;
 pub use tarball::TarballCache;
+pub use tarball_extract::extract_tarball;
+pub use tarball_extract::verify_tarball_integrity;
 
 /// Stores a single copy of npm packages in a cache.
 #[derive(Debug)]
@@ -278,3 +282,281 @@ fn with_folder_sync_lock(
     }
   }
 }
+
+#[cfg(test)]
+mod test {
+  use base64::prelude::BASE64_STANDARD;
+  use base64::Engine;
+  use deno_npm::registry::NpmPackageVersionDistInfo;
+  use deno_runtime::deno_fs::RealFs;
+  use test_util::TempDir;
+  use tokio::io::AsyncReadExt;
+  use tokio::io::AsyncWriteExt;
+  use tokio::net::TcpListener;
+
+  use super::*;
+  use crate::args::CliLockfile;
+  use crate::args::CliLockfileReadFromPathOptions;
+  use crate::cache::RealDenoCacheEnv;
+  use crate::http_util::CacheServer;
+  use crate::http_util::HttpClientProvider;
+  use crate::util::progress_bar::ProgressBar;
+  use crate::util::progress_bar::ProgressBarStyle;
+
+  // nothing listens on this port, so packages can only come from the cache
+  // server
+  const REGISTRY_URL: &str = "http://127.0.0.1:1/";
+
+  struct Fixture {
+    temp_dir: TempDir,
+    npmrc: Arc<ResolvedNpmRc>,
+    http_client_provider: Arc<HttpClientProvider>,
+    requests: Arc<Mutex<usize>>,
+  }
+
+  impl Fixture {
+    /// Starts a cache server that answers every request for a tarball with
+    /// `tarball` and every other one with `packument`.
+    async fn new(packument: Vec<u8>, tarball: Vec<u8>) -> Self {
+      let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+      let url = format!("http://{}/", listener.local_addr().unwrap());
+      let requests = Arc::new(Mutex::new(0));
+      deno_core::unsync::spawn({
+        let requests = requests.clone();
+        async move {
+          loop {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0; 1024];
+            while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+              let read = stream.read(&mut buf).await.unwrap();
+              if read == 0 {
+                break;
+              }
+              request.extend_from_slice(&buf[..read]);
+            }
+            *requests.lock() += 1;
+            let request = String::from_utf8(request).unwrap();
+            let request_line = request.lines().next().unwrap_or_default();
+            let body = if request_line.contains(".tgz") {
+              &tarball
+            } else {
+              &packument
+            };
+            let head = format!(
+              "HTTP/1.1 200 OK\r\ncontent-length: {}\r\nconnection: close\r\n\r\n",
+              body.len()
+            );
+            stream.write_all(head.as_bytes()).await.unwrap();
+            stream.write_all(body).await.unwrap();
+          }
+        }
+      });
+      Self {
+        temp_dir: TempDir::new(),
+        npmrc: Arc::new(ResolvedNpmRc {
+          default_config: deno_npm::npm_rc::RegistryConfigWithUrl {
+            registry_url: Url::parse(REGISTRY_URL).unwrap(),
+            config: Default::default(),
+          },
+          scopes: Default::default(),
+          registry_configs: Default::default(),
+        }),
+        http_client_provider: Arc::new(
+          HttpClientProvider::new(None, None).with_cache_server(Some(
+            CacheServer::new(Url::parse(&url).unwrap()).unwrap(),
+          )),
+        ),
+        requests,
+      }
+    }
+
+    fn npm_cache(&self, name: &str) -> Arc<NpmCache> {
+      Arc::new(NpmCache::new(
+        Arc::new(NpmCacheDir::new(
+          &RealDenoCacheEnv,
+          self.temp_dir.path().join(name).to_path_buf(),
+          vec![Url::parse(REGISTRY_URL).unwrap()],
+        )),
+        CacheSetting::Use,
+        self.npmrc.clone(),
+      ))
+    }
+
+    fn lockfile(&self, integrity: &str) -> Arc<CliLockfile> {
+      let path = self.temp_dir.path().join("deno.lock");
+      path.write(
+        serde_json::json!({
+          "version": "4",
+          "npm": {
+            "chalk@5.3.0": { "integrity": integrity },
+          },
+        })
+        .to_string(),
+      );
+      Arc::new(
+        CliLockfile::read_from_path(CliLockfileReadFromPathOptions {
+          file_path: path.to_path_buf(),
+          frozen: false,
+          skip_write: true,
+        })
+        .unwrap(),
+      )
+    }
+
+    fn requests(&self) -> usize {
+      *self.requests.lock()
+    }
+  }
+
+  fn npm_tarball() -> Vec<u8> {
+    let text = r#"{"name":"chalk","version":"5.3.0"}"#;
+    let mut builder = tar::Builder::new(flate2::write::GzEncoder::new(
+      Vec::new(),
+      flate2::Compression::default(),
+    ));
+    let mut header = tar::Header::new_gnu();
+    header.set_size(text.len() as u64);
+    header.set_mode(0o644);
+    header.set_cksum();
+    builder
+      .append_data(&mut header, "package/package.json", text.as_bytes())
+      .unwrap();
+    builder.into_inner().unwrap().finish().unwrap()
+  }
+
+  fn sha512_integrity(data: &[u8]) -> String {
+    let digest = ring::digest::digest(&ring::digest::SHA512, data);
+    format!("sha512-{}", BASE64_STANDARD.encode(digest.as_ref()))
+  }
+
+  fn dist(integrity: &str) -> serde_json::Value {
+    serde_json::json!({
+      "tarball": format!("{}chalk/-/chalk-5.3.0.tgz", REGISTRY_URL),
+      "shasum": "",
+      "integrity": integrity,
+    })
+  }
+
+  fn packument(integrity: &str) -> Vec<u8> {
+    serde_json::json!({
+      "name": "chalk",
+      "dist-tags": { "latest": "6.0.0" },
+      "versions": {
+        "5.3.0": {
+          "name": "chalk",
+          "version": "5.3.0",
+          "dist": dist(integrity),
+        },
+        // a version the lockfile doesn't vouch for
+        "6.0.0": {
+          "name": "chalk",
+          "version": "6.0.0",
+          "dist": {
+            "tarball": "https://example.com/chalk-6.0.0.tgz",
+            "shasum": "",
+            "integrity": "sha512-AAAA",
+          },
+        },
+      },
+    })
+    .to_string()
+    .into_bytes()
+  }
+
+  fn chalk() -> PackageNv {
+    PackageNv {
+      name: "chalk".to_string(),
+      version: Version::parse_from_npm("5.3.0").unwrap(),
+    }
+  }
+
+  #[tokio::test]
+  async fn uses_cache_server_packuments_the_lockfile_pins() {
+    let integrity = sha512_integrity(&npm_tarball());
+    let fixture = Fixture::new(packument(&integrity), Vec::new()).await;
+    let downloader = |name: &str, maybe_lockfile: Option<Arc<CliLockfile>>| {
+      Arc::new(RegistryInfoDownloader::new(
+        fixture.npm_cache(name),
+        fixture.http_client_provider.clone(),
+        fixture.npmrc.clone(),
+        ProgressBar::new(ProgressBarStyle::TextOnly),
+        maybe_lockfile,
+      ))
+    };
+
+    let npm_cache = fixture.npm_cache("pinned");
+    let info = Arc::new(RegistryInfoDownloader::new(
+      npm_cache.clone(),
+      fixture.http_client_provider.clone(),
+      fixture.npmrc.clone(),
+      ProgressBar::new(ProgressBarStyle::TextOnly),
+      Some(fixture.lockfile(&integrity)),
+    ))
+    .load_package_info("chalk")
+    .await
+    .unwrap()
+    .unwrap();
+    // only the pinned versions are used, and the packument isn't saved
+    assert_eq!(
+      info.versions.keys().collect::<Vec<_>>(),
+      vec![&chalk().version]
+    );
+    assert!(info.dist_tags.is_empty());
+    assert!(npm_cache.load_package_info("chalk").unwrap().is_none());
+    assert_eq!(fixture.requests(), 1);
+
+    // an unpinned packument isn't even requested from the cache server, so
+    // it can only come from the registry
+    downloader("unpinned", None)
+      .load_package_info("chalk")
+      .await
+      .unwrap_err();
+    assert_eq!(fixture.requests(), 1);
+
+    // a packument with a different integrity than the lockfile is rejected
+    downloader("different", Some(fixture.lockfile("sha512-AAAA")))
+      .load_package_info("chalk")
+      .await
+      .unwrap_err();
+    assert_eq!(fixture.requests(), 2);
+  }
+
+  #[tokio::test]
+  async fn uses_cache_server_tarballs_the_lockfile_pins() {
+    let tarball = npm_tarball();
+    let integrity = sha512_integrity(&tarball);
+    let fixture = Fixture::new(Vec::new(), tarball).await;
+    let dist: NpmPackageVersionDistInfo =
+      serde_json::from_value(dist(&integrity)).unwrap();
+    let tarball_cache =
+      |npm_cache: Arc<NpmCache>, maybe_lockfile: Option<Arc<CliLockfile>>| {
+        Arc::new(TarballCache::new(
+          npm_cache,
+          Arc::new(RealFs),
+          fixture.http_client_provider.clone(),
+          fixture.npmrc.clone(),
+          ProgressBar::new(ProgressBarStyle::TextOnly),
+          maybe_lockfile,
+        ))
+      };
+
+    let npm_cache = fixture.npm_cache("pinned");
+    tarball_cache(npm_cache.clone(), Some(fixture.lockfile(&integrity)))
+      .ensure_package(&chalk(), &dist)
+      .await
+      .unwrap();
+    assert!(npm_cache
+      .package_folder_for_nv(&chalk())
+      .join("package.json")
+      .exists());
+    assert_eq!(fixture.requests(), 1);
+
+    // an unpinned tarball isn't even requested from the cache server
+    tarball_cache(fixture.npm_cache("unpinned"), None)
+      .ensure_package(&chalk(), &dist)
+      .await
+      .unwrap_err();
+    assert_eq!(fixture.requests(), 1);
+  }
+}
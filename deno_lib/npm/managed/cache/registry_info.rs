@@ -1,6 +1,7 @@
 // Copyright 2018-2024 the Deno authors. All rights reserved. MIT license.
 
 use std::collections::HashMap;
+use std::collections::HashSet;
 use std::sync::Arc;
 
 use deno_core::anyhow::anyhow;
@@ -15,8 +16,11 @@ use deno_core::serde_json;
 use deno_core::url::Url;
 use deno_npm::npm_rc::ResolvedNpmRc;
 use deno_npm::registry::NpmPackageInfo;
+use deno_semver::Version;
 
 use crate::args::CacheSetting;
+use crate::args::CliLockfile;
+use crate::colors;
 use crate::http_util::HttpClientProvider;
 use crate::npm::common::maybe_auth_header_for_npm_registry;
 use crate::util::progress_bar::ProgressBar;
@@ -34,6 +38,8 @@ enum FutureResult {
   PackageNotExists,
   SavedFsCache(Arc<NpmPackageInfo>),
   ErroredFsCache(Arc<NpmPackageInfo>),
+  /// A packument of the cache server, which is never saved.
+  CacheServer(Arc<NpmPackageInfo>),
 }
 
 #[derive(Debug, Clone)]
@@ -58,6 +64,8 @@ pub struct RegistryInfoDownloader {
   http_client_provider: Arc<HttpClientProvider>,
   npmrc: Arc<ResolvedNpmRc>,
   progress_bar: ProgressBar,
+  /// Pins the packages whose packuments may come from the cache server.
+  maybe_lockfile: Option<Arc<CliLockfile>>,
   memory_cache: Mutex<HashMap<String, MemoryCacheItem>>,
 }
 
@@ -67,12 +75,14 @@ impl RegistryInfoDownloader {
     http_client_provider: Arc<HttpClientProvider>,
     npmrc: Arc<ResolvedNpmRc>,
     progress_bar: ProgressBar,
+    maybe_lockfile: Option<Arc<CliLockfile>>,
   ) -> Self {
     Self {
       cache,
       http_client_provider,
       npmrc,
       progress_bar,
+      maybe_lockfile,
       memory_cache: Default::default(),
     }
   }
@@ -145,6 +155,11 @@ impl RegistryInfoDownloader {
               MemoryCacheItem::MemoryCached(Ok(Some(info.clone())));
             Ok(Some(info))
           }
+          Ok(FutureResult::CacheServer(info)) => {
+            *self.memory_cache.lock().get_mut(name).unwrap() =
+              MemoryCacheItem::MemoryCached(Ok(Some(info.clone())));
+            Ok(Some(info))
+          }
           Ok(FutureResult::PackageNotExists) => {
             *self.memory_cache.lock().get_mut(name).unwrap() =
               MemoryCacheItem::MemoryCached(Ok(None));
@@ -200,9 +215,43 @@ impl RegistryInfoDownloader {
         }
       };
     let guard = self.progress_bar.update(package_url.as_str());
+    let locked = lockfile_versions(self.maybe_lockfile.as_deref(), name);
     let name = name.to_string();
     async move {
-      let client = downloader.http_client_provider.get_or_create()?;
+      // nothing vouches for the packument of a package the lockfile doesn't
+      // pin, so that one always comes from the registry
+      let maybe_cache_server_bytes = if locked.is_empty() {
+        None
+      } else {
+        downloader
+          .http_client_provider
+          .download_npm_from_cache_server(&package_url)
+          .await
+          .filter(|bytes| {
+            match verify_package_info_integrity(&name, bytes, &locked) {
+              Ok(()) => true,
+              Err(err) => {
+                log::warn!(
+                  "{} The cache server has a different packument of '{}', so it is downloaded from the registry instead.\n\n{:#}",
+                  colors::yellow("Warning"),
+                  name,
+                  err
+                );
+                false
+              }
+            }
+          })
+      };
+      if let Some(bytes) = maybe_cache_server_bytes {
+        // only the pinned versions are vouched for, and the packument isn't
+        // saved, so the registry's is used once the lockfile changes
+        let mut package_info = serde_json::from_slice(&bytes)?;
+        retain_locked_versions(&mut package_info, &locked);
+        return Ok(FutureResult::CacheServer(Arc::new(package_info)));
+      }
+      let client = downloader
+        .http_client_provider
+        .get_or_create_for_url(&package_url)?;
       let maybe_bytes = client
         .download_with_progress_and_retries(
           package_url,
@@ -241,6 +290,87 @@ impl RegistryInfoDownloader {
   }
 }
 
+/// Drops the versions of a packument that the lockfile doesn't pin, along
+/// with the dist tags that point to them.
+fn retain_locked_versions(
+  package_info: &mut NpmPackageInfo,
+  locked: &[(String, String)],
+) {
+  let versions = locked
+    .iter()
+    .filter_map(|(version, _)| Version::parse_from_npm(version).ok())
+    .collect::<HashSet<_>>();
+  package_info
+    .versions
+    .retain(|version, _| versions.contains(version));
+  package_info
+    .dist_tags
+    .retain(|_, version| versions.contains(version));
+}
+
+/// The versions of the npm package `name` the lockfile pins, with the
+/// integrity of each.
+pub fn lockfile_versions(
+  maybe_lockfile: Option<&CliLockfile>,
+  name: &str,
+) -> Vec<(String, String)> {
+  match maybe_lockfile {
+    Some(lockfile) => {
+      let lockfile = lockfile.lock();
+      locked_versions(
+        name,
+        lockfile
+          .content
+          .packages
+          .npm
+          .iter()
+          .map(|(id, info)| (id, &info.integrity)),
+      )
+    }
+    None => Vec::new(),
+  }
+}
+
+/// The versions of the npm package `name` among the package ids of a
+/// lockfile, with the integrity of each.
+pub fn locked_versions<'a>(
+  name: &str,
+  packages: impl IntoIterator<Item = (&'a String, &'a String)>,
+) -> Vec<(String, String)> {
+  let prefix = format!("{}@", name);
+  packages
+    .into_iter()
+    .filter_map(|(id, integrity)| {
+      // strip the peer dependencies
+      let version = id.strip_prefix(&prefix)?.split('_').next().unwrap();
+      Some((version.to_string(), integrity.clone()))
+    })
+    .collect()
+}
+
+/// Checks that the locked versions of the npm package `name` have the same
+/// integrity in its packument.
+pub fn verify_package_info_integrity(
+  name: &str,
+  data: &[u8],
+  locked: &[(String, String)],
+) -> Result<(), AnyError> {
+  let info = serde_json::from_slice::<serde_json::Value>(data)?;
+  for (version, expected) in locked {
+    let dist = &info["versions"][version.as_str()]["dist"];
+    // an integrity without an algorithm is the hex of a legacy sha1 hash
+    let integrity = if expected.contains('-') {
+      &dist["integrity"]
+    } else {
+      &dist["shasum"]
+    };
+    if integrity.as_str() != Some(expected.as_str()) {
+      bail!("{}@{} does not match the lockfile integrity", name, version);
+    }
+  }
+  Ok(())
+}
+
 pub fn get_package_url(npmrc: &ResolvedNpmRc, name: &str) -> Url {
   let registry_url = npmrc.get_registry_url(name);
   // The '/' character in scoped package names "@scope/name" must be
@@ -19,13 +19,17 @@ use deno_semver::package::PackageNv;
 use http::StatusCode;
 
 use crate::args::CacheSetting;
+use crate::args::CliLockfile;
+use crate::colors;
 use crate::http_util::DownloadError;
 use crate::http_util::HttpClientProvider;
 use crate::npm::common::maybe_auth_header_for_npm_registry;
 use crate::util::progress_bar::ProgressBar;
 use crate::util::sync::MultiRuntimeAsyncValueCreator;
 
+use super::registry_info::lockfile_versions;
 use super::tarball_extract::verify_and_extract_tarball;
+use super::tarball_extract::verify_tarball_integrity;
 use super::tarball_extract::TarballExtractionMode;
 use super::NpmCache;
 
@@ -55,6 +59,8 @@ pub struct TarballCache {
   http_client_provider: Arc<HttpClientProvider>,
   npmrc: Arc<ResolvedNpmRc>,
   progress_bar: ProgressBar,
+  /// Pins the tarballs that may come from the cache server.
+  maybe_lockfile: Option<Arc<CliLockfile>>,
   memory_cache: Mutex<HashMap<PackageNv, MemoryCacheItem>>,
 }
 
@@ -65,6 +71,7 @@ impl TarballCache {
     http_client_provider: Arc<HttpClientProvider>,
     npmrc: Arc<ResolvedNpmRc>,
     progress_bar: ProgressBar,
+    maybe_lockfile: Option<Arc<CliLockfile>>,
   ) -> Self {
     Self {
       cache,
@@ -72,6 +79,7 @@ impl TarballCache {
       http_client_provider,
       npmrc,
       progress_bar,
+      maybe_lockfile,
       memory_cache: Default::default(),
     }
   }
@@ -170,10 +178,48 @@ impl TarballCache {
       let maybe_auth_header = maybe_registry_config.and_then(|c| maybe_auth_header_for_npm_registry(c).ok()?);
 
       let guard = tarball_cache.progress_bar.update(&dist.tarball);
-      let result = tarball_cache.http_client_provider
-        .get_or_create()?
-        .download_with_progress_and_retries(tarball_uri, maybe_auth_header, &guard)
-        .await;
+      // a tarball of the cache server is only used when the lockfile pins
+      // its integrity, since the packument may have come from the cache
+      // server as well
+      let integrity = dist.integrity();
+      let version = package_nv.version.to_string();
+      let is_locked = lockfile_versions(
+        tarball_cache.maybe_lockfile.as_deref(),
+        &package_nv.name,
+      )
+      .into_iter()
+      .any(|(locked_version, locked_integrity)| {
+        locked_version == version && locked_integrity == integrity.for_lockfile()
+      });
+      let maybe_cache_server_bytes = if is_locked {
+        tarball_cache
+          .http_client_provider
+          .download_npm_from_cache_server(&tarball_uri)
+          .await
+          .filter(|bytes| {
+            match verify_tarball_integrity(&package_nv, bytes, &integrity) {
+              Ok(()) => true,
+              Err(err) => {
+                log::warn!(
+                  "{} The cache server has a different tarball of '{}', so it is downloaded from the registry instead.\n\n{:#}",
+                  colors::yellow("Warning"),
+                  package_nv,
+                  err
+                );
+                false
+              }
+            }
+          })
+      } else {
+        None
+      };
+      let result = match maybe_cache_server_bytes {
+        Some(bytes) => Ok(Some(bytes)),
+        None => tarball_cache.http_client_provider
+          .get_or_create_for_url(&tarball_uri)?
+          .download_with_progress_and_retries(tarball_uri, maybe_auth_header, &guard)
+          .await,
+      };
       let maybe_bytes = match result {
         Ok(maybe_bytes) => maybe_bytes,
         Err(DownloadError::BadResponse(err)) => {
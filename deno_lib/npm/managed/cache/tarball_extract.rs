@@ -90,7 +90,7 @@ fn rename_with_retries(
   }
 }
 
-fn verify_tarball_integrity(
+pub fn verify_tarball_integrity(
   package: &PackageNv,
   data: &[u8],
   npm_integrity: &NpmPackageVersionDistInfoIntegrity,
@@ -143,7 +143,11 @@ fn verify_tarball_integrity(
   Ok(())
 }
 
-fn extract_tarball(data: &[u8], output_folder: &Path) -> Result<(), AnyError> {
+/// Extracts a tarball whose integrity was already verified.
+pub fn extract_tarball(
+  data: &[u8],
+  output_folder: &Path,
+) -> Result<(), AnyError> {
   fs::create_dir_all(output_folder)?;
   let output_folder = fs::canonicalize(output_folder)?;
   let tar = GzDecoder::new(data);
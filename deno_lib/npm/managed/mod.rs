@@ -162,6 +162,7 @@ fn create_inner(
     http_client_provider.clone(),
     npm_rc.clone(),
     text_only_progress_bar.clone(),
+    maybe_lockfile.clone(),
   ));
   let fs_resolver = create_npm_fs_resolver(
     fs.clone(),
@@ -208,6 +209,7 @@ fn create_api(
       options.http_client_provider.clone(),
       options.npmrc.clone(),
       options.text_only_progress_bar.clone(),
+      options.maybe_lockfile.clone(),
     )),
   ))
 }
@@ -9,7 +9,6 @@ use std::path::Path;
 use std::path::PathBuf;
 use std::sync::Arc;
 
-use common::maybe_auth_header_for_npm_registry;
 use dashmap::DashMap;
 use deno_ast::ModuleSpecifier;
 use deno_core::error::AnyError;
@@ -33,6 +32,11 @@ use crate::file_fetcher::FileFetcher;
 
 pub use self::byonm::CliByonmNpmResolver;
 pub use self::byonm::CliByonmNpmResolverCreateOptions;
+pub use self::common::maybe_auth_header_for_npm_registry;
+pub use self::managed::cache::extract_tarball;
+pub use self::managed::cache::locked_versions;
+pub use self::managed::cache::verify_package_info_integrity;
+pub use self::managed::cache::verify_tarball_integrity;
 pub use self::managed::CliManagedInNpmPkgCheckerCreateOptions;
 pub use self::managed::CliManagedNpmResolverCreateOptions;
 pub use self::managed::CliNpmResolverManagedSnapshotOption;
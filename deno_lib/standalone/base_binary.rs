@@ -0,0 +1,282 @@
+// Copyright 2018-2024 the Deno authors. All rights reserved. MIT license.
+
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::Path;
+
+use deno_core::anyhow::bail;
+use deno_core::anyhow::Context;
+use deno_core::error::AnyError;
+use deno_core::serde_json;
+
+use crate::args::CompileBaseBinary;
+use crate::shared::ReleaseChannel;
+use crate::util;
+use crate::util::archive;
+
+/// The sha256 hashes of base binaries, keyed by version and then target.
+/// Canary versions are keyed by their git hash.
+///
+/// ```json
+/// { "2.0.6": { "x86_64-unknown-linux-gnu": "<sha256>" } }
+/// ```
+#[derive(Debug, Default)]
+pub struct BaseBinaryManifest(HashMap<String, HashMap<String, String>>);
+
+impl BaseBinaryManifest {
+  pub fn read(path: &Path) -> Result<Self, AnyError> {
+    let text = std::fs::read_to_string(path).with_context(|| {
+      format!("Failed reading base binary manifest '{}'", path.display())
+    })?;
+    Self::parse(&text).with_context(|| {
+      format!("Failed parsing base binary manifest '{}'", path.display())
+    })
+  }
+
+  fn parse(text: &str) -> Result<Self, AnyError> {
+    let mut checksums: HashMap<String, HashMap<String, String>> =
+      serde_json::from_str(text)?;
+    for hash in checksums
+      .values_mut()
+      .flat_map(|targets| targets.values_mut())
+    {
+      hash.make_ascii_lowercase();
+    }
+    Ok(Self(checksums))
+  }
+
+  /// Fails unless the manifest has an entry for the version and target
+  /// that matches the base binary.
+  pub fn verify(
+    &self,
+    version: &str,
+    target: &str,
+    base_binary: &[u8],
+  ) -> Result<(), AnyError> {
+    let Some(expected) = self.0.get(version).and_then(|t| t.get(target)) else {
+      bail!(
+        "The base binary manifest has no entry for version {} and target {}.",
+        version,
+        target
+      );
+    };
+    let actual = util::checksum::gen(&[base_binary]);
+    if &actual != expected {
+      bail!(
+        concat!(
+          "The base binary for version {} and target {} has the sha256 ",
+          "hash {}, but the manifest expects {}."
+        ),
+        version,
+        target,
+        actual,
+        expected
+      );
+    }
+    Ok(())
+  }
+}
+
+/// The version base binaries are keyed by in manifests.
+pub fn base_binary_version() -> String {
+  match crate::version::DENO_VERSION_INFO.release_channel {
+    ReleaseChannel::Canary => {
+      crate::version::DENO_VERSION_INFO.git_hash.to_string()
+    }
+    _ => env!("CARGO_PKG_VERSION").to_string(),
+  }
+}
+
+/// The name of the archive the base binary for `target` is released in.
+pub fn base_binary_archive_name(target: &str) -> String {
+  format!("denort-{target}.zip")
+}
+
+/// The path of the base binary archive on the download server, which is
+/// also how it's stored in the download cache and base binary directories.
+pub fn base_binary_path_suffix(target: &str) -> String {
+  let binary_name = base_binary_archive_name(target);
+  match crate::version::DENO_VERSION_INFO.release_channel {
+    ReleaseChannel::Canary => {
+      format!(
+        "canary/{}/{}",
+        crate::version::DENO_VERSION_INFO.git_hash,
+        binary_name
+      )
+    }
+    _ => {
+      format!("release/v{}/{}", env!("CARGO_PKG_VERSION"), binary_name)
+    }
+  }
+}
+
+/// Finds the base binary to use for `target` among the ones given
+/// explicitly, preferring one for that specific target.
+pub fn find_explicit_base_binary<'a>(
+  base_binaries: &'a [CompileBaseBinary],
+  target: &str,
+) -> Option<&'a CompileBaseBinary> {
+  base_binaries
+    .iter()
+    .find(|b| b.target.as_deref() == Some(target))
+    .or_else(|| base_binaries.iter().find(|b| b.target.is_none()))
+}
+
+/// Reads a denort executable or the zip archive it's released in.
+pub fn read_base_binary_file(
+  path: &Path,
+  target: &str,
+) -> Result<Vec<u8>, AnyError> {
+  let data = std::fs::read(path).with_context(|| {
+    format!("Could not find base binary at '{}'", path.display())
+  })?;
+  if path.extension().is_some_and(|ext| ext == "zip") {
+    let archive_name = path.file_name().unwrap().to_string_lossy();
+    unpack_base_binary(&data, &archive_name, target)
+  } else {
+    Ok(data)
+  }
+}
+
+/// Reads the base binary archive at `path_suffix` in a directory or zip
+/// archive laid out like the download server.
+pub fn read_base_binary_archive_from_dir(
+  dir: &Path,
+  path_suffix: &str,
+) -> Result<Vec<u8>, AnyError> {
+  if dir.is_file() {
+    let file = std::fs::File::open(dir)
+      .with_context(|| format!("Failed opening '{}'", dir.display()))?;
+    let mut archive = zip::ZipArchive::new(file)
+      .with_context(|| format!("Failed reading '{}'", dir.display()))?;
+    let mut entry = archive.by_name(path_suffix).with_context(|| {
+      format!(
+        "Could not find '{}' in base binary archive '{}'",
+        path_suffix,
+        dir.display()
+      )
+    })?;
+    let mut data = Vec::with_capacity(entry.size() as usize);
+    entry.read_to_end(&mut data)?;
+    Ok(data)
+  } else {
+    let path = dir.join(path_suffix);
+    std::fs::read(&path).with_context(|| {
+      format!("Could not find base binary archive '{}'", path.display())
+    })
+  }
+}
+
+/// Extracts the denort executable from its release archive.
+pub fn unpack_base_binary(
+  archive_data: &[u8],
+  archive_name: &str,
+  target: &str,
+) -> Result<Vec<u8>, AnyError> {
+  let temp_dir = tempfile::TempDir::new()?;
+  let base_binary_path = archive::unpack_into_dir(archive::UnpackArgs {
+    exe_name: "denort",
+    archive_name,
+    archive_data,
+    is_windows: target.contains("windows"),
+    dest_path: temp_dir.path(),
+  })?;
+  let base_binary = std::fs::read(base_binary_path)?;
+  drop(temp_dir); // delete the temp dir
+  Ok(base_binary)
+}
+
+#[cfg(test)]
+mod test {
+  use std::io::Write;
+
+  use test_util::TempDir;
+
+  use super::*;
+
+  #[test]
+  fn verifies_against_manifest() {
+    let hash = util::checksum::gen(&[b"denort"]);
+    let manifest = BaseBinaryManifest::parse(&format!(
+      r#"{{ "2.0.6": {{ "x86_64-unknown-linux-gnu": "{}" }} }}"#,
+      hash.to_ascii_uppercase()
+    ))
+    .unwrap();
+    assert!(manifest
+      .verify("2.0.6", "x86_64-unknown-linux-gnu", b"denort")
+      .is_ok());
+    assert!(manifest
+      .verify("2.0.6", "x86_64-unknown-linux-gnu", b"other")
+      .is_err());
+    assert!(manifest
+      .verify("2.0.5", "x86_64-unknown-linux-gnu", b"denort")
+      .is_err());
+    assert!(manifest
+      .verify("2.0.6", "aarch64-apple-darwin", b"denort")
+      .is_err());
+  }
+
+  #[test]
+  fn reads_archive_from_dir() {
+    let temp_dir = TempDir::new();
+    let suffix = "release/v2.0.6/denort-x86_64-unknown-linux-gnu.zip";
+    let dir = temp_dir.path().join("dir");
+    dir.join("release/v2.0.6").create_dir_all();
+    dir.join(suffix).write("archive");
+    assert_eq!(
+      read_base_binary_archive_from_dir(dir.as_path(), suffix).unwrap(),
+      b"archive"
+    );
+    assert!(read_base_binary_archive_from_dir(
+      dir.as_path(),
+      "release/v2.0.5/denort-x86_64-unknown-linux-gnu.zip"
+    )
+    .is_err());
+
+    let archive_path = temp_dir.path().join("dir.zip");
+    let mut writer =
+      zip::ZipWriter::new(std::fs::File::create(&archive_path).unwrap());
+    writer
+      .start_file(suffix, zip::write::SimpleFileOptions::default())
+      .unwrap();
+    writer.write_all(b"archive").unwrap();
+    writer.finish().unwrap();
+    assert_eq!(
+      read_base_binary_archive_from_dir(archive_path.as_path(), suffix)
+        .unwrap(),
+      b"archive"
+    );
+    assert!(read_base_binary_archive_from_dir(
+      archive_path.as_path(),
+      "missing"
+    )
+    .is_err());
+  }
+
+  #[test]
+  fn prefers_target_specific_base_binary() {
+    let base_binaries = vec![
+      CompileBaseBinary {
+        target: None,
+        path: "any".to_string(),
+      },
+      CompileBaseBinary {
+        target: Some("aarch64-apple-darwin".to_string()),
+        path: "mac".to_string(),
+      },
+    ];
+    assert_eq!(
+      find_explicit_base_binary(&base_binaries, "aarch64-apple-darwin")
+        .unwrap()
+        .path,
+      "mac"
+    );
+    assert_eq!(
+      find_explicit_base_binary(&base_binaries, "x86_64-pc-windows-msvc")
+        .unwrap()
+        .path,
+      "any"
+    );
+    assert!(find_explicit_base_binary(&[], "aarch64-apple-darwin").is_none());
+  }
+}
@@ -20,9 +20,12 @@ use std::path::PathBuf;
 use std::process::Command;
 use std::sync::Arc;
 
+use base64::prelude::BASE64_STANDARD;
+use base64::Engine;
 use deno_ast::MediaType;
 use deno_ast::ModuleKind;
 use deno_ast::ModuleSpecifier;
+use deno_config::glob::PathOrPatternSet;
 use deno_config::workspace::PackageJsonDepResolution;
 use deno_config::workspace::ResolverWorkspaceJsrPackage;
 use deno_config::workspace::Workspace;
@@ -33,8 +36,11 @@ use deno_core::error::AnyError;
 use deno_core::futures::io::AllowStdIo;
 use deno_core::futures::AsyncReadExt;
 use deno_core::futures::AsyncSeekExt;
+use deno_core::resolve_url_or_path;
 use deno_core::serde_json;
 use deno_core::url::Url;
+use deno_core::v8;
+use deno_core::JsRuntime;
 use deno_graph::source::RealFileSystem;
 use deno_graph::ModuleGraph;
 use deno_npm::resolution::SerializedNpmResolutionSnapshot;
@@ -62,6 +68,7 @@ use crate::args::CompileFlags;
 use crate::args::NpmInstallDepsProvider;
 use crate::args::PermissionFlags;
 use crate::args::UnstableConfig;
+use crate::args::VfsOverlayMode;
 use crate::cache::DenoDir;
 use crate::emit::Emitter;
 use crate::file_fetcher::FileFetcher;
@@ -69,20 +76,41 @@ use crate::http_util::HttpClientProvider;
 use crate::npm::CliNpmResolver;
 use crate::npm::InnerCliNpmResolverRef;
 use crate::resolver::CjsTracker;
-use crate::shared::ReleaseChannel;
 use crate::standalone::virtual_fs::VfsEntry;
-use crate::util::archive;
+use crate::util;
+use crate::util::display::human_size;
 use crate::util::fs::canonicalize_path_maybe_not_exists;
 use crate::util::progress_bar::ProgressBar;
 use crate::util::progress_bar::ProgressBarStyle;
 
+use super::base_binary::base_binary_archive_name;
+use super::base_binary::base_binary_path_suffix;
+use super::base_binary::base_binary_version;
+use super::base_binary::find_explicit_base_binary;
+use super::base_binary::read_base_binary_archive_from_dir;
+use super::base_binary::read_base_binary_file;
+use super::base_binary::unpack_base_binary;
+use super::base_binary::BaseBinaryManifest;
 use super::file_system::DenoCompileFileSystem;
+use super::integrity::encode_public_key;
+use super::integrity::load_signing_key;
+use super::integrity::trusted_public_key;
+use super::serialization::code_cache_source_hash;
 use super::serialization::deserialize_binary_data_section;
 use super::serialization::serialize_binary_data_section;
+use super::serialization::CodeCacheStore;
+use super::serialization::CodeCacheStoreBuilder;
 use super::serialization::DenoCompileModuleData;
 use super::serialization::DeserializedDataSection;
 use super::serialization::RemoteModulesStore;
 use super::serialization::RemoteModulesStoreBuilder;
+use super::serialization::CODE_CACHE_PART;
+use super::serialization::METADATA_PART;
+use super::serialization::NPM_SNAPSHOT_PART;
+use super::serialization::REMOTE_MODULES_PART;
+use super::serialization::VFS_FILES_PART;
+use super::serialization::VFS_HEADERS_PART;
+use super::vfs_overlay::VfsOverlay;
 use super::virtual_fs::FileBackedVfs;
 use super::virtual_fs::VfsBuilder;
 use super::virtual_fs::VfsRoot;
@@ -183,8 +211,249 @@ pub struct Metadata {
   pub env_vars_from_env_file: IndexMap<String, String>,
   pub workspace_resolver: SerializedWorkspaceResolver,
   pub entrypoint_key: String,
+  /// Additional entrypoints by name.
+  pub entrypoints: BTreeMap<String, EntrypointMetadata>,
   pub node_modules: Option<NodeModules>,
   pub unstable_config: UnstableConfig,
+  pub vfs_overlay: Option<VfsOverlayMode>,
+  /// sha256 of the base binary, recorded by reproducible builds.
+  pub base_binary_sha256: Option<String>,
+}
+
+/// The files embedded in the executable besides the modules.
+#[derive(Default)]
+pub struct CompileFileOptions {
+  /// Files embedded as is.
+  pub assets: Vec<PathBuf>,
+  /// Files and directories left out of the embedded file system.
+  pub exclude: PathOrPatternSet,
+  /// Files of npm packages left out of the embedded file system.
+  pub npm_exclude: Vec<NpmPackageExclude>,
+}
+
+/// A glob of files within an npm package, ex. `npm:lodash/fp/**`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct NpmPackageExclude {
+  pub name: String,
+  /// Glob relative to the package directory.
+  pub subpath: String,
+}
+
+impl NpmPackageExclude {
+  /// Parses an exclude pattern, returning `None` when it's not for an npm
+  /// package.
+  pub fn parse(pattern: &str) -> Result<Option<Self>, AnyError> {
+    let Some(value) = pattern.strip_prefix("npm:") else {
+      return Ok(None);
+    };
+    let name_len = match value.strip_prefix('@') {
+      Some(rest) => rest
+        .find('/')
+        .and_then(|scope_len| {
+          rest[scope_len + 1..]
+            .find('/')
+            .map(|name_len| scope_len + name_len + 2)
+        })
+        .unwrap_or(value.len()),
+      None => value.find('/').unwrap_or(value.len()),
+    };
+    let (name, subpath) = value.split_at(name_len);
+    let subpath = subpath.trim_start_matches('/');
+    if name.is_empty() || subpath.is_empty() {
+      bail!(
+        "Invalid exclude pattern '{}'. Expected npm:<PACKAGE>/<SUBPATH>",
+        pattern
+      );
+    }
+    Ok(Some(Self {
+      name: name.to_string(),
+      subpath: subpath.to_string(),
+    }))
+  }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct EntrypointMetadata {
+  pub entrypoint_key: String,
+  /// Used instead of `Metadata::permissions` when set.
+  pub permissions: Option<PermissionFlags>,
+  /// Used instead of `Metadata::argv`.
+  pub argv: Vec<String>,
+}
+
+/// Selects the entrypoint to run by the name the executable was invoked
+/// with (busybox style) or else by the first argument, which is removed.
+/// Returns `None` for the main entrypoint.
+fn select_entrypoint<'a>(
+  entrypoints: &'a BTreeMap<String, EntrypointMetadata>,
+  cli_args: &mut Vec<String>,
+) -> Option<&'a EntrypointMetadata> {
+  if entrypoints.is_empty() {
+    return None;
+  }
+  let invoked_name = cli_args
+    .first()
+    .and_then(|arg| Path::new(arg).file_stem())
+    .map(|name| name.to_string_lossy());
+  if let Some(entrypoint) =
+    invoked_name.and_then(|name| entrypoints.get(name.as_ref()))
+  {
+    return Some(entrypoint);
+  }
+  let entrypoint = entrypoints.get(cli_args.get(1)?)?;
+  cli_args.remove(1);
+  Some(entrypoint)
+}
+
+/// The paths to leave out of a node_modules directory within `root_path`.
+fn node_modules_exclude(
+  root_path: &Path,
+  file_options: &CompileFileOptions,
+) -> Result<PathOrPatternSet, AnyError> {
+  let npm_patterns = file_options
+    .npm_exclude
+    .iter()
+    .map(|exclude| {
+      // matches both node_modules/<name> and the packages within
+      // node_modules/.deno/<name>@<version>/node_modules/<name>
+      format!("**/node_modules/{}/{}", exclude.name, exclude.subpath)
+    })
+    .collect::<Vec<_>>();
+  exclude_with_patterns(file_options, root_path, &npm_patterns)
+}
+
+/// The paths to leave out of the npm package in the global cache at
+/// `package_path`.
+fn global_npm_package_exclude(
+  package_path: &Path,
+  package_name: &str,
+  file_options: &CompileFileOptions,
+) -> Result<PathOrPatternSet, AnyError> {
+  let npm_patterns = file_options
+    .npm_exclude
+    .iter()
+    .filter(|exclude| exclude.name == package_name)
+    .map(|exclude| exclude.subpath.clone())
+    .collect::<Vec<_>>();
+  exclude_with_patterns(file_options, package_path, &npm_patterns)
+}
+
+/// Combines the general exclude paths and globs with npm package patterns
+/// relative to `base`.
+fn exclude_with_patterns(
+  file_options: &CompileFileOptions,
+  base: &Path,
+  npm_patterns: &[String],
+) -> Result<PathOrPatternSet, AnyError> {
+  let mut path_or_patterns =
+    file_options.exclude.clone().into_path_or_patterns();
+  path_or_patterns.extend(
+    PathOrPatternSet::from_exclude_relative_path_or_patterns(
+      base,
+      npm_patterns,
+    )?
+    .into_path_or_patterns(),
+  );
+  Ok(PathOrPatternSet::new(path_or_patterns))
+}
+
+/// Removes what depends on the machine the executable is compiled on. The
+/// rest is already deterministic: entries are sorted as they're added, no
+/// timestamps are stored and paths are stored relative to the root
+/// directory, except for the ones in source maps (see
+/// `normalize_source_map_sources`).
+fn normalize_for_reproducible_build(
+  metadata: &mut Metadata,
+  vfs: &mut VfsBuilder,
+) {
+  // named after the directory the project is in, but renamed at runtime
+  vfs.with_root_dir(|root_dir| root_dir.name = "root".to_string());
+  metadata.unstable_config.features.sort();
+  metadata.unstable_config.features.dedup();
+}
+
+/// Makes the file paths in the inline source map of an emitted module
+/// relative to the root directory, as they're otherwise absolute paths on
+/// the machine the executable is compiled on.
+fn normalize_source_map_sources(
+  source: Vec<u8>,
+  root_dir_url: &StandaloneRelativeFileBaseUrl,
+) -> Result<Vec<u8>, AnyError> {
+  const SOURCE_MAP_PREFIX: &[u8] =
+    b"//# sourceMappingURL=data:application/json;base64,";
+
+  let Some(index) = source
+    .windows(SOURCE_MAP_PREFIX.len())
+    .rposition(|window| window == SOURCE_MAP_PREFIX)
+  else {
+    return Ok(source);
+  };
+  let start = index + SOURCE_MAP_PREFIX.len();
+  let end = source[start..]
+    .iter()
+    .position(|b| b.is_ascii_whitespace())
+    .map(|len| start + len)
+    .unwrap_or(source.len());
+  let source_map = BASE64_STANDARD
+    .decode(&source[start..end])
+    .context("decoding source map")?;
+  let mut source_map: serde_json::Value =
+    serde_json::from_slice(&source_map).context("parsing source map")?;
+  let Some(sources) = source_map
+    .get_mut("sources")
+    .and_then(|sources| sources.as_array_mut())
+  else {
+    return Ok(source);
+  };
+  for map_source in sources {
+    let Some(url) = map_source.as_str().and_then(|s| Url::parse(s).ok()) else {
+      continue;
+    };
+    *map_source = root_dir_url.specifier_key(&url).into_owned().into();
+  }
+  let mut normalized = source[..start].to_vec();
+  normalized.extend_from_slice(
+    BASE64_STANDARD
+      .encode(serde_json::to_vec(&source_map)?)
+      .as_bytes(),
+  );
+  normalized.extend_from_slice(&source[end..]);
+  Ok(normalized)
+}
+
+fn log_vfs_size_report(vfs: &VfsBuilder) {
+  const MAX_DEPTH: usize = 2;
+  const MAX_DIRS: usize = 10;
+
+  let mut sizes = vfs.dir_sizes(MAX_DEPTH);
+  let (_, total_size) = sizes.remove(0);
+  if total_size == 0 {
+    return;
+  }
+  sizes.sort_by(|(a_path, a_size), (b_path, b_size)| {
+    b_size.cmp(a_size).then_with(|| a_path.cmp(b_path))
+  });
+  log::info!(
+    "{} {} of files",
+    crate::colors::green("Embedding"),
+    human_size(total_size as f64)
+  );
+  for (path, size) in sizes.iter().take(MAX_DIRS) {
+    log::info!(
+      "  {} {}",
+      path,
+      crate::colors::gray(human_size(*size as f64))
+    );
+  }
+  if sizes.len() > MAX_DIRS {
+    log::info!(
+      "  {}",
+      crate::colors::gray(format!(
+        "...and {} more director(ies)",
+        sizes.len() - MAX_DIRS
+      ))
+    );
+  }
 }
 
 fn write_binary_bytes(
@@ -194,10 +463,29 @@ fn write_binary_bytes(
   npm_snapshot: Option<SerializedNpmResolutionSnapshot>,
   remote_modules: &RemoteModulesStoreBuilder,
   vfs: VfsBuilder,
+  code_cache: &CodeCacheStoreBuilder,
   compile_flags: &CompileFlags,
 ) -> Result<(), AnyError> {
-  let data_section_bytes =
-    serialize_binary_data_section(metadata, npm_snapshot, remote_modules, vfs)?;
+  let signing_key = compile_flags
+    .sign_key
+    .as_ref()
+    .map(|path| load_signing_key(Path::new(path)))
+    .transpose()?;
+  if let Some(signing_key) = &signing_key {
+    log::info!(
+      "{} with public key {}",
+      crate::colors::green("Signing"),
+      encode_public_key(signing_key)
+    );
+  }
+  let data_section_bytes = serialize_binary_data_section(
+    metadata,
+    npm_snapshot,
+    remote_modules,
+    vfs,
+    code_cache,
+    signing_key.as_ref(),
+  )?;
 
   let target = compile_flags.resolve_target();
   if target.contains("linux") {
@@ -223,6 +511,76 @@ fn write_binary_bytes(
   Ok(())
 }
 
+/// Collects the code cache of the given ES modules, so that the binary
+/// doesn't need to compile them from scratch on startup.
+///
+/// With the code cache `recorded` by a training run of the executable (see
+/// `RECORD_CODE_CACHE_ENV_VAR_NAME`), only the modules that run loaded are
+/// cached, with the data V8 created in the runtime the executable uses.
+///
+/// Otherwise the modules are compiled here. They aren't run, because that
+/// would run the program at build time. V8 compiles functions lazily when
+/// they're first called, so the modules are compiled eagerly instead to have
+/// the functions in the code cache as well, at the cost of caching functions
+/// the program never calls.
+fn create_code_cache(
+  modules: &[(String, Vec<u8>)],
+  recorded: Option<&CodeCacheStoreBuilder>,
+) -> Result<CodeCacheStoreBuilder, AnyError> {
+  let mut builder = CodeCacheStoreBuilder::default();
+  if let Some(recorded) = recorded {
+    for (key, source) in modules {
+      let source_hash = code_cache_source_hash(source);
+      match recorded.get(key, source_hash) {
+        Some(data) => builder.add(key.clone(), source_hash, data.to_vec()),
+        None => log::debug!("No recorded code cache for '{}'", key),
+      }
+    }
+    return Ok(builder);
+  }
+  let mut runtime = JsRuntime::new(Default::default());
+  let scope = &mut runtime.handle_scope();
+  for (key, source) in modules {
+    let Ok(text) = std::str::from_utf8(source) else {
+      continue;
+    };
+    let (Some(code), Some(name)) =
+      (v8::String::new(scope, text), v8::String::new(scope, key))
+    else {
+      continue;
+    };
+    let origin = v8::ScriptOrigin::new(
+      scope,
+      name.into(),
+      0,
+      0,
+      false,
+      0,
+      None,
+      false,
+      false,
+      true,
+      None,
+    );
+    let mut v8_source = v8::script_compiler::Source::new(code, Some(&origin));
+    let tc_scope = &mut v8::TryCatch::new(scope);
+    let Some(module) = v8::script_compiler::compile_module2(
+      tc_scope,
+      &mut v8_source,
+      v8::script_compiler::CompileOptions::EagerCompile,
+      v8::script_compiler::NoCacheReason::NoReason,
+    ) else {
+      log::debug!("Failed compiling '{}' for the code cache", key);
+      continue;
+    };
+    let unbound_module_script = module.get_unbound_module_script(tc_scope);
+    if let Some(data) = unbound_module_script.create_code_cache() {
+      builder.add(key.clone(), code_cache_source_hash(source), data.to_vec());
+    }
+  }
+  Ok(builder)
+}
+
 pub fn is_standalone_binary(exe_path: &Path) -> bool {
   let Ok(data) = std::fs::read(exe_path) else {
     return false;
@@ -240,6 +598,7 @@ pub struct StandaloneData {
   pub npm_snapshot: Option<ValidSerializedNpmResolutionSnapshot>,
   pub root_path: PathBuf,
   pub vfs: Arc<FileBackedVfs>,
+  pub code_cache: CodeCacheStore,
 }
 
 pub struct StandaloneModules {
@@ -294,6 +653,31 @@ impl StandaloneModules {
   }
 }
 
+/// Finds the data section of the current executable.
+///
+/// Executables other than Mach-O and PE ones get the section appended, which
+/// the OS doesn't load, so the executable is memory mapped instead of read.
+/// That way only the headers and the directory tree are paged in at startup
+/// and the files and modules as they're loaded.
+fn find_data_section() -> Option<&'static [u8]> {
+  #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+  if let Some(data) = map_data_section() {
+    return Some(data);
+  }
+  libsui::find_section("d3n0l4nd")
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+fn map_data_section() -> Option<&'static [u8]> {
+  let file = File::open(current_exe().ok()?).ok()?;
+  // SAFETY: the OS doesn't allow writing to the executable of a running
+  // process, and the mapping is read only
+  let mmap = unsafe { memmap2::Mmap::map(&file) }.ok()?;
+  // the mapping lives for the rest of the program
+  let mmap: &'static memmap2::Mmap = Box::leak(Box::new(mmap));
+  super::serialization::find_data_section_from_end(mmap)
+}
+
 /// This function will try to run this binary as a standalone binary
 /// produced by `deno compile`. It determines if this is a standalone
 /// binary by skipping over the trailer width at the end of the file,
@@ -302,20 +686,40 @@ impl StandaloneModules {
 pub fn extract_standalone(
   cli_args: Cow<Vec<OsString>>,
 ) -> Result<Option<StandaloneData>, AnyError> {
-  let Some(data) = libsui::find_section("d3n0l4nd") else {
+  let Some(data) = find_data_section() else {
     return Ok(None);
   };
 
   let DeserializedDataSection {
     mut metadata,
     npm_snapshot,
-    remote_modules,
+    mut remote_modules,
     mut vfs_dir,
     vfs_files_data,
+    mut code_cache,
+    integrity,
   } = match deserialize_binary_data_section(data)? {
     Some(data_section) => data_section,
     None => return Ok(None),
   };
+  // only the hashes are checked here and the parts are checked against
+  // them in chunks as they're read, so the files and modules that are never
+  // used aren't paged in and hashed on startup
+  let trusted_public_key = trusted_public_key()?;
+  integrity
+    .verify_hashes(trusted_public_key.as_deref())
+    .context("Refusing to run the executable")?;
+  for part in [METADATA_PART, NPM_SNAPSHOT_PART, VFS_HEADERS_PART] {
+    // these were already read in full to deserialize them
+    integrity
+      .part_check(part)
+      .check_all()
+      .context("Refusing to run the executable")?;
+  }
+  remote_modules
+    .set_integrity_check(integrity.part_check(REMOTE_MODULES_PART))
+    .context("Refusing to run the executable")?;
+  code_cache.set_integrity_check(integrity.part_check(CODE_CACHE_PART));
 
   let root_path = {
     let maybe_current_exe = std::env::current_exe().ok();
@@ -327,11 +731,22 @@ pub fn extract_standalone(
       .unwrap_or_else(|| Cow::Borrowed("binary"));
     std::env::temp_dir().join(format!("deno-compile-{}", current_exe_name))
   };
-  let cli_args = cli_args.into_owned();
-  metadata.argv.reserve(cli_args.len() - 1);
-  for arg in cli_args.into_iter().skip(1) {
-    metadata.argv.push(arg.into_string().unwrap());
+  let mut cli_args = cli_args
+    .into_owned()
+    .into_iter()
+    .map(|arg| arg.into_string().unwrap())
+    .collect::<Vec<_>>();
+  if let Some(entrypoint) =
+    select_entrypoint(&metadata.entrypoints, &mut cli_args)
+  {
+    metadata.entrypoint_key = entrypoint.entrypoint_key.clone();
+    if let Some(permissions) = &entrypoint.permissions {
+      metadata.permissions = permissions.clone();
+    }
+    metadata.argv = entrypoint.argv.clone();
   }
+  metadata.argv.reserve(cli_args.len() - 1);
+  metadata.argv.extend(cli_args.into_iter().skip(1));
   let vfs = {
     // align the name of the directory with the root dir
     vfs_dir.name = root_path.file_name().unwrap().to_string_lossy().to_string();
@@ -341,10 +756,19 @@ pub fn extract_standalone(
       root_path: root_path.clone(),
       start_file_offset: 0,
     };
-    Arc::new(FileBackedVfs::new(Cow::Borrowed(vfs_files_data), fs_root))
+    let mut vfs = FileBackedVfs::new(Cow::Borrowed(vfs_files_data), fs_root);
+    vfs.set_integrity_check(integrity.part_check(VFS_FILES_PART));
+    Arc::new(vfs)
+  };
+  let overlay = match metadata.vfs_overlay {
+    Some(mode) => Some(Arc::new(
+      VfsOverlay::new(vfs.clone(), mode)
+        .context("Failed setting up the file system overlay")?,
+    )),
+    None => None,
   };
   let fs: Arc<dyn deno_fs::FileSystem> =
-    Arc::new(DenoCompileFileSystem::new(vfs.clone()));
+    Arc::new(DenoCompileFileSystem::new(vfs.clone(), overlay));
   Ok(Some(StandaloneData {
     fs,
     metadata,
@@ -355,6 +779,7 @@ pub fn extract_standalone(
     npm_snapshot,
     root_path,
     vfs,
+    code_cache,
   }))
 }
 
@@ -367,6 +792,7 @@ pub struct DenoCompileBinaryWriter<'a> {
   npm_resolver: &'a dyn CliNpmResolver,
   workspace_resolver: &'a WorkspaceResolver,
   npm_system_info: NpmSystemInfo,
+  base_binary: Option<Vec<u8>>,
 }
 
 impl<'a> DenoCompileBinaryWriter<'a> {
@@ -390,20 +816,43 @@ impl<'a> DenoCompileBinaryWriter<'a> {
       npm_resolver,
       workspace_resolver,
       npm_system_info,
+      base_binary: None,
     }
   }
 
+  /// Uses the given denort executable as the base binary instead of
+  /// sourcing one according to the compile flags. It's still verified
+  /// against the base binary manifest, if any.
+  pub fn set_base_binary(&mut self, base_binary: Vec<u8>) {
+    self.base_binary = Some(base_binary);
+  }
+
+  #[allow(clippy::too_many_arguments)]
   pub async fn write_bin(
     &self,
     writer: File,
     graph: &ModuleGraph,
     root_dir_url: StandaloneRelativeFileBaseUrl<'_>,
     entrypoint: &ModuleSpecifier,
+    file_options: &CompileFileOptions,
     compile_flags: &CompileFlags,
     cli_options: &CliOptions,
   ) -> Result<(), AnyError> {
     // Select base binary based on target
     let mut original_binary = self.get_base_binary(compile_flags).await?;
+    let base_binary_sha256 = (compile_flags.reproducible
+      || compile_flags.base_binary_sha256.is_some())
+    .then(|| util::checksum::gen(&[&original_binary]));
+    if let Some(expected) = &compile_flags.base_binary_sha256 {
+      let actual = base_binary_sha256.as_ref().unwrap();
+      if actual != expected {
+        bail!(
+          "The base binary has the sha256 hash {}, but {} was expected.",
+          actual,
+          expected
+        );
+      }
+    }
 
     if compile_flags.no_terminal {
       let target = compile_flags.resolve_target();
@@ -431,6 +880,8 @@ impl<'a> DenoCompileBinaryWriter<'a> {
         graph,
         root_dir_url,
         entrypoint,
+        file_options,
+        base_binary_sha256,
         cli_options,
         compile_flags,
       )
@@ -441,6 +892,45 @@ impl<'a> DenoCompileBinaryWriter<'a> {
     &self,
     compile_flags: &CompileFlags,
   ) -> Result<Vec<u8>, AnyError> {
+    let target = compile_flags.resolve_target();
+    let base_binary = self.source_base_binary(compile_flags, &target).await?;
+    match &compile_flags.base_binary_manifest {
+      Some(path) => {
+        BaseBinaryManifest::read(Path::new(path))?.verify(
+          &base_binary_version(),
+          &target,
+          &base_binary,
+        )?;
+      }
+      None => {
+        let is_custom = self.base_binary.is_some()
+          || (std::env::var_os("DENORT_BIN").is_none()
+            && (compile_flags.base_binary_dir.is_some()
+              || find_explicit_base_binary(
+                &compile_flags.base_binaries,
+                &target,
+              )
+              .is_some()));
+        if is_custom && compile_flags.base_binary_sha256.is_none() {
+          log::warn!(
+            "{} The base binary is not verified. Pass --base-binary-manifest or --base-binary-sha256 to verify it.",
+            crate::colors::yellow("Warning"),
+          );
+        }
+      }
+    }
+    Ok(base_binary)
+  }
+
+  async fn source_base_binary(
+    &self,
+    compile_flags: &CompileFlags,
+    target: &str,
+  ) -> Result<Vec<u8>, AnyError> {
+    if let Some(base_binary) = &self.base_binary {
+      return Ok(base_binary.clone());
+    }
+
     // Used for testing.
     //
     // Phase 2 of the 'min sized' deno compile RFC talks
@@ -451,44 +941,32 @@ impl<'a> DenoCompileBinaryWriter<'a> {
       });
     }
 
-    let target = compile_flags.resolve_target();
-    let binary_name = format!("denort-{target}.zip");
-
-    let binary_path_suffix =
-      match crate::version::DENO_VERSION_INFO.release_channel {
-        ReleaseChannel::Canary => {
-          format!(
-            "canary/{}/{}",
-            crate::version::DENO_VERSION_INFO.git_hash,
-            binary_name
-          )
-        }
-        _ => {
-          format!("release/v{}/{}", env!("CARGO_PKG_VERSION"), binary_name)
-        }
-      };
+    if let Some(base_binary) =
+      find_explicit_base_binary(&compile_flags.base_binaries, target)
+    {
+      return read_base_binary_file(Path::new(&base_binary.path), target);
+    }
 
-    let download_directory = self.deno_dir.dl_folder_path();
-    let binary_path = download_directory.join(&binary_path_suffix);
+    let binary_name = base_binary_archive_name(target);
+    let binary_path_suffix = base_binary_path_suffix(target);
+    let archive_data = match &compile_flags.base_binary_dir {
+      Some(dir) => {
+        read_base_binary_archive_from_dir(Path::new(dir), &binary_path_suffix)?
+      }
+      None => {
+        let download_directory = self.deno_dir.dl_folder_path();
+        let binary_path = download_directory.join(&binary_path_suffix);
 
-    if !binary_path.exists() {
-      self
-        .download_base_binary(&download_directory, &binary_path_suffix)
-        .await?;
-    }
+        if !binary_path.exists() {
+          self
+            .download_base_binary(&download_directory, &binary_path_suffix)
+            .await?;
+        }
 
-    let archive_data = std::fs::read(binary_path)?;
-    let temp_dir = tempfile::TempDir::new()?;
-    let base_binary_path = archive::unpack_into_dir(archive::UnpackArgs {
-      exe_name: "denort",
-      archive_name: &binary_name,
-      archive_data: &archive_data,
-      is_windows: target.contains("windows"),
-      dest_path: temp_dir.path(),
-    })?;
-    let base_binary = std::fs::read(base_binary_path)?;
-    drop(temp_dir); // delete the temp dir
-    Ok(base_binary)
+        std::fs::read(binary_path)?
+      }
+    };
+    unpack_base_binary(&archive_data, &binary_name, target)
   }
 
   async fn download_base_binary(
@@ -536,6 +1014,8 @@ impl<'a> DenoCompileBinaryWriter<'a> {
     graph: &ModuleGraph,
     root_dir_url: StandaloneRelativeFileBaseUrl<'_>,
     entrypoint: &ModuleSpecifier,
+    file_options: &CompileFileOptions,
+    base_binary_sha256: Option<String>,
     cli_options: &CliOptions,
     compile_flags: &CompileFlags,
   ) -> Result<(), AnyError> {
@@ -548,15 +1028,14 @@ impl<'a> DenoCompileBinaryWriter<'a> {
       None => None,
     };
     let root_path = root_dir_url.inner().to_file_path().unwrap();
-    let (maybe_npm_vfs, node_modules, npm_snapshot) = match self
-      .npm_resolver
-      .as_inner()
-    {
+    let npm_resolver = self.npm_resolver.as_inner();
+    let (maybe_npm_vfs, node_modules, npm_snapshot) = match npm_resolver {
       InnerCliNpmResolverRef::Managed(managed) => {
         let snapshot =
           managed.serialized_valid_snapshot_for_system(&self.npm_system_info);
         if !snapshot.as_serialized().packages.is_empty() {
-          let npm_vfs_builder = self.build_npm_vfs(&root_path, cli_options)?;
+          let npm_vfs_builder =
+            self.build_npm_vfs(&root_path, file_options, cli_options)?;
           (
             Some(npm_vfs_builder),
             Some(NodeModules::Managed {
@@ -577,7 +1056,8 @@ impl<'a> DenoCompileBinaryWriter<'a> {
         }
       }
       InnerCliNpmResolverRef::Byonm(resolver) => {
-        let npm_vfs_builder = self.build_npm_vfs(&root_path, cli_options)?;
+        let npm_vfs_builder =
+          self.build_npm_vfs(&root_path, file_options, cli_options)?;
         (
           Some(npm_vfs_builder),
           Some(NodeModules::Byonm {
@@ -601,19 +1081,26 @@ impl<'a> DenoCompileBinaryWriter<'a> {
     } else {
       VfsBuilder::new(root_path.clone())?
     };
+    for asset in &file_options.assets {
+      vfs
+        .add_file_at_path(asset)
+        .with_context(|| format!("Embedding '{}'", asset.display()))?;
+    }
+    log_vfs_size_report(&vfs);
     let mut remote_modules_store = RemoteModulesStoreBuilder::default();
+    let mut code_cache_modules = Vec::new();
     for module in graph.modules() {
       if module.specifier().scheme() == "data" {
         continue; // don't store data urls as an entry as they're in the code
       }
       let (maybe_source, media_type) = match module {
         deno_graph::Module::Js(m) => {
+          let is_cjs = self.cjs_tracker.is_cjs_with_known_is_script(
+            &m.specifier,
+            m.media_type,
+            m.is_script,
+          )?;
           let source = if m.media_type.is_emittable() {
-            let is_cjs = self.cjs_tracker.is_cjs_with_known_is_script(
-              &m.specifier,
-              m.media_type,
-              m.is_script,
-            )?;
             let module_kind = ModuleKind::from_is_cjs(is_cjs);
             let source = self
               .emitter
@@ -624,10 +1111,36 @@ impl<'a> DenoCompileBinaryWriter<'a> {
                 &m.source,
               )
               .await?;
-            source.into_bytes()
+            let source = source.into_bytes();
+            if compile_flags.reproducible && m.specifier.scheme() == "file" {
+              normalize_source_map_sources(source, &root_dir_url).with_context(
+                || {
+                  format!("Failed normalizing source map of '{}'", m.specifier)
+                },
+              )?
+            } else {
+              source
+            }
           } else {
             m.source.as_bytes().to_vec()
           };
+          // cjs is translated to esm when loaded, so its code cache
+          // wouldn't match
+          let is_esm_source = matches!(
+            m.media_type,
+            MediaType::JavaScript
+              | MediaType::Jsx
+              | MediaType::Mjs
+              | MediaType::TypeScript
+              | MediaType::Mts
+              | MediaType::Tsx
+          );
+          if compile_flags.code_cache && is_esm_source && !is_cjs {
+            code_cache_modules.push((
+              root_dir_url.specifier_key(&m.specifier).into_owned(),
+              source.clone(),
+            ));
+          }
           (Some(source), m.media_type)
         }
         deno_graph::Module::Json(m) => {
@@ -655,6 +1168,37 @@ impl<'a> DenoCompileBinaryWriter<'a> {
       }
     }
     remote_modules_store.add_redirects(&graph.redirects);
+    let code_cache = if code_cache_modules.is_empty() {
+      CodeCacheStoreBuilder::default()
+    } else {
+      let recorded = match &compile_flags.code_cache_from {
+        Some(path) => {
+          let bytes = std::fs::read(path).with_context(|| {
+            format!("Failed reading the code cache at '{}'", path)
+          })?;
+          let recorded = CodeCacheStoreBuilder::from_bytes(&bytes)
+            .with_context(|| {
+              format!("Failed reading the code cache at '{}'", path)
+            })?;
+          if recorded.is_empty() {
+            log::warn!(
+              "{} The code cache at '{}' is empty or was recorded by a different version of Deno.",
+              crate::colors::yellow("Warning"),
+              path
+            );
+          }
+          Some(recorded)
+        }
+        None => None,
+      };
+      create_code_cache(&code_cache_modules, recorded.as_ref())
+        .context("Failed creating code cache")?
+    };
+    if let Some(level) = compile_flags.compress {
+      vfs
+        .compress_files(level)
+        .context("Failed compressing files")?;
+    }
 
     let env_vars_from_env_file = match cli_options.env_file_name() {
       Some(env_filename) => {
@@ -664,7 +1208,7 @@ impl<'a> DenoCompileBinaryWriter<'a> {
       None => Default::default(),
     };
 
-    let metadata = Metadata {
+    let mut metadata = Metadata {
       argv: compile_flags.args.clone(),
       seed: cli_options.seed(),
       location: cli_options.location_flag().clone(),
@@ -678,6 +1222,24 @@ impl<'a> DenoCompileBinaryWriter<'a> {
       ca_data,
       env_vars_from_env_file,
       entrypoint_key: root_dir_url.specifier_key(entrypoint).into_owned(),
+      entrypoints: compile_flags
+        .entrypoints
+        .iter()
+        .map(|entry| {
+          let specifier =
+            resolve_url_or_path(&entry.source_file, cli_options.initial_cwd())?;
+          Ok((
+            entry.name.clone(),
+            EntrypointMetadata {
+              entrypoint_key: root_dir_url
+                .specifier_key(&specifier)
+                .into_owned(),
+              permissions: entry.permissions.clone(),
+              argv: entry.args.clone(),
+            },
+          ))
+        })
+        .collect::<Result<_, AnyError>>()?,
       workspace_resolver: SerializedWorkspaceResolver {
         import_map: self.workspace_resolver.maybe_import_map().map(|i| {
           SerializedWorkspaceResolverImportMap {
@@ -722,7 +1284,16 @@ impl<'a> DenoCompileBinaryWriter<'a> {
         sloppy_imports: cli_options.unstable_sloppy_imports(),
         features: cli_options.unstable_features(),
       },
+      vfs_overlay: compile_flags.vfs_overlay,
+      base_binary_sha256: if compile_flags.reproducible {
+        base_binary_sha256
+      } else {
+        None
+      },
     };
+    if compile_flags.reproducible {
+      normalize_for_reproducible_build(&mut metadata, &mut vfs);
+    }
 
     write_binary_bytes(
       writer,
@@ -731,6 +1302,7 @@ impl<'a> DenoCompileBinaryWriter<'a> {
       npm_snapshot.map(|s| s.into_serialized()),
       &remote_modules_store,
       vfs,
+      &code_cache,
       compile_flags,
     )
   }
@@ -738,6 +1310,7 @@ impl<'a> DenoCompileBinaryWriter<'a> {
   fn build_npm_vfs(
     &self,
     root_path: &Path,
+    file_options: &CompileFileOptions,
     cli_options: &CliOptions,
   ) -> Result<VfsBuilder, AnyError> {
     fn maybe_warn_different_system(system_info: &NpmSystemInfo) {
@@ -751,6 +1324,7 @@ impl<'a> DenoCompileBinaryWriter<'a> {
         if let Some(node_modules_path) = npm_resolver.root_node_modules_path() {
           maybe_warn_different_system(&self.npm_system_info);
           let mut builder = VfsBuilder::new(root_path.to_path_buf())?;
+          builder.set_exclude(node_modules_exclude(root_path, file_options)?);
           builder.add_dir_recursive(node_modules_path)?;
           Ok(builder)
         } else {
@@ -765,8 +1339,14 @@ impl<'a> DenoCompileBinaryWriter<'a> {
           for package in packages {
             let folder =
               npm_resolver.resolve_pkg_folder_from_pkg_id(&package.id)?;
+            builder.set_exclude(global_npm_package_exclude(
+              &folder,
+              &package.id.nv.name,
+              file_options,
+            )?);
             builder.add_dir_recursive(&folder)?;
           }
+          builder.set_exclude(Default::default());
 
           // Flatten all the registries folders into a single ".deno_compile_node_modules/localhost" folder
           // that will be used by denort when loading the npm cache. This avoids us exposing
@@ -816,6 +1396,7 @@ impl<'a> DenoCompileBinaryWriter<'a> {
       InnerCliNpmResolverRef::Byonm(_) => {
         maybe_warn_different_system(&self.npm_system_info);
         let mut builder = VfsBuilder::new(root_path.to_path_buf())?;
+        builder.set_exclude(node_modules_exclude(root_path, file_options)?);
         for pkg_json in cli_options.workspace().package_jsons() {
           builder.add_file_at_path(&pkg_json.path)?;
         }
@@ -902,3 +1483,272 @@ fn set_windows_binary_to_gui(bin: &mut [u8]) -> Result<(), AnyError> {
     .copy_from_slice(&subsystem.to_le_bytes());
   Ok(())
 }
+
+#[cfg(test)]
+mod test {
+  use std::cell::Cell;
+  use std::pin::Pin;
+  use std::rc::Rc;
+
+  use deno_core::futures::FutureExt;
+  use deno_core::ModuleLoadResponse;
+  use deno_core::ModuleLoader;
+  use deno_core::ModuleSource;
+  use deno_core::ModuleSourceCode;
+  use deno_core::ModuleSpecifier;
+  use deno_core::ModuleType;
+  use deno_core::RequestedModuleType;
+  use deno_core::ResolutionKind;
+  use deno_core::RuntimeOptions;
+  use deno_core::SourceCodeCacheInfo;
+
+  use super::*;
+
+  /// Loads a single module with the given code cache, like the standalone
+  /// module loader.
+  struct CodeCacheTestLoader {
+    source: &'static str,
+    code_cache: Vec<u8>,
+    rejected: Rc<Cell<bool>>,
+  }
+
+  impl ModuleLoader for CodeCacheTestLoader {
+    fn resolve(
+      &self,
+      specifier: &str,
+      referrer: &str,
+      _kind: ResolutionKind,
+    ) -> Result<ModuleSpecifier, AnyError> {
+      Ok(deno_core::resolve_import(specifier, referrer)?)
+    }
+
+    fn load(
+      &self,
+      specifier: &ModuleSpecifier,
+      _maybe_referrer: Option<&ModuleSpecifier>,
+      _is_dynamic: bool,
+      _requested_module_type: RequestedModuleType,
+    ) -> ModuleLoadResponse {
+      ModuleLoadResponse::Sync(Ok(ModuleSource::new(
+        ModuleType::JavaScript,
+        ModuleSourceCode::String(self.source.to_string().into()),
+        specifier,
+        Some(SourceCodeCacheInfo {
+          hash: code_cache_source_hash(self.source.as_bytes()),
+          data: Some(Cow::Owned(self.code_cache.clone())),
+        }),
+      )))
+    }
+
+    fn code_cache_ready(
+      &self,
+      _specifier: ModuleSpecifier,
+      _source_hash: u64,
+      _code_cache: &[u8],
+    ) -> Pin<Box<dyn Future<Output = ()>>> {
+      // only called when V8 rejected the code cache and compiled the module
+      // from scratch
+      self.rejected.set(true);
+      std::future::ready(()).boxed_local()
+    }
+  }
+
+  async fn is_code_cache_rejected(
+    source: &'static str,
+    code_cache: Vec<u8>,
+  ) -> bool {
+    let rejected = Rc::new(Cell::new(false));
+    let mut runtime = JsRuntime::new(RuntimeOptions {
+      module_loader: Some(Rc::new(CodeCacheTestLoader {
+        source,
+        code_cache,
+        rejected: rejected.clone(),
+      })),
+      ..Default::default()
+    });
+    let specifier = ModuleSpecifier::parse("file:///main.js").unwrap();
+    let id = runtime.load_main_es_module(&specifier).await.unwrap();
+    let result = runtime.mod_evaluate(id);
+    runtime.run_event_loop(Default::default()).await.unwrap();
+    result.await.unwrap();
+    rejected.get()
+  }
+
+  #[tokio::test]
+  async fn embedded_code_cache_is_accepted() {
+    const SOURCE: &str =
+      "export function add(a, b) {\n  return a + b;\n}\nexport const sum = add(1, 2);\n";
+    let modules = [("main.js".to_string(), SOURCE.as_bytes().to_vec())];
+    let code_cache = create_code_cache(&modules, None).unwrap();
+    let data = code_cache
+      .get("main.js", code_cache_source_hash(SOURCE.as_bytes()))
+      .unwrap()
+      .to_vec();
+    assert!(!is_code_cache_rejected(SOURCE, data.clone()).await);
+    // a code cache for another source is noticed
+    assert!(is_code_cache_rejected("export const other = 1;\n", data).await);
+  }
+
+  #[test]
+  fn uses_recorded_code_cache() {
+    let modules = [
+      ("main.js".to_string(), b"export const a = 1;".to_vec()),
+      ("changed.js".to_string(), b"export const b = 2;".to_vec()),
+      ("unused.js".to_string(), b"export const c = 3;".to_vec()),
+    ];
+    let mut recorded = CodeCacheStoreBuilder::default();
+    recorded.add(
+      "main.js".to_string(),
+      code_cache_source_hash(b"export const a = 1;"),
+      vec![1, 2, 3],
+    );
+    recorded.add(
+      "changed.js".to_string(),
+      code_cache_source_hash(b"export const b = 1;"),
+      vec![4],
+    );
+    let code_cache = create_code_cache(&modules, Some(&recorded)).unwrap();
+    assert_eq!(code_cache.len(), 1);
+    assert_eq!(
+      code_cache.get("main.js", code_cache_source_hash(b"export const a = 1;")),
+      Some([1, 2, 3].as_slice())
+    );
+  }
+
+  #[test]
+  fn global_npm_package_exclude_includes_general_excludes() {
+    let root = if cfg!(windows) {
+      PathBuf::from("C:\\project")
+    } else {
+      PathBuf::from("/project")
+    };
+    let file_options = CompileFileOptions {
+      assets: vec![],
+      exclude: PathOrPatternSet::from_exclude_relative_path_or_patterns(
+        &root,
+        &["**/*.map".to_string()],
+      )
+      .unwrap(),
+      npm_exclude: vec![NpmPackageExclude {
+        name: "lodash".to_string(),
+        subpath: "fp/**".to_string(),
+      }],
+    };
+    let package_path = root.join("cache/lodash/4.17.21");
+    let exclude =
+      global_npm_package_exclude(&package_path, "lodash", &file_options)
+        .unwrap();
+    assert!(exclude.matches_path(&package_path.join("fp/map.js")));
+    assert!(exclude.matches_path(&package_path.join("index.js.map")));
+    assert!(!exclude.matches_path(&package_path.join("index.js")));
+
+    let other_path = root.join("cache/chalk/5.0.0");
+    let exclude =
+      global_npm_package_exclude(&other_path, "chalk", &file_options).unwrap();
+    assert!(!exclude.matches_path(&other_path.join("fp/map.js")));
+    assert!(exclude.matches_path(&other_path.join("index.js.map")));
+  }
+
+  #[test]
+  fn normalizes_source_map_sources() {
+    let root_url = Url::parse("file:///project/").unwrap();
+    let root_dir_url = StandaloneRelativeFileBaseUrl::new(&root_url);
+    let source_map = serde_json::json!({
+      "version": 3,
+      "sources": ["file:///project/src/main.ts", "https://deno.land/x/mod.ts"],
+      "mappings": "AAAA",
+    });
+    let source = format!(
+      "console.log(1);\n//# sourceMappingURL=data:application/json;base64,{}\n",
+      BASE64_STANDARD.encode(serde_json::to_vec(&source_map).unwrap())
+    );
+    let normalized =
+      normalize_source_map_sources(source.into_bytes(), &root_dir_url).unwrap();
+    let normalized = String::from_utf8(normalized).unwrap();
+    let (code, encoded) = normalized
+      .split_once("//# sourceMappingURL=data:application/json;base64,")
+      .unwrap();
+    assert_eq!(code, "console.log(1);\n");
+    assert!(encoded.ends_with('\n'));
+    let source_map: serde_json::Value =
+      serde_json::from_slice(&BASE64_STANDARD.decode(encoded.trim()).unwrap())
+        .unwrap();
+    assert_eq!(
+      source_map["sources"],
+      serde_json::json!(["src/main.ts", "https://deno.land/x/mod.ts"])
+    );
+
+    // sources without a source map are left as is
+    assert_eq!(
+      normalize_source_map_sources(b"console.log(1);".to_vec(), &root_dir_url)
+        .unwrap(),
+      b"console.log(1);"
+    );
+  }
+
+  #[test]
+  fn parses_npm_package_exclude() {
+    let parse = |pattern: &str| {
+      NpmPackageExclude::parse(pattern)
+        .unwrap()
+        .map(|exclude| (exclude.name, exclude.subpath))
+    };
+    assert_eq!(parse("**/*.map"), None);
+    assert_eq!(
+      parse("npm:lodash/fp/**"),
+      Some(("lodash".to_string(), "fp/**".to_string()))
+    );
+    assert_eq!(
+      parse("npm:@scope/pkg/dist/*.map"),
+      Some(("@scope/pkg".to_string(), "dist/*.map".to_string()))
+    );
+    assert!(NpmPackageExclude::parse("npm:lodash").is_err());
+    assert!(NpmPackageExclude::parse("npm:@scope/pkg").is_err());
+  }
+
+  #[test]
+  fn selects_entrypoint() {
+    let entrypoints = ["fmt", "lint"]
+      .into_iter()
+      .map(|name| {
+        (
+          name.to_string(),
+          EntrypointMetadata {
+            entrypoint_key: format!("{}.ts", name),
+            permissions: None,
+            argv: vec![],
+          },
+        )
+      })
+      .collect::<BTreeMap<_, _>>();
+    let select = |args: &[&str]| {
+      let mut args = args.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+      let key = select_entrypoint(&entrypoints, &mut args)
+        .map(|entrypoint| entrypoint.entrypoint_key.clone());
+      (key, args)
+    };
+
+    // by the name of the executable
+    assert_eq!(
+      select(&["/usr/bin/lint", "fmt"]),
+      (
+        Some("lint.ts".to_string()),
+        vec!["/usr/bin/lint".into(), "fmt".into()]
+      )
+    );
+    // by the first argument
+    assert_eq!(
+      select(&["tools", "fmt", "--check"]),
+      (
+        Some("fmt.ts".to_string()),
+        vec!["tools".into(), "--check".into()]
+      )
+    );
+    // the main entrypoint
+    assert_eq!(
+      select(&["tools", "other"]),
+      (None, vec!["tools".into(), "other".into()])
+    );
+    assert_eq!(select(&["tools"]), (None, vec!["tools".into()]));
+  }
+}
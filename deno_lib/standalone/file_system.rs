@@ -1,5 +1,6 @@
 // Copyright 2018-2024 the Deno authors. All rights reserved. MIT license.
 
+use std::borrow::Cow;
 use std::path::Path;
 use std::path::PathBuf;
 use std::rc::Rc;
@@ -16,27 +17,80 @@ use deno_runtime::deno_io::fs::FsError;
 use deno_runtime::deno_io::fs::FsResult;
 use deno_runtime::deno_io::fs::FsStat;
 
+use super::vfs_overlay::is_write;
+use super::vfs_overlay::VfsOverlay;
 use super::virtual_fs::FileBackedVfs;
 
 #[derive(Debug, Clone)]
-pub struct DenoCompileFileSystem(Arc<FileBackedVfs>);
+pub struct DenoCompileFileSystem {
+  vfs: Arc<FileBackedVfs>,
+  /// Receives the changes to the embedded files. Without it, they're
+  /// read only.
+  overlay: Option<Arc<VfsOverlay>>,
+}
 
 impl DenoCompileFileSystem {
-  pub fn new(vfs: Arc<FileBackedVfs>) -> Self {
-    Self(vfs)
+  pub fn new(
+    vfs: Arc<FileBackedVfs>,
+    overlay: Option<Arc<VfsOverlay>>,
+  ) -> Self {
+    Self { vfs, overlay }
   }
 
   fn error_if_in_vfs(&self, path: &Path) -> FsResult<()> {
-    if self.0.is_path_within(path) {
+    if self.vfs.is_path_within(path) {
       Err(FsError::NotSupported)
     } else {
       Ok(())
     }
   }
 
+  /// Gets the overlay entry that reads of the embedded `path` go to.
+  fn overlay_entry(&self, path: &Path) -> FsResult<Option<PathBuf>> {
+    match &self.overlay {
+      Some(overlay) => overlay.resolve(path),
+      None => Ok(None),
+    }
+  }
+
+  /// Resolves the real path an entry should be created at.
+  fn path_for_create<'a>(&self, path: &'a Path) -> FsResult<Cow<'a, Path>> {
+    if !self.vfs.is_path_within(path) {
+      return Ok(Cow::Borrowed(path));
+    }
+    match &self.overlay {
+      Some(overlay) => Ok(Cow::Owned(overlay.prepare_create(path)?)),
+      None => Err(FsError::NotSupported),
+    }
+  }
+
+  /// Resolves the real path an existing entry should be modified at.
+  fn path_for_modify<'a>(
+    &self,
+    path: &'a Path,
+    recursive: bool,
+  ) -> FsResult<Cow<'a, Path>> {
+    if !self.vfs.is_path_within(path) {
+      return Ok(Cow::Borrowed(path));
+    }
+    match &self.overlay {
+      Some(overlay) => Ok(Cow::Owned(overlay.copy_up(path, recursive)?)),
+      None => Err(FsError::NotSupported),
+    }
+  }
+
+  fn mark_removed(&self, path: &Path) -> FsResult<()> {
+    match &self.overlay {
+      Some(overlay) if self.vfs.is_path_within(path) => {
+        overlay.mark_removed(path)
+      }
+      _ => Ok(()),
+    }
+  }
+
   fn copy_to_real_path(&self, oldpath: &Path, newpath: &Path) -> FsResult<()> {
-    let old_file = self.0.file_entry(oldpath)?;
-    let old_file_bytes = self.0.read_file_all(old_file)?;
+    let old_file = self.vfs.file_entry(oldpath)?;
+    let old_file_bytes = self.vfs.read_file_all(old_file)?;
     RealFs.write_file_sync(
       newpath,
       OpenOptions {
@@ -79,8 +133,21 @@ impl FileSystem for DenoCompileFileSystem {
     options: OpenOptions,
     access_check: Option<AccessCheckCb>,
   ) -> FsResult<Rc<dyn File>> {
-    if self.0.is_path_within(path) {
-      Ok(self.0.open_file(path)?)
+    if self.vfs.is_path_within(path) {
+      if let Some(overlay) = &self.overlay {
+        if let Some(upper) = overlay.open_path(path, &options)? {
+          // Reads are allowed like for the embedded files, but writes need
+          // the same permission as writing outside the overlay would. The
+          // check is against the path in the vfs, not the overlay directory.
+          if let Some(access_check) = access_check {
+            if is_write(&options) {
+              access_check(true, path, &options)?;
+            }
+          }
+          return RealFs.open_sync(&upper, options, None);
+        }
+      }
+      Ok(self.vfs.open_file(path)?)
     } else {
       RealFs.open_sync(path, options, access_check)
     }
@@ -91,8 +158,18 @@ impl FileSystem for DenoCompileFileSystem {
     options: OpenOptions,
     access_check: Option<AccessCheckCb<'a>>,
   ) -> FsResult<Rc<dyn File>> {
-    if self.0.is_path_within(&path) {
-      Ok(self.0.open_file(&path)?)
+    if self.vfs.is_path_within(&path) {
+      if let Some(overlay) = &self.overlay {
+        if let Some(upper) = overlay.open_path(&path, &options)? {
+          if let Some(access_check) = access_check {
+            if is_write(&options) {
+              access_check(true, &path, &options)?;
+            }
+          }
+          return RealFs.open_async(upper, options, None).await;
+        }
+      }
+      Ok(self.vfs.open_file(&path)?)
     } else {
       RealFs.open_async(path, options, access_check).await
     }
@@ -104,7 +181,12 @@ impl FileSystem for DenoCompileFileSystem {
     recursive: bool,
     mode: Option<u32>,
   ) -> FsResult<()> {
-    self.error_if_in_vfs(path)?;
+    if self.vfs.is_path_within(path) {
+      return match &self.overlay {
+        Some(overlay) => overlay.mkdir(path, recursive, mode),
+        None => Err(FsError::NotSupported),
+      };
+    }
     RealFs.mkdir_sync(path, recursive, mode)
   }
   async fn mkdir_async(
@@ -113,16 +195,18 @@ impl FileSystem for DenoCompileFileSystem {
     recursive: bool,
     mode: Option<u32>,
   ) -> FsResult<()> {
-    self.error_if_in_vfs(&path)?;
+    if self.vfs.is_path_within(&path) {
+      return self.mkdir_sync(&path, recursive, mode);
+    }
     RealFs.mkdir_async(path, recursive, mode).await
   }
 
   fn chmod_sync(&self, path: &Path, mode: u32) -> FsResult<()> {
-    self.error_if_in_vfs(path)?;
-    RealFs.chmod_sync(path, mode)
+    let path = self.path_for_modify(path, false)?;
+    RealFs.chmod_sync(&path, mode)
   }
   async fn chmod_async(&self, path: PathBuf, mode: u32) -> FsResult<()> {
-    self.error_if_in_vfs(&path)?;
+    let path = self.path_for_modify(&path, false)?.into_owned();
     RealFs.chmod_async(path, mode).await
   }
 
@@ -132,8 +216,8 @@ impl FileSystem for DenoCompileFileSystem {
     uid: Option<u32>,
     gid: Option<u32>,
   ) -> FsResult<()> {
-    self.error_if_in_vfs(path)?;
-    RealFs.chown_sync(path, uid, gid)
+    let path = self.path_for_modify(path, false)?;
+    RealFs.chown_sync(&path, uid, gid)
   }
   async fn chown_async(
     &self,
@@ -141,7 +225,7 @@ impl FileSystem for DenoCompileFileSystem {
     uid: Option<u32>,
     gid: Option<u32>,
   ) -> FsResult<()> {
-    self.error_if_in_vfs(&path)?;
+    let path = self.path_for_modify(&path, false)?.into_owned();
     RealFs.chown_async(path, uid, gid).await
   }
 
@@ -151,8 +235,8 @@ impl FileSystem for DenoCompileFileSystem {
     uid: Option<u32>,
     gid: Option<u32>,
   ) -> FsResult<()> {
-    self.error_if_in_vfs(path)?;
-    RealFs.lchown_sync(path, uid, gid)
+    let path = self.path_for_modify(path, false)?;
+    RealFs.lchown_sync(&path, uid, gid)
   }
 
   async fn lchown_async(
@@ -161,25 +245,35 @@ impl FileSystem for DenoCompileFileSystem {
     uid: Option<u32>,
     gid: Option<u32>,
   ) -> FsResult<()> {
-    self.error_if_in_vfs(&path)?;
+    let path = self.path_for_modify(&path, false)?.into_owned();
     RealFs.lchown_async(path, uid, gid).await
   }
 
   fn remove_sync(&self, path: &Path, recursive: bool) -> FsResult<()> {
-    self.error_if_in_vfs(path)?;
+    if self.vfs.is_path_within(path) {
+      return match &self.overlay {
+        Some(overlay) => overlay.remove(path, recursive),
+        None => Err(FsError::NotSupported),
+      };
+    }
     RealFs.remove_sync(path, recursive)
   }
   async fn remove_async(&self, path: PathBuf, recursive: bool) -> FsResult<()> {
-    self.error_if_in_vfs(&path)?;
+    if self.vfs.is_path_within(&path) {
+      return self.remove_sync(&path, recursive);
+    }
     RealFs.remove_async(path, recursive).await
   }
 
   fn copy_file_sync(&self, oldpath: &Path, newpath: &Path) -> FsResult<()> {
-    self.error_if_in_vfs(newpath)?;
-    if self.0.is_path_within(oldpath) {
-      self.copy_to_real_path(oldpath, newpath)
+    let newpath = self.path_for_create(newpath)?;
+    if self.vfs.is_path_within(oldpath) {
+      match self.overlay_entry(oldpath)? {
+        Some(oldpath) => RealFs.copy_file_sync(&oldpath, &newpath),
+        None => self.copy_to_real_path(oldpath, &newpath),
+      }
     } else {
-      RealFs.copy_file_sync(oldpath, newpath)
+      RealFs.copy_file_sync(oldpath, &newpath)
     }
   }
   async fn copy_file_async(
@@ -187,8 +281,11 @@ impl FileSystem for DenoCompileFileSystem {
     oldpath: PathBuf,
     newpath: PathBuf,
   ) -> FsResult<()> {
-    self.error_if_in_vfs(&newpath)?;
-    if self.0.is_path_within(&oldpath) {
+    let newpath = self.path_for_create(&newpath)?.into_owned();
+    if self.vfs.is_path_within(&oldpath) {
+      if let Some(oldpath) = self.overlay_entry(&oldpath)? {
+        return RealFs.copy_file_async(oldpath, newpath).await;
+      }
       let fs = self.clone();
       tokio::task::spawn_blocking(move || {
         fs.copy_to_real_path(&oldpath, &newpath)
@@ -200,103 +297,136 @@ impl FileSystem for DenoCompileFileSystem {
   }
 
   fn cp_sync(&self, from: &Path, to: &Path) -> FsResult<()> {
-    self.error_if_in_vfs(to)?;
+    let to = self.path_for_create(to)?;
+    let overlay_from = self.overlay_entry(from)?;
 
-    RealFs.cp_sync(from, to)
+    RealFs.cp_sync(overlay_from.as_deref().unwrap_or(from), &to)
   }
   async fn cp_async(&self, from: PathBuf, to: PathBuf) -> FsResult<()> {
-    self.error_if_in_vfs(&to)?;
+    let to = self.path_for_create(&to)?.into_owned();
+    let from = self.overlay_entry(&from)?.unwrap_or(from);
 
     RealFs.cp_async(from, to).await
   }
 
   fn stat_sync(&self, path: &Path) -> FsResult<FsStat> {
-    if self.0.is_path_within(path) {
-      Ok(self.0.stat(path)?)
+    if self.vfs.is_path_within(path) {
+      if let Some(path) = self.overlay_entry(path)? {
+        return RealFs.stat_sync(&path);
+      }
+      Ok(self.vfs.stat(path)?)
     } else {
       RealFs.stat_sync(path)
     }
   }
   async fn stat_async(&self, path: PathBuf) -> FsResult<FsStat> {
-    if self.0.is_path_within(&path) {
-      Ok(self.0.stat(&path)?)
+    if self.vfs.is_path_within(&path) {
+      if let Some(path) = self.overlay_entry(&path)? {
+        return RealFs.stat_async(path).await;
+      }
+      Ok(self.vfs.stat(&path)?)
     } else {
       RealFs.stat_async(path).await
     }
   }
 
   fn lstat_sync(&self, path: &Path) -> FsResult<FsStat> {
-    if self.0.is_path_within(path) {
-      Ok(self.0.lstat(path)?)
+    if self.vfs.is_path_within(path) {
+      if let Some(path) = self.overlay_entry(path)? {
+        return RealFs.lstat_sync(&path);
+      }
+      Ok(self.vfs.lstat(path)?)
     } else {
       RealFs.lstat_sync(path)
     }
   }
   async fn lstat_async(&self, path: PathBuf) -> FsResult<FsStat> {
-    if self.0.is_path_within(&path) {
-      Ok(self.0.lstat(&path)?)
+    if self.vfs.is_path_within(&path) {
+      if let Some(path) = self.overlay_entry(&path)? {
+        return RealFs.lstat_async(path).await;
+      }
+      Ok(self.vfs.lstat(&path)?)
     } else {
       RealFs.lstat_async(path).await
     }
   }
 
   fn realpath_sync(&self, path: &Path) -> FsResult<PathBuf> {
-    if self.0.is_path_within(path) {
-      Ok(self.0.canonicalize(path)?)
+    if self.vfs.is_path_within(path) {
+      if let Some(overlay) = &self.overlay {
+        if let Some(path) = overlay.resolve(path)? {
+          return Ok(overlay.to_vfs_path(RealFs.realpath_sync(&path)?));
+        }
+      }
+      Ok(self.vfs.canonicalize(path)?)
     } else {
       RealFs.realpath_sync(path)
     }
   }
   async fn realpath_async(&self, path: PathBuf) -> FsResult<PathBuf> {
-    if self.0.is_path_within(&path) {
-      Ok(self.0.canonicalize(&path)?)
+    if self.vfs.is_path_within(&path) {
+      if let Some(overlay) = &self.overlay {
+        if let Some(path) = overlay.resolve(&path)? {
+          let path = RealFs.realpath_async(path).await?;
+          return Ok(overlay.to_vfs_path(path));
+        }
+      }
+      Ok(self.vfs.canonicalize(&path)?)
     } else {
       RealFs.realpath_async(path).await
     }
   }
 
   fn read_dir_sync(&self, path: &Path) -> FsResult<Vec<FsDirEntry>> {
-    if self.0.is_path_within(path) {
-      Ok(self.0.read_dir(path)?)
+    if self.vfs.is_path_within(path) {
+      if let Some(overlay) = &self.overlay {
+        return overlay.read_dir(path);
+      }
+      Ok(self.vfs.read_dir(path)?)
     } else {
       RealFs.read_dir_sync(path)
     }
   }
   async fn read_dir_async(&self, path: PathBuf) -> FsResult<Vec<FsDirEntry>> {
-    if self.0.is_path_within(&path) {
-      Ok(self.0.read_dir(&path)?)
+    if self.vfs.is_path_within(&path) {
+      if let Some(overlay) = &self.overlay {
+        return overlay.read_dir(&path);
+      }
+      Ok(self.vfs.read_dir(&path)?)
     } else {
       RealFs.read_dir_async(path).await
     }
   }
 
   fn rename_sync(&self, oldpath: &Path, newpath: &Path) -> FsResult<()> {
-    self.error_if_in_vfs(oldpath)?;
-    self.error_if_in_vfs(newpath)?;
-    RealFs.rename_sync(oldpath, newpath)
+    let from = self.path_for_modify(oldpath, true)?;
+    let to = self.path_for_create(newpath)?;
+    RealFs.rename_sync(&from, &to)?;
+    self.mark_removed(oldpath)
   }
   async fn rename_async(
     &self,
     oldpath: PathBuf,
     newpath: PathBuf,
   ) -> FsResult<()> {
-    self.error_if_in_vfs(&oldpath)?;
-    self.error_if_in_vfs(&newpath)?;
-    RealFs.rename_async(oldpath, newpath).await
+    let from = self.path_for_modify(&oldpath, true)?.into_owned();
+    let to = self.path_for_create(&newpath)?.into_owned();
+    RealFs.rename_async(from, to).await?;
+    self.mark_removed(&oldpath)
   }
 
   fn link_sync(&self, oldpath: &Path, newpath: &Path) -> FsResult<()> {
-    self.error_if_in_vfs(oldpath)?;
-    self.error_if_in_vfs(newpath)?;
-    RealFs.link_sync(oldpath, newpath)
+    let oldpath = self.path_for_modify(oldpath, false)?;
+    let newpath = self.path_for_create(newpath)?;
+    RealFs.link_sync(&oldpath, &newpath)
   }
   async fn link_async(
     &self,
     oldpath: PathBuf,
     newpath: PathBuf,
   ) -> FsResult<()> {
-    self.error_if_in_vfs(&oldpath)?;
-    self.error_if_in_vfs(&newpath)?;
+    let oldpath = self.path_for_modify(&oldpath, false)?.into_owned();
+    let newpath = self.path_for_create(&newpath)?.into_owned();
     RealFs.link_async(oldpath, newpath).await
   }
 
@@ -306,9 +436,10 @@ impl FileSystem for DenoCompileFileSystem {
     newpath: &Path,
     file_type: Option<FsFileType>,
   ) -> FsResult<()> {
+    // the target can't point into the vfs as it's resolved by the real fs
     self.error_if_in_vfs(oldpath)?;
-    self.error_if_in_vfs(newpath)?;
-    RealFs.symlink_sync(oldpath, newpath, file_type)
+    let newpath = self.path_for_create(newpath)?;
+    RealFs.symlink_sync(oldpath, &newpath, file_type)
   }
   async fn symlink_async(
     &self,
@@ -317,31 +448,37 @@ impl FileSystem for DenoCompileFileSystem {
     file_type: Option<FsFileType>,
   ) -> FsResult<()> {
     self.error_if_in_vfs(&oldpath)?;
-    self.error_if_in_vfs(&newpath)?;
+    let newpath = self.path_for_create(&newpath)?.into_owned();
     RealFs.symlink_async(oldpath, newpath, file_type).await
   }
 
   fn read_link_sync(&self, path: &Path) -> FsResult<PathBuf> {
-    if self.0.is_path_within(path) {
-      Ok(self.0.read_link(path)?)
+    if self.vfs.is_path_within(path) {
+      if let Some(path) = self.overlay_entry(path)? {
+        return RealFs.read_link_sync(&path);
+      }
+      Ok(self.vfs.read_link(path)?)
     } else {
       RealFs.read_link_sync(path)
     }
   }
   async fn read_link_async(&self, path: PathBuf) -> FsResult<PathBuf> {
-    if self.0.is_path_within(&path) {
-      Ok(self.0.read_link(&path)?)
+    if self.vfs.is_path_within(&path) {
+      if let Some(path) = self.overlay_entry(&path)? {
+        return RealFs.read_link_async(path).await;
+      }
+      Ok(self.vfs.read_link(&path)?)
     } else {
       RealFs.read_link_async(path).await
     }
   }
 
   fn truncate_sync(&self, path: &Path, len: u64) -> FsResult<()> {
-    self.error_if_in_vfs(path)?;
-    RealFs.truncate_sync(path, len)
+    let path = self.path_for_modify(path, false)?;
+    RealFs.truncate_sync(&path, len)
   }
   async fn truncate_async(&self, path: PathBuf, len: u64) -> FsResult<()> {
-    self.error_if_in_vfs(&path)?;
+    let path = self.path_for_modify(&path, false)?.into_owned();
     RealFs.truncate_async(path, len).await
   }
 
@@ -353,8 +490,8 @@ impl FileSystem for DenoCompileFileSystem {
     mtime_secs: i64,
     mtime_nanos: u32,
   ) -> FsResult<()> {
-    self.error_if_in_vfs(path)?;
-    RealFs.utime_sync(path, atime_secs, atime_nanos, mtime_secs, mtime_nanos)
+    let path = self.path_for_modify(path, false)?;
+    RealFs.utime_sync(&path, atime_secs, atime_nanos, mtime_secs, mtime_nanos)
   }
   async fn utime_async(
     &self,
@@ -364,7 +501,7 @@ impl FileSystem for DenoCompileFileSystem {
     mtime_secs: i64,
     mtime_nanos: u32,
   ) -> FsResult<()> {
-    self.error_if_in_vfs(&path)?;
+    let path = self.path_for_modify(&path, false)?.into_owned();
     RealFs
       .utime_async(path, atime_secs, atime_nanos, mtime_secs, mtime_nanos)
       .await
@@ -378,8 +515,8 @@ impl FileSystem for DenoCompileFileSystem {
     mtime_secs: i64,
     mtime_nanos: u32,
   ) -> FsResult<()> {
-    self.error_if_in_vfs(path)?;
-    RealFs.lutime_sync(path, atime_secs, atime_nanos, mtime_secs, mtime_nanos)
+    let path = self.path_for_modify(path, false)?;
+    RealFs.lutime_sync(&path, atime_secs, atime_nanos, mtime_secs, mtime_nanos)
   }
   async fn lutime_async(
     &self,
@@ -389,7 +526,7 @@ impl FileSystem for DenoCompileFileSystem {
     mtime_secs: i64,
     mtime_nanos: u32,
   ) -> FsResult<()> {
-    self.error_if_in_vfs(&path)?;
+    let path = self.path_for_modify(&path, false)?.into_owned();
     RealFs
       .lutime_async(path, atime_secs, atime_nanos, mtime_secs, mtime_nanos)
       .await
@@ -0,0 +1,609 @@
+// Copyright 2018-2024 the Deno authors. All rights reserved. MIT license.
+
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+use std::path::Component;
+use std::path::Path;
+use std::path::PathBuf;
+
+use deno_core::anyhow::bail;
+use deno_core::anyhow::Context;
+use deno_core::error::AnyError;
+use deno_core::serde_json;
+use deno_core::url::Url;
+use deno_npm::resolution::ValidSerializedNpmResolutionSnapshot;
+use serde::Serialize;
+
+use super::binary::Metadata;
+use super::integrity::trusted_public_key;
+use super::integrity::DataSectionIntegrity;
+use super::integrity::SignatureStatus;
+use super::serialization::find_binary_data_section;
+use super::serialization::find_binary_data_section_range;
+use super::serialization::split_binary_data_section;
+use super::serialization::CodeCacheStore;
+use super::serialization::DeserializedDataSection;
+use super::serialization::RemoteModulesStore;
+use super::serialization::DATA_SECTION_PARTS;
+use super::virtual_fs::FileBackedVfs;
+use super::virtual_fs::VfsEntry;
+use super::virtual_fs::VfsRoot;
+use super::virtual_fs::VirtualDirectory;
+use super::virtual_fs::VirtualFile;
+
+/// The contents of an executable created by `deno compile`, read for
+/// auditing purposes.
+pub struct StandaloneBinaryContents {
+  metadata: Metadata,
+  npm_snapshot: Option<ValidSerializedNpmResolutionSnapshot>,
+  remote_modules: RemoteModulesStore,
+  vfs: FileBackedVfs,
+  code_cache: CodeCacheStore,
+  integrity: DataSectionIntegrity,
+  /// The bytes of the executable when it was read from a file, which the
+  /// fields above borrow from. Declared last so it outlives them.
+  _data: Option<Vec<u8>>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StandaloneBinaryInfo<'a> {
+  pub metadata: &'a Metadata,
+  pub npm_packages: Vec<String>,
+  pub remote_modules: Vec<RemoteModuleInfo>,
+  pub files: VfsEntryInfo,
+  pub code_cache_entries: usize,
+  pub signature: SignatureStatus,
+  /// The base64 encoded public key the executable was signed with.
+  pub public_key: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RemoteModuleInfo {
+  pub specifier: String,
+  /// The specifier this one redirects to, if it's a redirect.
+  pub redirect: Option<String>,
+  pub media_type: Option<String>,
+  pub size: Option<u64>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum VfsEntryInfo {
+  Directory {
+    name: String,
+    entries: Vec<VfsEntryInfo>,
+  },
+  File {
+    name: String,
+    size: u64,
+    /// Size of the data in the executable, which differs from `size` for
+    /// compressed files.
+    #[serde(rename = "storedSize")]
+    stored_size: u64,
+  },
+  Symlink {
+    name: String,
+    /// The target, relative to the root directory.
+    target: String,
+  },
+}
+
+impl VfsEntryInfo {
+  pub fn name(&self) -> &str {
+    match self {
+      Self::Directory { name, .. }
+      | Self::File { name, .. }
+      | Self::Symlink { name, .. } => name,
+    }
+  }
+
+  fn from_dir(dir: &VirtualDirectory) -> Self {
+    Self::Directory {
+      name: dir.name.clone(),
+      entries: dir
+        .entries
+        .iter()
+        .map(|entry| match entry {
+          VfsEntry::Dir(dir) => Self::from_dir(dir),
+          VfsEntry::File(file) => Self::File {
+            name: file.name.clone(),
+            size: file.len,
+            stored_size: file
+              .compression
+              .map(|compression| compression.stored_len)
+              .unwrap_or(file.len),
+          },
+          VfsEntry::Symlink(symlink) => Self::Symlink {
+            name: symlink.name.clone(),
+            target: symlink.dest_parts.join("/"),
+          },
+        })
+        .collect(),
+    }
+  }
+}
+
+impl StandaloneBinaryContents {
+  /// Reads the executable at `path`.
+  pub fn read(path: &Path) -> Result<Self, AnyError> {
+    let data = std::fs::read(path)
+      .with_context(|| format!("Failed reading '{}'", path.display()))?;
+    // SAFETY: the bytes are moved into the returned contents, which drop
+    // them after everything borrowing from them, and nothing borrowed
+    // from them is handed out for longer than `&self`. Moving the vec
+    // doesn't move its heap allocation.
+    let borrowed: &'static [u8] =
+      unsafe { std::slice::from_raw_parts(data.as_ptr(), data.len()) };
+    match Self::from_data(borrowed)? {
+      Some(mut contents) => {
+        contents._data = Some(data);
+        Ok(contents)
+      }
+      None => bail!(
+        "'{}' is not an executable created by deno compile.",
+        path.display()
+      ),
+    }
+  }
+
+  /// Reads the contents from the bytes of an executable, returning `None`
+  /// if it wasn't created by `deno compile`.
+  pub fn from_data(data: &'static [u8]) -> Result<Option<Self>, AnyError> {
+    let Some(DeserializedDataSection {
+      metadata,
+      npm_snapshot,
+      remote_modules,
+      vfs_dir,
+      vfs_files_data,
+      code_cache,
+      integrity,
+    }) = find_binary_data_section(data)?
+    else {
+      return Ok(None);
+    };
+    // paths are only used to look up entries, so any root will do
+    let root_path = std::env::temp_dir().join(&vfs_dir.name);
+    let vfs = FileBackedVfs::new(
+      Cow::Borrowed(vfs_files_data),
+      VfsRoot {
+        dir: vfs_dir,
+        root_path,
+        start_file_offset: 0,
+      },
+    );
+    Ok(Some(Self {
+      metadata,
+      npm_snapshot,
+      remote_modules,
+      vfs,
+      code_cache,
+      integrity,
+      _data: None,
+    }))
+  }
+
+  pub fn metadata(&self) -> &Metadata {
+    &self.metadata
+  }
+
+  pub fn info(&self) -> StandaloneBinaryInfo {
+    let mut npm_packages = self
+      .npm_snapshot
+      .as_ref()
+      .map(|snapshot| {
+        snapshot
+          .as_serialized()
+          .packages
+          .iter()
+          .map(|pkg| pkg.id.as_serialized())
+          .collect::<Vec<_>>()
+      })
+      .unwrap_or_default();
+    npm_packages.sort();
+    let mut remote_modules = self
+      .remote_modules
+      .specifiers()
+      .map(|(specifier, redirect)| {
+        let data = match redirect {
+          Some(_) => None,
+          None => self.remote_modules.read(specifier).ok().flatten(),
+        };
+        RemoteModuleInfo {
+          specifier: specifier.to_string(),
+          redirect: redirect.map(|r| r.to_string()),
+          media_type: data.as_ref().map(|d| d.media_type.to_string()),
+          size: data.as_ref().map(|d| d.data.len() as u64),
+        }
+      })
+      .collect::<Vec<_>>();
+    remote_modules.sort_by(|a, b| a.specifier.cmp(&b.specifier));
+    StandaloneBinaryInfo {
+      metadata: &self.metadata,
+      npm_packages,
+      remote_modules,
+      files: VfsEntryInfo::from_dir(self.vfs.root_dir()),
+      code_cache_entries: self.code_cache.len(),
+      signature: match trusted_public_key() {
+        Ok(trusted_public_key) => self
+          .integrity
+          .signature_status(trusted_public_key.as_deref()),
+        Err(_) => SignatureStatus::Invalid,
+      },
+      public_key: self.integrity.public_key(),
+    }
+  }
+
+  /// Checks the integrity hash and signature of the embedded data, that
+  /// every embedded file and module can be read and that the entrypoint
+  /// and symlinks resolve, returning the problems found. The signature is
+  /// only checked when there's a trusted public key.
+  pub fn verify(&self) -> Vec<String> {
+    let mut problems = Vec::new();
+    let verified = trusted_public_key().and_then(|trusted_public_key| {
+      self.integrity.verify(trusted_public_key.as_deref())
+    });
+    if let Err(err) = verified {
+      problems.push(format!("{:#}", err));
+    }
+    problems.extend(verify_vfs(&self.vfs));
+    for (specifier, _) in self.remote_modules.specifiers() {
+      match self.remote_modules.read(specifier) {
+        Ok(Some(_)) => {}
+        Ok(None) => {
+          problems.push(format!("{}: redirect does not resolve", specifier))
+        }
+        Err(err) => problems.push(format!("{}: {:#}", specifier, err)),
+      }
+    }
+    let entrypoint_keys = std::iter::once(&self.metadata.entrypoint_key).chain(
+      self
+        .metadata
+        .entrypoints
+        .values()
+        .map(|entrypoint| &entrypoint.entrypoint_key),
+    );
+    for entrypoint_key in entrypoint_keys {
+      let has_entrypoint = match Url::parse(entrypoint_key) {
+        Ok(specifier) => {
+          matches!(self.remote_modules.read(&specifier), Ok(Some(_)))
+        }
+        Err(_) => self
+          .vfs
+          .file_entry(&self.vfs.root().join(entrypoint_key))
+          .is_ok(),
+      };
+      if !has_entrypoint {
+        problems.push(format!(
+          "{}: entrypoint is not embedded in the executable",
+          entrypoint_key
+        ));
+      }
+    }
+    problems
+  }
+
+  /// Writes the embedded files to `dest`, either all of them or only the
+  /// file or directory at `entry` (relative to the root directory).
+  /// Symlinks are skipped so the extracted files can't point outside
+  /// `dest`. Returns the number of files written.
+  pub fn extract(
+    &self,
+    entry: Option<&str>,
+    dest: &Path,
+  ) -> Result<usize, AnyError> {
+    extract_vfs(&self.vfs, entry, dest)
+  }
+}
+
+/// Compares the bytes of two executables created by `deno compile`,
+/// returning the names of the parts that differ. The top level metadata
+/// fields are compared individually to make differences easier to track
+/// down.
+pub fn diff_standalone_binaries(
+  a: &[u8],
+  b: &[u8],
+) -> Result<Vec<String>, AnyError> {
+  fn split(data: &[u8]) -> Result<(&[u8], &[u8], Vec<&[u8]>), AnyError> {
+    let Some(range) = find_binary_data_section_range(data) else {
+      bail!("Not an executable created by deno compile.");
+    };
+    let (parts, _) = split_binary_data_section(&data[range.clone()])?;
+    Ok((&data[..range.start], &data[range.end..], parts))
+  }
+
+  let (a_before, a_after, a_parts) = split(a)?;
+  let (b_before, b_after, b_parts) = split(b)?;
+  let mut differences = Vec::new();
+  if a_before != b_before || a_after != b_after {
+    differences.push("executable".to_string());
+  }
+  for ((name, a_part), b_part) in
+    DATA_SECTION_PARTS.iter().zip(&a_parts).zip(&b_parts)
+  {
+    if a_part == b_part {
+      continue;
+    }
+    if *name == "metadata" {
+      let a_metadata: serde_json::Map<String, serde_json::Value> =
+        serde_json::from_slice(a_part).context("deserializing metadata")?;
+      let b_metadata: serde_json::Map<String, serde_json::Value> =
+        serde_json::from_slice(b_part).context("deserializing metadata")?;
+      let keys = a_metadata
+        .keys()
+        .chain(b_metadata.keys())
+        .collect::<BTreeSet<_>>();
+      for key in keys {
+        if a_metadata.get(key) != b_metadata.get(key) {
+          differences.push(format!("metadata.{}", key));
+        }
+      }
+    } else {
+      differences.push(name.to_string());
+    }
+  }
+  Ok(differences)
+}
+
+fn verify_vfs(vfs: &FileBackedVfs) -> Vec<String> {
+  fn verify_dir(
+    vfs: &FileBackedVfs,
+    dir: &VirtualDirectory,
+    path: &Path,
+    problems: &mut Vec<String>,
+  ) {
+    for entry in &dir.entries {
+      let entry_path = path.join(entry.name());
+      let display_path = || {
+        entry_path
+          .strip_prefix(vfs.root())
+          .unwrap_or(&entry_path)
+          .display()
+          .to_string()
+      };
+      if let Err(err) = validate_entry_name(entry.name()) {
+        problems.push(format!("{}: {:#}", display_path(), err));
+        continue;
+      }
+      match entry {
+        VfsEntry::Dir(dir) => verify_dir(vfs, dir, &entry_path, problems),
+        VfsEntry::File(file) => match vfs.read_file_all(file) {
+          Ok(data) if data.len() as u64 == file.len => {}
+          Ok(data) => problems.push(format!(
+            "{}: expected {} bytes, but read {}",
+            display_path(),
+            file.len,
+            data.len()
+          )),
+          Err(err) => problems.push(format!("{}: {:#}", display_path(), err)),
+        },
+        VfsEntry::Symlink(_) => {
+          if let Err(err) = vfs.canonicalize(&entry_path) {
+            problems.push(format!(
+              "{}: symlink does not resolve ({:#})",
+              display_path(),
+              err
+            ));
+          }
+        }
+      }
+    }
+  }
+
+  let mut problems = Vec::new();
+  verify_dir(vfs, vfs.root_dir(), vfs.root(), &mut problems);
+  problems
+}
+
+fn extract_vfs(
+  vfs: &FileBackedVfs,
+  entry: Option<&str>,
+  dest: &Path,
+) -> Result<usize, AnyError> {
+  fn extract_dir(
+    vfs: &FileBackedVfs,
+    dir: &VirtualDirectory,
+    dest: &Path,
+  ) -> Result<usize, AnyError> {
+    std::fs::create_dir_all(dest)
+      .with_context(|| format!("Failed creating '{}'", dest.display()))?;
+    let mut count = 0;
+    for entry in &dir.entries {
+      validate_entry_name(entry.name())?;
+      let entry_dest = dest.join(entry.name());
+      match entry {
+        VfsEntry::Dir(dir) => count += extract_dir(vfs, dir, &entry_dest)?,
+        VfsEntry::File(file) => {
+          extract_file(vfs, file, &entry_dest)?;
+          count += 1;
+        }
+        VfsEntry::Symlink(symlink) => {
+          log::warn!(
+            "Skipped symlink '{}' -> '{}'",
+            entry_dest.display(),
+            symlink.dest_parts.join("/")
+          );
+        }
+      }
+    }
+    Ok(count)
+  }
+
+  fn extract_file(
+    vfs: &FileBackedVfs,
+    file: &VirtualFile,
+    dest: &Path,
+  ) -> Result<(), AnyError> {
+    let data = vfs
+      .read_file_all(file)
+      .with_context(|| format!("Failed reading '{}'", file.name))?;
+    std::fs::write(dest, data)
+      .with_context(|| format!("Failed writing '{}'", dest.display()))?;
+    Ok(())
+  }
+
+  let Some(entry) = entry else {
+    return extract_dir(vfs, vfs.root_dir(), dest);
+  };
+  let path = resolve_entry_path(vfs, entry)?;
+  let dest = dest.join(path.file_name().unwrap_or_default());
+  match vfs.dir_entry(&path) {
+    Ok(dir) => extract_dir(vfs, dir, &dest),
+    Err(_) => {
+      let file = vfs
+        .file_entry(&path)
+        .with_context(|| format!("Could not find '{}'", entry))?;
+      if let Some(parent) = dest.parent() {
+        std::fs::create_dir_all(parent)?;
+      }
+      extract_file(vfs, file, &dest)?;
+      Ok(1)
+    }
+  }
+}
+
+/// Resolves a path relative to the root directory, which may also start
+/// with the name of the root directory as shown in the file tree.
+fn resolve_entry_path(
+  vfs: &FileBackedVfs,
+  entry: &str,
+) -> Result<PathBuf, AnyError> {
+  let root_name = &vfs.root_dir().name;
+  let mut path = vfs.root().to_path_buf();
+  for (index, part) in entry.split(['/', '\\']).enumerate() {
+    if part.is_empty() || part == "." || (index == 0 && part == root_name) {
+      continue;
+    }
+    if part == ".." {
+      bail!("Entry '{}' must not contain '..'", entry);
+    }
+    path.push(part);
+  }
+  Ok(path)
+}
+
+/// Ensures an entry name from the executable can't escape the directory
+/// it's extracted to.
+fn validate_entry_name(name: &str) -> Result<(), AnyError> {
+  let mut components = Path::new(name).components();
+  let is_single_name = matches!(
+    (components.next(), components.next()),
+    (Some(Component::Normal(_)), None)
+  );
+  // backslashes and drive letters are only special on windows, but are
+  // rejected everywhere so extracting doesn't depend on the platform
+  let has_drive = matches!(
+    name.as_bytes(),
+    [letter, b':', ..] if letter.is_ascii_alphabetic()
+  );
+  if !is_single_name || name.contains(['/', '\\']) || has_drive {
+    bail!("Invalid entry name '{}'", name);
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod test {
+  use test_util::TempDir;
+
+  use super::super::serialization::create_raw_data_section;
+  use super::super::virtual_fs::VfsBuilder;
+  use super::*;
+
+  fn create_binary(exe: &[u8], metadata: &str, vfs_files: &[u8]) -> Vec<u8> {
+    let mut binary = exe.to_vec();
+    binary.extend(create_raw_data_section(&[
+      metadata.as_bytes(),
+      b"",
+      b"",
+      b"{}",
+      vfs_files,
+      b"",
+      b"",
+    ]));
+    binary.extend_from_slice(b"trailer");
+    binary
+  }
+
+  fn create_vfs(temp_dir: &TempDir) -> FileBackedVfs {
+    let src_path = temp_dir.path().canonicalize().join("src");
+    src_path.join("sub").create_dir_all();
+    src_path.join("main.ts").write("console.log(1);");
+    src_path.join("sub").join("data.txt").write("data");
+    let src_path = src_path.to_path_buf();
+    let mut builder = VfsBuilder::new(src_path.clone()).unwrap();
+    builder.add_dir_recursive(&src_path).unwrap();
+    let (root_dir, files) = builder.into_dir_and_files();
+    FileBackedVfs::new(
+      Cow::Owned(files.concat()),
+      VfsRoot {
+        dir: root_dir,
+        root_path: src_path,
+        start_file_offset: 0,
+      },
+    )
+  }
+
+  #[test]
+  fn verifies_and_extracts_files() {
+    let temp_dir = TempDir::new();
+    let vfs = create_vfs(&temp_dir);
+    assert!(verify_vfs(&vfs).is_empty());
+
+    let dest = temp_dir.path().join("out");
+    assert_eq!(extract_vfs(&vfs, None, dest.as_path()).unwrap(), 2);
+    assert_eq!(dest.join("main.ts").read_to_string(), "console.log(1);");
+    assert_eq!(dest.join("sub").join("data.txt").read_to_string(), "data");
+
+    let dest = temp_dir.path().join("out_single");
+    assert_eq!(
+      extract_vfs(&vfs, Some("src/sub/data.txt"), dest.as_path()).unwrap(),
+      1
+    );
+    assert_eq!(dest.join("data.txt").read_to_string(), "data");
+    assert_eq!(extract_vfs(&vfs, Some("sub"), dest.as_path()).unwrap(), 1);
+    assert!(extract_vfs(&vfs, Some("../x"), dest.as_path()).is_err());
+    assert!(extract_vfs(&vfs, Some("missing"), dest.as_path()).is_err());
+  }
+
+  #[test]
+  fn validates_entry_names() {
+    assert!(validate_entry_name("file.ts").is_ok());
+    assert!(validate_entry_name("..").is_err());
+    assert!(validate_entry_name("a/b").is_err());
+    assert!(validate_entry_name("a\\b").is_err());
+    assert!(validate_entry_name("").is_err());
+    assert!(validate_entry_name(".").is_err());
+    assert!(validate_entry_name("C:").is_err());
+    assert!(validate_entry_name("c:file.ts").is_err());
+    assert!(validate_entry_name("npm:chalk").is_ok());
+    assert!(validate_entry_name("a:b:c").is_ok());
+  }
+
+  #[test]
+  fn diffs_standalone_binaries() {
+    let binary = create_binary(b"exe", r#"{"seed":1,"argv":["a"]}"#, b"files");
+    assert!(diff_standalone_binaries(&binary, &binary)
+      .unwrap()
+      .is_empty());
+
+    let other = create_binary(
+      b"exe",
+      r#"{"seed":2,"argv":["a"],"v8_flags":[]}"#,
+      b"files",
+    );
+    assert_eq!(
+      diff_standalone_binaries(&binary, &other).unwrap(),
+      vec!["metadata.seed".to_string(), "metadata.v8_flags".to_string()]
+    );
+
+    let other = create_binary(b"other exe", r#"{"seed":1,"argv":["a"]}"#, b"");
+    assert_eq!(
+      diff_standalone_binaries(&binary, &other).unwrap(),
+      vec!["executable".to_string(), "vfs files".to_string()]
+    );
+
+    assert!(diff_standalone_binaries(&binary, b"not compiled").is_err());
+  }
+}
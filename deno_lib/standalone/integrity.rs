@@ -0,0 +1,466 @@
+// Copyright 2018-2024 the Deno authors. All rights reserved. MIT license.
+
+use std::ops::Range;
+use std::path::Path;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+
+use base64::prelude::BASE64_STANDARD;
+use base64::Engine;
+use deno_core::anyhow::bail;
+use deno_core::anyhow::Context;
+use deno_core::error::AnyError;
+use ring::digest::digest;
+use ring::digest::Digest;
+use ring::digest::SHA256;
+use ring::signature::Ed25519KeyPair;
+use ring::signature::KeyPair;
+use ring::signature::UnparsedPublicKey;
+use ring::signature::ED25519;
+use serde::Serialize;
+
+/// Environment variable with the base64 encoded ed25519 public key that
+/// the executable must be signed with. When it's set while building deno,
+/// the key is compiled in and the variable is ignored at runtime.
+pub const PUBLIC_KEY_ENV_VAR_NAME: &str = "DENO_COMPILE_PUBLIC_KEY";
+
+/// The public key compiled into this build, if any.
+const COMPILED_PUBLIC_KEY: Option<&str> =
+  option_env!("DENO_COMPILE_PUBLIC_KEY");
+
+/// What's known about the signature of an executable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SignatureStatus {
+  Unsigned,
+  /// The executable is signed, but there's no trusted public key to check
+  /// the signature with. The embedded public key proves nothing, as anyone
+  /// who can modify the executable can also re-sign it with their own.
+  Unverified,
+  /// The executable is signed with the trusted public key.
+  Verified,
+  /// The executable isn't signed with the trusted public key.
+  Invalid,
+}
+
+/// Loads an ed25519 private key from a PKCS#8 file, PEM or DER encoded
+/// (ex. `openssl genpkey -algorithm ed25519 -out key.pem`).
+pub fn load_signing_key(path: &Path) -> Result<Ed25519KeyPair, AnyError> {
+  let bytes = std::fs::read(path)
+    .with_context(|| format!("Failed reading '{}'", path.display()))?;
+  let der = match std::str::from_utf8(&bytes) {
+    Ok(text) if text.contains("-----BEGIN") => {
+      let body = text
+        .lines()
+        .filter(|line| !line.starts_with("-----"))
+        .collect::<String>();
+      BASE64_STANDARD
+        .decode(body.trim())
+        .with_context(|| format!("Failed decoding '{}'", path.display()))?
+    }
+    _ => bytes,
+  };
+  match Ed25519KeyPair::from_pkcs8_maybe_unchecked(&der) {
+    Ok(key_pair) => Ok(key_pair),
+    Err(err) => bail!(
+      "'{}' is not an ed25519 private key in PKCS#8 format: {}",
+      path.display(),
+      err
+    ),
+  }
+}
+
+pub fn encode_public_key(key_pair: &Ed25519KeyPair) -> String {
+  BASE64_STANDARD.encode(key_pair.public_key().as_ref())
+}
+
+/// Size of the chunks the parts of the data section are hashed in, which
+/// allows checking a part as it's read instead of all at once on startup.
+const CHUNK_SIZE: usize = 256 * 1024;
+
+fn chunk_count(part_len: usize) -> usize {
+  part_len.div_ceil(CHUNK_SIZE)
+}
+
+/// Hashes the part lengths and the hashes of their chunks, which is what
+/// gets signed.
+fn root_hash(
+  parts_len: impl Iterator<Item = usize>,
+  chunk_hashes: &[u8],
+) -> Digest {
+  let mut context = ring::digest::Context::new(&SHA256);
+  for part_len in parts_len {
+    context.update(&(part_len as u64).to_le_bytes());
+  }
+  context.update(chunk_hashes);
+  context.finish()
+}
+
+/// Writes the hashes of the chunks of each of the `parts`, the hash over
+/// those and its signature if there's a signing key, along with the public
+/// key to verify it.
+///
+/// Format:
+/// * <root_sha256>
+/// * <chunk_hashes_len><chunk_sha256...>
+/// * <signature_len><signature>
+/// * <public_key_len><public_key>
+pub fn write_integrity(
+  bytes: &mut Vec<u8>,
+  parts: &[&[u8]],
+  signing_key: Option<&Ed25519KeyPair>,
+) {
+  fn write_bytes_with_len(bytes: &mut Vec<u8>, data: &[u8]) {
+    bytes.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(data);
+  }
+
+  let mut chunk_hashes = Vec::new();
+  for part in parts {
+    for chunk in part.chunks(CHUNK_SIZE) {
+      chunk_hashes.extend_from_slice(digest(&SHA256, chunk).as_ref());
+    }
+  }
+  let hash = root_hash(parts.iter().map(|part| part.len()), &chunk_hashes);
+  bytes.extend_from_slice(hash.as_ref());
+  write_bytes_with_len(bytes, &chunk_hashes);
+  match signing_key {
+    Some(signing_key) => {
+      write_bytes_with_len(bytes, signing_key.sign(hash.as_ref()).as_ref());
+      write_bytes_with_len(bytes, signing_key.public_key().as_ref());
+    }
+    None => {
+      write_bytes_with_len(bytes, &[]);
+      write_bytes_with_len(bytes, &[]);
+    }
+  }
+}
+
+/// The hashes and optional signature over the parts of the data section
+/// of an executable.
+pub struct DataSectionIntegrity {
+  parts: Vec<&'static [u8]>,
+  root_sha256: &'static [u8],
+  chunk_hashes: &'static [u8],
+  signature: &'static [u8],
+  public_key: &'static [u8],
+}
+
+impl DataSectionIntegrity {
+  pub fn read(
+    parts: Vec<&'static [u8]>,
+    data: &'static [u8],
+  ) -> Result<Self, AnyError> {
+    fn read_bytes(
+      input: &'static [u8],
+      len: usize,
+    ) -> Result<(&'static [u8], &'static [u8]), AnyError> {
+      if input.len() < len {
+        bail!("Unexpected end of data.");
+      }
+      let (data, input) = input.split_at(len);
+      Ok((input, data))
+    }
+
+    fn read_bytes_with_len(
+      input: &'static [u8],
+    ) -> Result<(&'static [u8], &'static [u8]), AnyError> {
+      let (input, len) = read_bytes(input, 4)?;
+      let len = u32::from_le_bytes(len.try_into().unwrap());
+      read_bytes(input, len as usize)
+    }
+
+    let (input, root_sha256) = read_bytes(data, SHA256.output_len())?;
+    let (input, chunk_hashes) = read_bytes_with_len(input)?;
+    let (input, signature) = read_bytes_with_len(input)?;
+    let (_input, public_key) = read_bytes_with_len(input)?;
+    let expected_chunks = parts
+      .iter()
+      .map(|part| chunk_count(part.len()))
+      .sum::<usize>();
+    if chunk_hashes.len() != expected_chunks * SHA256.output_len() {
+      bail!("The number of chunk hashes does not match the data.");
+    }
+    Ok(Self {
+      parts,
+      root_sha256,
+      chunk_hashes,
+      signature,
+      public_key,
+    })
+  }
+
+  pub fn is_signed(&self) -> bool {
+    !self.signature.is_empty()
+  }
+
+  /// The base64 encoded public key embedded in the executable.
+  pub fn public_key(&self) -> Option<String> {
+    if self.public_key.is_empty() {
+      None
+    } else {
+      Some(BASE64_STANDARD.encode(self.public_key))
+    }
+  }
+
+  /// Checks that all of the data wasn't modified after it was compiled
+  /// and, when there's a `trusted_public_key`, that it's signed with it.
+  pub fn verify(
+    &self,
+    trusted_public_key: Option<&[u8]>,
+  ) -> Result<SignatureStatus, AnyError> {
+    let status = self.verify_hashes(trusted_public_key)?;
+    for index in 0..self.parts.len() {
+      self.part_check(index).check_all()?;
+    }
+    Ok(status)
+  }
+
+  /// Like `verify`, but only checks the hashes the parts are checked
+  /// against, which is cheap. The parts still need to be checked with
+  /// `part_check` before their data is used.
+  pub fn verify_hashes(
+    &self,
+    trusted_public_key: Option<&[u8]>,
+  ) -> Result<SignatureStatus, AnyError> {
+    let hash =
+      root_hash(self.parts.iter().map(|part| part.len()), self.chunk_hashes);
+    if hash.as_ref() != self.root_sha256 {
+      bail!("The embedded data was modified after it was compiled.");
+    }
+    let status = self.signature_status(trusted_public_key);
+    if status == SignatureStatus::Invalid {
+      if self.is_signed() {
+        bail!("The executable is not signed with the trusted public key.");
+      } else {
+        bail!("The executable is not signed, but a public key is required.");
+      }
+    }
+    Ok(status)
+  }
+
+  /// Checks the signature against the hashes, without checking the hashes
+  /// against the data.
+  pub fn signature_status(
+    &self,
+    trusted_public_key: Option<&[u8]>,
+  ) -> SignatureStatus {
+    match trusted_public_key {
+      Some(_) if !self.is_signed() => SignatureStatus::Invalid,
+      Some(public_key) => {
+        let is_valid = UnparsedPublicKey::new(&ED25519, public_key)
+          .verify(self.root_sha256, self.signature)
+          .is_ok();
+        if is_valid {
+          SignatureStatus::Verified
+        } else {
+          SignatureStatus::Invalid
+        }
+      }
+      None if self.is_signed() => SignatureStatus::Unverified,
+      None => SignatureStatus::Unsigned,
+    }
+  }
+
+  /// Gets the check of the part at `index`, in the order the parts were
+  /// passed to `write_integrity`.
+  pub fn part_check(&self, index: usize) -> PartIntegrityCheck {
+    let hash_len = SHA256.output_len();
+    let start = self.parts[..index]
+      .iter()
+      .map(|part| chunk_count(part.len()))
+      .sum::<usize>();
+    let count = chunk_count(self.parts[index].len());
+    PartIntegrityCheck {
+      data: self.parts[index],
+      chunk_hashes: &self.chunk_hashes
+        [start * hash_len..(start + count) * hash_len],
+      checked_chunks: (0..count).map(|_| AtomicBool::new(false)).collect(),
+    }
+  }
+}
+
+/// Checks a part of the data section against its hashes as it's read, one
+/// chunk at a time, so that starting an executable doesn't require reading
+/// and hashing all of its data.
+///
+/// The hashes must be verified with `DataSectionIntegrity::verify_hashes`
+/// for the check to mean anything.
+#[derive(Debug)]
+pub struct PartIntegrityCheck {
+  data: &'static [u8],
+  chunk_hashes: &'static [u8],
+  checked_chunks: Box<[AtomicBool]>,
+}
+
+impl PartIntegrityCheck {
+  /// Checks the bytes of `slice`, which must be borrowed from the part.
+  pub fn check_slice(&self, slice: &[u8]) -> Result<(), AnyError> {
+    let start = (slice.as_ptr() as usize)
+      .checked_sub(self.data.as_ptr() as usize)
+      .filter(|start| start + slice.len() <= self.data.len());
+    match start {
+      Some(start) => self.check_range(start..start + slice.len()),
+      None => bail!("The data to check is not part of the data section."),
+    }
+  }
+
+  /// Checks the bytes in `range` of the part.
+  pub fn check_range(&self, range: Range<usize>) -> Result<(), AnyError> {
+    if range.end > self.data.len() {
+      bail!("The data to check is not part of the data section.");
+    }
+    if range.is_empty() {
+      return Ok(());
+    }
+    let hash_len = SHA256.output_len();
+    for index in range.start / CHUNK_SIZE..=(range.end - 1) / CHUNK_SIZE {
+      if self.checked_chunks[index].load(Ordering::Relaxed) {
+        continue;
+      }
+      let chunk_start = index * CHUNK_SIZE;
+      let chunk_end = std::cmp::min(chunk_start + CHUNK_SIZE, self.data.len());
+      let hash = digest(&SHA256, &self.data[chunk_start..chunk_end]);
+      let expected =
+        &self.chunk_hashes[index * hash_len..(index + 1) * hash_len];
+      if hash.as_ref() != expected {
+        bail!("The embedded data was modified after it was compiled.");
+      }
+      self.checked_chunks[index].store(true, Ordering::Relaxed);
+    }
+    Ok(())
+  }
+
+  pub fn check_all(&self) -> Result<(), AnyError> {
+    self.check_range(0..self.data.len())
+  }
+}
+
+/// Gets the public key executables must be signed with, either compiled
+/// into this build or read from the environment.
+pub fn trusted_public_key() -> Result<Option<Vec<u8>>, AnyError> {
+  let value = match COMPILED_PUBLIC_KEY {
+    Some(value) => value.to_string(),
+    None => match std::env::var(PUBLIC_KEY_ENV_VAR_NAME) {
+      Ok(value) => value,
+      Err(_) => return Ok(None),
+    },
+  };
+  let public_key = BASE64_STANDARD
+    .decode(value.trim())
+    .with_context(|| format!("Failed decoding {}", PUBLIC_KEY_ENV_VAR_NAME))?;
+  Ok(Some(public_key))
+}
+
+#[cfg(test)]
+mod test {
+  use ring::rand::SystemRandom;
+
+  use super::*;
+
+  fn leak(bytes: Vec<u8>) -> &'static [u8] {
+    Box::leak(bytes.into_boxed_slice())
+  }
+
+  fn generate_key_pair() -> Ed25519KeyPair {
+    let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).unwrap();
+    Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).unwrap()
+  }
+
+  fn read_integrity(
+    parts: &[&'static [u8]],
+    signing_key: Option<&Ed25519KeyPair>,
+  ) -> DataSectionIntegrity {
+    let mut bytes = Vec::new();
+    write_integrity(&mut bytes, parts, signing_key);
+    DataSectionIntegrity::read(parts.to_vec(), leak(bytes)).unwrap()
+  }
+
+  #[test]
+  fn verifies_hash() {
+    let mut bytes = Vec::new();
+    write_integrity(&mut bytes, &[b"data", b""], None);
+    let bytes = leak(bytes);
+    let integrity =
+      DataSectionIntegrity::read(vec![b"data", b""], bytes).unwrap();
+    assert!(!integrity.is_signed());
+    assert_eq!(integrity.public_key(), None);
+    assert_eq!(integrity.verify(None).unwrap(), SignatureStatus::Unsigned);
+
+    let integrity =
+      DataSectionIntegrity::read(vec![b"dat!", b""], bytes).unwrap();
+    assert_eq!(
+      integrity.verify_hashes(None).unwrap(),
+      SignatureStatus::Unsigned
+    );
+    assert!(integrity.part_check(0).check_all().is_err());
+    assert!(integrity.verify(None).is_err());
+
+    // moving bytes between parts changes the number of chunks
+    assert!(DataSectionIntegrity::read(vec![b"dat", b"a"], bytes).is_err());
+  }
+
+  #[test]
+  fn checks_parts_lazily() {
+    let part = leak(vec![0; CHUNK_SIZE * 2 + 10]);
+    let integrity = read_integrity(&[b"small", part], None);
+    integrity.verify(None).unwrap();
+
+    let mut tampered = part.to_vec();
+    tampered[CHUNK_SIZE + 1] = 1;
+    let tampered = leak(tampered);
+    let mut bytes = Vec::new();
+    write_integrity(&mut bytes, &[b"small", part], None);
+    let integrity =
+      DataSectionIntegrity::read(vec![b"small", tampered], leak(bytes))
+        .unwrap();
+    // only the hashes are checked up front
+    integrity.verify_hashes(None).unwrap();
+    integrity.part_check(0).check_all().unwrap();
+    let check = integrity.part_check(1);
+    check.check_range(0..CHUNK_SIZE).unwrap();
+    check.check_slice(&tampered[CHUNK_SIZE * 2..]).unwrap();
+    assert!(check.check_range(CHUNK_SIZE - 1..CHUNK_SIZE + 1).is_err());
+    assert!(check.check_slice(&tampered[CHUNK_SIZE..]).is_err());
+    assert!(check.check_slice(b"other").is_err());
+    assert!(check.check_range(0..tampered.len() + 1).is_err());
+    assert!(integrity.verify(None).is_err());
+  }
+
+  #[test]
+  fn verifies_signature() {
+    let key_pair = generate_key_pair();
+    let other_key_pair = generate_key_pair();
+    let integrity = read_integrity(&[b"data"], Some(&key_pair));
+    assert!(integrity.is_signed());
+    assert_eq!(integrity.public_key(), Some(encode_public_key(&key_pair)));
+    // the embedded public key isn't trusted
+    assert_eq!(integrity.verify(None).unwrap(), SignatureStatus::Unverified);
+    assert_eq!(
+      integrity
+        .verify(Some(key_pair.public_key().as_ref()))
+        .unwrap(),
+      SignatureStatus::Verified
+    );
+    assert!(integrity
+      .verify(Some(other_key_pair.public_key().as_ref()))
+      .is_err());
+    assert_eq!(
+      integrity.signature_status(Some(other_key_pair.public_key().as_ref())),
+      SignatureStatus::Invalid
+    );
+
+    // re-signing with another key isn't trusted either
+    let integrity = read_integrity(&[b"modified"], Some(&other_key_pair));
+    assert_eq!(integrity.verify(None).unwrap(), SignatureStatus::Unverified);
+    assert!(integrity
+      .verify(Some(key_pair.public_key().as_ref()))
+      .is_err());
+
+    // an unsigned executable doesn't pass when a key is required
+    let integrity = read_integrity(&[b"data"], None);
+    assert!(integrity
+      .verify(Some(key_pair.public_key().as_ref()))
+      .is_err());
+  }
+}
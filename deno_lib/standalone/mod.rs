@@ -7,6 +7,7 @@
 
 use binary::StandaloneData;
 use binary::StandaloneModules;
+use binary::StandaloneRelativeFileBaseUrl;
 use deno_ast::MediaType;
 use deno_cache_dir::npm::NpmCacheDir;
 use deno_config::workspace::MappedResolution;
@@ -18,6 +19,7 @@ use deno_core::error::generic_error;
 use deno_core::error::type_error;
 use deno_core::error::AnyError;
 use deno_core::futures::FutureExt;
+use deno_core::parking_lot::Mutex;
 use deno_core::v8_set_flags;
 use deno_core::FastString;
 use deno_core::FeatureChecker;
@@ -27,6 +29,7 @@ use deno_core::ModuleSpecifier;
 use deno_core::ModuleType;
 use deno_core::RequestedModuleType;
 use deno_core::ResolutionKind;
+use deno_core::SourceCodeCacheInfo;
 use deno_npm::npm_rc::ResolvedNpmRc;
 use deno_package_json::PackageJsonDepValue;
 use deno_runtime::deno_fs;
@@ -43,11 +46,19 @@ use deno_runtime::permissions::RuntimePermissionDescriptorParser;
 use deno_runtime::WorkerExecutionMode;
 use deno_runtime::WorkerLogLevel;
 use deno_semver::npm::NpmPackageReqReference;
+use deno_terminal::colors;
 use import_map::parse_from_json;
 use node_resolver::analyze::NodeCodeTranslator;
 use node_resolver::NodeResolutionMode;
+use serialization::code_cache_source_hash;
+use serialization::CodeCacheStore;
+use serialization::CodeCacheStoreBuilder;
 use serialization::DenoCompileModuleSource;
 use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::path::PathBuf;
+use std::pin::Pin;
 use std::rc::Rc;
 use std::sync::Arc;
 
@@ -63,7 +74,9 @@ use crate::cache::DenoCacheEnvFsAdapter;
 use crate::cache::DenoDirProvider;
 use crate::cache::NodeAnalysisCache;
 use crate::cache::RealDenoCacheEnv;
+use crate::cache::CACHE_PERM;
 use crate::http_util::HttpClientProvider;
+use crate::module_loader::MODULE_NOT_FOUND;
 use crate::node::CliCjsCodeAnalyzer;
 use crate::node::CliNodeCodeTranslator;
 use crate::npm::create_cli_npm_resolver;
@@ -80,6 +93,7 @@ use crate::resolver::CjsTrackerOptions;
 use crate::resolver::CliDenoResolverFs;
 use crate::resolver::CliNodeResolver;
 use crate::resolver::NpmModuleLoader;
+use crate::util::fs::atomic_write_file_with_retries;
 use crate::util::progress_bar::ProgressBar;
 use crate::util::progress_bar::ProgressBarStyle;
 use crate::util::v8::construct_v8_flags;
@@ -88,29 +102,106 @@ use crate::worker::CliMainWorkerOptions;
 use crate::worker::CreateModuleLoaderResult;
 use crate::worker::ModuleLoaderFactory;
 
+mod base_binary;
 pub mod binary;
 mod file_system;
+mod inspect;
+mod integrity;
 mod serialization;
+mod vfs_overlay;
 mod virtual_fs;
 
 pub use binary::extract_standalone;
 pub use binary::is_standalone_binary;
 pub use binary::DenoCompileBinaryWriter;
+pub use inspect::diff_standalone_binaries;
+pub use inspect::RemoteModuleInfo;
+pub use inspect::StandaloneBinaryContents;
+pub use inspect::StandaloneBinaryInfo;
+pub use inspect::VfsEntryInfo;
+pub use integrity::SignatureStatus;
+pub use integrity::PUBLIC_KEY_ENV_VAR_NAME;
+#[cfg(test)]
+pub(crate) use serialization::create_raw_data_section;
 
 use self::binary::Metadata;
 use self::file_system::DenoCompileFileSystem;
 
+/// Environment variable with the path of a file that the executable records
+/// the code cache of the modules it loads to, for
+/// `deno compile --code-cache-from`.
+pub const RECORD_CODE_CACHE_ENV_VAR_NAME: &str =
+  "DENO_COMPILE_RECORD_CODE_CACHE";
+
+/// Records the code cache V8 creates for the modules that are loaded. The
+/// file is rewritten after each module, so it's complete however the program
+/// exits.
+struct CodeCacheRecorder {
+  path: PathBuf,
+  entries: Mutex<BTreeMap<String, (u64, Vec<u8>)>>,
+}
+
+impl CodeCacheRecorder {
+  fn record(&self, key: String, source_hash: u64, data: &[u8]) {
+    let mut entries = self.entries.lock();
+    entries.insert(key, (source_hash, data.to_vec()));
+    let mut builder = CodeCacheStoreBuilder::default();
+    for (key, (source_hash, data)) in entries.iter() {
+      builder.add(key.clone(), *source_hash, data.clone());
+    }
+    if let Err(err) =
+      atomic_write_file_with_retries(&self.path, builder.to_bytes(), CACHE_PERM)
+    {
+      log::warn!(
+        "{} Failed recording the code cache to '{}': {:#}",
+        colors::yellow("Warning"),
+        self.path.display(),
+        err
+      );
+    }
+  }
+}
+
 struct SharedModuleLoaderState {
   cjs_tracker: Arc<CjsTracker>,
+  code_cache: CodeCacheStore,
+  code_cache_recorder: Option<CodeCacheRecorder>,
   fs: Arc<dyn deno_fs::FileSystem>,
   modules: StandaloneModules,
   node_code_translator: Arc<CliNodeCodeTranslator>,
   node_resolver: Arc<CliNodeResolver>,
   npm_module_loader: Arc<NpmModuleLoader>,
   npm_resolver: Arc<dyn CliNpmResolver>,
+  root_dir_url: Arc<ModuleSpecifier>,
   workspace_resolver: WorkspaceResolver,
 }
 
+impl SharedModuleLoaderState {
+  fn code_cache_info(
+    &self,
+    specifier: &ModuleSpecifier,
+    source: &[u8],
+  ) -> Option<SourceCodeCacheInfo> {
+    let source_hash = code_cache_source_hash(source);
+    if self.code_cache_recorder.is_some() {
+      // have V8 create the code cache of every module, which it then passes
+      // to `code_cache_ready`
+      return Some(SourceCodeCacheInfo {
+        hash: source_hash,
+        data: None,
+      });
+    }
+    let key = StandaloneRelativeFileBaseUrl::new(&self.root_dir_url)
+      .specifier_key(specifier);
+    let data = self.code_cache.get(&key, source_hash)?;
+    log::debug!("V8 code cache hit for ES module: {specifier}");
+    Some(SourceCodeCacheInfo {
+      hash: source_hash,
+      data: Some(Cow::Borrowed(data)),
+    })
+  }
+}
+
 #[derive(Clone)]
 struct EmbeddedModuleLoader {
   shared: Arc<SharedModuleLoaderState>,
@@ -122,9 +213,6 @@ impl std::fmt::Debug for EmbeddedModuleLoader {
   }
 }
 
-pub const MODULE_NOT_FOUND: &str = "Module not found";
-pub const UNSUPPORTED_SCHEME: &str = "Unsupported scheme";
-
 impl ModuleLoader for EmbeddedModuleLoader {
   fn resolve(
     &self,
@@ -384,6 +472,13 @@ impl ModuleLoader for EmbeddedModuleLoader {
             .boxed_local(),
           )
         } else {
+          let code_cache = if module_type == ModuleType::JavaScript {
+            self
+              .shared
+              .code_cache_info(module_specifier, module_source.as_bytes())
+          } else {
+            None
+          };
           let module_source = module_source.into_for_v8();
           deno_core::ModuleLoadResponse::Sync(Ok(
             deno_core::ModuleSource::new_with_redirect(
@@ -391,7 +486,7 @@ impl ModuleLoader for EmbeddedModuleLoader {
               module_source,
               original_specifier,
               module_specifier,
-              None,
+              code_cache,
             ),
           ))
         }
@@ -404,6 +499,21 @@ impl ModuleLoader for EmbeddedModuleLoader {
       ))),
     }
   }
+
+  fn code_cache_ready(
+    &self,
+    specifier: ModuleSpecifier,
+    source_hash: u64,
+    code_cache: &[u8],
+  ) -> Pin<Box<dyn Future<Output = ()>>> {
+    if let Some(recorder) = &self.shared.code_cache_recorder {
+      let key = StandaloneRelativeFileBaseUrl::new(&self.shared.root_dir_url)
+        .specifier_key(&specifier)
+        .into_owned();
+      recorder.record(key, source_hash, code_cache);
+    }
+    std::future::ready(()).boxed_local()
+  }
 }
 
 impl NodeRequireLoader for EmbeddedModuleLoader {
@@ -487,6 +597,7 @@ pub async fn run(data: StandaloneData) -> Result<i32, AnyError> {
     npm_snapshot,
     root_path,
     vfs,
+    code_cache,
   } = data;
   let deno_dir_provider = Arc::new(DenoDirProvider::new(None));
   let root_cert_store_provider = Arc::new(StandaloneRootCertStoreProvider {
@@ -708,6 +819,12 @@ pub async fn run(data: StandaloneData) -> Result<i32, AnyError> {
   let module_loader_factory = StandaloneModuleLoaderFactory {
     shared: Arc::new(SharedModuleLoaderState {
       cjs_tracker: cjs_tracker.clone(),
+      code_cache,
+      code_cache_recorder: std::env::var_os(RECORD_CODE_CACHE_ENV_VAR_NAME)
+        .map(|path| CodeCacheRecorder {
+          path: PathBuf::from(path),
+          entries: Default::default(),
+        }),
       fs: fs.clone(),
       modules,
       node_code_translator: node_code_translator.clone(),
@@ -718,6 +835,7 @@ pub async fn run(data: StandaloneData) -> Result<i32, AnyError> {
         node_code_translator,
       )),
       npm_resolver: npm_resolver.clone(),
+      root_dir_url: root_dir_url.clone(),
       workspace_resolver,
     }),
   };
@@ -757,7 +875,7 @@ pub async fn run(data: StandaloneData) -> Result<i32, AnyError> {
   });
   let worker_factory = CliMainWorkerFactory::new(
     Arc::new(BlobStore::default()),
-    // Code cache is not supported for standalone binary yet.
+    // The embedded code cache is provided by the module loader instead.
     None,
     feature_checker,
     fs,
@@ -795,6 +913,7 @@ pub async fn run(data: StandaloneData) -> Result<i32, AnyError> {
       unsafely_ignore_certificate_errors: metadata
         .unsafely_ignore_certificate_errors,
       create_hmr_runner: None,
+      create_module_replacer: None,
       create_coverage_collector: None,
       node_ipc: None,
       serve_port: None,
@@ -4,6 +4,7 @@ use std::borrow::Cow;
 use std::collections::BTreeMap;
 use std::collections::HashMap;
 use std::io::Write;
+use std::ops::Range;
 
 use deno_ast::MediaType;
 use deno_core::anyhow::bail;
@@ -19,10 +20,17 @@ use deno_npm::resolution::SerializedNpmResolutionSnapshotPackage;
 use deno_npm::resolution::ValidSerializedNpmResolutionSnapshot;
 use deno_npm::NpmPackageId;
 use deno_semver::package::PackageReq;
+use memmem::Searcher;
+use ring::signature::Ed25519KeyPair;
 
 use crate::standalone::virtual_fs::VirtualDirectory;
 
+use crate::cache::FastInsecureHasher;
+
 use super::binary::Metadata;
+use super::integrity::write_integrity;
+use super::integrity::DataSectionIntegrity;
+use super::integrity::PartIntegrityCheck;
 use super::virtual_fs::VfsBuilder;
 
 const MAGIC_BYTES: &[u8; 8] = b"d3n0l4nd";
@@ -34,31 +42,43 @@ const MAGIC_BYTES: &[u8; 8] = b"d3n0l4nd";
 /// * <remote_modules_len><remote_modules>
 /// * <vfs_headers_len><vfs_headers>
 /// * <vfs_file_data_len><vfs_file_data>
+/// * <code_cache_len><code_cache>
+/// * <integrity_len><integrity>
+/// * <section_len>
 /// * d3n0l4nd
+///
+/// The section length allows finding the start of the section from its end
+/// without reading the rest of the executable.
 pub fn serialize_binary_data_section(
   metadata: &Metadata,
   npm_snapshot: Option<SerializedNpmResolutionSnapshot>,
   remote_modules: &RemoteModulesStoreBuilder,
   vfs: VfsBuilder,
+  code_cache: &CodeCacheStoreBuilder,
+  signing_key: Option<&Ed25519KeyPair>,
 ) -> Result<Vec<u8>, AnyError> {
-  fn write_bytes_with_len(bytes: &mut Vec<u8>, data: &[u8]) {
+  fn write_bytes_with_len(bytes: &mut Vec<u8>, data: &[u8]) -> Range<usize> {
     bytes.extend_from_slice(&(data.len() as u64).to_le_bytes());
+    let start_index = bytes.len();
     bytes.extend_from_slice(data);
+    start_index..bytes.len()
   }
 
   let mut bytes = Vec::new();
   bytes.extend_from_slice(MAGIC_BYTES);
+  // the ranges of the parts covered by the integrity data
+  let mut part_ranges = Vec::with_capacity(6);
 
   // 1. Metadata
   {
     let metadata = serde_json::to_string(metadata)?;
-    write_bytes_with_len(&mut bytes, metadata.as_bytes());
+    part_ranges.push(write_bytes_with_len(&mut bytes, metadata.as_bytes()));
   }
   // 2. Npm snapshot
   {
     let npm_snapshot =
       npm_snapshot.map(serialize_npm_snapshot).unwrap_or_default();
-    write_bytes_with_len(&mut bytes, &npm_snapshot);
+    part_ranges.push(write_bytes_with_len(&mut bytes, &npm_snapshot));
   }
   // 3. Remote modules
   {
@@ -70,18 +90,47 @@ pub fn serialize_binary_data_section(
     let length_bytes = (length as u64).to_le_bytes();
     bytes[update_index..update_index + length_bytes.len()]
       .copy_from_slice(&length_bytes);
+    part_ranges.push(start_index..bytes.len());
   }
   // 4. VFS
   {
     let (vfs, vfs_files) = vfs.into_dir_and_files();
     let vfs = serde_json::to_string(&vfs)?;
-    write_bytes_with_len(&mut bytes, vfs.as_bytes());
+    part_ranges.push(write_bytes_with_len(&mut bytes, vfs.as_bytes()));
     let vfs_bytes_len = vfs_files.iter().map(|f| f.len() as u64).sum::<u64>();
     bytes.extend_from_slice(&vfs_bytes_len.to_le_bytes());
+    let start_index = bytes.len();
     for file in &vfs_files {
       bytes.extend_from_slice(file);
     }
+    part_ranges.push(start_index..bytes.len());
   }
+  // 5. Code cache
+  {
+    let update_index = bytes.len();
+    bytes.extend_from_slice(&(0_u64).to_le_bytes());
+    let start_index = bytes.len();
+    code_cache.write(&mut bytes)?;
+    let length = bytes.len() - start_index;
+    let length_bytes = (length as u64).to_le_bytes();
+    bytes[update_index..update_index + length_bytes.len()]
+      .copy_from_slice(&length_bytes);
+    part_ranges.push(start_index..bytes.len());
+  }
+  // 6. Integrity, covering all of the above
+  {
+    let parts = part_ranges
+      .iter()
+      .map(|range| &bytes[range.clone()])
+      .collect::<Vec<_>>();
+    let mut integrity = Vec::new();
+    write_integrity(&mut integrity, &parts, signing_key);
+    write_bytes_with_len(&mut bytes, &integrity);
+  }
+
+  // 7. Length of the whole section
+  let section_len = bytes.len() + 8 + MAGIC_BYTES.len();
+  bytes.extend_from_slice(&(section_len as u64).to_le_bytes());
 
   // write the magic bytes at the end so we can use it
   // to make sure we've deserialized correctly
@@ -96,6 +145,8 @@ pub struct DeserializedDataSection {
   pub remote_modules: RemoteModulesStore,
   pub vfs_dir: VirtualDirectory,
   pub vfs_files_data: &'static [u8],
+  pub code_cache: CodeCacheStore,
+  pub integrity: DataSectionIntegrity,
 }
 
 pub fn deserialize_binary_data_section(
@@ -122,14 +173,21 @@ pub fn deserialize_binary_data_section(
   if !found {
     return Ok(None);
   }
+  let section_data = data;
+
+  // the parts covered by the integrity data, indexed by the `*_PART`
+  // constants
+  let mut parts = Vec::with_capacity(6);
 
   // 1. Metadata
   let (input, data) = read_bytes_with_len(input).context("reading metadata")?;
+  parts.push(data);
   let metadata: Metadata =
     serde_json::from_slice(data).context("deserializing metadata")?;
   // 2. Npm snapshot
   let (input, data) =
     read_bytes_with_len(input).context("reading npm snapshot")?;
+  parts.push(data);
   let npm_snapshot = if data.is_empty() {
     None
   } else {
@@ -138,20 +196,40 @@ pub fn deserialize_binary_data_section(
   // 3. Remote modules
   let (input, data) =
     read_bytes_with_len(input).context("reading remote modules data")?;
+  parts.push(data);
   let remote_modules =
     RemoteModulesStore::build(data).context("deserializing remote modules")?;
   // 4. VFS
   let (input, data) = read_bytes_with_len(input).context("vfs")?;
+  parts.push(data);
   let vfs_dir: VirtualDirectory =
     serde_json::from_slice(data).context("deserializing vfs data")?;
   let (input, vfs_files_data) =
     read_bytes_with_len(input).context("reading vfs files data")?;
+  parts.push(vfs_files_data);
+  // 5. Code cache
+  let (input, data) =
+    read_bytes_with_len(input).context("reading code cache data")?;
+  parts.push(data);
+  let code_cache =
+    CodeCacheStore::build(data).context("deserializing code cache")?;
+  // 6. Integrity
+  let (input, integrity_data) =
+    read_bytes_with_len(input).context("reading integrity data")?;
+  let integrity = DataSectionIntegrity::read(parts, integrity_data)
+    .context("deserializing integrity data")?;
+  // 7. Length of the whole section
+  let (input, section_len) =
+    read_u64(input).context("reading section length")?;
 
   // finally ensure we read the magic bytes at the end
-  let (_input, found) = read_magic_bytes(input)?;
+  let (input, found) = read_magic_bytes(input)?;
   if !found {
     bail!("Could not find magic bytes at the end of the data.");
   }
+  if section_len as usize != section_data.len() - input.len() {
+    bail!("The length of the data section does not match.");
+  }
 
   Ok(Some(DeserializedDataSection {
     metadata,
@@ -159,9 +237,138 @@ pub fn deserialize_binary_data_section(
     remote_modules,
     vfs_dir,
     vfs_files_data,
+    code_cache,
+    integrity,
   }))
 }
 
+/// Finds the data section that ends within the last bytes of `data` using
+/// its stored length, so only the pages at the end of `data` and the ones
+/// later read from the section are touched.
+pub fn find_data_section_from_end(data: &[u8]) -> Option<&[u8]> {
+  // leaves room for what the executable format writes after the section
+  const MAX_TRAILER_LEN: usize = 4096;
+
+  let tail_start = data
+    .len()
+    .saturating_sub(MAX_TRAILER_LEN + MAGIC_BYTES.len());
+  let tail = &data[tail_start..];
+  let searcher = memmem::TwoWaySearcher::new(MAGIC_BYTES);
+  let mut offset = 0;
+  let mut end = None;
+  while let Some(index) = searcher.search_in(&tail[offset..]) {
+    end = Some(tail_start + offset + index + MAGIC_BYTES.len());
+    offset += index + 1;
+  }
+  let end = end?;
+  let len_start = end.checked_sub(MAGIC_BYTES.len() + 8)?;
+  let (_, section_len) = read_u64(&data[len_start..]).ok()?;
+  let start = end.checked_sub(usize::try_from(section_len).ok()?)?;
+  let section = &data[start..end];
+  section.starts_with(MAGIC_BYTES).then_some(section)
+}
+
+/// Finds and deserializes the data section in the bytes of a whole
+/// executable. Unlike `libsui::find_section`, this works for executables
+/// other than the current one.
+pub fn find_binary_data_section(
+  data: &'static [u8],
+) -> Result<Option<DeserializedDataSection>, AnyError> {
+  // the magic bytes also appear in the code of the executable, so try every
+  // occurrence until one deserializes
+  let searcher = memmem::TwoWaySearcher::new(MAGIC_BYTES);
+  let mut offset = 0;
+  while let Some(index) = searcher.search_in(&data[offset..]) {
+    let start = offset + index;
+    if let Ok(Some(data_section)) =
+      deserialize_binary_data_section(&data[start..])
+    {
+      return Ok(Some(data_section));
+    }
+    offset = start + MAGIC_BYTES.len();
+  }
+  Ok(None)
+}
+
+/// Indexes of the parts of the data section covered by its integrity data,
+/// as passed to `DataSectionIntegrity::part_check`.
+pub const METADATA_PART: usize = 0;
+pub const NPM_SNAPSHOT_PART: usize = 1;
+pub const REMOTE_MODULES_PART: usize = 2;
+pub const VFS_HEADERS_PART: usize = 3;
+pub const VFS_FILES_PART: usize = 4;
+pub const CODE_CACHE_PART: usize = 5;
+
+/// The names of the parts of the data section, in the order they're written.
+pub const DATA_SECTION_PARTS: [&str; 7] = [
+  "metadata",
+  "npm snapshot",
+  "remote modules",
+  "vfs headers",
+  "vfs files",
+  "code cache",
+  "integrity",
+];
+
+/// Splits a data section into its raw parts without deserializing them,
+/// returning the parts named as in `DATA_SECTION_PARTS` along with the
+/// length of the whole section.
+pub fn split_binary_data_section(
+  data: &[u8],
+) -> Result<(Vec<&[u8]>, usize), AnyError> {
+  let Some(mut input) = data.strip_prefix(MAGIC_BYTES.as_slice()) else {
+    bail!("Could not find magic bytes.");
+  };
+  let mut parts = Vec::with_capacity(DATA_SECTION_PARTS.len());
+  for name in DATA_SECTION_PARTS {
+    let (rest, len) =
+      read_u64(input).with_context(|| format!("reading {}", name))?;
+    let (rest, part) = read_bytes(rest, len as usize)
+      .with_context(|| format!("reading {}", name))?;
+    parts.push(part);
+    input = rest;
+  }
+  let (input, section_len) =
+    read_u64(input).context("reading section length")?;
+  let Some(input) = input.strip_prefix(MAGIC_BYTES.as_slice()) else {
+    bail!("Could not find magic bytes at the end of the data.");
+  };
+  if section_len as usize != data.len() - input.len() {
+    bail!("The length of the data section does not match.");
+  }
+  Ok((parts, section_len as usize))
+}
+
+/// Creates a data section out of the raw `parts`, named as in
+/// `DATA_SECTION_PARTS`, without checking that they deserialize.
+#[cfg(test)]
+pub fn create_raw_data_section(parts: &[&[u8]]) -> Vec<u8> {
+  let mut section = MAGIC_BYTES.to_vec();
+  for part in parts {
+    section.extend_from_slice(&(part.len() as u64).to_le_bytes());
+    section.extend_from_slice(part);
+  }
+  let section_len = section.len() + 8 + MAGIC_BYTES.len();
+  section.extend_from_slice(&(section_len as u64).to_le_bytes());
+  section.extend_from_slice(MAGIC_BYTES);
+  section
+}
+
+/// Finds the position of the data section in the bytes of a whole
+/// executable.
+pub fn find_binary_data_section_range(data: &[u8]) -> Option<Range<usize>> {
+  let searcher = memmem::TwoWaySearcher::new(MAGIC_BYTES);
+  let mut offset = 0;
+  while let Some(index) = searcher.search_in(&data[offset..]) {
+    let start = offset + index;
+    if let Ok((_, len)) = split_binary_data_section(&data[start..]) {
+      return Some(start..start + len);
+    }
+    offset = start + MAGIC_BYTES.len();
+  }
+  None
+}
+
 #[derive(Default)]
 pub struct RemoteModulesStoreBuilder {
   specifiers: Vec<(String, u64)>,
@@ -214,12 +421,195 @@ impl RemoteModulesStoreBuilder {
   }
 }
 
+/// The embedded code cache is only valid for the Deno and V8 versions that
+/// produced it.
+fn code_cache_runtime_version() -> String {
+  format!(
+    "{}/{}",
+    crate::version::DENO_VERSION_INFO.deno,
+    deno_core::v8::VERSION_STRING
+  )
+}
+
+/// Hash of a module's source that the embedded code cache is keyed by.
+pub fn code_cache_source_hash(source: &[u8]) -> u64 {
+  FastInsecureHasher::new_deno_versioned()
+    .write(source)
+    .finish()
+}
+
+#[derive(Default)]
+pub struct CodeCacheStoreBuilder {
+  entries: Vec<(String, u64, Vec<u8>)>,
+}
+
+impl CodeCacheStoreBuilder {
+  /// Adds the V8 code cache for the module with the given specifier key and
+  /// source hash.
+  pub fn add(&mut self, key: String, source_hash: u64, data: Vec<u8>) {
+    log::debug!("Adding code cache for '{}'", key);
+    self.entries.push((key, source_hash, data));
+  }
+
+  pub fn len(&self) -> usize {
+    self.entries.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.entries.is_empty()
+  }
+
+  /// Gets the code cache for the module, unless its source changed.
+  pub fn get(&self, key: &str, source_hash: u64) -> Option<&[u8]> {
+    self
+      .entries
+      .iter()
+      .find(|(entry_key, entry_source_hash, _)| {
+        entry_key == key && *entry_source_hash == source_hash
+      })
+      .map(|(_, _, data)| data.as_slice())
+  }
+
+  /// Serializes the entries the same way they're embedded, which is also
+  /// how an executable records its code cache for `--code-cache-from`.
+  pub fn to_bytes(&self) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    // writing to a vec can't fail
+    self.write(&mut bytes).unwrap();
+    bytes
+  }
+
+  /// Reads entries serialized by `to_bytes`. Entries created by a different
+  /// runtime are dropped, since V8 would reject them.
+  pub fn from_bytes(data: &[u8]) -> Result<Self, AnyError> {
+    let mut builder = Self::default();
+    if data.is_empty() {
+      return Ok(builder);
+    }
+    let (input, version) = read_string_lossy(data)?;
+    if version != code_cache_runtime_version() {
+      log::debug!(
+        "Ignoring code cache created by a different runtime ({})",
+        version
+      );
+      return Ok(builder);
+    }
+    let (mut input, len) = read_u32_as_usize(input)?;
+    for _ in 0..len {
+      let (current_input, key) = read_string_lossy(input)?;
+      let (current_input, source_hash) = read_u64(current_input)?;
+      let (current_input, data_len) = read_u32_as_usize(current_input)?;
+      let (current_input, data) = read_bytes(current_input, data_len)?;
+      input = current_input;
+      builder
+        .entries
+        .push((key.into_owned(), source_hash, data.to_vec()));
+    }
+    Ok(builder)
+  }
+
+  fn write(&self, writer: &mut dyn Write) -> Result<(), AnyError> {
+    if self.entries.is_empty() {
+      return Ok(());
+    }
+    let version = code_cache_runtime_version();
+    writer.write_all(&(version.len() as u32).to_le_bytes())?;
+    writer.write_all(version.as_bytes())?;
+    writer.write_all(&(self.entries.len() as u32).to_le_bytes())?;
+    for (key, source_hash, data) in &self.entries {
+      writer.write_all(&(key.len() as u32).to_le_bytes())?;
+      writer.write_all(key.as_bytes())?;
+      writer.write_all(&source_hash.to_le_bytes())?;
+      writer.write_all(&(data.len() as u32).to_le_bytes())?;
+      writer.write_all(data)?;
+    }
+    Ok(())
+  }
+}
+
+/// V8 code cache embedded in the binary, keyed by specifier key.
+#[derive(Default)]
+pub struct CodeCacheStore {
+  /// The source hash and data of each entry, along with all of its bytes
+  /// for checking its integrity.
+  entries: HashMap<String, (u64, &'static [u8], &'static [u8])>,
+  integrity_check: Option<PartIntegrityCheck>,
+}
+
+impl CodeCacheStore {
+  fn build(data: &'static [u8]) -> Result<Self, AnyError> {
+    if data.is_empty() {
+      return Ok(Self::default());
+    }
+    let (input, version) = read_string_lossy(data)?;
+    if version != code_cache_runtime_version() {
+      log::debug!(
+        "Ignoring code cache created by a different runtime ({})",
+        version
+      );
+      return Ok(Self::default());
+    }
+    let (mut input, len) = read_u32_as_usize(input)?;
+    let mut entries = HashMap::with_capacity(len);
+    for _ in 0..len {
+      let (current_input, key) = read_string_lossy(input)?;
+      let (current_input, source_hash) = read_u64(current_input)?;
+      let (current_input, data_len) = read_u32_as_usize(current_input)?;
+      let (current_input, data) = read_bytes(current_input, data_len)?;
+      let entry = &input[..input.len() - current_input.len()];
+      input = current_input;
+      entries.insert(key.into_owned(), (source_hash, data, entry));
+    }
+    Ok(Self {
+      entries,
+      integrity_check: None,
+    })
+  }
+
+  /// Checks each entry against the integrity data of the executable before
+  /// it's used.
+  pub fn set_integrity_check(&mut self, check: PartIntegrityCheck) {
+    self.integrity_check = Some(check);
+  }
+
+  pub fn len(&self) -> usize {
+    self.entries.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.entries.is_empty()
+  }
+
+  /// Gets the code cache for the module, unless its source changed.
+  pub fn get(&self, key: &str, source_hash: u64) -> Option<&'static [u8]> {
+    let (cached_source_hash, data, entry) = self.entries.get(key)?;
+    if *cached_source_hash != source_hash {
+      return None;
+    }
+    if let Some(check) = &self.integrity_check {
+      // a modified cache entry is not used, the module is compiled instead
+      if let Err(err) = check.check_slice(entry) {
+        log::debug!("Ignoring code cache for '{}': {:#}", key, err);
+        return None;
+      }
+    }
+    Some(data)
+  }
+}
+
 pub enum DenoCompileModuleSource {
   String(&'static str),
   Bytes(Cow<'static, [u8]>),
 }
 
 impl DenoCompileModuleSource {
+  pub fn as_bytes(&self) -> &[u8] {
+    match self {
+      Self::String(s) => s.as_bytes(),
+      Self::Bytes(b) => b,
+    }
+  }
+
   pub fn into_for_v8(self) -> ModuleSourceCode {
     fn into_bytes(data: Cow<'static, [u8]>) -> ModuleSourceCode {
       ModuleSourceCode::Bytes(match data {
@@ -291,7 +681,9 @@ enum RemoteModulesStoreSpecifierValue {
 
 pub struct RemoteModulesStore {
   specifiers: HashMap<Url, RemoteModulesStoreSpecifierValue>,
+  headers: &'static [u8],
   files_data: &'static [u8],
+  integrity_check: Option<PartIntegrityCheck>,
 }
 
 impl RemoteModulesStore {
@@ -339,13 +731,39 @@ impl RemoteModulesStore {
     }
 
     let (files_data, specifiers) = read_headers(data)?;
+    let headers = &data[..data.len() - files_data.len()];
 
     Ok(Self {
       specifiers,
+      headers,
       files_data,
+      integrity_check: None,
     })
   }
 
+  /// Checks the specifiers against the integrity data of the executable
+  /// now and the data of each module once it's read.
+  pub fn set_integrity_check(
+    &mut self,
+    check: PartIntegrityCheck,
+  ) -> Result<(), AnyError> {
+    check.check_slice(self.headers)?;
+    self.integrity_check = Some(check);
+    Ok(())
+  }
+
+  /// Gets the stored specifiers along with the specifier each one
+  /// redirects to, if it's a redirect.
+  pub fn specifiers(&self) -> impl Iterator<Item = (&Url, Option<&Url>)> {
+    self
+      .specifiers
+      .iter()
+      .map(|(specifier, value)| match value {
+        RemoteModulesStoreSpecifierValue::Data(_) => (specifier, None),
+        RemoteModulesStoreSpecifierValue::Redirect(to) => (specifier, Some(to)),
+      })
+  }
+
   pub fn resolve_specifier<'a>(
     &'a self,
     specifier: &'a Url,
@@ -387,11 +805,19 @@ impl RemoteModulesStore {
           count += 1;
         }
         Some(RemoteModulesStoreSpecifierValue::Data(offset)) => {
-          let input = &self.files_data[*offset..];
-          let (input, media_type_byte) = read_bytes(input, 1)?;
-          let media_type = deserialize_media_type(media_type_byte[0])?;
+          let module_input = self
+            .files_data
+            .get(*offset..)
+            .context("Invalid remote module offset")?;
+          let (input, media_type_byte) = read_bytes(module_input, 1)?;
           let (input, len) = read_u64(input)?;
-          let (_input, data) = read_bytes(input, len as usize)?;
+          let (input, data) = read_bytes(input, len as usize)?;
+          if let Some(check) = &self.integrity_check {
+            check
+              .check_slice(&module_input[..module_input.len() - input.len()])
+              .with_context(|| format!("Failed reading '{}'", specifier))?;
+          }
+          let media_type = deserialize_media_type(media_type_byte[0])?;
           return Ok(Some(DenoCompileModuleData {
             specifier,
             media_type,
@@ -659,3 +1085,125 @@ fn read_u64(input: &[u8]) -> Result<(&[u8], u64), AnyError> {
   let len = u64::from_le_bytes(len_bytes.try_into()?);
   Ok((input, len))
 }
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn code_cache_store_round_trip() {
+    let mut builder = CodeCacheStoreBuilder::default();
+    let hash = code_cache_source_hash(b"export const a = 1;");
+    builder.add("main.js".to_string(), hash, vec![1, 2, 3]);
+    builder.add("https://deno.land/x/mod.js".to_string(), 5, vec![4]);
+    let mut bytes = Vec::new();
+    builder.write(&mut bytes).unwrap();
+
+    let store =
+      CodeCacheStore::build(Box::leak(bytes.into_boxed_slice())).unwrap();
+    assert_eq!(store.get("main.js", hash), Some([1, 2, 3].as_slice()));
+    assert_eq!(store.get("main.js", hash + 1), None);
+    assert_eq!(
+      store.get("https://deno.land/x/mod.js", 5),
+      Some([4].as_slice())
+    );
+    assert_eq!(store.get("other.js", hash), None);
+
+    // nothing is written without entries
+    let mut bytes = Vec::new();
+    CodeCacheStoreBuilder::default().write(&mut bytes).unwrap();
+    assert!(bytes.is_empty());
+    assert!(CodeCacheStore::build(&[]).unwrap().entries.is_empty());
+  }
+
+  #[test]
+  fn code_cache_store_builder_round_trip() {
+    let mut builder = CodeCacheStoreBuilder::default();
+    builder.add("main.js".to_string(), 1, vec![1, 2, 3]);
+    builder.add("other.js".to_string(), 2, vec![4]);
+    let builder =
+      CodeCacheStoreBuilder::from_bytes(&builder.to_bytes()).unwrap();
+    assert_eq!(builder.len(), 2);
+    assert_eq!(builder.get("main.js", 1), Some([1, 2, 3].as_slice()));
+    assert_eq!(builder.get("main.js", 2), None);
+    assert_eq!(builder.get("other.js", 2), Some([4].as_slice()));
+    assert!(CodeCacheStoreBuilder::from_bytes(&[]).unwrap().is_empty());
+
+    // entries of a different runtime are dropped
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(&5u32.to_le_bytes());
+    bytes.extend_from_slice(b"0.0.0");
+    bytes.extend_from_slice(&0u32.to_le_bytes());
+    assert!(CodeCacheStoreBuilder::from_bytes(&bytes)
+      .unwrap()
+      .is_empty());
+  }
+
+  #[test]
+  fn code_cache_store_checks_integrity() {
+    let mut builder = CodeCacheStoreBuilder::default();
+    builder.add("a.js".to_string(), 1, vec![1, 2, 3]);
+    builder.add("b.js".to_string(), 2, vec![4, 5, 6]);
+    let mut bytes = Vec::new();
+    builder.write(&mut bytes).unwrap();
+    let mut integrity = Vec::new();
+    write_integrity(&mut integrity, &[&bytes], None);
+    // modify the data of the last entry after the integrity was written
+    *bytes.last_mut().unwrap() = 7;
+
+    let bytes: &'static [u8] = Box::leak(bytes.into_boxed_slice());
+    let integrity = DataSectionIntegrity::read(
+      vec![bytes],
+      Box::leak(integrity.into_boxed_slice()),
+    )
+    .unwrap();
+    let mut store = CodeCacheStore::build(bytes).unwrap();
+    store.set_integrity_check(integrity.part_check(0));
+    assert_eq!(store.get("a.js", 1), None);
+    assert_eq!(store.get("b.js", 2), None);
+  }
+
+  #[test]
+  fn finds_data_section_from_end() {
+    let mut section = MAGIC_BYTES.to_vec();
+    section.extend_from_slice(b"data");
+    let section_len = section.len() + 8 + MAGIC_BYTES.len();
+    section.extend_from_slice(&(section_len as u64).to_le_bytes());
+    section.extend_from_slice(MAGIC_BYTES);
+
+    // the magic bytes also appear in the code of the executable
+    let mut exe = b"code d3n0l4nd code".to_vec();
+    exe.extend_from_slice(&section);
+    assert_eq!(find_data_section_from_end(&exe), Some(section.as_slice()));
+    exe.extend_from_slice(b"trailer");
+    assert_eq!(find_data_section_from_end(&exe), Some(section.as_slice()));
+
+    assert_eq!(find_data_section_from_end(b"code d3n0l4nd"), None);
+    assert_eq!(find_data_section_from_end(b""), None);
+  }
+
+  #[test]
+  fn splits_data_section() {
+    let parts = (0..DATA_SECTION_PARTS.len())
+      .map(|i| vec![i as u8; i])
+      .collect::<Vec<_>>();
+    let section = create_raw_data_section(
+      &parts.iter().map(|part| part.as_slice()).collect::<Vec<_>>(),
+    );
+
+    let (parts, len) = split_binary_data_section(&section).unwrap();
+    assert_eq!(len, section.len());
+    assert_eq!(parts.len(), DATA_SECTION_PARTS.len());
+    for (i, part) in parts.iter().enumerate() {
+      assert_eq!(*part, vec![i as u8; i].as_slice());
+    }
+
+    let mut exe = b"code d3n0l4nd code".to_vec();
+    exe.extend_from_slice(&section);
+    exe.extend_from_slice(b"trailer");
+    assert_eq!(find_binary_data_section_range(&exe), Some(18..18 + len));
+
+    assert!(split_binary_data_section(&section[..len - 1]).is_err());
+    assert_eq!(find_binary_data_section_range(b"code d3n0l4nd"), None);
+  }
+}
@@ -0,0 +1,770 @@
+// Copyright 2018-2024 the Deno authors. All rights reserved. MIT license.
+
+use std::collections::BTreeSet;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use deno_core::anyhow::Context;
+use deno_core::error::AnyError;
+use deno_core::parking_lot::Mutex;
+use deno_core::serde_json;
+use deno_runtime::deno_fs::FileSystem;
+use deno_runtime::deno_fs::FsDirEntry;
+use deno_runtime::deno_fs::OpenOptions;
+use deno_runtime::deno_fs::RealFs;
+use deno_runtime::deno_io::fs::FsError;
+use deno_runtime::deno_io::fs::FsResult;
+use deno_runtime::deno_io::fs::FsStat;
+
+use crate::args::VfsOverlayMode;
+use crate::cache::DenoDir;
+use crate::cache::FastInsecureHasher;
+use crate::cache::CACHE_PERM;
+use crate::util::fs::atomic_write_file_with_retries;
+use crate::util::fs::canonicalize_path;
+
+use super::virtual_fs::FileBackedVfs;
+
+/// A copy-on-write layer over the embedded file system.
+///
+/// Modified entries are stored in a real directory that mirrors the vfs
+/// root (the "upper" directory) and shadow the embedded ones. Removed
+/// embedded entries are recorded as "whiteouts" so they stay hidden.
+///
+/// The overlay directory isn't covered by the integrity check of the
+/// executable and, when it's persistent, can be written by any process of
+/// the same user. So embedded modules and npm packages can't be modified
+/// through it: writes to them are refused and any overlay entries or
+/// whiteouts for them are ignored.
+#[derive(Debug)]
+pub struct VfsOverlay {
+  vfs: Arc<FileBackedVfs>,
+  upper_root: PathBuf,
+  /// Removed embedded entries, relative to the vfs root.
+  whiteouts: Mutex<BTreeSet<PathBuf>>,
+  /// File the whiteouts are persisted to across runs, if any.
+  whiteouts_path: Option<PathBuf>,
+  _temp_overlay_dir: Option<TempOverlayDir>,
+}
+
+impl VfsOverlay {
+  pub fn new(
+    vfs: Arc<FileBackedVfs>,
+    mode: VfsOverlayMode,
+  ) -> Result<Self, AnyError> {
+    match mode {
+      VfsOverlayMode::Persistent => {
+        let dir = persistent_overlay_dir()?;
+        let upper_root = dir.join("root");
+        std::fs::create_dir_all(&upper_root).with_context(|| {
+          format!(
+            "Failed creating overlay directory '{}'",
+            upper_root.display()
+          )
+        })?;
+        let whiteouts_path = dir.join("whiteouts.json");
+        let whiteouts = match std::fs::read_to_string(&whiteouts_path) {
+          Ok(text) => serde_json::from_str(&text).with_context(|| {
+            format!("Failed reading '{}'", whiteouts_path.display())
+          })?,
+          Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            Default::default()
+          }
+          Err(err) => return Err(err.into()),
+        };
+        Ok(Self {
+          vfs,
+          upper_root: canonicalize_path(&upper_root)?,
+          whiteouts: Mutex::new(whiteouts),
+          whiteouts_path: Some(whiteouts_path),
+          _temp_overlay_dir: None,
+        })
+      }
+      VfsOverlayMode::TempDir => {
+        let temp_overlay_dir = TempOverlayDir::create()
+          .context("Failed creating overlay directory")?;
+        Ok(Self {
+          vfs,
+          upper_root: canonicalize_path(&temp_overlay_dir.upper_root())?,
+          whiteouts: Default::default(),
+          whiteouts_path: None,
+          _temp_overlay_dir: Some(temp_overlay_dir),
+        })
+      }
+    }
+  }
+
+  fn upper_path(&self, path: &Path) -> Option<PathBuf> {
+    let relative = path.strip_prefix(self.vfs.root()).ok()?;
+    Some(self.upper_root.join(relative))
+  }
+
+  /// Gets the path of the overlay entry that shadows `path`, if any.
+  pub fn upper_entry(&self, path: &Path) -> Option<PathBuf> {
+    let upper = self.upper_path(path)?;
+    upper.symlink_metadata().ok().map(|_| upper)
+  }
+
+  /// Gets if the embedded entry at `path` (or one of its ancestors)
+  /// was removed.
+  pub fn is_removed(&self, path: &Path) -> bool {
+    let Ok(relative) = path.strip_prefix(self.vfs.root()) else {
+      return false;
+    };
+    let whiteouts = self.whiteouts.lock();
+    relative
+      .ancestors()
+      .any(|ancestor| whiteouts.contains(ancestor))
+  }
+
+  /// Resolves the entry that reads of `path` should go to. `None` means
+  /// the embedded data.
+  pub fn resolve(&self, path: &Path) -> FsResult<Option<PathBuf>> {
+    if self.is_protected(path) {
+      Ok(None)
+    } else if let Some(upper) = self.upper_entry(path) {
+      Ok(Some(upper))
+    } else if self.is_removed(path) {
+      Err(not_found())
+    } else {
+      Ok(None)
+    }
+  }
+
+  /// Gets if `path` is an embedded module or is in an embedded npm
+  /// package, which the overlay must not shadow.
+  fn is_protected(&self, path: &Path) -> bool {
+    let Ok(relative) = path.strip_prefix(self.vfs.root()) else {
+      return false;
+    };
+    let in_node_modules = relative
+      .ancestors()
+      .filter(|ancestor| ancestor.ends_with("node_modules"))
+      .any(|ancestor| self.vfs.lstat(&self.vfs.root().join(ancestor)).is_ok());
+    in_node_modules
+      || self.vfs.lstat(path).is_ok_and(|stat| {
+        stat.is_file
+          && path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| MODULE_EXTENSIONS.contains(&ext))
+      })
+  }
+
+  fn ensure_not_protected(&self, path: &Path) -> FsResult<()> {
+    if self.is_protected(path) {
+      Err(
+        std::io::Error::new(
+          std::io::ErrorKind::PermissionDenied,
+          "embedded modules and npm packages can't be modified",
+        )
+        .into(),
+      )
+    } else {
+      Ok(())
+    }
+  }
+
+  /// Maps a real path in the overlay directory back to the vfs.
+  pub fn to_vfs_path(&self, path: PathBuf) -> PathBuf {
+    match path.strip_prefix(&self.upper_root) {
+      Ok(relative) => self.vfs.root().join(relative),
+      Err(_) => path,
+    }
+  }
+
+  fn lower_stat(&self, path: &Path) -> Option<FsStat> {
+    if self.is_removed(path) && !self.is_protected(path) {
+      None
+    } else {
+      self.vfs.lstat(path).ok()
+    }
+  }
+
+  fn exists(&self, path: &Path) -> bool {
+    (self.upper_entry(path).is_some() && !self.is_protected(path))
+      || self.lower_stat(path).is_some()
+  }
+
+  fn is_dir(&self, path: &Path) -> bool {
+    match self.resolve(path).ok().flatten() {
+      Some(upper) => upper.is_dir(),
+      None => self.lower_stat(path).is_some_and(|stat| stat.is_directory),
+    }
+  }
+
+  /// Creates the overlay directories leading to `path`.
+  fn ensure_upper_parent(&self, path: &Path) -> FsResult<()> {
+    let Some(parent) = path.parent() else {
+      return Ok(());
+    };
+    let Some(upper_parent) = self.upper_path(parent) else {
+      return Ok(());
+    };
+    if upper_parent.is_dir() {
+      return Ok(());
+    }
+    let is_lower_dir = !self.is_removed(parent)
+      && self.vfs.stat(parent).is_ok_and(|stat| stat.is_directory);
+    if !is_lower_dir {
+      return Err(not_found());
+    }
+    self.ensure_upper_parent(parent)?;
+    std::fs::create_dir(&upper_parent)?;
+    Ok(())
+  }
+
+  /// Prepares for an entry to be created at `path`, replacing any
+  /// existing one, and returns the real path to create it at.
+  pub fn prepare_create(&self, path: &Path) -> FsResult<PathBuf> {
+    self.ensure_not_protected(path)?;
+    self.ensure_upper_parent(path)?;
+    let relative = path.strip_prefix(self.vfs.root()).unwrap();
+    let mut whiteouts = self.whiteouts.lock();
+    if whiteouts.remove(relative) {
+      // keep the removed directory's embedded entries hidden
+      if let Ok(entries) = self.vfs.read_dir(path) {
+        for entry in entries {
+          whiteouts.insert(relative.join(entry.name));
+        }
+      }
+      self.persist_whiteouts(&whiteouts)?;
+    }
+    Ok(self.upper_path(path).unwrap())
+  }
+
+  /// Copies the embedded entry at `path` to the overlay, if it isn't
+  /// there already, and returns the real path to modify it at.
+  pub fn copy_up(&self, path: &Path, recursive: bool) -> FsResult<PathBuf> {
+    self.ensure_not_protected(path)?;
+    let upper = match self.upper_entry(path) {
+      Some(upper) => upper,
+      None => {
+        let stat = self.lower_stat(path).ok_or_else(not_found)?;
+        if stat.is_symlink {
+          return Err(FsError::NotSupported);
+        }
+        self.ensure_upper_parent(path)?;
+        let upper = self.upper_path(path).unwrap();
+        if stat.is_directory {
+          std::fs::create_dir(&upper)?;
+        } else {
+          let file = self.vfs.file_entry(path)?;
+          std::fs::write(&upper, self.vfs.read_file_all(file)?)?;
+        }
+        upper
+      }
+    };
+    if recursive
+      && upper.symlink_metadata()?.is_dir()
+      && self.lower_stat(path).is_some_and(|stat| stat.is_directory)
+    {
+      for entry in self.vfs.read_dir(path)? {
+        let child = path.join(entry.name);
+        if self.lower_stat(&child).is_some() {
+          self.copy_up(&child, true)?;
+        }
+      }
+    }
+    Ok(upper)
+  }
+
+  /// Resolves the real path to open `path` at, or `None` to read it
+  /// from the embedded data.
+  pub fn open_path(
+    &self,
+    path: &Path,
+    options: &OpenOptions,
+  ) -> FsResult<Option<PathBuf>> {
+    if !is_write(options) {
+      return self.resolve(path);
+    }
+    let exists = self.exists(path);
+    if exists && options.create_new {
+      return Err(
+        std::io::Error::new(
+          std::io::ErrorKind::AlreadyExists,
+          "path already exists",
+        )
+        .into(),
+      );
+    }
+    if !exists && !options.create && !options.create_new {
+      return Err(not_found());
+    }
+    if exists && !options.truncate {
+      self.copy_up(path, false).map(Some)
+    } else {
+      self.prepare_create(path).map(Some)
+    }
+  }
+
+  pub fn mkdir(
+    &self,
+    path: &Path,
+    recursive: bool,
+    mode: Option<u32>,
+  ) -> FsResult<()> {
+    if self.exists(path) {
+      if recursive && self.is_dir(path) {
+        return Ok(());
+      }
+      return Err(
+        std::io::Error::new(
+          std::io::ErrorKind::AlreadyExists,
+          "path already exists",
+        )
+        .into(),
+      );
+    }
+    if recursive {
+      if let Some(parent) = path.parent() {
+        if self.vfs.is_path_within(parent) && !self.exists(parent) {
+          self.mkdir(parent, true, mode)?;
+        }
+      }
+    }
+    let upper = self.prepare_create(path)?;
+    RealFs.mkdir_sync(&upper, false, mode)
+  }
+
+  pub fn remove(&self, path: &Path, recursive: bool) -> FsResult<()> {
+    self.ensure_not_protected(path)?;
+    let upper = self.upper_entry(path);
+    let lower = self.lower_stat(path);
+    if upper.is_none() && lower.is_none() {
+      return Err(not_found());
+    }
+    if !recursive
+      && lower.as_ref().is_some_and(|stat| stat.is_directory)
+      && !self.read_dir(path)?.is_empty()
+    {
+      return Err(
+        std::io::Error::new(std::io::ErrorKind::Other, "directory not empty")
+          .into(),
+      );
+    }
+    if let Some(upper) = upper {
+      RealFs.remove_sync(&upper, recursive)?;
+    }
+    if lower.is_some() {
+      self.mark_removed(path)?;
+    }
+    Ok(())
+  }
+
+  /// Hides the embedded entry at `path`, if there is one.
+  pub fn mark_removed(&self, path: &Path) -> FsResult<()> {
+    self.ensure_not_protected(path)?;
+    if self.lower_stat(path).is_none() {
+      return Ok(());
+    }
+    let relative = path.strip_prefix(self.vfs.root()).unwrap();
+    let mut whiteouts = self.whiteouts.lock();
+    whiteouts.retain(|whiteout| !whiteout.starts_with(relative));
+    whiteouts.insert(relative.to_path_buf());
+    self.persist_whiteouts(&whiteouts)
+  }
+
+  /// Lists the directory at `path`, merging the overlay and embedded
+  /// entries.
+  pub fn read_dir(&self, path: &Path) -> FsResult<Vec<FsDirEntry>> {
+    if self.is_protected(path) {
+      return Ok(self.vfs.read_dir(path)?);
+    }
+    let upper = self.upper_entry(path);
+    let mut entries = match &upper {
+      Some(upper) => RealFs
+        .read_dir_sync(upper)?
+        .into_iter()
+        .filter(|entry| !self.is_protected(&path.join(&entry.name)))
+        .collect(),
+      None => Vec::new(),
+    };
+    if self.is_removed(path) {
+      return match upper {
+        Some(_) => Ok(entries),
+        None => Err(not_found()),
+      };
+    }
+    match self.vfs.read_dir(path) {
+      Ok(lower_entries) => {
+        for entry in lower_entries {
+          let is_shadowed =
+            entries.iter().any(|upper| upper.name == entry.name);
+          if !is_shadowed && !self.is_removed(&path.join(&entry.name)) {
+            entries.push(entry);
+          }
+        }
+        Ok(entries)
+      }
+      Err(_) if upper.is_some() => Ok(entries),
+      Err(err) => Err(err.into()),
+    }
+  }
+
+  fn persist_whiteouts(&self, whiteouts: &BTreeSet<PathBuf>) -> FsResult<()> {
+    if let Some(whiteouts_path) = &self.whiteouts_path {
+      let text = serde_json::to_string(whiteouts).unwrap();
+      atomic_write_file_with_retries(whiteouts_path, text, CACHE_PERM)?;
+    }
+    Ok(())
+  }
+}
+
+/// Gets the per-user directory the overlay of the current executable is
+/// kept in. It's keyed on the executable path so that different programs
+/// (or copies of one) don't share their changes.
+fn persistent_overlay_dir() -> Result<PathBuf, AnyError> {
+  let current_exe = std::env::current_exe()
+    .and_then(|path| canonicalize_path(&path))
+    .context("Failed resolving the current executable")?;
+  let name = current_exe
+    .file_stem()
+    .map(|stem| stem.to_string_lossy().to_string())
+    .unwrap_or_else(|| "deno".to_string());
+  let hash = FastInsecureHasher::new_without_deno_version()
+    .write_str(&current_exe.to_string_lossy())
+    .finish();
+  let deno_dir = DenoDir::new(None)?;
+  Ok(
+    deno_dir
+      .root
+      .join("compile_overlay")
+      .join(format!("{}-{:x}", name, hash)),
+  )
+}
+
+/// Extensions of the embedded files that are loaded as code.
+const MODULE_EXTENSIONS: &[&str] = &[
+  "js", "mjs", "cjs", "jsx", "ts", "mts", "cts", "tsx", "json", "wasm", "node",
+];
+
+const TEMP_OVERLAY_PREFIX: &str = "deno-compile-overlay-";
+
+/// Gets if opening a file with `options` may modify it.
+pub fn is_write(options: &OpenOptions) -> bool {
+  options.write
+    || options.append
+    || options.create
+    || options.create_new
+    || options.truncate
+}
+
+/// The temporary directory of an temporary overlay.
+///
+/// `Deno.exit()` ends the process without running destructors, so the
+/// directory is also removed by an exit handler where the platform runs
+/// them. Directories that are still left behind (ex. after a crash or a
+/// signal) are removed by the next temporary overlay that's created, which
+/// can tell they're unused because the lock file in them isn't held.
+#[derive(Debug)]
+struct TempOverlayDir {
+  temp_dir: Option<tempfile::TempDir>,
+  lock_file: Option<std::fs::File>,
+}
+
+impl TempOverlayDir {
+  fn create() -> std::io::Result<Self> {
+    use fs3::FileExt;
+
+    let parent_dir = std::env::temp_dir();
+    remove_unused_temp_overlay_dirs(&parent_dir);
+    let temp_dir = tempfile::Builder::new()
+      .prefix(TEMP_OVERLAY_PREFIX)
+      .tempdir_in(&parent_dir)?;
+    let lock_file = std::fs::File::create(temp_dir.path().join("lock"))?;
+    lock_file.lock_exclusive()?;
+    // only create the root once locked so that a directory being set up
+    // isn't mistaken for an unused one
+    std::fs::create_dir(temp_dir.path().join("root"))?;
+    remove_on_exit(temp_dir.path().to_path_buf());
+    Ok(Self {
+      temp_dir: Some(temp_dir),
+      lock_file: Some(lock_file),
+    })
+  }
+
+  fn upper_root(&self) -> PathBuf {
+    self.temp_dir.as_ref().unwrap().path().join("root")
+  }
+}
+
+impl Drop for TempOverlayDir {
+  fn drop(&mut self) {
+    // the lock file needs to be closed before it can be removed on windows
+    drop(self.lock_file.take());
+    if let Some(temp_dir) = self.temp_dir.take() {
+      let path = temp_dir.path().to_path_buf();
+      let _ = temp_dir.close();
+      EXIT_REMOVALS.lock().retain(|removal| *removal != path);
+    }
+  }
+}
+
+fn remove_unused_temp_overlay_dirs(parent_dir: &Path) {
+  use fs3::FileExt;
+
+  let Ok(entries) = std::fs::read_dir(parent_dir) else {
+    return;
+  };
+  for entry in entries.flatten() {
+    let is_overlay_dir = entry
+      .file_name()
+      .to_str()
+      .is_some_and(|name| name.starts_with(TEMP_OVERLAY_PREFIX));
+    if !is_overlay_dir {
+      continue;
+    }
+    let dir = entry.path();
+    let Ok(lock_file) = std::fs::File::open(dir.join("lock")) else {
+      continue;
+    };
+    let is_unused =
+      lock_file.try_lock_exclusive().is_ok() && dir.join("root").exists();
+    drop(lock_file);
+    if is_unused {
+      log::debug!("Removing unused overlay directory '{}'", dir.display());
+      let _ = std::fs::remove_dir_all(&dir);
+    }
+  }
+}
+
+static EXIT_REMOVALS: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+#[cfg(unix)]
+fn remove_on_exit(path: PathBuf) {
+  static REGISTER: std::sync::Once = std::sync::Once::new();
+
+  extern "C" fn remove_all() {
+    for path in std::mem::take(&mut *EXIT_REMOVALS.lock()) {
+      let _ = std::fs::remove_dir_all(path);
+    }
+  }
+
+  EXIT_REMOVALS.lock().push(path);
+  REGISTER.call_once(|| {
+    // SAFETY: `remove_all` is a valid function for the life of the process
+    unsafe {
+      libc::atexit(remove_all);
+    }
+  });
+}
+
+#[cfg(not(unix))]
+fn remove_on_exit(_path: PathBuf) {
+  // `std::process::exit` doesn't run exit handlers here, so leftover
+  // directories are only removed by `remove_unused_temp_overlay_dirs`
+}
+
+fn not_found() -> FsError {
+  std::io::Error::new(std::io::ErrorKind::NotFound, "path not found").into()
+}
+
+#[cfg(test)]
+mod test {
+  use std::borrow::Cow;
+
+  use test_util::TempDir;
+
+  use super::super::virtual_fs::VfsBuilder;
+  use super::super::virtual_fs::VfsRoot;
+  use super::*;
+
+  fn create_overlay(temp_dir: &TempDir) -> VfsOverlay {
+    create_overlay_with_files(temp_dir, &["a.txt", "dir/b.txt"])
+  }
+
+  fn create_overlay_with_files(
+    temp_dir: &TempDir,
+    files: &[&str],
+  ) -> VfsOverlay {
+    let src_path = temp_dir.path().canonicalize().join("src");
+    for file in files {
+      let path = src_path.join(file);
+      path.parent().create_dir_all();
+      let name = path.as_path().file_stem().unwrap().to_string_lossy();
+      path.write(name.to_string());
+    }
+    let src_path = src_path.to_path_buf();
+    let mut builder = VfsBuilder::new(src_path.clone()).unwrap();
+    builder.add_dir_recursive(&src_path).unwrap();
+    let (root_dir, files) = builder.into_dir_and_files();
+    let vfs = Arc::new(FileBackedVfs::new(
+      Cow::Owned(files.concat()),
+      VfsRoot {
+        dir: root_dir,
+        root_path: temp_dir.path().join("dest").to_path_buf(),
+        start_file_offset: 0,
+      },
+    ));
+    VfsOverlay::new(vfs, VfsOverlayMode::TempDir).unwrap()
+  }
+
+  fn names(entries: Vec<FsDirEntry>) -> Vec<String> {
+    let mut names = entries
+      .into_iter()
+      .map(|entry| entry.name)
+      .collect::<Vec<_>>();
+    names.sort();
+    names
+  }
+
+  #[test]
+  fn copy_on_write() {
+    let temp_dir = TempDir::new();
+    let overlay = create_overlay(&temp_dir);
+    let root = overlay.vfs.root().to_path_buf();
+
+    // reads go to the embedded data until an entry is modified
+    assert_eq!(overlay.resolve(&root.join("a.txt")).unwrap(), None);
+    let upper = overlay.copy_up(&root.join("a.txt"), false).unwrap();
+    assert_eq!(std::fs::read_to_string(&upper).unwrap(), "a");
+    std::fs::write(&upper, "changed").unwrap();
+    assert_eq!(
+      overlay.resolve(&root.join("a.txt")).unwrap(),
+      Some(upper.clone())
+    );
+    assert_eq!(overlay.to_vfs_path(upper), root.join("a.txt"));
+
+    // new entries are merged with the embedded ones
+    overlay.mkdir(&root.join("new/nested"), true, None).unwrap();
+    let upper = overlay.prepare_create(&root.join("dir/c.txt")).unwrap();
+    std::fs::write(upper, "c").unwrap();
+    assert_eq!(
+      names(overlay.read_dir(&root).unwrap()),
+      vec!["a.txt", "dir", "new"]
+    );
+    assert_eq!(
+      names(overlay.read_dir(&root.join("dir")).unwrap()),
+      vec!["b.txt", "c.txt"]
+    );
+
+    // removed embedded entries stay hidden, even when recreated
+    assert!(overlay.remove(&root.join("dir"), false).is_err());
+    overlay.remove(&root.join("dir"), true).unwrap();
+    assert!(overlay.is_removed(&root.join("dir/b.txt")));
+    assert!(overlay.resolve(&root.join("dir/b.txt")).is_err());
+    assert!(overlay.read_dir(&root.join("dir")).is_err());
+    overlay.mkdir(&root.join("dir"), false, None).unwrap();
+    assert!(overlay.read_dir(&root.join("dir")).unwrap().is_empty());
+    assert_eq!(
+      names(overlay.read_dir(&root).unwrap()),
+      vec!["a.txt", "dir", "new"]
+    );
+  }
+
+  #[test]
+  fn removes_unused_temp_overlay_dirs() {
+    use fs3::FileExt;
+
+    let temp_dir = TempDir::new();
+    let unused_dir = temp_dir.path().join("deno-compile-overlay-unused");
+    unused_dir.join("root").create_dir_all();
+    unused_dir.join("lock").write("");
+    let used_dir = temp_dir.path().join("deno-compile-overlay-used");
+    used_dir.join("root").create_dir_all();
+    used_dir.join("lock").write("");
+    let lock_file = std::fs::File::open(used_dir.join("lock")).unwrap();
+    lock_file.lock_exclusive().unwrap();
+    let other_dir = temp_dir.path().join("other");
+    other_dir.join("root").create_dir_all();
+    other_dir.join("lock").write("");
+
+    remove_unused_temp_overlay_dirs(temp_dir.path().as_path());
+    assert!(!unused_dir.exists());
+    assert!(used_dir.exists());
+    assert!(other_dir.exists());
+  }
+
+  #[test]
+  fn temp_overlay_dir_removed_on_drop() {
+    let dir = TempOverlayDir::create().unwrap();
+    let upper_root = dir.upper_root();
+    assert!(upper_root.is_dir());
+    drop(dir);
+    assert!(!upper_root.parent().unwrap().exists());
+    assert!(!EXIT_REMOVALS
+      .lock()
+      .iter()
+      .any(|path| upper_root.starts_with(path)));
+  }
+
+  #[test]
+  fn open_path() {
+    let temp_dir = TempDir::new();
+    let overlay = create_overlay(&temp_dir);
+    let root = overlay.vfs.root().to_path_buf();
+    let read = OpenOptions::read();
+    let write = OpenOptions::write(false, false, false, None);
+    let create_new = OpenOptions::write(true, false, true, None);
+
+    assert_eq!(overlay.open_path(&root.join("a.txt"), &read).unwrap(), None);
+    assert!(overlay.open_path(&root.join("a.txt"), &create_new).is_err());
+    assert!(overlay.open_path(&root.join("missing"), &write).is_err());
+    let upper = overlay
+      .open_path(&root.join("a.txt"), &write)
+      .unwrap()
+      .unwrap();
+    assert_eq!(std::fs::read_to_string(upper).unwrap(), "a");
+    let upper = overlay
+      .open_path(&root.join("dir/new.txt"), &create_new)
+      .unwrap()
+      .unwrap();
+    assert!(upper.starts_with(&overlay.upper_root));
+  }
+
+  #[test]
+  fn embedded_modules_cant_be_shadowed() {
+    let temp_dir = TempDir::new();
+    let overlay = create_overlay_with_files(
+      &temp_dir,
+      &["main.ts", "data.txt", "node_modules/pkg/index.js"],
+    );
+    let root = overlay.vfs.root().to_path_buf();
+    let upper_root = overlay.upper_root.clone();
+    let write = OpenOptions::write(false, false, false, None);
+    let create = OpenOptions::write(true, false, false, None);
+
+    // writes to embedded modules and npm packages are refused
+    assert!(overlay.open_path(&root.join("main.ts"), &write).is_err());
+    assert!(overlay.remove(&root.join("main.ts"), false).is_err());
+    assert!(overlay
+      .open_path(&root.join("node_modules/pkg/other.js"), &create)
+      .is_err());
+    assert!(overlay
+      .mkdir(&root.join("node_modules/new"), true, None)
+      .is_err());
+
+    // entries placed in the overlay directory by another process are
+    // ignored for them
+    std::fs::write(upper_root.join("main.ts"), "tampered").unwrap();
+    std::fs::create_dir_all(upper_root.join("node_modules/pkg")).unwrap();
+    std::fs::write(upper_root.join("node_modules/pkg/index.js"), "tampered")
+      .unwrap();
+    std::fs::write(upper_root.join("node_modules/pkg/extra.js"), "").unwrap();
+    assert_eq!(overlay.resolve(&root.join("main.ts")).unwrap(), None);
+    assert_eq!(
+      overlay
+        .resolve(&root.join("node_modules/pkg/index.js"))
+        .unwrap(),
+      None
+    );
+    assert_eq!(
+      names(overlay.read_dir(&root.join("node_modules/pkg")).unwrap()),
+      vec!["index.js"]
+    );
+
+    // other embedded files and new modules can still be written
+    let upper = overlay
+      .open_path(&root.join("data.txt"), &write)
+      .unwrap()
+      .unwrap();
+    assert_eq!(std::fs::read_to_string(upper).unwrap(), "data");
+    assert!(overlay
+      .open_path(&root.join("new.ts"), &create)
+      .unwrap()
+      .is_some());
+  }
+}
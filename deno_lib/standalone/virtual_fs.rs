@@ -3,6 +3,7 @@
 use std::borrow::Cow;
 use std::collections::HashMap;
 use std::collections::HashSet;
+use std::collections::VecDeque;
 use std::fs::File;
 use std::io::Read;
 use std::io::Seek;
@@ -13,6 +14,7 @@ use std::path::PathBuf;
 use std::rc::Rc;
 use std::sync::Arc;
 
+use deno_config::glob::PathOrPatternSet;
 use deno_core::anyhow::anyhow;
 use deno_core::anyhow::Context;
 use deno_core::error::AnyError;
@@ -32,6 +34,8 @@ use thiserror::Error;
 use crate::util;
 use crate::util::fs::canonicalize_path;
 
+use super::integrity::PartIntegrityCheck;
+
 #[derive(Error, Debug)]
 #[error(
   "Failed to strip prefix '{}' from '{}'", root_path.display(), target.display()
@@ -47,6 +51,8 @@ pub struct VfsBuilder {
   files: Vec<Vec<u8>>,
   current_offset: u64,
   file_offsets: HashMap<String, u64>,
+  /// Paths skipped when adding directories.
+  exclude: PathOrPatternSet,
 }
 
 impl VfsBuilder {
@@ -65,9 +71,16 @@ impl VfsBuilder {
       files: Vec::new(),
       current_offset: 0,
       file_offsets: Default::default(),
+      exclude: Default::default(),
     })
   }
 
+  /// Skips the files and directories matching `exclude` when adding
+  /// directories.
+  pub fn set_exclude(&mut self, exclude: PathOrPatternSet) {
+    self.exclude = exclude;
+  }
+
   pub fn set_new_root_path(
     &mut self,
     root_path: PathBuf,
@@ -118,6 +131,10 @@ impl VfsBuilder {
     for entry in dir_entries {
       let file_type = entry.file_type()?;
       let path = entry.path();
+      if self.exclude.matches_path(&path) {
+        log::debug!("Excluding '{}'", path.display());
+        continue;
+      }
 
       if file_type.is_dir() {
         self.add_dir_recursive_internal(&path)?;
@@ -262,6 +279,7 @@ impl VfsBuilder {
             name: name.to_string(),
             offset,
             len: data.len() as u64,
+            compression: None,
           }),
         );
       }
@@ -318,6 +336,96 @@ impl VfsBuilder {
     Ok(())
   }
 
+  /// Compresses the data of every file added so far with zstd at the given
+  /// level. Files that don't get smaller are stored uncompressed.
+  ///
+  /// This should be called once, after all the files have been added.
+  pub fn compress_files(&mut self, level: i32) -> Result<(), AnyError> {
+    fn update_dir(
+      dir: &mut VirtualDirectory,
+      new_offsets: &HashMap<u64, (u64, Option<VfsFileCompression>)>,
+    ) {
+      for entry in &mut dir.entries {
+        match entry {
+          VfsEntry::Dir(dir) => update_dir(dir, new_offsets),
+          VfsEntry::File(file) => {
+            let (offset, compression) = new_offsets[&file.offset];
+            file.offset = offset;
+            file.compression = compression;
+          }
+          VfsEntry::Symlink(_) => {}
+        }
+      }
+    }
+
+    // maps the old offset of each file's data to its new offset
+    let mut new_offsets = HashMap::with_capacity(self.files.len());
+    let mut old_offset = 0;
+    let mut new_offset = 0;
+    for data in &mut self.files {
+      let data_len = data.len() as u64;
+      let compressed = zstd::bulk::compress(data, level)?;
+      let compression = if compressed.len() < data.len() {
+        let compression = VfsFileCompression {
+          algorithm: VfsCompressionAlgorithm::Zstd,
+          stored_len: compressed.len() as u64,
+        };
+        *data = compressed;
+        Some(compression)
+      } else {
+        None
+      };
+      new_offsets.insert(old_offset, (new_offset, compression));
+      old_offset += data_len;
+      new_offset += data.len() as u64;
+    }
+    update_dir(&mut self.root_dir, &new_offsets);
+    self.current_offset = new_offset;
+    // files added from now on shouldn't be deduplicated against compressed data
+    self.file_offsets.clear();
+    Ok(())
+  }
+
+  /// The total size of the files in each directory, down to `max_depth`
+  /// levels below the root, which is included as `"."`.
+  pub fn dir_sizes(&self, max_depth: usize) -> Vec<(String, u64)> {
+    fn visit(
+      dir: &VirtualDirectory,
+      path: &str,
+      depth: usize,
+      max_depth: usize,
+      sizes: &mut Vec<(String, u64)>,
+    ) -> u64 {
+      let index = sizes.len();
+      if depth <= max_depth {
+        sizes.push((path.to_string(), 0));
+      }
+      let mut size = 0;
+      for entry in &dir.entries {
+        match entry {
+          VfsEntry::Dir(dir) => {
+            let path = if depth == 0 {
+              dir.name.clone()
+            } else {
+              format!("{}/{}", path, dir.name)
+            };
+            size += visit(dir, &path, depth + 1, max_depth, sizes);
+          }
+          VfsEntry::File(file) => size += file.len,
+          VfsEntry::Symlink(_) => {}
+        }
+      }
+      if depth <= max_depth {
+        sizes[index].1 = size;
+      }
+      size
+    }
+
+    let mut sizes = Vec::new();
+    visit(&self.root_dir, ".", 0, max_depth, &mut sizes);
+    sizes
+  }
+
   pub fn into_dir_and_files(self) -> (VirtualDirectory, Vec<Vec<u8>>) {
     (self.root_dir, self.files)
   }
@@ -450,7 +558,25 @@ pub struct VirtualDirectory {
 pub struct VirtualFile {
   pub name: String,
   pub offset: u64,
+  /// Length of the file's contents, which is not the length of the stored
+  /// data when the file is compressed.
   pub len: u64,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub compression: Option<VfsFileCompression>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum VfsCompressionAlgorithm {
+  Zstd,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VfsFileCompression {
+  pub algorithm: VfsCompressionAlgorithm,
+  /// Length of the compressed data.
+  pub stored_len: u64,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -793,10 +919,48 @@ impl deno_io::fs::File for FileBackedVfsFile {
   }
 }
 
+/// Keeps the most recently decompressed files, so that reading a compressed
+/// file in chunks doesn't decompress it again for every read.
+#[derive(Debug, Default)]
+struct DecompressedFileCache {
+  /// Keyed by the offset of the file data. Most recently used last.
+  entries: VecDeque<(u64, Arc<[u8]>)>,
+  total_len: usize,
+}
+
+impl DecompressedFileCache {
+  const MAX_ENTRIES: usize = 64;
+  const MAX_TOTAL_LEN: usize = 32 * 1024 * 1024;
+
+  fn get(&mut self, offset: u64) -> Option<Arc<[u8]>> {
+    let index = self.entries.iter().position(|(o, _)| *o == offset)?;
+    let entry = self.entries.remove(index).unwrap();
+    let data = entry.1.clone();
+    self.entries.push_back(entry);
+    Some(data)
+  }
+
+  fn insert(&mut self, offset: u64, data: Arc<[u8]>) {
+    if data.len() > Self::MAX_TOTAL_LEN {
+      return;
+    }
+    self.total_len += data.len();
+    self.entries.push_back((offset, data));
+    while self.total_len > Self::MAX_TOTAL_LEN
+      || self.entries.len() > Self::MAX_ENTRIES
+    {
+      let (_, data) = self.entries.pop_front().unwrap();
+      self.total_len -= data.len();
+    }
+  }
+}
+
 #[derive(Debug)]
 pub struct FileBackedVfs {
   vfs_data: Cow<'static, [u8]>,
   fs_root: VfsRoot,
+  decompressed_files: Mutex<DecompressedFileCache>,
+  integrity_check: Option<PartIntegrityCheck>,
 }
 
 impl FileBackedVfs {
@@ -804,13 +968,25 @@ impl FileBackedVfs {
     Self {
       vfs_data: data,
       fs_root,
+      decompressed_files: Default::default(),
+      integrity_check: None,
     }
   }
 
+  /// Checks the data of the files against the integrity data of the
+  /// executable as they're read.
+  pub fn set_integrity_check(&mut self, check: PartIntegrityCheck) {
+    self.integrity_check = Some(check);
+  }
+
   pub fn root(&self) -> &Path {
     &self.fs_root.root_path
   }
 
+  pub fn root_dir(&self) -> &VirtualDirectory {
+    &self.fs_root.dir
+  }
+
   pub fn is_path_within(&self, path: &Path) -> bool {
     path.starts_with(&self.fs_root.root_path)
   }
@@ -875,7 +1051,12 @@ impl FileBackedVfs {
     &self,
     file: &VirtualFile,
   ) -> std::io::Result<Cow<'static, [u8]>> {
+    if let Some(compression) = &file.compression {
+      let data = self.decompressed_file(file, compression)?;
+      return Ok(Cow::Owned(data.to_vec()));
+    }
     let read_range = self.get_read_range(file, 0, file.len)?;
+    self.check_integrity(read_range.clone())?;
     match &self.vfs_data {
       Cow::Borrowed(data) => Ok(Cow::Borrowed(&data[read_range])),
       Cow::Owned(data) => Ok(Cow::Owned(data[read_range].to_vec())),
@@ -888,11 +1069,48 @@ impl FileBackedVfs {
     pos: u64,
     buf: &mut [u8],
   ) -> std::io::Result<usize> {
+    if let Some(compression) = &file.compression {
+      let data = self.decompressed_file(file, compression)?;
+      // reads past the end are short, like reads of a real file
+      let start = std::cmp::min(pos as usize, data.len());
+      let read_len = std::cmp::min(buf.len(), data.len() - start);
+      buf[..read_len].copy_from_slice(&data[start..start + read_len]);
+      return Ok(read_len);
+    }
     let read_range = self.get_read_range(file, pos, buf.len() as u64)?;
+    self.check_integrity(read_range.clone())?;
     buf.copy_from_slice(&self.vfs_data[read_range]);
     Ok(buf.len())
   }
 
+  fn decompressed_file(
+    &self,
+    file: &VirtualFile,
+    compression: &VfsFileCompression,
+  ) -> std::io::Result<Arc<[u8]>> {
+    if let Some(data) = self.decompressed_files.lock().get(file.offset) {
+      return Ok(data);
+    }
+    let stored_file = VirtualFile {
+      len: compression.stored_len,
+      compression: None,
+      ..file.clone()
+    };
+    let read_range = self.get_read_range(&stored_file, 0, stored_file.len)?;
+    self.check_integrity(read_range.clone())?;
+    let data: Arc<[u8]> = match compression.algorithm {
+      VfsCompressionAlgorithm::Zstd => {
+        zstd::bulk::decompress(&self.vfs_data[read_range], file.len as usize)?
+          .into()
+      }
+    };
+    self
+      .decompressed_files
+      .lock()
+      .insert(file.offset, data.clone());
+    Ok(data)
+  }
+
   fn get_read_range(
     &self,
     file: &VirtualFile,
@@ -911,6 +1129,15 @@ impl FileBackedVfs {
     Ok(start as usize..end as usize)
   }
 
+  fn check_integrity(&self, range: Range<usize>) -> std::io::Result<()> {
+    match &self.integrity_check {
+      Some(check) => check.check_range(range).map_err(|err| {
+        std::io::Error::new(std::io::ErrorKind::InvalidData, format!("{err:#}"))
+      }),
+      None => Ok(()),
+    }
+  }
+
   pub fn dir_entry(&self, path: &Path) -> std::io::Result<&VirtualDirectory> {
     let (_, entry) = self.fs_root.find_entry(path)?;
     match entry {
@@ -1075,6 +1302,91 @@ mod test {
     );
   }
 
+  #[test]
+  fn excludes_and_reports_sizes() {
+    let temp_dir = TempDir::new();
+    let temp_dir_path = temp_dir.path().canonicalize();
+    temp_dir.create_dir_all("src/nested/fixtures");
+    temp_dir.write("src/a.js", "data");
+    temp_dir.write("src/a.js.map", "map");
+    temp_dir.write("src/nested/b.js", "b");
+    temp_dir.write("src/nested/fixtures/c.txt", "c");
+
+    let src_path = temp_dir_path.join("src").to_path_buf();
+    let mut builder = VfsBuilder::new(src_path.clone()).unwrap();
+    builder.set_exclude(
+      PathOrPatternSet::from_exclude_relative_path_or_patterns(
+        src_path.as_path(),
+        &["**/*.map".to_string(), "nested/fixtures".to_string()],
+      )
+      .unwrap(),
+    );
+    builder.add_dir_recursive(&src_path).unwrap();
+    assert_eq!(
+      builder.dir_sizes(1),
+      vec![(".".to_string(), 5), ("nested".to_string(), 1)]
+    );
+    let (dest_path, virtual_fs) = into_virtual_fs(builder, &temp_dir);
+    assert_eq!(read_file(&virtual_fs, &dest_path.join("a.js")), "data");
+    assert!(virtual_fs.lstat(&dest_path.join("a.js.map")).is_err());
+    assert!(virtual_fs
+      .lstat(&dest_path.join("nested/fixtures"))
+      .is_err());
+  }
+
+  #[test]
+  fn compressed_files() {
+    let temp_dir = TempDir::new();
+    let src_path = temp_dir.path().canonicalize().join("src");
+    src_path.create_dir_all();
+    let src_path = src_path.to_path_buf();
+    let large_text = "compressible ".repeat(1000);
+    let mut builder = VfsBuilder::new(src_path.clone()).unwrap();
+    builder
+      .add_file_with_data_inner(&src_path.join("a.txt"), "a".into())
+      .unwrap();
+    builder
+      .add_file_with_data_inner(
+        &src_path.join("sub_dir").join("b.txt"),
+        large_text.clone().into(),
+      )
+      .unwrap();
+    builder
+      .add_file_with_data_inner(&src_path.join("c.txt"), "c".into())
+      .unwrap();
+    builder.compress_files(3).unwrap();
+    let (dest_path, virtual_fs) = into_virtual_fs(builder, &temp_dir);
+
+    // too small to benefit from compression
+    let file = virtual_fs.file_entry(&dest_path.join("a.txt")).unwrap();
+    assert_eq!(file.compression, None);
+    let b_path = dest_path.join("sub_dir").join("b.txt");
+    let file = virtual_fs.file_entry(&b_path).unwrap();
+    assert_eq!(
+      file.compression.map(|c| c.algorithm),
+      Some(VfsCompressionAlgorithm::Zstd)
+    );
+    assert_eq!(file.len, large_text.len() as u64);
+
+    assert_eq!(read_file(&virtual_fs, &dest_path.join("a.txt")), "a");
+    assert_eq!(read_file(&virtual_fs, &b_path), large_text);
+    assert_eq!(read_file(&virtual_fs, &dest_path.join("c.txt")), "c");
+    let mut buf = vec![0; 12];
+    virtual_fs.read_file(file, 13, &mut buf).unwrap();
+    assert_eq!(buf, b"compressible");
+
+    // reading past the end returns the remaining bytes, then nothing
+    let mut buf = vec![0; 20];
+    let pos = file.len - 5;
+    assert_eq!(virtual_fs.read_file(file, pos, &mut buf).unwrap(), 5);
+    assert_eq!(&buf[..5], b"ible ");
+    assert_eq!(virtual_fs.read_file(file, file.len, &mut buf).unwrap(), 0);
+    assert_eq!(
+      virtual_fs.read_file(file, file.len + 1, &mut buf).unwrap(),
+      0
+    );
+  }
+
   fn into_virtual_fs(
     builder: VfsBuilder,
     temp_dir: &TempDir,
@@ -0,0 +1,1082 @@
+// Copyright 2018-2024 the Deno authors. All rights reserved. MIT license.
+
+//! Exports the cache entries a program needs into a single archive, and
+//! merges such an archive into the cache of another machine, for example one
+//! without network access.
+
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::io::Read;
+use std::io::Write;
+use std::path::Path;
+use std::sync::Arc;
+
+use deno_ast::ModuleSpecifier;
+use deno_core::anyhow::bail;
+use deno_core::anyhow::Context;
+use deno_core::error::AnyError;
+use deno_core::serde_json;
+use deno_core::url::Url;
+use deno_graph::packages::JsrPackageVersionInfo;
+use deno_npm::registry::NpmPackageVersionDistInfoIntegrity;
+use deno_npm::NpmSystemInfo;
+use deno_path_util::normalize_path;
+use deno_semver::package::PackageNv;
+use deno_semver::Version;
+use deno_terminal::colors;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::args::jsr_url;
+use crate::args::CliLockfile;
+use crate::args::Flags;
+use crate::cache::HttpCache;
+use crate::cache::CACHE_PERM;
+use crate::factory::CliFactory;
+use crate::graph_container::ModuleGraphContainer;
+use crate::npm::extract_tarball;
+use crate::npm::locked_versions;
+use crate::npm::maybe_auth_header_for_npm_registry;
+use crate::npm::verify_package_info_integrity;
+use crate::npm::verify_tarball_integrity;
+use crate::tools::clean::parse_npm_package_id;
+use crate::util::checksum;
+use crate::util::fs::atomic_write_file_with_retries;
+use crate::util::path::get_atomic_dir_path;
+use crate::util::path::get_atomic_file_path;
+use crate::version::DENO_VERSION_INFO;
+
+/// Bumped when the layout of the archive changes.
+const FORMAT_VERSION: u32 = 2;
+const MANIFEST_FILE_NAME: &str = "manifest.json";
+const NPM_PACKAGE_SYNC_LOCK_FILENAME: &str = ".deno_sync_lock";
+
+/// Describes the contents of the archive. The files themselves are stored
+/// next to it, with their checksums here.
+///
+/// The analysis caches aren't included, because their rows can't be checked
+/// against the lockfile. They're filled again when the modules are used.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BundleManifest {
+  format_version: u32,
+  deno_version: String,
+  remote: Vec<RemoteEntry>,
+  npm_packages: Vec<NpmPackageEntry>,
+  /// The `registry.json` of each npm package name.
+  npm_registry_info: Vec<NpmRegistryInfoEntry>,
+}
+
+/// A response in the HTTP cache, including redirects and JSR metadata.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RemoteEntry {
+  url: ModuleSpecifier,
+  headers: HashMap<String, String>,
+  checksum: String,
+}
+
+impl RemoteEntry {
+  fn archive_path(&self) -> String {
+    format!("remote/{}", self.checksum)
+  }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct NpmPackageEntry {
+  /// The package id the way the lockfile has it.
+  id: String,
+  name: String,
+  version: String,
+  /// The integrity of the package's tarball, the way the lockfile has it.
+  integrity: String,
+}
+
+impl NpmPackageEntry {
+  /// The tarball is stored rather than the extracted package, so that it
+  /// can be checked against the integrity of the lockfile.
+  fn archive_path(&self) -> String {
+    format!("npm/{}/{}.tgz", self.name, self.version)
+  }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct NpmRegistryInfoEntry {
+  name: String,
+  checksum: String,
+}
+
+impl NpmRegistryInfoEntry {
+  fn archive_path(&self) -> String {
+    format!("npm/{}/registry.json", self.name)
+  }
+}
+
+/// Caches `files` the same way `deno cache` does, then writes the cache
+/// entries they need to the archive at `archive_path`.
+pub async fn export(
+  flags: Arc<Flags>,
+  files: &[String],
+  archive_path: &str,
+) -> Result<(), AnyError> {
+  let factory = CliFactory::from_flags(flags);
+  let cli_options = factory.cli_options()?;
+  let archive_path =
+    normalize_path(cli_options.initial_cwd().join(archive_path));
+  let main_graph_container = factory.main_module_graph_container().await?;
+  main_graph_container
+    .load_and_type_check_files(files)
+    .await?;
+  let graph = main_graph_container.graph();
+
+  let temp_path = get_atomic_file_path(&archive_path);
+  let file = std::fs::File::create(&temp_path)
+    .with_context(|| format!("Failed creating '{}'", temp_path.display()))?;
+  let mut writer = BundleWriter::new(file);
+  let mut manifest = BundleManifest {
+    format_version: FORMAT_VERSION,
+    deno_version: DENO_VERSION_INFO.deno.to_string(),
+    ..Default::default()
+  };
+
+  // remote modules, the redirects to them and the metadata of their jsr
+  // packages
+  let http_cache = factory.http_cache()?;
+  let mut urls = BTreeSet::new();
+  for module in graph.modules() {
+    if is_remote(module.specifier()) {
+      urls.insert(module.specifier().clone());
+    }
+  }
+  for from in graph.redirects.keys() {
+    if is_remote(from) {
+      urls.insert(from.clone());
+    }
+  }
+  let mut jsr_meta_urls = BTreeSet::new();
+  for (_, nv) in graph.packages.mappings() {
+    jsr_meta_urls.insert(jsr_url().join(&format!("{}/meta.json", nv.name))?);
+    jsr_meta_urls.insert(
+      jsr_url().join(&format!("{}/{}_meta.json", nv.name, nv.version))?,
+    );
+  }
+  for url in urls {
+    let Some((headers, content)) = read_cache_entry(http_cache.as_ref(), &url)?
+    else {
+      bail!("'{}' is not cached.", url);
+    };
+    manifest
+      .remote
+      .push(writer.add_remote(url, headers, &content)?);
+  }
+  for url in jsr_meta_urls {
+    // the package metadata isn't downloaded when the lockfile has the version
+    if let Some((headers, content)) =
+      read_cache_entry(http_cache.as_ref(), &url)?
+    {
+      manifest
+        .remote
+        .push(writer.add_remote(url, headers, &content)?);
+    }
+  }
+
+  // npm packages, which are only cached extracted, so their tarballs are
+  // downloaded again
+  let npm_resolver = factory.npm_resolver().await?;
+  if let Some(managed) = npm_resolver.as_managed() {
+    let npm_cache_dir = factory.npm_cache_dir()?;
+    let npmrc = cli_options.npmrc();
+    let http_client_provider = factory.http_client_provider()?;
+    let progress_bar = factory.text_only_progress_bar();
+    let mut seen_nvs = HashSet::new();
+    let mut seen_names = HashSet::new();
+    for package in managed.all_system_packages(&NpmSystemInfo::default()) {
+      let nv = &package.id.nv;
+      if !seen_nvs.insert(nv.clone()) {
+        continue;
+      }
+      let tarball_url = Url::parse(&package.dist.tarball)?;
+      let maybe_auth_header = match npmrc.tarball_config(&tarball_url) {
+        Some(registry_config) => {
+          maybe_auth_header_for_npm_registry(registry_config)?
+        }
+        None => None,
+      };
+      let guard = progress_bar.update(tarball_url.as_str());
+      let Some(tarball) = http_client_provider
+        .get_or_create_for_url(&tarball_url)?
+        .download_with_progress_and_retries(
+          tarball_url,
+          maybe_auth_header,
+          &guard,
+        )
+        .await?
+      else {
+        bail!(
+          "Could not find npm package tarball at: {}",
+          package.dist.tarball
+        );
+      };
+      drop(guard);
+      verify_tarball_integrity(nv, &tarball, &package.dist.integrity())?;
+      let entry = NpmPackageEntry {
+        id: package.id.as_serialized(),
+        name: nv.name.clone(),
+        version: nv.version.to_string(),
+        integrity: package.dist.integrity().for_lockfile(),
+      };
+      writer.add_file(&entry.archive_path(), &tarball)?;
+      manifest.npm_packages.push(entry);
+
+      if seen_names.insert(nv.name.clone()) {
+        let registry_info_path = npm_cache_dir
+          .package_name_folder(&nv.name, npmrc.get_registry_url(&nv.name))
+          .join("registry.json");
+        if let Ok(data) = std::fs::read(&registry_info_path) {
+          let entry = NpmRegistryInfoEntry {
+            name: nv.name.clone(),
+            checksum: checksum::gen(&[&data]),
+          };
+          writer.add_file(&entry.archive_path(), &data)?;
+          manifest.npm_registry_info.push(entry);
+        }
+      }
+    }
+  }
+
+  writer
+    .add_file(MANIFEST_FILE_NAME, &serde_json::to_vec_pretty(&manifest)?)?;
+  writer.finish()?;
+  std::fs::rename(&temp_path, &archive_path)
+    .with_context(|| format!("Failed writing '{}'", archive_path.display()))?;
+  log::info!(
+    "{} {} remote modules and {} npm packages to {}",
+    colors::green("Exported"),
+    manifest.remote.len(),
+    manifest.npm_packages.len(),
+    archive_path.display()
+  );
+  Ok(())
+}
+
+/// Merges the archive at `archive_path` into the cache, after checking its
+/// contents against the lockfile. Entries that are already cached are kept.
+pub async fn import(
+  flags: Arc<Flags>,
+  archive_path: &str,
+) -> Result<(), AnyError> {
+  let factory = CliFactory::from_flags(flags);
+  let cli_options = factory.cli_options()?;
+  let archive_path =
+    normalize_path(cli_options.initial_cwd().join(archive_path));
+  let file = std::fs::File::open(&archive_path)
+    .with_context(|| format!("Failed opening '{}'", archive_path.display()))?;
+  let mut archive = zip::ZipArchive::new(file).with_context(|| {
+    format!("'{}' is not a cache archive", archive_path.display())
+  })?;
+  let manifest = read_manifest(&mut archive)?;
+
+  let maybe_locked = cli_options
+    .maybe_lockfile()
+    .map(|lockfile| LockedChecksums::from_lockfile(lockfile));
+  if maybe_locked.is_none() {
+    log::warn!(
+      "{} No lockfile was found, so the archive is only checked against its own checksums.",
+      colors::yellow("Warning"),
+    );
+  }
+  let problems = verify(&mut archive, &manifest, maybe_locked.as_ref())?;
+  if !problems.is_empty() {
+    for problem in &problems {
+      log::error!("{} {}", colors::red("error:"), problem);
+    }
+    bail!(
+      "Found {} problem(s) in '{}'. Nothing was imported.",
+      problems.len(),
+      archive_path.display()
+    );
+  }
+
+  let http_cache = factory.http_cache()?;
+  let imported_remote =
+    import_remote_entries(&mut archive, &manifest, http_cache.as_ref())?;
+
+  let npm_cache_dir = factory.npm_cache_dir()?;
+  let npmrc = cli_options.npmrc();
+  let mut imported_npm = 0;
+  for entry in &manifest.npm_packages {
+    let folder = npm_cache_dir.package_folder_for_id(
+      &entry.name,
+      &entry.version,
+      0,
+      npmrc.get_registry_url(&entry.name),
+    );
+    if is_complete_package_folder(&folder) {
+      continue;
+    }
+    import_npm_package(&mut archive, entry, &folder)?;
+    imported_npm += 1;
+  }
+  for entry in &manifest.npm_registry_info {
+    let path = npm_cache_dir
+      .package_name_folder(&entry.name, npmrc.get_registry_url(&entry.name))
+      .join("registry.json");
+    if !path.exists() {
+      let data = read_archive_file(&mut archive, &entry.archive_path())?;
+      atomic_write_file_with_retries(&path, data, CACHE_PERM)?;
+    }
+  }
+
+  log::info!(
+    "{} {} remote modules and {} npm packages from {} ({} already cached)",
+    colors::green("Imported"),
+    imported_remote,
+    imported_npm,
+    archive_path.display(),
+    manifest.remote.len() + manifest.npm_packages.len()
+      - imported_remote
+      - imported_npm,
+  );
+  Ok(())
+}
+
+fn read_manifest(
+  archive: &mut zip::ZipArchive<std::fs::File>,
+) -> Result<BundleManifest, AnyError> {
+  let manifest = serde_json::from_slice::<BundleManifest>(&read_archive_file(
+    archive,
+    MANIFEST_FILE_NAME,
+  )?)
+  .context("Failed reading the manifest of the cache archive")?;
+  if manifest.format_version != FORMAT_VERSION {
+    bail!(
+      "Unsupported cache archive format {} (expected {}). Export it again with this version of Deno.",
+      manifest.format_version,
+      FORMAT_VERSION
+    );
+  }
+  Ok(manifest)
+}
+
+/// The checksums of the lockfile, copied so that the lockfile isn't kept
+/// locked while reading the archive.
+struct LockedChecksums {
+  remote: BTreeMap<String, String>,
+  jsr: HashMap<PackageNv, String>,
+  npm: HashMap<String, String>,
+}
+
+impl LockedChecksums {
+  fn from_lockfile(lockfile: &CliLockfile) -> Self {
+    let lockfile = lockfile.lock();
+    Self {
+      remote: lockfile.remote().clone(),
+      jsr: lockfile
+        .content
+        .packages
+        .jsr
+        .iter()
+        .map(|(nv, info)| (nv.clone(), info.integrity.clone()))
+        .collect(),
+      npm: lockfile
+        .content
+        .packages
+        .npm
+        .iter()
+        .map(|(id, info)| (id.clone(), info.integrity.clone()))
+        .collect(),
+    }
+  }
+}
+
+/// Checks the files of the archive against the checksums of its manifest
+/// and, when there is one, the lockfile, returning the differences. The
+/// npm tarballs are hashed against the integrity of the lockfile.
+fn verify(
+  archive: &mut zip::ZipArchive<std::fs::File>,
+  manifest: &BundleManifest,
+  maybe_locked: Option<&LockedChecksums>,
+) -> Result<Vec<String>, AnyError> {
+  let mut problems = Vec::new();
+  let mut version_infos = HashMap::new();
+  for entry in &manifest.remote {
+    let data = read_archive_file(archive, &entry.archive_path())?;
+    if checksum::gen(&[&data]) != entry.checksum {
+      problems.push(format!("{}: corrupted in the archive", entry.url));
+      continue;
+    }
+    if let Some(JsrUrl::VersionMeta(nv)) = parse_jsr_url(&entry.url) {
+      match serde_json::from_slice::<JsrPackageVersionInfo>(&data) {
+        Ok(info) => {
+          version_infos.insert(nv, info);
+        }
+        Err(err) => {
+          problems.push(format!("{}: invalid ({:#})", entry.url, err));
+        }
+      }
+    }
+  }
+  if let Some(locked) = maybe_locked {
+    for entry in &manifest.remote {
+      if let Some(expected) = locked.remote.get(entry.url.as_str()) {
+        if expected != &entry.checksum {
+          problems.push(format!(
+            "{}: does not match the lockfile checksum",
+            entry.url
+          ));
+        }
+        continue;
+      }
+      if entry.headers.contains_key("location") {
+        // redirects have no content
+        continue;
+      }
+      match parse_jsr_url(&entry.url) {
+        // not used when the lockfile has the version
+        Some(JsrUrl::PackageMeta) => {}
+        Some(JsrUrl::VersionMeta(nv)) => match locked.jsr.get(&nv) {
+          Some(expected) if expected == &entry.checksum => {}
+          Some(_) => problems.push(format!(
+            "{}: does not match the lockfile checksum of {}",
+            entry.url, nv
+          )),
+          None => problems
+            .push(format!("{}: {} is not in the lockfile", entry.url, nv)),
+        },
+        // jsr modules are checked against their package's checksums
+        Some(JsrUrl::Module(nv, path)) => {
+          let expected = version_infos
+            .get(&nv)
+            .and_then(|info| info.manifest.get(&path))
+            .map(|entry| entry.checksum.as_str());
+          if expected != Some(format!("sha256-{}", entry.checksum).as_str()) {
+            problems.push(format!(
+              "{}: does not match the checksum of {}",
+              entry.url, nv
+            ));
+          }
+        }
+        None => problems.push(format!("{} is not in the lockfile", entry.url)),
+      }
+    }
+  }
+
+  for entry in &manifest.npm_packages {
+    // the tarball is checked against the integrity of the id, but extracted
+    // into the folder of the name and version
+    if parse_npm_package_id(&entry.id)
+      != Some((entry.name.as_str(), entry.version.as_str()))
+    {
+      problems.push(format!(
+        "npm:{}: the name and version don't match the id",
+        entry.id
+      ));
+      continue;
+    }
+    if !is_valid_npm_package_name(&entry.name) {
+      problems.push(format!("npm:{}: invalid package name", entry.id));
+      continue;
+    }
+    let version = match Version::parse_from_npm(&entry.version) {
+      Ok(version) if version.to_string() == entry.version => version,
+      _ => {
+        problems.push(format!("npm:{}: invalid version", entry.id));
+        continue;
+      }
+    };
+    let integrity = match maybe_locked {
+      Some(locked) => match locked.npm.get(&entry.id) {
+        Some(integrity) => integrity,
+        None => {
+          problems.push(format!("npm:{} is not in the lockfile", entry.id));
+          continue;
+        }
+      },
+      None => &entry.integrity,
+    };
+    let nv = PackageNv {
+      name: entry.name.clone(),
+      version,
+    };
+    let tarball = read_archive_file(archive, &entry.archive_path())?;
+    if let Err(err) =
+      verify_tarball_integrity(&nv, &tarball, &parse_integrity(integrity))
+    {
+      problems.push(format!("npm:{}: {:#}", entry.id, err));
+    }
+  }
+  for entry in &manifest.npm_registry_info {
+    if !is_valid_npm_package_name(&entry.name) {
+      problems.push(format!("npm:{}: invalid package name", entry.name));
+      continue;
+    }
+    let data = read_archive_file(archive, &entry.archive_path())?;
+    if checksum::gen(&[&data]) != entry.checksum {
+      problems.push(format!(
+        "{}: corrupted in the archive",
+        entry.archive_path()
+      ));
+      continue;
+    }
+    if let Some(locked) = maybe_locked {
+      if let Err(err) = verify_registry_info(&entry.name, &data, locked) {
+        problems.push(format!("{}: {:#}", entry.archive_path(), err));
+      }
+    }
+  }
+  Ok(problems)
+}
+
+/// Parses an integrity the way the lockfile has it.
+fn parse_integrity(integrity: &str) -> NpmPackageVersionDistInfoIntegrity {
+  match integrity.split_once('-') {
+    Some((algorithm, base64_hash)) => {
+      NpmPackageVersionDistInfoIntegrity::Integrity {
+        algorithm,
+        base64_hash,
+      }
+    }
+    None => NpmPackageVersionDistInfoIntegrity::LegacySha1Hex(integrity),
+  }
+}
+
+/// Checks that the lockfile has versions of a package and that they have the
+/// same integrity in its `registry.json`, which is used to resolve new
+/// versions.
+fn verify_registry_info(
+  name: &str,
+  data: &[u8],
+  locked: &LockedChecksums,
+) -> Result<(), AnyError> {
+  let versions = locked_versions(name, &locked.npm);
+  if versions.is_empty() {
+    bail!("{} is not in the lockfile", name);
+  }
+  verify_package_info_integrity(name, data, &versions)
+}
+
+/// Whether `name` is a valid npm package name, which also keeps it from
+/// leaving the folders of the npm cache.
+fn is_valid_npm_package_name(name: &str) -> bool {
+  fn is_valid_part(part: &str) -> bool {
+    !part.is_empty()
+      && !part.starts_with(['.', '_'])
+      && part
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || "-._~!*'()".contains(c))
+  }
+
+  let bare_name = match name.strip_prefix('@') {
+    Some(scoped) => match scoped.split_once('/') {
+      Some((scope, bare_name)) if is_valid_part(scope) => bare_name,
+      _ => return false,
+    },
+    None => name,
+  };
+  name.len() <= 214 && is_valid_part(bare_name)
+}
+
+/// Writes the remote entries of the archive that aren't cached yet to the
+/// HTTP cache, returning how many were written.
+fn import_remote_entries(
+  archive: &mut zip::ZipArchive<std::fs::File>,
+  manifest: &BundleManifest,
+  http_cache: &dyn HttpCache,
+) -> Result<usize, AnyError> {
+  let mut imported = 0;
+  for entry in &manifest.remote {
+    let key = http_cache.cache_item_key(&entry.url)?;
+    if let Ok(Some(_)) = http_cache.read_headers(&key) {
+      continue;
+    }
+    let content = read_archive_file(archive, &entry.archive_path())?;
+    http_cache.set(&entry.url, entry.headers.clone(), &content)?;
+    imported += 1;
+  }
+  Ok(imported)
+}
+
+/// Extracts the tarball of an npm package to `folder`, replacing it.
+fn import_npm_package(
+  archive: &mut zip::ZipArchive<std::fs::File>,
+  entry: &NpmPackageEntry,
+  folder: &Path,
+) -> Result<(), AnyError> {
+  let tarball = read_archive_file(archive, &entry.archive_path())?;
+  let temp_dir = get_atomic_dir_path(folder);
+  extract_tarball(&tarball, &temp_dir)?;
+  if folder.exists() {
+    std::fs::remove_dir_all(folder)?;
+  }
+  std::fs::create_dir_all(folder.parent().unwrap())?;
+  std::fs::rename(&temp_dir, folder).with_context(|| {
+    format!("Failed moving npm package to '{}'", folder.display())
+  })
+}
+
+struct BundleWriter {
+  zip: zip::ZipWriter<std::fs::File>,
+  written_paths: HashSet<String>,
+}
+
+impl BundleWriter {
+  fn new(file: std::fs::File) -> Self {
+    Self {
+      zip: zip::ZipWriter::new(file),
+      written_paths: HashSet::new(),
+    }
+  }
+
+  fn add_remote(
+    &mut self,
+    url: ModuleSpecifier,
+    headers: HashMap<String, String>,
+    content: &[u8],
+  ) -> Result<RemoteEntry, AnyError> {
+    let entry = RemoteEntry {
+      url,
+      headers,
+      checksum: checksum::gen(&[content]),
+    };
+    self.add_file(&entry.archive_path(), content)?;
+    Ok(entry)
+  }
+
+  fn add_file(&mut self, path: &str, data: &[u8]) -> Result<(), AnyError> {
+    // identical content is stored once
+    if !self.written_paths.insert(path.to_string()) {
+      return Ok(());
+    }
+    self
+      .zip
+      .start_file(path, zip::write::SimpleFileOptions::default())?;
+    self.zip.write_all(data)?;
+    Ok(())
+  }
+
+  fn finish(self) -> Result<(), AnyError> {
+    self.zip.finish()?;
+    Ok(())
+  }
+}
+
+fn read_archive_file(
+  archive: &mut zip::ZipArchive<std::fs::File>,
+  path: &str,
+) -> Result<Vec<u8>, AnyError> {
+  let mut file = archive
+    .by_name(path)
+    .with_context(|| format!("'{}' is missing from the cache archive", path))?;
+  let mut data = Vec::with_capacity(file.size() as usize);
+  file.read_to_end(&mut data)?;
+  Ok(data)
+}
+
+fn read_cache_entry(
+  http_cache: &dyn HttpCache,
+  url: &ModuleSpecifier,
+) -> Result<Option<(HashMap<String, String>, Vec<u8>)>, AnyError> {
+  let key = http_cache.cache_item_key(url)?;
+  match http_cache.get(&key, None) {
+    Ok(entry) => Ok(entry.map(|entry| (entry.metadata.headers, entry.content))),
+    Err(deno_cache_dir::CacheReadFileError::Io(err)) => Err(err.into()),
+    Err(deno_cache_dir::CacheReadFileError::ChecksumIntegrity(_)) => {
+      unreachable!("no checksum was provided")
+    }
+  }
+}
+
+fn is_remote(specifier: &ModuleSpecifier) -> bool {
+  matches!(specifier.scheme(), "http" | "https")
+}
+
+/// Whether the package folder exists and wasn't left behind by an
+/// interrupted extraction.
+fn is_complete_package_folder(folder: &Path) -> bool {
+  folder.is_dir() && !folder.join(NPM_PACKAGE_SYNC_LOCK_FILENAME).exists()
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum JsrUrl {
+  /// The versions of a package.
+  PackageMeta,
+  /// The files of a package version and their checksums.
+  VersionMeta(PackageNv),
+  /// A module of a package version, with its path in the package.
+  Module(PackageNv, String),
+}
+
+fn parse_jsr_url(url: &ModuleSpecifier) -> Option<JsrUrl> {
+  let rest = url.as_str().strip_prefix(jsr_url().as_str())?;
+  let mut parts = rest.splitn(4, '/');
+  let scope = parts.next().filter(|scope| scope.starts_with('@'))?;
+  let name = format!("{}/{}", scope, parts.next()?);
+  let third = parts.next()?;
+  let nv = |version: &str| {
+    Some(PackageNv {
+      name: name.clone(),
+      version: Version::parse_standard(version).ok()?,
+    })
+  };
+  match parts.next() {
+    Some(path) => Some(JsrUrl::Module(nv(third)?, format!("/{}", path))),
+    None if third == "meta.json" => Some(JsrUrl::PackageMeta),
+    None => Some(JsrUrl::VersionMeta(nv(third.strip_suffix("_meta.json")?)?)),
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::str::FromStr;
+
+  use base64::prelude::BASE64_STANDARD;
+  use base64::Engine;
+  use test_util::TempDir;
+
+  use super::*;
+  use crate::cache::GlobalHttpCache;
+  use crate::cache::RealDenoCacheEnv;
+
+  type RemoteFixture<'a> = [(&'a str, &'a str)];
+  type NpmFixture<'a> = [(&'a str, &'a str, Vec<u8>)];
+
+  fn npm_tarball(files: &[(&str, &str)]) -> Vec<u8> {
+    let mut builder = tar::Builder::new(flate2::write::GzEncoder::new(
+      Vec::new(),
+      flate2::Compression::default(),
+    ));
+    for (path, text) in files {
+      let mut header = tar::Header::new_gnu();
+      header.set_size(text.len() as u64);
+      header.set_mode(0o644);
+      header.set_cksum();
+      builder
+        .append_data(&mut header, format!("package/{}", path), text.as_bytes())
+        .unwrap();
+    }
+    builder.into_inner().unwrap().finish().unwrap()
+  }
+
+  fn sha512_integrity(data: &[u8]) -> String {
+    let digest = ring::digest::digest(&ring::digest::SHA512, data);
+    format!("sha512-{}", BASE64_STANDARD.encode(digest.as_ref()))
+  }
+
+  /// Writes an archive whose manifest matches its files, like one that was
+  /// exported or rewritten after tampering with it.
+  fn write_archive(path: &Path, remote: &RemoteFixture, npm: &NpmFixture) {
+    let mut writer = BundleWriter::new(std::fs::File::create(path).unwrap());
+    let mut manifest = BundleManifest {
+      format_version: FORMAT_VERSION,
+      deno_version: DENO_VERSION_INFO.deno.to_string(),
+      ..Default::default()
+    };
+    for (url, content) in remote {
+      let headers = HashMap::from([(
+        "content-type".to_string(),
+        "application/typescript".to_string(),
+      )]);
+      let url = ModuleSpecifier::parse(url).unwrap();
+      let entry = writer.add_remote(url, headers, content.as_bytes()).unwrap();
+      manifest.remote.push(entry);
+    }
+    for (name, version, tarball) in npm {
+      let entry = NpmPackageEntry {
+        id: format!("{}@{}", name, version),
+        name: name.to_string(),
+        version: version.to_string(),
+        integrity: sha512_integrity(tarball),
+      };
+      writer.add_file(&entry.archive_path(), tarball).unwrap();
+      manifest.npm_packages.push(entry);
+    }
+    writer
+      .add_file(
+        MANIFEST_FILE_NAME,
+        &serde_json::to_vec_pretty(&manifest).unwrap(),
+      )
+      .unwrap();
+    writer.finish().unwrap();
+  }
+
+  fn open_archive(
+    path: &Path,
+  ) -> (zip::ZipArchive<std::fs::File>, BundleManifest) {
+    let file = std::fs::File::open(path).unwrap();
+    let mut archive = zip::ZipArchive::new(file).unwrap();
+    let manifest = read_manifest(&mut archive).unwrap();
+    (archive, manifest)
+  }
+
+  fn locked(remote: &RemoteFixture, npm: &NpmFixture) -> LockedChecksums {
+    LockedChecksums {
+      remote: remote
+        .iter()
+        .map(|(url, content)| {
+          (url.to_string(), checksum::gen(&[content.as_bytes()]))
+        })
+        .collect(),
+      jsr: HashMap::new(),
+      npm: npm
+        .iter()
+        .map(|(name, version, tarball)| {
+          (format!("{}@{}", name, version), sha512_integrity(tarball))
+        })
+        .collect(),
+    }
+  }
+
+  #[test]
+  fn imports_exported_archive() {
+    let temp_dir = TempDir::new();
+    let remote = [("https://deno.land/x/mod.ts", "export const a = 1;\n")];
+    let npm = [(
+      "chalk",
+      "5.3.0",
+      npm_tarball(&[
+        ("package.json", r#"{"name":"chalk"}"#),
+        ("source/index.js", "export default 1;\n"),
+      ]),
+    )];
+    let archive_path = temp_dir.path().join("cache.zip");
+    write_archive(archive_path.as_path(), &remote, &npm);
+
+    let (mut archive, manifest) = open_archive(archive_path.as_path());
+    let problems =
+      verify(&mut archive, &manifest, Some(&locked(&remote, &npm))).unwrap();
+    assert_eq!(problems, Vec::<String>::new());
+
+    let http_cache = GlobalHttpCache::new(
+      temp_dir.path().join("remote").to_path_buf(),
+      RealDenoCacheEnv,
+    );
+    assert_eq!(
+      import_remote_entries(&mut archive, &manifest, &http_cache).unwrap(),
+      1
+    );
+    let url = ModuleSpecifier::parse(remote[0].0).unwrap();
+    let (headers, content) =
+      read_cache_entry(&http_cache, &url).unwrap().unwrap();
+    assert_eq!(content, remote[0].1.as_bytes());
+    assert_eq!(
+      headers.get("content-type").unwrap(),
+      "application/typescript"
+    );
+    // cached entries are kept
+    assert_eq!(
+      import_remote_entries(&mut archive, &manifest, &http_cache).unwrap(),
+      0
+    );
+
+    let folder = temp_dir.path().join("npm/chalk/5.3.0");
+    import_npm_package(
+      &mut archive,
+      &manifest.npm_packages[0],
+      folder.as_path(),
+    )
+    .unwrap();
+    assert_eq!(
+      folder.join("source/index.js").read_to_string(),
+      "export default 1;\n"
+    );
+    assert_eq!(
+      folder.join("package.json").read_to_string(),
+      r#"{"name":"chalk"}"#
+    );
+  }
+
+  #[test]
+  fn rejects_tampered_archive() {
+    let temp_dir = TempDir::new();
+    let remote = [("https://deno.land/x/mod.ts", "export const a = 1;\n")];
+    let npm = [(
+      "chalk",
+      "5.3.0",
+      npm_tarball(&[("index.js", "export default 1;\n")]),
+    )];
+    // the manifest matches the tampered files
+    let tampered_remote = [
+      ("https://deno.land/x/mod.ts", "export const a = 2;\n"),
+      ("https://deno.land/x/extra.ts", ""),
+    ];
+    let tampered_npm = [
+      (
+        "chalk",
+        "5.3.0",
+        npm_tarball(&[("index.js", "export default 2;\n")]),
+      ),
+      ("other", "1.0.0", npm_tarball(&[])),
+    ];
+    let archive_path = temp_dir.path().join("cache.zip");
+    write_archive(archive_path.as_path(), &tampered_remote, &tampered_npm);
+
+    let (mut archive, manifest) = open_archive(archive_path.as_path());
+    let problems =
+      verify(&mut archive, &manifest, Some(&locked(&remote, &npm))).unwrap();
+    assert_eq!(problems.len(), 4, "{:?}", problems);
+    assert_eq!(
+      problems[0],
+      "https://deno.land/x/mod.ts: does not match the lockfile checksum"
+    );
+    assert_eq!(
+      problems[1],
+      "https://deno.land/x/extra.ts is not in the lockfile"
+    );
+    assert!(
+      problems[2]
+        .starts_with("npm:chalk@5.3.0: Tarball checksum did not match"),
+      "{}",
+      problems[2]
+    );
+    assert_eq!(problems[3], "npm:other@1.0.0 is not in the lockfile");
+
+    // without a lockfile, the archive is only checked against itself
+    assert!(verify(&mut archive, &manifest, None).unwrap().is_empty());
+  }
+
+  #[test]
+  fn rejects_npm_entries_that_disagree_with_their_id() {
+    let temp_dir = TempDir::new();
+    let tarball = npm_tarball(&[("index.js", "export default 1;\n")]);
+    let npm = [("chalk", "5.3.0", tarball.clone())];
+    let archive_path = temp_dir.path().join("cache.zip");
+    let mut writer =
+      BundleWriter::new(std::fs::File::create(archive_path.as_path()).unwrap());
+    let mut manifest = BundleManifest {
+      format_version: FORMAT_VERSION,
+      deno_version: DENO_VERSION_INFO.deno.to_string(),
+      ..Default::default()
+    };
+    // the pinned tarball of chalk, put in the folders of other packages
+    for (name, version) in [("other", "5.3.0"), ("../evil", "5.3.0")] {
+      let entry = NpmPackageEntry {
+        id: "chalk@5.3.0".to_string(),
+        name: name.to_string(),
+        version: version.to_string(),
+        integrity: sha512_integrity(&tarball),
+      };
+      writer.add_file(&entry.archive_path(), &tarball).unwrap();
+      manifest.npm_packages.push(entry);
+    }
+    let entry = NpmRegistryInfoEntry {
+      name: "../evil".to_string(),
+      checksum: checksum::gen(&[b"{}"]),
+    };
+    writer.add_file(&entry.archive_path(), b"{}").unwrap();
+    manifest.npm_registry_info.push(entry);
+    writer
+      .add_file(
+        MANIFEST_FILE_NAME,
+        &serde_json::to_vec_pretty(&manifest).unwrap(),
+      )
+      .unwrap();
+    writer.finish().unwrap();
+
+    let (mut archive, manifest) = open_archive(archive_path.as_path());
+    let locked = locked(&[], &npm);
+    let problems = verify(&mut archive, &manifest, Some(&locked)).unwrap();
+    assert_eq!(
+      problems,
+      vec![
+        "npm:chalk@5.3.0: the name and version don't match the id",
+        "npm:chalk@5.3.0: the name and version don't match the id",
+        "npm:../evil: invalid package name",
+      ]
+    );
+  }
+
+  #[test]
+  fn validates_npm_package_names() {
+    for name in ["chalk", "@std/path", "lodash.merge", "Legacy_Name"] {
+      assert!(is_valid_npm_package_name(name), "{}", name);
+    }
+    for name in [
+      "",
+      "..",
+      ".bin",
+      "_private",
+      "a/b",
+      "@scope",
+      "@scope/",
+      "@/name",
+      "@scope/../x",
+      "a\\b",
+      "é",
+    ] {
+      assert!(!is_valid_npm_package_name(name), "{}", name);
+    }
+  }
+
+  #[test]
+  fn checks_registry_info_against_lockfile() {
+    let npm = [("chalk", "5.3.0", npm_tarball(&[]))];
+    let locked = locked(&[], &npm);
+    let registry_info = |integrity: &str| {
+      serde_json::json!({
+        "name": "chalk",
+        "versions": {
+          "5.3.0": {
+            "version": "5.3.0",
+            "dist": {
+              "tarball": "https://registry.npmjs.org/chalk/-/chalk-5.3.0.tgz",
+              "integrity": integrity,
+            },
+          },
+        },
+      })
+      .to_string()
+    };
+    let data = registry_info(&sha512_integrity(&npm[0].2));
+    verify_registry_info("chalk", data.as_bytes(), &locked).unwrap();
+    // nothing vouches for the packages the lockfile doesn't have
+    let err =
+      verify_registry_info("chalk-cli", data.as_bytes(), &locked).unwrap_err();
+    assert_eq!(err.to_string(), "chalk-cli is not in the lockfile");
+    let data = registry_info("sha512-AAAA");
+    let err =
+      verify_registry_info("chalk", data.as_bytes(), &locked).unwrap_err();
+    assert_eq!(
+      err.to_string(),
+      "chalk@5.3.0 does not match the lockfile integrity"
+    );
+  }
+
+  fn jsr(path: &str) -> ModuleSpecifier {
+    jsr_url().join(path).unwrap()
+  }
+
+  fn nv(text: &str) -> PackageNv {
+    PackageNv::from_str(text).unwrap()
+  }
+
+  #[test]
+  fn parses_jsr_urls() {
+    assert_eq!(
+      parse_jsr_url(&jsr("@std/path/meta.json")),
+      Some(JsrUrl::PackageMeta)
+    );
+    assert_eq!(
+      parse_jsr_url(&jsr("@std/path/1.0.8_meta.json")),
+      Some(JsrUrl::VersionMeta(nv("@std/path@1.0.8")))
+    );
+    assert_eq!(
+      parse_jsr_url(&jsr("@std/path/1.0.8/posix/mod.ts")),
+      Some(JsrUrl::Module(
+        nv("@std/path@1.0.8"),
+        "/posix/mod.ts".to_string()
+      ))
+    );
+    assert_eq!(parse_jsr_url(&jsr("@std/path/latest/mod.ts")), None);
+    assert_eq!(parse_jsr_url(&jsr("std/path/1.0.8/mod.ts")), None);
+    assert_eq!(
+      parse_jsr_url(
+        &ModuleSpecifier::parse("https://deno.land/std/path/mod.ts").unwrap()
+      ),
+      None
+    );
+  }
+}
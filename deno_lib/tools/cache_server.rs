@@ -0,0 +1,599 @@
+// Copyright 2018-2024 the Deno authors. All rights reserved. MIT license.
+
+//! Serves the cache of this machine to others over HTTP, so a team or a CI
+//! fleet downloads remote modules and npm packages once. Clients point
+//! `DENO_CACHE_SERVER` at it, only use the entries their lockfile pins and
+//! fall back to the origin for everything else.
+//!
+//! Entries that aren't cached yet are only downloaded from the JSR and npm
+//! registries, and without the credentials of this machine, so the server
+//! can't be used to reach other hosts or private packages. Clients must send
+//! the `DENO_CACHE_SERVER_TOKEN` when one is set, which it has to be for
+//! addresses other than loopback ones.
+
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use base64::prelude::BASE64_STANDARD;
+use base64::Engine;
+use bytes::Bytes;
+use deno_core::anyhow::bail;
+use deno_core::anyhow::Context;
+use deno_core::error::AnyError;
+use deno_core::serde_json;
+use deno_core::url::Url;
+use http::Method;
+use http::Request;
+use http::Response;
+use http::StatusCode;
+use http_body_util::Full;
+use hyper::body::Incoming;
+use hyper::service::service_fn;
+use hyper_util::rt::TokioIo;
+use tokio::net::TcpListener;
+
+use crate::args::jsr_url;
+use crate::args::npm_registry_url;
+use crate::args::resolve_cache_server_token;
+use crate::args::CacheSetting;
+use crate::args::Flags;
+use crate::auth_tokens::AuthTokens;
+use crate::cache::HttpCache;
+use crate::cache::CACHE_PERM;
+use crate::colors;
+use crate::factory::CliFactory;
+use crate::file_fetcher::FetchNoFollowOptions;
+use crate::file_fetcher::FetchOptions;
+use crate::file_fetcher::FetchPermissionsOptionRef;
+use crate::file_fetcher::FileFetcher;
+use crate::http_util::HeadersMap;
+use crate::http_util::HttpClientProvider;
+use crate::http_util::CACHE_SERVER_HEADERS_HEADER;
+use crate::http_util::CACHE_SERVER_TOKEN_ENV_VAR_NAME;
+use crate::util::checksum;
+use crate::util::fs::atomic_write_file_with_retries;
+use crate::util::progress_bar::ProgressBar;
+use crate::util::progress_bar::ProgressBarStyle;
+
+pub async fn serve(flags: Arc<Flags>, address: &str) -> Result<(), AnyError> {
+  let address = address
+    .parse::<SocketAddr>()
+    .with_context(|| format!("Invalid cache server address '{}'.", address))?;
+  let maybe_token = resolve_cache_server_token();
+  if maybe_token.is_none() && !address.ip().is_loopback() {
+    bail!(
+      "Set {} to serve the cache on {}, so only clients with the token can use it.",
+      CACHE_SERVER_TOKEN_ENV_VAR_NAME,
+      address
+    );
+  }
+  let factory = CliFactory::from_flags(flags.clone());
+  let cli_options = factory.cli_options()?;
+  // the server downloads from the origins itself, never from another cache
+  // server, which could be this one, and without the client certificates of
+  // this machine
+  let http_client_provider = Arc::new(
+    HttpClientProvider::new(
+      Some(factory.root_cert_store_provider().clone()),
+      flags.unsafely_ignore_certificate_errors.clone(),
+    )
+    .with_download_policy(cli_options.download_policy().clone()),
+  );
+  let http_cache: Arc<dyn HttpCache> = factory.global_http_cache()?.clone();
+  let progress_bar = factory.text_only_progress_bar().clone();
+  let mut file_fetcher = FileFetcher::new(
+    http_cache.clone(),
+    CacheSetting::Use,
+    true,
+    http_client_provider.clone(),
+    factory.blob_store().clone(),
+    Some(progress_bar.clone()),
+  );
+  file_fetcher.set_auth_tokens(AuthTokens::new(None));
+  file_fetcher.set_mirrors(cli_options.mirrors().clone());
+  let state = CacheServerState {
+    http_cache,
+    file_fetcher,
+    http_client_provider,
+    progress_bar,
+    npm_folder: factory.deno_dir()?.cache_server_folder_path(),
+    allowed_origins: vec![jsr_url().clone(), npm_registry_url().clone()],
+    maybe_token,
+  };
+
+  let listener = TcpListener::bind(address).await.with_context(|| {
+    format!("Failed binding the cache server to {}.", address)
+  })?;
+  log::info!(
+    "{} the cache of {} on http://{}/",
+    colors::green("Serving"),
+    factory.deno_dir()?.root_path_for_display(),
+    listener.local_addr()?
+  );
+  serve_listener(listener, state).await
+}
+
+struct CacheServerState {
+  http_cache: Arc<dyn HttpCache>,
+  file_fetcher: FileFetcher,
+  http_client_provider: Arc<HttpClientProvider>,
+  progress_bar: ProgressBar,
+  /// Where the npm packuments and tarballs are stored. The npm cache itself
+  /// only has the extracted packages.
+  npm_folder: PathBuf,
+  /// The URL prefixes that entries which aren't cached are downloaded from.
+  allowed_origins: Vec<Url>,
+  /// The token clients have to send as a bearer token.
+  maybe_token: Option<String>,
+}
+
+impl CacheServerState {
+  fn is_authorized(&self, request: &Request<Incoming>) -> bool {
+    let Some(token) = &self.maybe_token else {
+      return true;
+    };
+    let Some(value) = request
+      .headers()
+      .get(http::header::AUTHORIZATION)
+      .and_then(|value| value.to_str().ok())
+    else {
+      return false;
+    };
+    // compares the hashes so the time taken doesn't depend on how much of
+    // the token is right
+    checksum::gen(&[value.as_bytes()])
+      == checksum::gen(&[format!("Bearer {}", token).as_bytes()])
+  }
+
+  fn is_allowed_origin(&self, url: &Url) -> bool {
+    self
+      .allowed_origins
+      .iter()
+      .any(|origin| url.as_str().starts_with(origin.as_str()))
+  }
+}
+
+async fn serve_listener(
+  listener: TcpListener,
+  state: CacheServerState,
+) -> Result<(), AnyError> {
+  let state = Rc::new(state);
+  loop {
+    let (stream, _) = listener.accept().await?;
+    let state = state.clone();
+    deno_core::unsync::spawn(async move {
+      let service = service_fn(move |request| {
+        let state = state.clone();
+        async move {
+          let response = handle_request(&state, request).await;
+          Ok::<_, Infallible>(response)
+        }
+      });
+      if let Err(err) = hyper::server::conn::http1::Builder::new()
+        .serve_connection(TokioIo::new(stream), service)
+        .await
+      {
+        log::debug!("Cache server connection failed: {:#}", err);
+      }
+    });
+  }
+}
+
+async fn handle_request(
+  state: &CacheServerState,
+  request: Request<Incoming>,
+) -> Response<Full<Bytes>> {
+  if request.method() != Method::GET {
+    return empty_response(StatusCode::METHOD_NOT_ALLOWED);
+  }
+  if !state.is_authorized(&request) {
+    return empty_response(StatusCode::UNAUTHORIZED);
+  }
+  let Some(url) = parse_url_query(request.uri()) else {
+    return empty_response(StatusCode::BAD_REQUEST);
+  };
+  let result = match request.uri().path() {
+    "/remote" => serve_remote(state, &url).await,
+    "/npm" => serve_npm(state, &url).await,
+    _ => return empty_response(StatusCode::NOT_FOUND),
+  };
+  match result {
+    Ok(Some(response)) => response,
+    Ok(None) => empty_response(StatusCode::NOT_FOUND),
+    // the client downloads it from the origin instead, which reports the
+    // error if it's not specific to this machine
+    Err(err) => {
+      log::warn!(
+        "{} Failed serving '{}': {:#}",
+        colors::yellow("Warning"),
+        url,
+        err
+      );
+      empty_response(StatusCode::NOT_FOUND)
+    }
+  }
+}
+
+/// The http or https URL in the `url` query parameter.
+fn parse_url_query(uri: &http::Uri) -> Option<Url> {
+  let query = uri.query()?;
+  let (_, url) = deno_core::url::form_urlencoded::parse(query.as_bytes())
+    .find(|(key, _)| key == "url")?;
+  let url = Url::parse(&url).ok()?;
+  matches!(url.scheme(), "http" | "https").then_some(url)
+}
+
+async fn serve_remote(
+  state: &CacheServerState,
+  url: &Url,
+) -> Result<Option<Response<Full<Bytes>>>, AnyError> {
+  if read_cache_entry(state.http_cache.as_ref(), url)?.is_none() {
+    if !state.is_allowed_origin(url) {
+      return Ok(None);
+    }
+    // this caches the module, including a redirect
+    state
+      .file_fetcher
+      .fetch_no_follow_with_options(FetchNoFollowOptions {
+        fetch_options: FetchOptions {
+          specifier: url,
+          permissions: FetchPermissionsOptionRef::AllowAll,
+          maybe_auth: None,
+          maybe_accept: None,
+          maybe_cache_setting: None,
+        },
+        maybe_checksum: None,
+      })
+      .await?;
+  }
+  let Some((headers, content)) =
+    read_cache_entry(state.http_cache.as_ref(), url)?
+  else {
+    return Ok(None);
+  };
+  let headers = BASE64_STANDARD.encode(serde_json::to_vec(&headers)?);
+  let response = Response::builder()
+    .header(CACHE_SERVER_HEADERS_HEADER, headers)
+    .body(Full::new(Bytes::from(content)))?;
+  Ok(Some(response))
+}
+
+async fn serve_npm(
+  state: &CacheServerState,
+  url: &Url,
+) -> Result<Option<Response<Full<Bytes>>>, AnyError> {
+  let path = state
+    .npm_folder
+    .join(checksum::gen(&[url.as_str().as_bytes()]));
+  // a published tarball never changes, unlike a packument
+  let is_tarball = url.path().ends_with(".tgz");
+  if is_tarball {
+    if let Ok(bytes) = std::fs::read(&path) {
+      return Ok(Some(bytes_response(bytes)));
+    }
+  }
+  if !state.is_allowed_origin(url) {
+    return Ok(None);
+  }
+
+  let result = download_npm(state, url).await;
+  match result {
+    Ok(Some(bytes)) => {
+      if let Err(err) =
+        atomic_write_file_with_retries(&path, &bytes, CACHE_PERM)
+      {
+        log::debug!("Failed storing '{}': {:#}", url, err);
+      }
+      Ok(Some(bytes_response(bytes)))
+    }
+    Ok(None) => Ok(None),
+    // serve the last packument when the registry can't be reached
+    Err(err) => match std::fs::read(&path) {
+      Ok(bytes) => {
+        log::debug!("Serving the stored '{}': {:#}", url, err);
+        Ok(Some(bytes_response(bytes)))
+      }
+      Err(_) => Err(err),
+    },
+  }
+}
+
+async fn download_npm(
+  state: &CacheServerState,
+  url: &Url,
+) -> Result<Option<Vec<u8>>, AnyError> {
+  let guard = state.progress_bar.update(url.as_str());
+  let client = state.http_client_provider.get_or_create_for_url(url)?;
+  Ok(
+    client
+      .download_with_progress_and_retries(url.clone(), None, &guard)
+      .await?,
+  )
+}
+
+fn read_cache_entry(
+  http_cache: &dyn HttpCache,
+  url: &Url,
+) -> Result<Option<(HeadersMap, Vec<u8>)>, AnyError> {
+  let key = http_cache.cache_item_key(url)?;
+  match http_cache.get(&key, None) {
+    Ok(entry) => Ok(entry.map(|entry| (entry.metadata.headers, entry.content))),
+    Err(deno_cache_dir::CacheReadFileError::Io(err)) => Err(err.into()),
+    Err(deno_cache_dir::CacheReadFileError::ChecksumIntegrity(_)) => {
+      bail!("The cache entry of '{}' is corrupt.", url)
+    }
+  }
+}
+
+fn bytes_response(bytes: Vec<u8>) -> Response<Full<Bytes>> {
+  Response::new(Full::new(Bytes::from(bytes)))
+}
+
+fn empty_response(status: StatusCode) -> Response<Full<Bytes>> {
+  let mut response = Response::new(Full::new(Bytes::new()));
+  *response.status_mut() = status;
+  response
+}
+
+#[cfg(test)]
+mod test {
+  use deno_core::resolve_url;
+  use deno_graph::source::LoaderChecksum;
+  use test_util::TempDir;
+
+  use super::*;
+  use crate::cache::GlobalHttpCache;
+  use crate::cache::RealDenoCacheEnv;
+  use crate::file_fetcher::FileOrRedirect;
+  use crate::http_util::CacheServer;
+
+  async fn start_server(
+    temp_dir: &TempDir,
+    allowed_origins: Vec<Url>,
+    maybe_token: Option<&str>,
+  ) -> (CacheServer, Arc<dyn HttpCache>) {
+    let http_cache: Arc<dyn HttpCache> = Arc::new(GlobalHttpCache::new(
+      temp_dir.path().join("server/remote").to_path_buf(),
+      RealDenoCacheEnv,
+    ));
+    let http_client_provider = Arc::new(HttpClientProvider::new(None, None));
+    let mut file_fetcher = FileFetcher::new(
+      http_cache.clone(),
+      CacheSetting::Use,
+      true,
+      http_client_provider.clone(),
+      Default::default(),
+      None,
+    );
+    file_fetcher.set_auth_tokens(AuthTokens::new(None));
+    let state = CacheServerState {
+      http_cache: http_cache.clone(),
+      file_fetcher,
+      http_client_provider,
+      progress_bar: ProgressBar::new(ProgressBarStyle::TextOnly),
+      npm_folder: temp_dir.path().join("server/cache_server").to_path_buf(),
+      allowed_origins,
+      maybe_token: maybe_token.map(|token| token.to_string()),
+    };
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/", listener.local_addr().unwrap());
+    deno_core::unsync::spawn(serve_listener(listener, state));
+    (
+      CacheServer::new(Url::parse(&url).unwrap()).unwrap(),
+      http_cache,
+    )
+  }
+
+  fn client_file_fetcher(
+    temp_dir: &TempDir,
+    cache_server: CacheServer,
+  ) -> FileFetcher {
+    FileFetcher::new(
+      Arc::new(GlobalHttpCache::new(
+        temp_dir.path().join("client/remote").to_path_buf(),
+        RealDenoCacheEnv,
+      )),
+      CacheSetting::Use,
+      true,
+      Arc::new(
+        HttpClientProvider::new(None, None)
+          .with_cache_server(Some(cache_server)),
+      ),
+      Default::default(),
+      None,
+    )
+  }
+
+  #[tokio::test]
+  async fn serves_remote_modules() {
+    let temp_dir = TempDir::new();
+    let (cache_server, server_cache) =
+      start_server(&temp_dir, Vec::new(), None).await;
+    // nothing listens on this port, so the module can only come from the
+    // cache server
+    let specifier = resolve_url("http://127.0.0.1:1/mod.ts").unwrap();
+    let source = "export const a = 1;\n";
+    server_cache
+      .set(
+        &specifier,
+        [(
+          "content-type".to_string(),
+          "application/typescript".to_string(),
+        )]
+        .into_iter()
+        .collect(),
+        source.as_bytes(),
+      )
+      .unwrap();
+
+    let fetch = |file_fetcher: FileFetcher,
+                 checksum: Option<LoaderChecksum>| {
+      let specifier = specifier.clone();
+      async move {
+        file_fetcher
+          .fetch_no_follow_with_options(FetchNoFollowOptions {
+            fetch_options: FetchOptions {
+              specifier: &specifier,
+              permissions: FetchPermissionsOptionRef::AllowAll,
+              maybe_auth: None,
+              maybe_accept: None,
+              maybe_cache_setting: None,
+            },
+            maybe_checksum: checksum.as_ref(),
+          })
+          .await
+      }
+    };
+
+    let checksum = LoaderChecksum::new(checksum::gen(&[source.as_bytes()]));
+    let file_fetcher = client_file_fetcher(&temp_dir, cache_server.clone());
+    let Ok(FileOrRedirect::File(file)) =
+      fetch(file_fetcher, Some(checksum)).await
+    else {
+      panic!("expected the module of the cache server");
+    };
+    assert_eq!(file.source.as_ref(), source.as_bytes());
+    assert_eq!(
+      file.maybe_headers.unwrap().get("content-type").unwrap(),
+      "application/typescript"
+    );
+
+    // without a checksum in the lockfile, or with a different one, the
+    // module is downloaded from its origin
+    let file_fetcher =
+      client_file_fetcher(&TempDir::new(), cache_server.clone());
+    let result = fetch(file_fetcher, None).await;
+    assert!(!matches!(result, Ok(FileOrRedirect::File(_))));
+    let checksum = LoaderChecksum::new(checksum::gen(&[b"other"]));
+    let file_fetcher = client_file_fetcher(&TempDir::new(), cache_server);
+    let result = fetch(file_fetcher, Some(checksum)).await;
+    assert!(!matches!(result, Ok(FileOrRedirect::File(_))));
+  }
+
+  #[tokio::test]
+  async fn serves_npm_tarballs() {
+    let temp_dir = TempDir::new();
+    let (cache_server, _) = start_server(&temp_dir, Vec::new(), None).await;
+    let url = Url::parse("http://127.0.0.1:1/pkg/-/pkg-1.0.0.tgz").unwrap();
+    let path = temp_dir
+      .path()
+      .join("server/cache_server")
+      .join(checksum::gen(&[url.as_str().as_bytes()]));
+    path.parent().create_dir_all();
+    path.write("tarball");
+
+    let provider =
+      HttpClientProvider::new(None, None).with_cache_server(Some(cache_server));
+    assert_eq!(
+      provider.download_npm_from_cache_server(&url).await,
+      Some(b"tarball".to_vec())
+    );
+    // a missing entry is a 404, after which the cache server is still used
+    let missing = Url::parse("http://127.0.0.1:1/pkg/-/pkg-2.0.0.tgz").unwrap();
+    assert_eq!(
+      provider.download_npm_from_cache_server(&missing).await,
+      None
+    );
+    assert!(provider.cache_server().is_some());
+  }
+
+  #[tokio::test]
+  async fn requires_the_token() {
+    let temp_dir = TempDir::new();
+    let (cache_server, server_cache) =
+      start_server(&temp_dir, Vec::new(), Some("secret")).await;
+    let specifier = resolve_url("http://127.0.0.1:1/mod.ts").unwrap();
+    server_cache
+      .set(&specifier, Default::default(), b"export {};\n")
+      .unwrap();
+
+    let provider = HttpClientProvider::new(None, None).with_cache_server(Some(
+      cache_server.clone().with_token(Some("wrong".to_string())),
+    ));
+    assert!(provider
+      .fetch_remote_from_cache_server(&specifier)
+      .await
+      .is_none());
+    // the rejected request is an error, so the cache server isn't used again
+    assert!(provider.cache_server().is_none());
+
+    let provider = HttpClientProvider::new(None, None).with_cache_server(Some(
+      cache_server.with_token(Some("secret".to_string())),
+    ));
+    let (_, content) = provider
+      .fetch_remote_from_cache_server(&specifier)
+      .await
+      .unwrap();
+    assert_eq!(content, b"export {};\n");
+  }
+
+  #[tokio::test]
+  async fn only_downloads_from_allowed_origins() {
+    use tokio::io::AsyncReadExt;
+    use tokio::io::AsyncWriteExt;
+
+    // an origin that records the authorization header of each request
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let origin =
+      Url::parse(&format!("http://{}/", listener.local_addr().unwrap()))
+        .unwrap();
+    let seen_auth = Arc::new(deno_core::parking_lot::Mutex::new(Vec::new()));
+    deno_core::unsync::spawn({
+      let seen_auth = seen_auth.clone();
+      async move {
+        loop {
+          let (mut stream, _) = listener.accept().await.unwrap();
+          let mut request = Vec::new();
+          let mut buf = [0; 1024];
+          while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+            let read = stream.read(&mut buf).await.unwrap();
+            if read == 0 {
+              break;
+            }
+            request.extend_from_slice(&buf[..read]);
+          }
+          let request = String::from_utf8(request).unwrap();
+          seen_auth.lock().push(request.lines().find_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            name
+              .eq_ignore_ascii_case("authorization")
+              .then(|| value.trim().to_string())
+          }));
+          let response = "HTTP/1.1 200 OK\r\ncontent-type: application/javascript\r\ncontent-length: 11\r\nconnection: close\r\n\r\nexport {};\n";
+          stream.write_all(response.as_bytes()).await.unwrap();
+        }
+      }
+    });
+    let specifier = origin.join("mod.js").unwrap();
+
+    let temp_dir = TempDir::new();
+    let (cache_server, _) = start_server(&temp_dir, Vec::new(), None).await;
+    let provider =
+      HttpClientProvider::new(None, None).with_cache_server(Some(cache_server));
+    assert!(provider
+      .fetch_remote_from_cache_server(&specifier)
+      .await
+      .is_none());
+    assert!(provider
+      .download_npm_from_cache_server(&specifier)
+      .await
+      .is_none());
+    assert!(seen_auth.lock().is_empty());
+
+    let temp_dir = TempDir::new();
+    let (cache_server, _) =
+      start_server(&temp_dir, vec![origin.clone()], None).await;
+    let provider =
+      HttpClientProvider::new(None, None).with_cache_server(Some(cache_server));
+    let (_, content) = provider
+      .fetch_remote_from_cache_server(&specifier)
+      .await
+      .unwrap();
+    assert_eq!(content, b"export {};\n");
+    // the server downloads without credentials
+    assert_eq!(*seen_auth.lock(), vec![None]);
+  }
+}
@@ -0,0 +1,517 @@
+// Copyright 2018-2024 the Deno authors. All rights reserved. MIT license.
+
+use std::collections::BTreeMap;
+use std::collections::HashSet;
+use std::fmt::Write;
+use std::path::Path;
+use std::path::PathBuf;
+
+use deno_core::error::AnyError;
+use deno_path_util::url_from_directory_path;
+use serde::Serialize;
+
+use crate::cache::CacheDB;
+use crate::cache::CacheDBHealth;
+use crate::cache::DenoDir;
+use crate::colors;
+use crate::display;
+use crate::factory::CliFactory;
+use crate::tools::clean::parse_npm_package_id;
+
+/// The number of origins and packages shown in the text output.
+const TOP_CONSUMERS_LEN: usize = 10;
+
+/// How the cache directory is used.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CacheUsageReport {
+  pub deno_dir: PathBuf,
+  pub total_bytes: u64,
+  /// Sorted by size, largest first.
+  pub categories: Vec<CacheCategoryUsage>,
+  /// The remote modules grouped by origin, largest first.
+  pub remote_origins: Vec<CacheConsumerUsage>,
+  /// Largest first.
+  pub npm_packages: Vec<NpmPackageUsage>,
+  pub databases: Vec<CacheDatabaseUsage>,
+}
+
+impl CacheUsageReport {
+  /// The cached npm packages that the lockfile of the project doesn't
+  /// reference, or none when there is no lockfile.
+  pub fn unused_npm_packages(&self) -> impl Iterator<Item = &NpmPackageUsage> {
+    self
+      .npm_packages
+      .iter()
+      .filter(|package| package.in_lockfile == Some(false))
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CacheCategory {
+  Remote,
+  Npm,
+  Emit,
+  Databases,
+  Registries,
+  OriginStorage,
+  Other,
+}
+
+impl CacheCategory {
+  fn display_name(&self) -> &'static str {
+    match self {
+      CacheCategory::Remote => "remote modules",
+      CacheCategory::Npm => "npm packages",
+      CacheCategory::Emit => "emitted files",
+      CacheCategory::Databases => "databases",
+      CacheCategory::Registries => "registry data",
+      CacheCategory::OriginStorage => "origin storage",
+      CacheCategory::Other => "other files",
+    }
+  }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CacheCategoryUsage {
+  pub category: CacheCategory,
+  pub bytes: u64,
+  pub files: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CacheConsumerUsage {
+  pub name: String,
+  pub bytes: u64,
+  pub files: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NpmPackageUsage {
+  pub name: String,
+  pub version: String,
+  /// Non-zero for the copies made for different peer dependencies.
+  pub copy_index: u8,
+  pub bytes: u64,
+  /// Whether the lockfile of the project references this version, when
+  /// there is a lockfile.
+  pub in_lockfile: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CacheDatabaseUsage {
+  pub name: &'static str,
+  pub path: PathBuf,
+  /// Including the journal files.
+  pub bytes: u64,
+  pub health: CacheDBHealth,
+}
+
+pub fn cache_usage_report(
+  factory: &CliFactory,
+) -> Result<CacheUsageReport, AnyError> {
+  let deno_dir = factory.deno_dir()?;
+  let databases = database_usage(deno_dir);
+  let categories = category_usage(deno_dir, &databases)?;
+  let total_bytes = categories.iter().map(|usage| usage.bytes).sum();
+  let remote_origins = remote_origin_usage(&deno_dir.remote_folder_path())?;
+
+  let maybe_locked = factory.cli_options()?.maybe_lockfile().map(|lockfile| {
+    let lockfile = lockfile.lock();
+    lockfile
+      .content
+      .packages
+      .npm
+      .keys()
+      .filter_map(|id| parse_npm_package_id(id))
+      .map(|(name, version)| (name.to_string(), version.to_string()))
+      .collect::<HashSet<_>>()
+  });
+  let mut npm_packages = Vec::new();
+  let npm_cache_dir = factory.npm_cache_dir()?;
+  let mut walker = walkdir::WalkDir::new(npm_cache_dir.root_dir())
+    .min_depth(1)
+    .into_iter();
+  while let Some(entry) = walker.next() {
+    let entry = entry?;
+    if !entry.file_type().is_dir() {
+      continue;
+    }
+    let Ok(url) = url_from_directory_path(entry.path()) else {
+      continue;
+    };
+    let Some(id) = npm_cache_dir.resolve_package_folder_id_from_specifier(&url)
+    else {
+      continue;
+    };
+    // the files of the package aren't packages themselves
+    walker.skip_current_dir();
+    let in_lockfile = maybe_locked
+      .as_ref()
+      .map(|locked| locked.contains(&(id.name.clone(), id.version.clone())));
+    npm_packages.push(NpmPackageUsage {
+      name: id.name,
+      version: id.version,
+      copy_index: id.copy_index,
+      bytes: dir_usage(entry.path())?.0,
+      in_lockfile,
+    });
+  }
+  npm_packages.sort_by(|a, b| b.bytes.cmp(&a.bytes));
+
+  Ok(CacheUsageReport {
+    deno_dir: deno_dir.root.clone(),
+    total_bytes,
+    categories,
+    remote_origins,
+    npm_packages,
+    databases,
+  })
+}
+
+fn database_usage(deno_dir: &DenoDir) -> Vec<CacheDatabaseUsage> {
+  [
+    ("dependency analysis", deno_dir.dep_analysis_db_file_path()),
+    ("fast check", deno_dir.fast_check_cache_db_file_path()),
+    ("node analysis", deno_dir.node_analysis_db_file_path()),
+    ("type checking", deno_dir.type_checking_cache_db_file_path()),
+    ("code cache", deno_dir.code_cache_db_file_path()),
+    (
+      "fmt incremental",
+      deno_dir.fmt_incremental_cache_db_file_path(),
+    ),
+    (
+      "lint incremental",
+      deno_dir.lint_incremental_cache_db_file_path(),
+    ),
+  ]
+  .into_iter()
+  .map(|(name, path)| {
+    let bytes = database_files(&path)
+      .iter()
+      .filter_map(|path| std::fs::metadata(path).ok())
+      .map(|metadata| metadata.len())
+      .sum();
+    let health =
+      CacheDB::check_health(&path, crate::version::DENO_VERSION_INFO.deno);
+    CacheDatabaseUsage {
+      name,
+      path,
+      bytes,
+      health,
+    }
+  })
+  .collect()
+}
+
+/// The sqlite file followed by its journal files.
+fn database_files(path: &Path) -> Vec<PathBuf> {
+  let mut files = vec![path.to_path_buf()];
+  for suffix in ["-journal", "-wal", "-shm"] {
+    let mut file_name = path.file_name().unwrap().to_os_string();
+    file_name.push(suffix);
+    files.push(path.with_file_name(file_name));
+  }
+  files
+}
+
+fn category_usage(
+  deno_dir: &DenoDir,
+  databases: &[CacheDatabaseUsage],
+) -> Result<Vec<CacheCategoryUsage>, AnyError> {
+  let folders = [
+    (deno_dir.remote_folder_path(), CacheCategory::Remote),
+    (deno_dir.npm_folder_path(), CacheCategory::Npm),
+    (deno_dir.gen_cache.location.clone(), CacheCategory::Emit),
+    (deno_dir.registries_folder_path(), CacheCategory::Registries),
+    (
+      deno_dir.origin_data_folder_path(),
+      CacheCategory::OriginStorage,
+    ),
+  ];
+  let database_files = databases
+    .iter()
+    .flat_map(|database| database_files(&database.path))
+    .collect::<HashSet<_>>();
+
+  let mut usages: Vec<CacheCategoryUsage> = Vec::new();
+  let read_dir = match std::fs::read_dir(&deno_dir.root) {
+    Ok(read_dir) => read_dir,
+    Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+      return Ok(usages)
+    }
+    Err(err) => return Err(err.into()),
+  };
+  for entry in read_dir {
+    let path = entry?.path();
+    let category = folders
+      .iter()
+      .find(|(folder, _)| *folder == path)
+      .map(|(_, category)| *category)
+      .unwrap_or_else(|| {
+        if database_files.contains(&path) {
+          CacheCategory::Databases
+        } else {
+          CacheCategory::Other
+        }
+      });
+    let (bytes, files) = dir_usage(&path)?;
+    match usages.iter_mut().find(|usage| usage.category == category) {
+      Some(usage) => {
+        usage.bytes += bytes;
+        usage.files += files;
+      }
+      None => usages.push(CacheCategoryUsage {
+        category,
+        bytes,
+        files,
+      }),
+    }
+  }
+  usages.sort_by(|a, b| b.bytes.cmp(&a.bytes));
+  Ok(usages)
+}
+
+/// The remote folder is laid out as `<scheme>/<host>/<hashed path>`.
+fn remote_origin_usage(
+  remote_folder: &Path,
+) -> Result<Vec<CacheConsumerUsage>, AnyError> {
+  let mut origins = BTreeMap::new();
+  for scheme_dir in read_sub_dirs(remote_folder)? {
+    let scheme = scheme_dir
+      .file_name()
+      .unwrap()
+      .to_string_lossy()
+      .to_string();
+    for host_dir in read_sub_dirs(&scheme_dir)? {
+      let host = host_dir.file_name().unwrap().to_string_lossy();
+      let name = remote_origin_name(&scheme, &host);
+      let (bytes, files) = dir_usage(&host_dir)?;
+      let usage = origins.entry(name.clone()).or_insert(CacheConsumerUsage {
+        name,
+        bytes: 0,
+        files: 0,
+      });
+      usage.bytes += bytes;
+      usage.files += files;
+    }
+  }
+  let mut origins = origins.into_values().collect::<Vec<_>>();
+  origins.sort_by(|a, b| b.bytes.cmp(&a.bytes));
+  Ok(origins)
+}
+
+/// Ports are stored as `<host>_PORT<port>`.
+fn remote_origin_name(scheme: &str, host_folder: &str) -> String {
+  match host_folder.split_once("_PORT") {
+    Some((host, port)) => format!("{}://{}:{}", scheme, host, port),
+    None => format!("{}://{}", scheme, host_folder),
+  }
+}
+
+fn read_sub_dirs(dir: &Path) -> Result<Vec<PathBuf>, AnyError> {
+  let read_dir = match std::fs::read_dir(dir) {
+    Ok(read_dir) => read_dir,
+    Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+      return Ok(Vec::new())
+    }
+    Err(err) => return Err(err.into()),
+  };
+  let mut dirs = Vec::new();
+  for entry in read_dir {
+    let entry = entry?;
+    if entry.file_type()?.is_dir() {
+      dirs.push(entry.path());
+    }
+  }
+  Ok(dirs)
+}
+
+/// The size and number of the files in `path`, which may be a file.
+fn dir_usage(path: &Path) -> Result<(u64, u64), AnyError> {
+  let mut bytes = 0;
+  let mut files = 0;
+  for entry in walkdir::WalkDir::new(path) {
+    let entry = match entry {
+      Ok(entry) => entry,
+      // removed while walking
+      Err(err)
+        if err.io_error().map(|err| err.kind())
+          == Some(std::io::ErrorKind::NotFound) =>
+      {
+        continue
+      }
+      Err(err) => return Err(err.into()),
+    };
+    if entry.file_type().is_file() {
+      bytes += entry.metadata()?.len();
+      files += 1;
+    }
+  }
+  Ok((bytes, files))
+}
+
+pub fn print_cache_usage(
+  report: &CacheUsageReport,
+  json: bool,
+) -> Result<(), AnyError> {
+  if json {
+    return display::write_json_to_stdout(report);
+  }
+  let size = |bytes: u64| display::human_size(bytes as f64);
+  let mut output = String::new();
+  writeln!(
+    output,
+    "{} {}",
+    colors::bold("DENO_DIR location:"),
+    report.deno_dir.display()
+  )?;
+  writeln!(
+    output,
+    "{} {}",
+    colors::bold("Total:"),
+    size(report.total_bytes)
+  )?;
+
+  writeln!(output)?;
+  writeln!(output, "{}", colors::bold("Categories:"))?;
+  for usage in &report.categories {
+    writeln!(
+      output,
+      "  {:<16} {:>10} {}",
+      usage.category.display_name(),
+      size(usage.bytes),
+      colors::gray(format!("({} files)", usage.files))
+    )?;
+  }
+
+  if !report.remote_origins.is_empty() {
+    writeln!(output)?;
+    writeln!(output, "{}", colors::bold("Largest remote origins:"))?;
+    for usage in report.remote_origins.iter().take(TOP_CONSUMERS_LEN) {
+      writeln!(output, "  {:<40} {:>10}", usage.name, size(usage.bytes))?;
+    }
+  }
+
+  if !report.npm_packages.is_empty() {
+    writeln!(output)?;
+    writeln!(output, "{}", colors::bold("Largest npm packages:"))?;
+    for package in report.npm_packages.iter().take(TOP_CONSUMERS_LEN) {
+      writeln!(
+        output,
+        "  {:<40} {:>10}",
+        npm_package_display_name(package),
+        size(package.bytes)
+      )?;
+    }
+  }
+
+  let unused = report.unused_npm_packages().collect::<Vec<_>>();
+  if !unused.is_empty() {
+    writeln!(output)?;
+    writeln!(
+      output,
+      "{} {}",
+      colors::bold("npm packages not in the lockfile:"),
+      colors::gray(format!(
+        "({} packages, {})",
+        unused.len(),
+        size(unused.iter().map(|package| package.bytes).sum())
+      ))
+    )?;
+    for package in unused {
+      writeln!(
+        output,
+        "  {:<40} {:>10}",
+        npm_package_display_name(package),
+        size(package.bytes)
+      )?;
+    }
+  }
+
+  writeln!(output)?;
+  writeln!(output, "{}", colors::bold("Databases:"))?;
+  for database in &report.databases {
+    let health = match &database.health {
+      CacheDBHealth::Missing => colors::gray("not created").to_string(),
+      CacheDBHealth::Ok => colors::green("ok").to_string(),
+      CacheDBHealth::Outdated { version } => colors::yellow(format!(
+        "outdated, written by {}",
+        if version.is_empty() {
+          "unknown"
+        } else {
+          version
+        }
+      ))
+      .to_string(),
+      CacheDBHealth::Corrupt { message } => {
+        colors::red(format!("corrupt: {}", message)).to_string()
+      }
+    };
+    writeln!(
+      output,
+      "  {:<20} {:>10} {}",
+      database.name,
+      size(database.bytes),
+      health
+    )?;
+  }
+  display::write_to_stdout_ignore_sigpipe(output.as_bytes())?;
+  Ok(())
+}
+
+fn npm_package_display_name(package: &NpmPackageUsage) -> String {
+  if package.copy_index == 0 {
+    format!("{}@{}", package.name, package.version)
+  } else {
+    format!(
+      "{}@{} (copy {})",
+      package.name, package.version, package.copy_index
+    )
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use test_util::TempDir;
+
+  use super::*;
+
+  #[test]
+  fn names_remote_origins() {
+    assert_eq!(
+      remote_origin_name("https", "deno.land"),
+      "https://deno.land"
+    );
+    assert_eq!(
+      remote_origin_name("http", "localhost_PORT4545"),
+      "http://localhost:4545"
+    );
+  }
+
+  #[test]
+  fn sums_remote_origins() {
+    let temp_dir = TempDir::new();
+    let remote = temp_dir.path().join("remote");
+    remote.join("https/deno.land").create_dir_all();
+    remote.join("https/deno.land/a").write("12345");
+    remote.join("https/deno.land/b").write("123");
+    remote.join("http/localhost_PORT4545").create_dir_all();
+    remote.join("http/localhost_PORT4545/c").write("1");
+
+    let origins = remote_origin_usage(remote.as_path()).unwrap();
+    let origins = origins
+      .iter()
+      .map(|usage| (usage.name.as_str(), usage.bytes, usage.files))
+      .collect::<Vec<_>>();
+    assert_eq!(
+      origins,
+      vec![("https://deno.land", 8, 2), ("http://localhost:4545", 1, 1)]
+    );
+  }
+}
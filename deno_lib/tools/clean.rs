@@ -1,10 +1,31 @@
 // Copyright 2018-2024 the Deno authors. All rights reserved. MIT license.
 
+use deno_cache_dir::npm::mixed_case_package_name_decode;
+use deno_cache_dir::url_to_filename;
+use deno_core::anyhow::bail;
 use deno_core::anyhow::Context;
 use deno_core::error::AnyError;
+use deno_core::serde_json;
+use deno_core::url::Url;
+use deno_graph::packages::JsrPackageInfo;
+use deno_graph::packages::JsrPackageVersionInfo;
+use deno_lockfile::Lockfile;
+use deno_semver::package::PackageNv;
+use deno_semver::Version;
+use std::collections::BTreeMap;
+use std::collections::HashSet;
 use std::path::Path;
+use std::path::PathBuf;
+use std::time::Duration;
+use std::time::SystemTime;
 
+use crate::args::jsr_url;
+use crate::args::CleanCategory;
+use crate::args::CleanFlags;
 use crate::cache::DenoDir;
+use crate::cache::GlobalHttpCache;
+use crate::cache::HttpCache;
+use crate::cache::RealDenoCacheEnv;
 use crate::colors;
 use crate::display;
 use crate::util::progress_bar::ProgressBar;
@@ -16,6 +37,8 @@ struct CleanState {
   files_removed: u64,
   dirs_removed: u64,
   bytes_removed: u64,
+  /// Only count what would be removed.
+  dry_run: bool,
   progress_guard: UpdateGuard,
 }
 
@@ -27,32 +50,100 @@ impl CleanState {
   }
 }
 
-pub fn clean() -> Result<(), AnyError> {
+/// What was removed from a category, or from the other files of the cache
+/// directory when there is none.
+#[derive(Debug, Default, Clone, Copy)]
+struct CategoryStats {
+  files: u64,
+  bytes: u64,
+}
+
+pub fn clean(clean_flags: CleanFlags) -> Result<(), AnyError> {
   let deno_dir = DenoDir::new(None)?;
-  if deno_dir.root.exists() {
-    let no_of_files = walkdir::WalkDir::new(&deno_dir.root).into_iter().count();
-    let progress_bar = ProgressBar::new(ProgressBarStyle::ProgressBars);
-    let progress_guard =
-      progress_bar.update_with_prompt(ProgressMessagePrompt::Cleaning, "");
-
-    let mut state = CleanState {
-      files_removed: 0,
-      dirs_removed: 0,
-      bytes_removed: 0,
-      progress_guard,
-    };
-    state
-      .progress_guard
-      .set_total_size(no_of_files.try_into().unwrap());
+  if !deno_dir.root.exists() {
+    return Ok(());
+  }
+  let selection = Selection::new(&deno_dir, &clean_flags)?;
+  let is_everything =
+    clean_flags.categories.is_empty() && selection.is_everything();
+  let entries = collect_entries(&deno_dir, &clean_flags.categories)?
+    .into_iter()
+    .filter(|entry| selection.includes(entry))
+    .collect::<Vec<_>>();
 
-    rm_rf(&mut state, &deno_dir.root)?;
+  let no_of_files = entries
+    .iter()
+    .map(|entry| walkdir::WalkDir::new(&entry.path).into_iter().count())
+    .sum::<usize>();
+  let progress_bar = ProgressBar::new(ProgressBarStyle::ProgressBars);
+  let progress_guard =
+    progress_bar.update_with_prompt(ProgressMessagePrompt::Cleaning, "");
+  let mut state = CleanState {
+    files_removed: 0,
+    dirs_removed: 0,
+    bytes_removed: 0,
+    dry_run: clean_flags.dry_run,
+    progress_guard,
+  };
+  state
+    .progress_guard
+    .set_total_size(no_of_files.try_into().unwrap());
+
+  let mut stats_by_category = BTreeMap::<Option<CleanCategory>, _>::new();
+  for entry in &entries {
+    let files_before = state.files_removed;
+    let bytes_before = state.bytes_removed;
+    rm_rf(&mut state, &entry.path)?;
+    let stats: &mut CategoryStats =
+      stats_by_category.entry(entry.kind.category()).or_default();
+    stats.files += state.files_removed - files_before;
+    stats.bytes += state.bytes_removed - bytes_before;
+  }
+  if !state.dry_run {
+    if is_everything {
+      rm_rf(&mut state, &deno_dir.root)?;
+    } else {
+      for path in [
+        deno_dir.npm_folder_path(),
+        deno_dir.remote_folder_path(),
+        deno_dir.gen_cache.location.clone(),
+      ] {
+        remove_empty_dirs(&path);
+      }
+    }
+  }
 
-    // Drop the guard so that progress bar disappears.
-    drop(state.progress_guard);
+  // Drop the guard so that progress bar disappears.
+  drop(state.progress_guard);
 
+  let verb = if state.dry_run {
+    colors::green("Would remove")
+  } else {
+    colors::green("Removed")
+  };
+  if !is_everything || state.dry_run {
+    // the other files last
+    let mut stats_by_category =
+      stats_by_category.into_iter().collect::<Vec<_>>();
+    stats_by_category
+      .sort_by_key(|(category, _)| (category.is_none(), *category));
+    for (category, stats) in stats_by_category {
+      log::info!(
+        "{} {} {}",
+        verb,
+        category_display_name(category),
+        colors::gray(&format!(
+          "({} files, {})",
+          stats.files,
+          display::human_size(stats.bytes as f64)
+        ))
+      );
+    }
+  }
+  if is_everything {
     log::info!(
       "{} {} {}",
-      colors::green("Removed"),
+      verb,
       deno_dir.root.display(),
       colors::gray(&format!(
         "({} files, {})",
@@ -60,11 +151,555 @@ pub fn clean() -> Result<(), AnyError> {
         display::human_size(state.bytes_removed as f64)
       ))
     );
+  } else if entries.is_empty() {
+    log::info!("Nothing to remove.");
+  }
+
+  Ok(())
+}
+
+fn category_display_name(category: Option<CleanCategory>) -> &'static str {
+  match category {
+    Some(CleanCategory::Npm) => "npm packages",
+    Some(CleanCategory::Remote) => "remote modules",
+    Some(CleanCategory::Emit) => "emitted and code caches",
+    None => "other cache files",
+  }
+}
+
+#[derive(Debug, Clone)]
+enum EntryKind {
+  /// The folder of a version of an npm package, or of a copy of it.
+  NpmPackage {
+    name: String,
+    version: String,
+  },
+  /// The `registry.json` of an npm package.
+  NpmRegistryInfo {
+    name: String,
+  },
+  /// A response in the HTTP cache.
+  Remote,
+  Emit,
+  Other,
+}
+
+impl EntryKind {
+  fn category(&self) -> Option<CleanCategory> {
+    match self {
+      EntryKind::NpmPackage { .. } | EntryKind::NpmRegistryInfo { .. } => {
+        Some(CleanCategory::Npm)
+      }
+      EntryKind::Remote => Some(CleanCategory::Remote),
+      EntryKind::Emit => Some(CleanCategory::Emit),
+      EntryKind::Other => None,
+    }
+  }
+}
+
+/// A file or folder that is removed as a whole.
+#[derive(Debug)]
+struct CleanEntry {
+  path: PathBuf,
+  kind: EntryKind,
+}
+
+/// Lists the entries of the categories, or of the whole cache directory when
+/// there are none.
+fn collect_entries(
+  deno_dir: &DenoDir,
+  categories: &[CleanCategory],
+) -> Result<Vec<CleanEntry>, AnyError> {
+  let includes =
+    |category| categories.is_empty() || categories.contains(&category);
+  let npm_folder = deno_dir.npm_folder_path();
+  let remote_folder = deno_dir.remote_folder_path();
+  let gen_folder = deno_dir.gen_cache.location.clone();
+  // the sqlite files are followed by their -journal, -wal and -shm files
+  let emit_db_file_names = [
+    deno_dir.code_cache_db_file_path(),
+    deno_dir.type_checking_cache_db_file_path(),
+  ]
+  .map(|path| path.file_name().unwrap().to_string_lossy().to_string());
+  let is_emit_db = |path: &Path| {
+    let file_name = path.file_name().unwrap().to_string_lossy();
+    emit_db_file_names
+      .iter()
+      .any(|name| file_name.starts_with(name.as_str()))
+  };
+
+  let mut entries = Vec::new();
+  if includes(CleanCategory::Npm) {
+    collect_npm_entries(&npm_folder, &mut entries)?;
+  }
+  if includes(CleanCategory::Remote) {
+    collect_files(&remote_folder, EntryKind::Remote, &mut entries)?;
+  }
+  if includes(CleanCategory::Emit) {
+    collect_files(&gen_folder, EntryKind::Emit, &mut entries)?;
+  }
+  for dir_entry in std::fs::read_dir(&deno_dir.root)? {
+    let path = dir_entry?.path();
+    if path == npm_folder || path == remote_folder || path == gen_folder {
+      continue;
+    }
+    if is_emit_db(&path) {
+      if includes(CleanCategory::Emit) {
+        entries.push(CleanEntry {
+          path,
+          kind: EntryKind::Emit,
+        });
+      }
+    } else if categories.is_empty() {
+      entries.push(CleanEntry {
+        path,
+        kind: EntryKind::Other,
+      });
+    }
+  }
+  Ok(entries)
+}
+
+fn collect_files(
+  dir: &Path,
+  kind: EntryKind,
+  entries: &mut Vec<CleanEntry>,
+) -> Result<(), AnyError> {
+  if !dir.exists() {
+    return Ok(());
   }
+  for dir_entry in walkdir::WalkDir::new(dir) {
+    let dir_entry = dir_entry?;
+    if dir_entry.file_type().is_file() {
+      entries.push(CleanEntry {
+        path: dir_entry.into_path(),
+        kind: kind.clone(),
+      });
+    }
+  }
+  Ok(())
+}
 
+/// The npm cache is laid out as `<registry>/<name>/<version>`, where scoped
+/// names are two folders and names with upper case letters are encoded.
+fn collect_npm_entries(
+  npm_folder: &Path,
+  entries: &mut Vec<CleanEntry>,
+) -> Result<(), AnyError> {
+  for registry_dir in read_sub_dirs(npm_folder)? {
+    for name_dir in read_sub_dirs(&registry_dir)? {
+      let folder_name = file_name(&name_dir);
+      if folder_name.starts_with('@') {
+        for scoped_name_dir in read_sub_dirs(&name_dir)? {
+          let name = format!("{}/{}", folder_name, file_name(&scoped_name_dir));
+          collect_npm_name_entries(name, &scoped_name_dir, entries)?;
+        }
+      } else {
+        let name = match folder_name.strip_prefix('_') {
+          Some(encoded_name) => {
+            match mixed_case_package_name_decode(encoded_name) {
+              Some(name) => name,
+              None => continue,
+            }
+          }
+          None => folder_name,
+        };
+        collect_npm_name_entries(name, &name_dir, entries)?;
+      }
+    }
+  }
   Ok(())
 }
 
+fn collect_npm_name_entries(
+  name: String,
+  name_dir: &Path,
+  entries: &mut Vec<CleanEntry>,
+) -> Result<(), AnyError> {
+  let registry_info_path = name_dir.join("registry.json");
+  if registry_info_path.exists() {
+    entries.push(CleanEntry {
+      path: registry_info_path,
+      kind: EntryKind::NpmRegistryInfo { name: name.clone() },
+    });
+  }
+  for version_dir in read_sub_dirs(name_dir)? {
+    let folder_name = file_name(&version_dir);
+    // copies of a package have the copy index after the version
+    let version = match folder_name.split_once('_') {
+      Some((version, _)) => version.to_string(),
+      None => folder_name,
+    };
+    entries.push(CleanEntry {
+      path: version_dir,
+      kind: EntryKind::NpmPackage {
+        name: name.clone(),
+        version,
+      },
+    });
+  }
+  Ok(())
+}
+
+fn read_sub_dirs(dir: &Path) -> Result<Vec<PathBuf>, AnyError> {
+  let read_dir = match std::fs::read_dir(dir) {
+    Ok(read_dir) => read_dir,
+    Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+      return Ok(Vec::new())
+    }
+    Err(err) => return Err(err.into()),
+  };
+  let mut dirs = Vec::new();
+  for entry in read_dir {
+    let entry = entry?;
+    if entry.file_type()?.is_dir() {
+      dirs.push(entry.path());
+    }
+  }
+  dirs.sort();
+  Ok(dirs)
+}
+
+fn file_name(path: &Path) -> String {
+  path.file_name().unwrap().to_string_lossy().to_string()
+}
+
+/// Decides which entries are removed.
+struct Selection {
+  maybe_unused_since: Option<SystemTime>,
+  maybe_locked: Option<LockedEntries>,
+  maybe_package: Option<PackageSelection>,
+}
+
+impl Selection {
+  fn new(deno_dir: &DenoDir, flags: &CleanFlags) -> Result<Self, AnyError> {
+    let remote_cache = RemoteCache::new(deno_dir.remote_folder_path());
+    let maybe_unused_since = flags
+      .older_than_days
+      .map(|days| unused_since(SystemTime::now(), days));
+    let maybe_locked = if flags.keep_locked_in.is_empty() {
+      None
+    } else {
+      Some(LockedEntries::find(&flags.keep_locked_in, &remote_cache)?)
+    };
+    let maybe_package = flags
+      .package
+      .as_deref()
+      .map(|package| PackageSelection::parse(package, &remote_cache))
+      .transpose()?;
+    Ok(Self {
+      maybe_unused_since,
+      maybe_locked,
+      maybe_package,
+    })
+  }
+
+  fn is_everything(&self) -> bool {
+    self.maybe_unused_since.is_none()
+      && self.maybe_locked.is_none()
+      && self.maybe_package.is_none()
+  }
+
+  fn includes(&self, entry: &CleanEntry) -> bool {
+    if let Some(locked) = &self.maybe_locked {
+      if locked.contains(entry) {
+        return false;
+      }
+    }
+    if let Some(package) = &self.maybe_package {
+      if !package.contains(entry) {
+        return false;
+      }
+    }
+    if let Some(unused_since) = self.maybe_unused_since {
+      if last_used(&entry.path).is_some_and(|time| time >= unused_since) {
+        return false;
+      }
+    }
+    true
+  }
+}
+
+/// The last time a file of the entry was read or written.
+/// The time `days` days before `now`, or the epoch when that's before it.
+fn unused_since(now: SystemTime, days: u64) -> SystemTime {
+  now
+    .checked_sub(Duration::from_secs(days.saturating_mul(24 * 60 * 60)))
+    .unwrap_or(SystemTime::UNIX_EPOCH)
+}
+
+fn last_used(path: &Path) -> Option<SystemTime> {
+  walkdir::WalkDir::new(path)
+    .into_iter()
+    .filter_map(|entry| entry.ok()?.metadata().ok())
+    .filter(|metadata| metadata.is_file())
+    .filter_map(|metadata| {
+      let modified = metadata.modified().ok();
+      let accessed = metadata.accessed().ok();
+      modified.max(accessed)
+    })
+    .max()
+}
+
+/// The npm packages and remote modules that lockfiles reference.
+#[derive(Debug, Default)]
+struct LockedEntries {
+  npm_packages: HashSet<(String, String)>,
+  npm_names: HashSet<String>,
+  remote_paths: HashSet<PathBuf>,
+}
+
+impl LockedEntries {
+  /// Reads the `deno.lock` files in `dirs` and their sub directories.
+  fn find(
+    dirs: &[String],
+    remote_cache: &RemoteCache,
+  ) -> Result<Self, AnyError> {
+    let mut locked = Self::default();
+    for dir in dirs {
+      let walker = walkdir::WalkDir::new(dir).into_iter().filter_entry(|e| {
+        !matches!(e.file_name().to_str(), Some("node_modules" | ".git"))
+      });
+      for entry in walker {
+        let entry =
+          entry.with_context(|| format!("Failed reading '{}'", dir))?;
+        if entry.file_type().is_file() && entry.file_name() == "deno.lock" {
+          locked.add_lockfile(entry.path(), remote_cache)?;
+        }
+      }
+    }
+    Ok(locked)
+  }
+
+  fn add_lockfile(
+    &mut self,
+    path: &Path,
+    remote_cache: &RemoteCache,
+  ) -> Result<(), AnyError> {
+    let text = std::fs::read_to_string(path)
+      .with_context(|| format!("Failed reading '{}'", path.display()))?;
+    let lockfile = Lockfile::new(deno_lockfile::NewLockfileOptions {
+      file_path: path.to_path_buf(),
+      content: &text,
+      overwrite: false,
+    })
+    .with_context(|| format!("Failed reading lockfile '{}'", path.display()))?;
+    let content = &lockfile.content;
+    let urls = content
+      .remote
+      .keys()
+      .chain(content.redirects.keys())
+      .chain(content.redirects.values())
+      .filter_map(|specifier| Url::parse(specifier).ok());
+    for url in urls {
+      self.remote_paths.extend(remote_cache.path(&url));
+    }
+    for nv in content.packages.jsr.keys() {
+      self
+        .remote_paths
+        .extend(jsr_package_meta_path(remote_cache, &nv.name));
+      self
+        .remote_paths
+        .extend(jsr_package_version_paths(remote_cache, nv));
+    }
+    for id in content.packages.npm.keys() {
+      if let Some((name, version)) = parse_npm_package_id(id) {
+        self.npm_names.insert(name.to_string());
+        self
+          .npm_packages
+          .insert((name.to_string(), version.to_string()));
+      }
+    }
+    Ok(())
+  }
+
+  fn contains(&self, entry: &CleanEntry) -> bool {
+    match &entry.kind {
+      EntryKind::NpmPackage { name, version } => {
+        self.npm_packages.contains(&(name.clone(), version.clone()))
+      }
+      EntryKind::NpmRegistryInfo { name } => self.npm_names.contains(name),
+      EntryKind::Remote => self.remote_paths.contains(&entry.path),
+      // these can't be told apart by package, so they are kept
+      EntryKind::Emit | EntryKind::Other => true,
+    }
+  }
+}
+
+/// Splits `name@version_peer@version` into the name and version.
+pub(crate) fn parse_npm_package_id(id: &str) -> Option<(&str, &str)> {
+  let (name, version) = split_name_and_version(id);
+  let version = version?;
+  let version = version.split_once('_').map_or(version, |(v, _)| v);
+  Some((name, version))
+}
+
+/// Splits `name@version` at the `@` that doesn't start a scope.
+fn split_name_and_version(text: &str) -> (&str, Option<&str>) {
+  let rest = text.strip_prefix('@').unwrap_or(text);
+  match rest.find('@') {
+    Some(at) => {
+      let at = text.len() - rest.len() + at;
+      (&text[..at], Some(&text[at + 1..]))
+    }
+    None => (text, None),
+  }
+}
+
+#[derive(Debug)]
+enum PackageSelection {
+  Npm {
+    name: String,
+    maybe_version: Option<String>,
+  },
+  Jsr {
+    remote_paths: HashSet<PathBuf>,
+  },
+}
+
+impl PackageSelection {
+  fn parse(text: &str, remote_cache: &RemoteCache) -> Result<Self, AnyError> {
+    let (scheme, package) = match text.split_once(':') {
+      Some((scheme @ ("npm" | "jsr"), package)) if !package.is_empty() => {
+        (scheme, package)
+      }
+      _ => bail!(
+        "Invalid package '{}'. Expected for example npm:chalk@5.3.0 or jsr:@std/path.",
+        text
+      ),
+    };
+    let (name, maybe_version) = split_name_and_version(package);
+    if scheme == "npm" {
+      let maybe_version = maybe_version
+        .map(|version| {
+          Version::parse_from_npm(version)
+            .map(|version| version.to_string())
+            .with_context(|| format!("Invalid version in '{}'", text))
+        })
+        .transpose()?;
+      return Ok(Self::Npm {
+        name: name.to_string(),
+        maybe_version,
+      });
+    }
+
+    let versions = match maybe_version {
+      Some(version) => vec![Version::parse_standard(version)
+        .with_context(|| format!("Invalid version in '{}'", text))?],
+      None => {
+        let meta_url = jsr_url().join(&format!("{}/meta.json", name))?;
+        remote_cache
+          .read(&meta_url)
+          .and_then(|data| serde_json::from_slice::<JsrPackageInfo>(&data).ok())
+          .map(|info| info.versions.into_keys().collect())
+          .unwrap_or_default()
+      }
+    };
+    let mut remote_paths = HashSet::new();
+    if maybe_version.is_none() {
+      remote_paths.extend(jsr_package_meta_path(remote_cache, name));
+    }
+    for version in versions {
+      let nv = PackageNv {
+        name: name.to_string(),
+        version,
+      };
+      remote_paths.extend(jsr_package_version_paths(remote_cache, &nv));
+    }
+    Ok(Self::Jsr { remote_paths })
+  }
+
+  fn contains(&self, entry: &CleanEntry) -> bool {
+    match (self, &entry.kind) {
+      (
+        PackageSelection::Npm {
+          name,
+          maybe_version,
+        },
+        EntryKind::NpmPackage {
+          name: entry_name,
+          version,
+        },
+      ) => {
+        name == entry_name
+          && maybe_version.as_ref().map_or(true, |v| v == version)
+      }
+      (
+        PackageSelection::Npm {
+          name,
+          maybe_version: None,
+        },
+        EntryKind::NpmRegistryInfo { name: entry_name },
+      ) => name == entry_name,
+      (PackageSelection::Jsr { remote_paths }, EntryKind::Remote) => {
+        remote_paths.contains(&entry.path)
+      }
+      _ => false,
+    }
+  }
+}
+
+/// The HTTP cache along with the folder it stores its files in.
+struct RemoteCache {
+  folder: PathBuf,
+  http_cache: GlobalHttpCache,
+}
+
+impl RemoteCache {
+  fn new(folder: PathBuf) -> Self {
+    Self {
+      http_cache: GlobalHttpCache::new(folder.clone(), RealDenoCacheEnv),
+      folder,
+    }
+  }
+
+  fn path(&self, url: &Url) -> Option<PathBuf> {
+    let path = url_to_filename(url).ok()?;
+    Some(self.folder.join(path))
+  }
+
+  fn read(&self, url: &Url) -> Option<Vec<u8>> {
+    let key = self.http_cache.cache_item_key(url).ok()?;
+    let entry = self.http_cache.get(&key, None).ok()??;
+    Some(entry.content)
+  }
+}
+
+fn jsr_package_meta_path(
+  remote_cache: &RemoteCache,
+  name: &str,
+) -> Option<PathBuf> {
+  let url = jsr_url().join(&format!("{}/meta.json", name)).ok()?;
+  remote_cache.path(&url)
+}
+
+/// The version metadata of a jsr package and the modules it lists.
+fn jsr_package_version_paths(
+  remote_cache: &RemoteCache,
+  nv: &PackageNv,
+) -> Vec<PathBuf> {
+  let mut paths = Vec::new();
+  let Ok(meta_url) =
+    jsr_url().join(&format!("{}/{}_meta.json", nv.name, nv.version))
+  else {
+    return paths;
+  };
+  paths.extend(remote_cache.path(&meta_url));
+  let Some(info) = remote_cache.read(&meta_url).and_then(|data| {
+    serde_json::from_slice::<JsrPackageVersionInfo>(&data).ok()
+  }) else {
+    return paths;
+  };
+  for module_path in info.manifest.keys() {
+    let module_url =
+      jsr_url().join(&format!("{}/{}{}", nv.name, nv.version, module_path));
+    if let Ok(module_url) = module_url {
+      paths.extend(remote_cache.path(&module_url));
+    }
+  }
+  paths
+}
+
 fn rm_rf(state: &mut CleanState, path: &Path) -> Result<(), AnyError> {
   for entry in walkdir::WalkDir::new(path).contents_first(true) {
     let entry = entry?;
@@ -72,7 +707,9 @@ fn rm_rf(state: &mut CleanState, path: &Path) -> Result<(), AnyError> {
     if entry.file_type().is_dir() {
       state.dirs_removed += 1;
       state.update_progress();
-      std::fs::remove_dir_all(entry.path())?;
+      if !state.dry_run {
+        std::fs::remove_dir_all(entry.path())?;
+      }
     } else {
       remove_file(state, entry.path(), entry.metadata().ok())?;
     }
@@ -91,7 +728,130 @@ fn remove_file(
   }
   state.files_removed += 1;
   state.update_progress();
+  if state.dry_run {
+    return Ok(());
+  }
   std::fs::remove_file(path)
     .with_context(|| format!("Failed to remove file: {}", path.display()))?;
   Ok(())
 }
+
+/// Removes the folders that removing entries left empty.
+fn remove_empty_dirs(path: &Path) {
+  let dirs = walkdir::WalkDir::new(path)
+    .min_depth(1)
+    .contents_first(true)
+    .into_iter()
+    .filter_map(|entry| entry.ok())
+    .filter(|entry| entry.file_type().is_dir());
+  for dir in dirs {
+    // fails for the folders that aren't empty
+    let _ = std::fs::remove_dir(dir.path());
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use test_util::TempDir;
+
+  use super::*;
+
+  #[test]
+  fn parses_npm_package_ids() {
+    assert_eq!(
+      parse_npm_package_id("chalk@5.3.0"),
+      Some(("chalk", "5.3.0"))
+    );
+    assert_eq!(
+      parse_npm_package_id("@types/node@22.5.4"),
+      Some(("@types/node", "22.5.4"))
+    );
+    assert_eq!(
+      parse_npm_package_id("react-dom@18.3.1_react@18.3.1"),
+      Some(("react-dom", "18.3.1"))
+    );
+    assert_eq!(parse_npm_package_id("chalk"), None);
+    assert_eq!(parse_npm_package_id(""), None);
+    assert_eq!(parse_npm_package_id("@"), None);
+    assert_eq!(parse_npm_package_id("é"), None);
+    assert_eq!(parse_npm_package_id("é@1.0.0"), Some(("é", "1.0.0")));
+  }
+
+  #[test]
+  fn computes_unused_since() {
+    let now = SystemTime::UNIX_EPOCH + Duration::from_secs(10 * 24 * 60 * 60);
+    assert_eq!(
+      unused_since(now, 3),
+      SystemTime::UNIX_EPOCH + Duration::from_secs(7 * 24 * 60 * 60)
+    );
+    // far more days than have passed doesn't panic
+    assert_eq!(unused_since(now, 1_000_000_000_000), SystemTime::UNIX_EPOCH);
+    assert_eq!(unused_since(now, u64::MAX), SystemTime::UNIX_EPOCH);
+  }
+
+  #[test]
+  fn collects_npm_entries() {
+    let temp_dir = TempDir::new();
+    let registry_dir = temp_dir.path().join("registry.npmjs.org");
+    registry_dir.join("chalk/5.3.0").create_dir_all();
+    registry_dir.join("chalk/5.3.0_1").create_dir_all();
+    registry_dir.join("chalk/registry.json").write("{}");
+    registry_dir.join("@types/node/22.5.4").create_dir_all();
+
+    let mut entries = Vec::new();
+    collect_npm_entries(temp_dir.path().as_path(), &mut entries).unwrap();
+    let entries = entries
+      .into_iter()
+      .map(|entry| match entry.kind {
+        EntryKind::NpmPackage { name, version } => format!("{name}@{version}"),
+        EntryKind::NpmRegistryInfo { name } => format!("{name} registry"),
+        kind => panic!("unexpected {:?}", kind),
+      })
+      .collect::<Vec<_>>();
+    assert_eq!(
+      entries,
+      vec![
+        "@types/node@22.5.4",
+        "chalk registry",
+        "chalk@5.3.0",
+        "chalk@5.3.0",
+      ]
+    );
+  }
+
+  #[test]
+  fn selects_npm_packages() {
+    let remote_cache = RemoteCache::new(PathBuf::from("/deno/remote"));
+    let selection =
+      PackageSelection::parse("npm:chalk@5.3.0", &remote_cache).unwrap();
+    let entry = |name: &str, version: &str| CleanEntry {
+      path: PathBuf::new(),
+      kind: EntryKind::NpmPackage {
+        name: name.to_string(),
+        version: version.to_string(),
+      },
+    };
+    assert!(selection.contains(&entry("chalk", "5.3.0")));
+    assert!(!selection.contains(&entry("chalk", "5.2.0")));
+    assert!(!selection.contains(&entry("chalk-template", "5.3.0")));
+    assert!(!selection.contains(&CleanEntry {
+      path: PathBuf::new(),
+      kind: EntryKind::NpmRegistryInfo {
+        name: "chalk".to_string()
+      },
+    }));
+
+    assert!(PackageSelection::parse("chalk", &remote_cache).is_err());
+    assert!(PackageSelection::parse("npm:chalk@x", &remote_cache).is_err());
+    assert!(PackageSelection::parse("", &remote_cache).is_err());
+    assert!(PackageSelection::parse("é", &remote_cache).is_err());
+    assert!(matches!(
+      PackageSelection::parse("npm:é", &remote_cache).unwrap(),
+      PackageSelection::Npm { name, maybe_version: None } if name == "é"
+    ));
+    assert!(matches!(
+      PackageSelection::parse("npm:@", &remote_cache).unwrap(),
+      PackageSelection::Npm { name, maybe_version: None } if name == "@"
+    ));
+  }
+}
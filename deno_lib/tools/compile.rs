@@ -1,21 +1,45 @@
 // Copyright 2018-2024 the Deno authors. All rights reserved. MIT license.
 
 use crate::args::check_warn_tsconfig;
+use crate::args::CliOptions;
 use crate::args::CompileFlags;
+use crate::args::CompileInspectFlags;
 use crate::args::Flags;
 use crate::factory::CliFactory;
 use crate::http_util::HttpClientProvider;
+use crate::standalone::binary::CompileFileOptions;
+use crate::standalone::binary::NpmPackageExclude;
 use crate::standalone::binary::StandaloneRelativeFileBaseUrl;
+use crate::standalone::diff_standalone_binaries;
 use crate::standalone::is_standalone_binary;
+use crate::standalone::SignatureStatus;
+use crate::standalone::StandaloneBinaryContents;
+use crate::standalone::StandaloneBinaryInfo;
+use crate::standalone::VfsEntryInfo;
+use crate::standalone::PUBLIC_KEY_ENV_VAR_NAME;
+use crate::tools::info::print_tree_node;
+use crate::tools::info::TreeNode;
+use crate::util::display;
+use crate::util::display::human_size;
+use crate::util::fs::specifier_from_file_path;
+use crate::util::path::is_script_ext;
 use deno_ast::ModuleSpecifier;
+use deno_config::glob::FileCollector;
+use deno_config::glob::FilePatterns;
+use deno_config::glob::PathOrPattern;
+use deno_config::glob::PathOrPatternSet;
 use deno_core::anyhow::bail;
 use deno_core::anyhow::Context;
 use deno_core::error::generic_error;
 use deno_core::error::AnyError;
 use deno_core::resolve_url_or_path;
+use deno_core::specifier_has_uri_scheme;
 use deno_graph::GraphKind;
 use deno_terminal::colors;
 use rand::Rng;
+use std::collections::HashSet;
+use std::fmt;
+use std::fmt::Write;
 use std::path::Path;
 use std::path::PathBuf;
 use std::sync::Arc;
@@ -26,18 +50,71 @@ pub async fn compile(
   flags: Arc<Flags>,
   compile_flags: CompileFlags,
 ) -> Result<(), AnyError> {
+  compile_with_base_binary(flags, compile_flags, None).await
+}
+
+/// Like `compile`, but embeds the script in `base_binary` when it's provided
+/// instead of sourcing the base binary according to the flags.
+pub async fn compile_with_base_binary(
+  flags: Arc<Flags>,
+  mut compile_flags: CompileFlags,
+  base_binary: Option<Vec<u8>>,
+) -> Result<(), AnyError> {
+  if let Some(inspect_flags) = &compile_flags.inspect {
+    return inspect(&compile_flags.source_file, inspect_flags);
+  }
+
+  // when verifying a build, compile to a temporary directory with the base
+  // binary the given executable was compiled with
+  let verify_temp_dir = match &compile_flags.verify_build {
+    Some(expected_path) => {
+      let expected = StandaloneBinaryContents::read(Path::new(expected_path))?;
+      let Some(hash) = &expected.metadata().base_binary_sha256 else {
+        bail!(
+          "'{}' was not compiled with --reproducible, so it can't be verified.",
+          expected_path
+        );
+      };
+      if compile_flags.base_binary_sha256.is_none() {
+        compile_flags.base_binary_sha256 = Some(hash.clone());
+      }
+      Some(tempfile::TempDir::new()?)
+    }
+    None => None,
+  };
+
   let factory = CliFactory::from_flags(flags);
   let cli_options = factory.cli_options()?;
   let module_graph_creator = factory.module_graph_creator().await?;
-  let binary_writer = factory.create_compile_binary_writer().await?;
-  let http_client = factory.http_client_provider();
+  let mut binary_writer = factory.create_compile_binary_writer().await?;
+  if let Some(base_binary) = base_binary {
+    binary_writer.set_base_binary(base_binary);
+  }
+  let http_client = factory.http_client_provider()?;
   let module_specifier = cli_options.resolve_main_module()?;
+  let mut entrypoint_names = HashSet::new();
+  for entrypoint in &compile_flags.entrypoints {
+    if !entrypoint_names.insert(&entrypoint.name) {
+      bail!(
+        "The entrypoint name '{}' is used more than once.",
+        entrypoint.name
+      );
+    }
+  }
+  let (included_modules, file_options) =
+    resolve_compile_files(cli_options, &compile_flags)?;
   let module_roots = {
-    let mut vec = Vec::with_capacity(compile_flags.include.len() + 1);
+    let mut vec = Vec::with_capacity(
+      included_modules.len() + compile_flags.entrypoints.len() + 1,
+    );
     vec.push(module_specifier.clone());
-    for side_module in &compile_flags.include {
-      vec.push(resolve_url_or_path(side_module, cli_options.initial_cwd())?);
+    for entrypoint in &compile_flags.entrypoints {
+      vec.push(resolve_url_or_path(
+        &entrypoint.source_file,
+        cli_options.initial_cwd(),
+      )?);
     }
+    vec.extend(included_modules);
     vec
   };
 
@@ -53,12 +130,19 @@ pub async fn compile(
     );
   }
 
-  let output_path = resolve_compile_executable_output_path(
-    http_client,
-    &compile_flags,
-    cli_options.initial_cwd(),
-  )
-  .await?;
+  let output_path = match (&verify_temp_dir, &compile_flags.verify_build) {
+    (Some(temp_dir), Some(expected_path)) => temp_dir
+      .path()
+      .join(Path::new(expected_path).file_name().unwrap()),
+    _ => {
+      resolve_compile_executable_output_path(
+        http_client,
+        &compile_flags,
+        cli_options.initial_cwd(),
+      )
+      .await?
+    }
+  };
 
   let graph = Arc::try_unwrap(
     module_graph_creator
@@ -80,14 +164,23 @@ pub async fn compile(
   let ts_config_for_emit = cli_options
     .resolve_ts_config_for_emit(deno_config::deno_json::TsConfigType::Emit)?;
   check_warn_tsconfig(&ts_config_for_emit);
+  let asset_specifiers = file_options
+    .assets
+    .iter()
+    .map(|path| specifier_from_file_path(path))
+    .collect::<Result<Vec<_>, _>>()?;
   let root_dir_url = resolve_root_dir_from_specifiers(
     cli_options.workspace().root_dir(),
-    graph.specifiers().map(|(s, _)| s).chain(
-      cli_options
-        .node_modules_dir_path()
-        .and_then(|p| ModuleSpecifier::from_directory_path(p).ok())
-        .iter(),
-    ),
+    graph
+      .specifiers()
+      .map(|(s, _)| s)
+      .chain(
+        cli_options
+          .node_modules_dir_path()
+          .and_then(|p| ModuleSpecifier::from_directory_path(p).ok())
+          .iter(),
+      )
+      .chain(asset_specifiers.iter()),
   );
   log::debug!("Binary root dir: {}", root_dir_url);
   log::info!(
@@ -119,6 +212,7 @@ pub async fn compile(
       &graph,
       StandaloneRelativeFileBaseUrl::from(&root_dir_url),
       module_specifier,
+      &file_options,
       &compile_flags,
       cli_options,
     )
@@ -159,9 +253,365 @@ pub async fn compile(
     return Err(err);
   }
 
+  if let Some(expected_path) = &compile_flags.verify_build {
+    verify_build(Path::new(expected_path), &output_path)?;
+  }
+
   Ok(())
 }
 
+/// Compares a freshly compiled executable with the one that's expected to
+/// have been built from the same sources.
+fn verify_build(
+  expected_path: &Path,
+  actual_path: &Path,
+) -> Result<(), AnyError> {
+  let expected = std::fs::read(expected_path)
+    .with_context(|| format!("Failed reading '{}'", expected_path.display()))?;
+  let actual = std::fs::read(actual_path)
+    .with_context(|| format!("Failed reading '{}'", actual_path.display()))?;
+  let differences = diff_standalone_binaries(&expected, &actual)?;
+  if !differences.is_empty() {
+    bail!(
+      "'{}' could not be reproduced from the sources. These parts differ: {}",
+      expected_path.display(),
+      differences.join(", ")
+    );
+  }
+  log::info!(
+    "{} {} is reproducible",
+    colors::green("Verified"),
+    expected_path.display()
+  );
+  Ok(())
+}
+
+/// Resolves the `--include` and `--exclude` flags along with the `compile`
+/// section of the root deno.json into the modules to add to the module graph
+/// and the files to embed as is.
+fn resolve_compile_files(
+  cli_options: &CliOptions,
+  compile_flags: &CompileFlags,
+) -> Result<(Vec<ModuleSpecifier>, CompileFileOptions), AnyError> {
+  let mut sources = vec![(
+    cli_options.initial_cwd().to_path_buf(),
+    compile_flags.include.clone(),
+    compile_flags.exclude.clone(),
+  )];
+  if let Some((dir_path, config)) = cli_options.resolve_compile_config()? {
+    sources.push((dir_path, config.include, config.exclude));
+  }
+
+  let mut file_options = CompileFileOptions::default();
+  let mut exclude = Vec::new();
+  for (base, _, patterns) in &sources {
+    let mut path_patterns = Vec::with_capacity(patterns.len());
+    for pattern in patterns {
+      match NpmPackageExclude::parse(pattern)? {
+        Some(npm_exclude) => file_options.npm_exclude.push(npm_exclude),
+        None => path_patterns.push(pattern.clone()),
+      }
+    }
+    exclude.extend(
+      PathOrPatternSet::from_exclude_relative_path_or_patterns(
+        base,
+        &path_patterns,
+      )?
+      .into_path_or_patterns(),
+    );
+  }
+  file_options.exclude = PathOrPatternSet::new(exclude);
+
+  let mut modules = Vec::new();
+  for (base, includes, _) in &sources {
+    let mut path_includes = Vec::with_capacity(includes.len());
+    for include in includes {
+      if specifier_has_uri_scheme(include) && !include.starts_with("file:") {
+        modules.push(resolve_url_or_path(include, base)?);
+      } else {
+        path_includes.push(include.clone());
+      }
+    }
+    let include_set = PathOrPatternSet::from_include_relative_path_or_patterns(
+      base,
+      &path_includes,
+    )?;
+    let mut patterns = Vec::new();
+    for path_or_pattern in include_set.into_path_or_patterns() {
+      match path_or_pattern {
+        // explicitly included files are always added to the module graph
+        PathOrPattern::Path(path) if !path.is_dir() => {
+          modules.push(specifier_from_file_path(&path)?);
+        }
+        PathOrPattern::RemoteUrl(url) => modules.push(url),
+        path_or_pattern => patterns.push(path_or_pattern),
+      }
+    }
+    if patterns.is_empty() {
+      continue;
+    }
+    let files = FileCollector::new(|_| true)
+      .ignore_git_folder()
+      .ignore_node_modules()
+      .collect_file_patterns(
+        &deno_config::fs::RealDenoConfigFs,
+        FilePatterns {
+          base: base.clone(),
+          include: Some(PathOrPatternSet::new(patterns)),
+          exclude: file_options.exclude.clone(),
+        },
+      )?;
+    for path in files {
+      if is_script_ext(&path) {
+        modules.push(specifier_from_file_path(&path)?);
+      } else {
+        file_options.assets.push(path);
+      }
+    }
+  }
+  Ok((modules, file_options))
+}
+
+/// Prints, verifies or extracts the contents of an executable created by
+/// `deno compile`.
+fn inspect(
+  binary_path: &str,
+  inspect_flags: &CompileInspectFlags,
+) -> Result<(), AnyError> {
+  let contents = StandaloneBinaryContents::read(Path::new(binary_path))?;
+  if let Some(dir) = &inspect_flags.extract {
+    let count =
+      contents.extract(inspect_flags.entry.as_deref(), Path::new(dir))?;
+    log::info!(
+      "{} {} file(s) to {}",
+      colors::green("Extracted"),
+      count,
+      dir
+    );
+  }
+  if inspect_flags.verify {
+    let problems = contents.verify();
+    if !problems.is_empty() {
+      for problem in &problems {
+        log::error!("{} {}", colors::red("error:"), problem);
+      }
+      bail!("Found {} problem(s) in '{}'.", problems.len(), binary_path);
+    }
+    log::info!("{} {}", colors::green("Verified"), binary_path);
+    if contents.info().signature == SignatureStatus::Unverified {
+      log::warn!(
+        "{} The signature was not checked, set {} to the trusted key.",
+        colors::yellow("Warning"),
+        PUBLIC_KEY_ENV_VAR_NAME
+      );
+    }
+  }
+
+  if inspect_flags.json {
+    display::write_json_to_stdout(&contents.info())
+  } else if inspect_flags.extract.is_none() && !inspect_flags.verify {
+    let mut output = String::new();
+    write_binary_info(&contents.info(), &mut output)?;
+    display::write_to_stdout_ignore_sigpipe(output.as_bytes())?;
+    Ok(())
+  } else {
+    Ok(())
+  }
+}
+
+fn write_binary_info<TWrite: Write>(
+  info: &StandaloneBinaryInfo,
+  writer: &mut TWrite,
+) -> fmt::Result {
+  fn to_tree_node(entry: &VfsEntryInfo) -> TreeNode {
+    match entry {
+      VfsEntryInfo::Directory { name, entries } => TreeNode {
+        text: colors::bold(name).to_string(),
+        children: entries.iter().map(to_tree_node).collect(),
+      },
+      VfsEntryInfo::File {
+        name,
+        size,
+        stored_size,
+      } => {
+        let size = if size == stored_size {
+          human_size(*size as f64)
+        } else {
+          format!(
+            "{}, {} compressed",
+            human_size(*size as f64),
+            human_size(*stored_size as f64)
+          )
+        };
+        TreeNode::from_text(format!("{} {}", name, colors::gray(size)))
+      }
+      VfsEntryInfo::Symlink { name, target } => {
+        TreeNode::from_text(format!("{} {}", name, colors::gray(target)))
+      }
+    }
+  }
+
+  let metadata = info.metadata;
+  let permissions = Flags {
+    permissions: metadata.permissions.clone(),
+    ..Default::default()
+  }
+  .to_permission_args();
+  let mut unstable = metadata.unstable_config.features.clone();
+  if metadata.unstable_config.bare_node_builtins {
+    unstable.push("bare-node-builtins".to_string());
+  }
+  if metadata.unstable_config.detect_cjs {
+    unstable.push("detect-cjs".to_string());
+  }
+  if metadata.unstable_config.sloppy_imports {
+    unstable.push("sloppy-imports".to_string());
+  }
+
+  writeln!(
+    writer,
+    "{} {}",
+    colors::bold("entrypoint:"),
+    metadata.entrypoint_key
+  )?;
+  for (name, entrypoint) in &metadata.entrypoints {
+    writeln!(
+      writer,
+      "{} {}",
+      colors::bold(format!("entrypoint {}:", name)),
+      entrypoint.entrypoint_key
+    )?;
+  }
+  writeln!(
+    writer,
+    "{} {}",
+    colors::bold("permissions:"),
+    if permissions.is_empty() {
+      "none".to_string()
+    } else {
+      permissions.join(" ")
+    }
+  )?;
+  if !metadata.argv.is_empty() {
+    writeln!(
+      writer,
+      "{} {}",
+      colors::bold("args:"),
+      metadata.argv.join(" ")
+    )?;
+  }
+  if !unstable.is_empty() {
+    writeln!(
+      writer,
+      "{} {}",
+      colors::bold("unstable:"),
+      unstable.join(", ")
+    )?;
+  }
+  if !metadata.v8_flags.is_empty() {
+    writeln!(
+      writer,
+      "{} {}",
+      colors::bold("v8 flags:"),
+      metadata.v8_flags.join(" ")
+    )?;
+  }
+  if let Some(location) = &metadata.location {
+    writeln!(writer, "{} {}", colors::bold("location:"), location)?;
+  }
+  if let Some(seed) = metadata.seed {
+    writeln!(writer, "{} {}", colors::bold("seed:"), seed)?;
+  }
+  if let Some(hosts) = &metadata.unsafely_ignore_certificate_errors {
+    writeln!(
+      writer,
+      "{} {}",
+      colors::bold("ignored certificate errors:"),
+      if hosts.is_empty() {
+        "all hosts".to_string()
+      } else {
+        hosts.join(", ")
+      }
+    )?;
+  }
+  if let Some(mode) = metadata.vfs_overlay {
+    writeln!(writer, "{} {:?}", colors::bold("vfs overlay:"), mode)?;
+  }
+  writeln!(
+    writer,
+    "{} {}",
+    colors::bold("signature:"),
+    match (info.signature, &info.public_key) {
+      (SignatureStatus::Unsigned, _) | (_, None) => "none".to_string(),
+      (SignatureStatus::Unverified, Some(public_key)) => format!(
+        "unverified, public key {} (set {} to the trusted key to verify)",
+        public_key, PUBLIC_KEY_ENV_VAR_NAME
+      ),
+      (SignatureStatus::Verified, Some(public_key)) => {
+        format!("verified, public key {}", public_key)
+      }
+      (SignatureStatus::Invalid, Some(public_key)) => format!(
+        "{}, public key {}",
+        colors::red("not signed with the trusted key"),
+        public_key
+      ),
+    }
+  )?;
+  if !metadata.env_vars_from_env_file.is_empty() {
+    writeln!(writer, "{}", colors::bold("env vars (from --env-file):"))?;
+    for (key, value) in &metadata.env_vars_from_env_file {
+      writeln!(writer, "  {}={}", key, value)?;
+    }
+  }
+  if !info.npm_packages.is_empty() {
+    writeln!(
+      writer,
+      "{} {}",
+      colors::bold("npm packages:"),
+      info.npm_packages.len()
+    )?;
+    for package in &info.npm_packages {
+      writeln!(writer, "  npm:{}", package)?;
+    }
+  }
+  if !info.remote_modules.is_empty() {
+    writeln!(
+      writer,
+      "{} {}",
+      colors::bold("remote modules:"),
+      info.remote_modules.len()
+    )?;
+    for module in &info.remote_modules {
+      match (&module.redirect, &module.media_type, module.size) {
+        (Some(redirect), _, _) => {
+          writeln!(writer, "  {} -> {}", module.specifier, redirect)?
+        }
+        (None, Some(media_type), Some(size)) => writeln!(
+          writer,
+          "  {} {}",
+          module.specifier,
+          colors::gray(format!(
+            "({}, {})",
+            media_type,
+            human_size(size as f64)
+          ))
+        )?,
+        _ => writeln!(writer, "  {}", module.specifier)?,
+      }
+    }
+  }
+  if info.code_cache_entries > 0 {
+    writeln!(
+      writer,
+      "{} {} module(s)",
+      colors::bold("code cache:"),
+      info.code_cache_entries
+    )?;
+  }
+  writeln!(writer, "{}", colors::bold("files:"))?;
+  print_tree_node(&to_tree_node(&info.files), writer)
+}
+
 /// This function writes out a final binary to specified path. If output path
 /// is not already standalone binary it will return error instead.
 fn validate_output_path(output_path: &Path) -> Result<(), AnyError> {
@@ -337,6 +787,7 @@ fn resolve_root_dir_from_specifiers<'a>(
 #[cfg(test)]
 mod test {
   pub use super::*;
+  use crate::standalone::create_raw_data_section;
 
   #[tokio::test]
   async fn resolve_compile_executable_output_path_target_linux() {
@@ -351,6 +802,20 @@ mod test {
         no_terminal: false,
         icon: None,
         include: vec![],
+        exclude: vec![],
+        entrypoints: vec![],
+        compress: None,
+        code_cache: false,
+        code_cache_from: None,
+        vfs_overlay: None,
+        sign_key: None,
+        reproducible: false,
+        base_binary_sha256: None,
+        verify_build: None,
+        base_binaries: vec![],
+        base_binary_dir: None,
+        base_binary_manifest: None,
+        inspect: None,
       },
       &std::env::current_dir().unwrap(),
     )
@@ -374,8 +839,22 @@ mod test {
         args: Vec::new(),
         target: Some("x86_64-pc-windows-msvc".to_string()),
         include: vec![],
+        exclude: vec![],
+        entrypoints: vec![],
         icon: None,
         no_terminal: false,
+        compress: None,
+        code_cache: false,
+        code_cache_from: None,
+        vfs_overlay: None,
+        sign_key: None,
+        reproducible: false,
+        base_binary_sha256: None,
+        verify_build: None,
+        base_binaries: vec![],
+        base_binary_dir: None,
+        base_binary_manifest: None,
+        inspect: None,
       },
       &std::env::current_dir().unwrap(),
     )
@@ -444,4 +923,38 @@ mod test {
       );
     }
   }
+
+  #[test]
+  fn verifies_build() {
+    fn create_binary(metadata: &str) -> Vec<u8> {
+      let mut binary = b"exe".to_vec();
+      binary.extend(create_raw_data_section(&[
+        metadata.as_bytes(),
+        b"",
+        b"",
+        b"{}",
+        b"",
+        b"",
+        b"",
+      ]));
+      binary
+    }
+
+    let temp_dir = test_util::TempDir::new();
+    let expected_path = temp_dir.path().join("expected");
+    let actual_path = temp_dir.path().join("actual");
+    expected_path.write(create_binary(r#"{"seed":1}"#));
+    actual_path.write(create_binary(r#"{"seed":1}"#));
+    verify_build(expected_path.as_path(), actual_path.as_path()).unwrap();
+
+    actual_path.write(create_binary(r#"{"seed":2}"#));
+    let err = verify_build(expected_path.as_path(), actual_path.as_path())
+      .unwrap_err()
+      .to_string();
+    assert!(
+      err.ends_with("These parts differ: metadata.seed"),
+      "{}",
+      err
+    );
+  }
 }
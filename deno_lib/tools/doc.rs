@@ -42,7 +42,7 @@ async fn generate_doc_nodes_for_builtin_types(
 ) -> Result<IndexMap<ModuleSpecifier, Vec<doc::DocNode>>, AnyError> {
   let source_file_specifier =
     ModuleSpecifier::parse("file:///lib.deno.d.ts").unwrap();
-  let content = get_types_declaration_file_text();
+  let content = get_types_declaration_file_text()?;
   let loader = deno_graph::source::MemoryLoader::new(
     vec![(
       source_file_specifier.to_string(),
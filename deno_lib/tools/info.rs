@@ -9,9 +9,7 @@ use std::sync::Arc;
 use deno_ast::ModuleSpecifier;
 use deno_core::anyhow::bail;
 use deno_core::error::AnyError;
-use deno_core::resolve_url_or_path;
 use deno_core::serde_json;
-use deno_core::url;
 use deno_graph::Dependency;
 use deno_graph::GraphKind;
 use deno_graph::Module;
@@ -31,9 +29,11 @@ use crate::args::Flags;
 use crate::args::InfoFlags;
 use crate::display;
 use crate::factory::CliFactory;
+use crate::graph_info::resolve_root_specifier;
 use crate::graph_util::graph_exit_integrity_errors;
 use crate::npm::CliNpmResolver;
 use crate::npm::ManagedCliNpmResolver;
+use crate::tools::cache_usage;
 use crate::util::checksum;
 
 const JSON_SCHEMA_VERSION: u8 = 1;
@@ -52,25 +52,11 @@ pub async fn info(
     let npmrc = cli_options.npmrc();
     let resolver = factory.workspace_resolver().await?;
 
-    let cwd_url =
-      url::Url::from_directory_path(cli_options.initial_cwd()).unwrap();
-
-    let maybe_import_specifier = if let Some(import_map) =
-      resolver.maybe_import_map()
-    {
-      if let Ok(imports_specifier) = import_map.resolve(&specifier, &cwd_url) {
-        Some(imports_specifier)
-      } else {
-        None
-      }
-    } else {
-      None
-    };
-
-    let specifier = match maybe_import_specifier {
-      Some(specifier) => specifier,
-      None => resolve_url_or_path(&specifier, cli_options.initial_cwd())?,
-    };
+    let specifier = resolve_root_specifier(
+      &specifier,
+      resolver.maybe_import_map(),
+      cli_options.initial_cwd(),
+    )?;
 
     let mut loader = module_graph_builder.create_graph_loader();
     loader.enable_loading_cache_info(); // for displaying the cache information
@@ -101,6 +87,9 @@ pub async fn info(
       GraphDisplayContext::write(&graph, npm_resolver.as_ref(), &mut output)?;
       display::write_to_stdout_ignore_sigpipe(output.as_bytes())?;
     }
+  } else if info_flags.cache_usage {
+    let report = cache_usage::cache_usage_report(&factory)?;
+    cache_usage::print_cache_usage(&report, info_flags.json)?;
   } else {
     // If it was just "deno info" print location of caches and exit
     print_cache_info(
@@ -273,9 +262,9 @@ fn add_npm_packages_to_json(
   json.insert("npmPackages".to_string(), json_packages.into());
 }
 
-struct TreeNode {
-  text: String,
-  children: Vec<TreeNode>,
+pub struct TreeNode {
+  pub text: String,
+  pub children: Vec<TreeNode>,
 }
 
 impl TreeNode {
@@ -287,7 +276,7 @@ impl TreeNode {
   }
 }
 
-fn print_tree_node<TWrite: Write>(
+pub fn print_tree_node<TWrite: Write>(
   tree_node: &TreeNode,
   writer: &mut TWrite,
 ) -> fmt::Result {
@@ -359,7 +359,7 @@ async fn install_global(
     .await?
     .load_and_type_check_files(&[install_flags_global.module_url.clone()])
     .await?;
-  let http_client = factory.http_client_provider();
+  let http_client = factory.http_client_provider()?;
 
   // create the install shim
   create_install_shim(http_client, &flags, install_flags_global).await
@@ -1,8 +1,12 @@
 // Copyright 2018-2024 the Deno authors. All rights reserved. MIT license.
 
 pub mod bench;
+pub mod cache_bundle;
+pub mod cache_server;
+pub mod cache_usage;
 pub mod check;
 pub mod clean;
+#[cfg(feature = "compile")]
 pub mod compile;
 pub mod coverage;
 pub mod doc;
@@ -10,12 +14,16 @@ pub mod fmt;
 pub mod info;
 pub mod init;
 pub mod installer;
+#[cfg(feature = "jupyter")]
 pub mod jupyter;
 pub mod lint;
 pub mod registry;
+#[cfg(feature = "repl")]
 pub mod repl;
 pub mod run;
 pub mod serve;
 pub mod task;
 pub mod test;
+#[cfg(feature = "upgrade")]
 pub mod upgrade;
+pub mod vendor;
@@ -383,7 +383,7 @@ pub async fn add(
     (None, _) => false,
   };
 
-  let http_client = cli_factory.http_client_provider();
+  let http_client = cli_factory.http_client_provider()?;
   let deps_http_cache = cli_factory.global_http_cache()?;
   let mut deps_file_fetcher = FileFetcher::new(
     deps_http_cache.clone(),
@@ -397,6 +397,8 @@ pub async fn add(
   let npmrc = cli_factory.cli_options().unwrap().npmrc();
 
   deps_file_fetcher.set_download_log_level(log::Level::Trace);
+  deps_file_fetcher
+    .set_mirrors(cli_factory.cli_options().unwrap().mirrors().clone());
   let deps_file_fetcher = Arc::new(deps_file_fetcher);
   let jsr_resolver = Arc::new(JsrFetchResolver::new(deps_file_fetcher.clone()));
   let npm_resolver =
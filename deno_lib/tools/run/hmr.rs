@@ -8,11 +8,14 @@ use deno_ast::MediaType;
 use deno_ast::ModuleKind;
 use deno_core::error::generic_error;
 use deno_core::error::AnyError;
+use deno_core::futures::channel::mpsc::UnboundedReceiver;
+use deno_core::futures::FutureExt;
 use deno_core::futures::StreamExt;
 use deno_core::serde_json::json;
 use deno_core::serde_json::{self};
 use deno_core::url::Url;
 use deno_core::LocalInspectorSession;
+use deno_core::ModuleSpecifier;
 use deno_terminal::colors;
 use tokio::select;
 
@@ -21,6 +24,8 @@ use crate::emit::Emitter;
 use crate::resolver::CjsTracker;
 use crate::util::file_watcher::WatcherCommunicator;
 use crate::util::file_watcher::WatcherRestartMode;
+use crate::worker::HmrRejectReason;
+use crate::worker::ReplaceModuleError;
 
 fn explain(status: &cdp::Status) -> &'static str {
   match status {
@@ -34,6 +39,22 @@ fn explain(status: &cdp::Status) -> &'static str {
   }
 }
 
+fn reject_reason(status: &cdp::Status) -> Option<HmrRejectReason> {
+  match status {
+    cdp::Status::Ok => None,
+    cdp::Status::CompileError => Some(HmrRejectReason::CompileError),
+    cdp::Status::BlockedByActiveGenerator => {
+      Some(HmrRejectReason::BlockedByActiveGenerator)
+    }
+    cdp::Status::BlockedByActiveFunction => {
+      Some(HmrRejectReason::BlockedByActiveFunction)
+    }
+    cdp::Status::BlockedByTopLevelEsModuleChange => {
+      Some(HmrRejectReason::BlockedByTopLevelEsModuleChange)
+    }
+  }
+}
+
 fn should_retry(status: &cdp::Status) -> bool {
   match status {
     cdp::Status::Ok => false,
@@ -224,43 +245,185 @@ impl HmrRunner {
     script_id: &str,
     source: &str,
   ) -> Result<cdp::SetScriptSourceResponse, AnyError> {
-    let result = self
-      .session
-      .post_message(
-        "Debugger.setScriptSource",
-        Some(json!({
-          "scriptId": script_id,
-          "scriptSource": source,
-          "allowTopFrameEditing": true,
-        })),
-      )
-      .await?;
-
-    Ok(serde_json::from_value::<cdp::SetScriptSourceResponse>(
-      result,
-    )?)
+    set_script_source(&mut self.session, script_id, source).await
   }
 
   async fn dispatch_hmr_event(
     &mut self,
     script_id: &str,
   ) -> Result<(), AnyError> {
-    let expr = format!(
-      "dispatchEvent(new CustomEvent(\"hmr\", {{ detail: {{ path: \"{}\" }} }}));",
-      script_id
-    );
+    dispatch_hmr_event(&mut self.session, script_id).await
+  }
+}
+
+async fn set_script_source(
+  session: &mut LocalInspectorSession,
+  script_id: &str,
+  source: &str,
+) -> Result<cdp::SetScriptSourceResponse, AnyError> {
+  let result = session
+    .post_message(
+      "Debugger.setScriptSource",
+      Some(json!({
+        "scriptId": script_id,
+        "scriptSource": source,
+        "allowTopFrameEditing": true,
+      })),
+    )
+    .await?;
+
+  Ok(serde_json::from_value::<cdp::SetScriptSourceResponse>(
+    result,
+  )?)
+}
+
+async fn dispatch_hmr_event(
+  session: &mut LocalInspectorSession,
+  script_id: &str,
+) -> Result<(), AnyError> {
+  let expr = format!(
+    "dispatchEvent(new CustomEvent(\"hmr\", {{ detail: {{ path: \"{}\" }} }}));",
+    script_id
+  );
+
+  let _result = session
+    .post_message(
+      "Runtime.evaluate",
+      Some(json!({
+        "expression": expr,
+        "contextId": Some(1),
+      })),
+    )
+    .await?;
+
+  Ok(())
+}
+
+/// Hot-replaces modules of a running worker with sources provided by an
+/// embedder, without involving the file watcher.
+pub struct HmrModuleReplacer {
+  session: LocalInspectorSession,
+  session_rx: Option<UnboundedReceiver<serde_json::Value>>,
+  script_ids: HashMap<String, String>,
+  cjs_tracker: Arc<CjsTracker>,
+  emitter: Arc<Emitter>,
+}
+
+impl HmrModuleReplacer {
+  pub fn new(
+    cjs_tracker: Arc<CjsTracker>,
+    emitter: Arc<Emitter>,
+    session: LocalInspectorSession,
+  ) -> Self {
+    Self {
+      session,
+      session_rx: None,
+      script_ids: HashMap::new(),
+      cjs_tracker,
+      emitter,
+    }
+  }
 
-    let _result = self
+  /// Records the script ids of all scripts V8 reported as parsed so far.
+  fn collect_parsed_scripts(&mut self) -> Result<(), AnyError> {
+    // route messages V8 already sent, without waiting for new ones
+    while self
       .session
-      .post_message(
-        "Runtime.evaluate",
-        Some(json!({
-          "expression": expr,
-          "contextId": Some(1),
-        })),
-      )
-      .await?;
+      .receive_from_v8_session()
+      .now_or_never()
+      .is_some()
+    {}
+    let Some(session_rx) = self.session_rx.as_mut() else {
+      return Ok(());
+    };
+    while let Ok(Some(notification)) = session_rx.try_next() {
+      let notification =
+        serde_json::from_value::<cdp::Notification>(notification)?;
+      if notification.method != "Debugger.scriptParsed" {
+        continue;
+      }
+      let params =
+        serde_json::from_value::<cdp::ScriptParsed>(notification.params)?;
+      let url = Url::parse(&params.url)
+        .ok()
+        .and_then(|url| canonicalize_file_url(&url))
+        .unwrap_or(params.url);
+      self.script_ids.insert(url, params.script_id);
+    }
+    Ok(())
+  }
+}
+
+fn canonicalize_file_url(url: &Url) -> Option<String> {
+  if url.scheme() != "file" {
+    return None;
+  }
+  let file_path = url.to_file_path().ok()?.canonicalize().ok()?;
+  Some(Url::from_file_path(file_path).ok()?.to_string())
+}
 
+#[async_trait::async_trait(?Send)]
+impl crate::worker::ModuleReplacer for HmrModuleReplacer {
+  async fn start(&mut self) -> Result<(), AnyError> {
+    self.session_rx = Some(self.session.take_notification_rx());
+    // enabling the debugger reports all scripts that were already parsed
+    self
+      .session
+      .post_message::<()>("Debugger.enable", None)
+      .await?;
+    self
+      .session
+      .post_message::<()>("Runtime.enable", None)
+      .await?;
     Ok(())
   }
+
+  async fn replace_module(
+    &mut self,
+    specifier: &ModuleSpecifier,
+    source: String,
+  ) -> Result<(), ReplaceModuleError> {
+    self
+      .collect_parsed_scripts()
+      .map_err(ReplaceModuleError::Other)?;
+    let key =
+      canonicalize_file_url(specifier).unwrap_or_else(|| specifier.to_string());
+    let Some(script_id) = self.script_ids.get(&key).cloned() else {
+      return Err(ReplaceModuleError::NotLoaded(specifier.clone()));
+    };
+
+    let module_kind = self
+      .cjs_tracker
+      .is_maybe_cjs(specifier, MediaType::from_specifier(specifier))
+      .map(ModuleKind::from_is_cjs)
+      .map_err(|err| ReplaceModuleError::Other(err.into()))?;
+    let source_code = self
+      .emitter
+      .emit_for_hmr(specifier, source, module_kind)
+      .map_err(ReplaceModuleError::Other)?;
+
+    let mut tries = 1;
+    loop {
+      let result =
+        set_script_source(&mut self.session, &script_id, &source_code)
+          .await
+          .map_err(ReplaceModuleError::Other)?;
+      let Some(reason) = reject_reason(&result.status) else {
+        break;
+      };
+      if should_retry(&result.status) && tries <= 2 {
+        tries += 1;
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        continue;
+      }
+      return Err(ReplaceModuleError::Rejected {
+        specifier: specifier.clone(),
+        reason,
+      });
+    }
+
+    dispatch_hmr_event(&mut self.session, specifier.as_str())
+      .await
+      .map_err(ReplaceModuleError::Other)
+  }
 }
@@ -1,10 +1,13 @@
 // Copyright 2018-2024 the Deno authors. All rights reserved. MIT license.
 
 use std::io::Read;
+use std::path::PathBuf;
 use std::sync::Arc;
 
 use deno_config::deno_json::NodeModulesDirMode;
+use deno_core::anyhow::bail;
 use deno_core::error::AnyError;
+use deno_core::parking_lot::Mutex;
 use deno_runtime::WorkerExecutionMode;
 
 use crate::args::EvalFlags;
@@ -13,6 +16,11 @@ use crate::args::WatchFlagsWithPaths;
 use crate::factory::CliFactory;
 use crate::file_fetcher::File;
 use crate::util;
+use crate::util::file_watcher::PrintConfig;
+use crate::util::file_watcher::WatcherChangeAction;
+use crate::util::file_watcher::WatcherChannels;
+use crate::util::file_watcher::WatcherCommunicator;
+use crate::util::file_watcher::WatcherEvent;
 use crate::util::file_watcher::WatcherRestartMode;
 
 pub mod hmr;
@@ -56,7 +64,7 @@ pub async fn run_script(
   let factory = CliFactory::from_flags(flags);
   let cli_options = factory.cli_options()?;
   let deno_dir = factory.deno_dir()?;
-  let http_client = factory.http_client_provider();
+  let http_client = factory.http_client_provider()?;
 
   // Run a background task that checks for available upgrades or output
   // if an earlier run of this background task found a new version of Deno.
@@ -116,14 +124,61 @@ async fn run_with_watch(
   flags: Arc<Flags>,
   watch_flags: WatchFlagsWithPaths,
 ) -> Result<i32, AnyError> {
-  util::file_watcher::watch_recv(
+  let print_config = PrintConfig::new_with_banner(
+    if watch_flags.hmr { "HMR" } else { "Watcher" },
+    "Process",
+    !watch_flags.no_clear_screen,
+  );
+  let (watcher_communicator, channels) =
+    WatcherCommunicator::new(&print_config, WatcherRestartMode::Automatic);
+  watch_script(
+    mode,
+    flags,
+    watch_flags,
+    print_config,
+    watcher_communicator,
+    channels,
+  )
+  .await?;
+
+  Ok(0)
+}
+
+/// Runs a script in watch mode under a [`WatchSession`] until the session is
+/// stopped. Nothing is printed to the terminal.
+pub async fn run_with_watch_session(
+  mode: WorkerExecutionMode,
+  flags: Arc<Flags>,
+  watch_flags: WatchFlagsWithPaths,
+  session: &WatchSession,
+) -> Result<(), AnyError> {
+  let Some(channels) = session.channels.lock().take() else {
+    bail!("The watch session has already been run.");
+  };
+  watch_script(
+    mode,
+    flags,
+    watch_flags,
+    PrintConfig::quiet(),
+    session.communicator.clone(),
+    channels,
+  )
+  .await
+}
+
+async fn watch_script(
+  mode: WorkerExecutionMode,
+  flags: Arc<Flags>,
+  watch_flags: WatchFlagsWithPaths,
+  print_config: PrintConfig,
+  watcher_communicator: Arc<WatcherCommunicator>,
+  channels: WatcherChannels,
+) -> Result<(), AnyError> {
+  util::file_watcher::watch_recv_with_communicator(
     flags,
-    util::file_watcher::PrintConfig::new_with_banner(
-      if watch_flags.hmr { "HMR" } else { "Watcher" },
-      "Process",
-      !watch_flags.no_clear_screen,
-    ),
-    WatcherRestartMode::Automatic,
+    print_config,
+    watcher_communicator,
+    channels,
     move |flags, watcher_communicator, _changed_paths| {
       Ok(async move {
         let factory = CliFactory::from_flags_for_watcher(
@@ -157,9 +212,68 @@ async fn run_with_watch(
       })
     },
   )
-  .await?;
+  .await
+}
 
-  Ok(0)
+/// A file watcher session for running a script from an embedder.
+///
+/// The session writes nothing to the terminal. The host observes it through
+/// [`WatchSession::subscribe`], decides how changes are handled with
+/// [`WatchSession::set_change_handler`], and drives it with
+/// [`WatchSession::restart`] and [`WatchSession::stop`].
+pub struct WatchSession {
+  communicator: Arc<WatcherCommunicator>,
+  channels: Mutex<Option<WatcherChannels>>,
+}
+
+impl Default for WatchSession {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl WatchSession {
+  pub fn new() -> Self {
+    let (communicator, channels) = WatcherCommunicator::new(
+      &PrintConfig::quiet(),
+      WatcherRestartMode::Automatic,
+    );
+    Self {
+      communicator,
+      channels: Mutex::new(Some(channels)),
+    }
+  }
+
+  /// Subscribes to changed-path and run lifecycle events.
+  pub fn subscribe(&self) -> tokio::sync::broadcast::Receiver<WatcherEvent> {
+    self.communicator.subscribe()
+  }
+
+  /// Installs a handler deciding whether changed paths restart the script,
+  /// are hot-replaced by the HMR runner, or are ignored. Without a handler
+  /// the script is restarted, or hot-replaced when running with HMR.
+  pub fn set_change_handler(
+    &self,
+    handler: impl Fn(&[PathBuf]) -> WatcherChangeAction + Send + Sync + 'static,
+  ) {
+    self.communicator.set_change_handler(handler);
+  }
+
+  /// Watches additional paths besides the script's module graph.
+  pub fn watch_paths(&self, paths: Vec<PathBuf>) -> Result<(), AnyError> {
+    self.communicator.watch_paths(paths)
+  }
+
+  /// Restarts the script.
+  pub fn restart(&self) -> Result<(), AnyError> {
+    self.communicator.force_restart()
+  }
+
+  /// Stops watching. The running script is dropped and the future returned
+  /// by [`run_with_watch_session`] resolves.
+  pub fn stop(&self) -> Result<(), AnyError> {
+    self.communicator.stop()
+  }
 }
 
 pub async fn eval_command(
@@ -29,7 +29,7 @@ pub async fn serve(
   let factory = CliFactory::from_flags(flags);
   let cli_options = factory.cli_options()?;
   let deno_dir = factory.deno_dir()?;
-  let http_client = factory.http_client_provider();
+  let http_client = factory.http_client_provider()?;
 
   // Run a background task that checks for available upgrades or output
   // if an earlier run of this background task found a new version of Deno.
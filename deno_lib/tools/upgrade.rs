@@ -485,7 +485,7 @@ pub async fn upgrade(
   upgrade_flags: UpgradeFlags,
 ) -> Result<(), AnyError> {
   let factory = CliFactory::from_flags(flags);
-  let http_client_provider = factory.http_client_provider();
+  let http_client_provider = factory.http_client_provider()?;
   let client = http_client_provider.get_or_create()?;
   let current_exe_path = std::env::current_exe()?;
   let full_path_output_flag = match &upgrade_flags.output {
@@ -0,0 +1,870 @@
+// Copyright 2018-2024 the Deno authors. All rights reserved. MIT license.
+
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::collections::HashSet;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use deno_ast::MediaType;
+use deno_ast::ModuleSpecifier;
+use deno_core::anyhow::bail;
+use deno_core::anyhow::Context;
+use deno_core::error::AnyError;
+use deno_core::serde_json;
+use deno_core::url::Url;
+use deno_graph::packages::JsrPackageVersionInfo;
+use deno_graph::GraphKind;
+use deno_graph::Module;
+use deno_graph::ModuleGraph;
+use deno_graph::Resolution;
+use deno_path_util::normalize_path;
+use deno_path_util::url_from_directory_path;
+use deno_path_util::url_to_file_path;
+use deno_terminal::colors;
+
+use crate::args::jsr_url;
+use crate::args::ConfigFlag;
+use crate::args::Flags;
+use crate::args::VendorFlags;
+use crate::factory::CliFactory;
+use crate::graph_info::resolve_root_specifier;
+use crate::util::checksum;
+use crate::util::fs::canonicalize_path_maybe_not_exists;
+
+const IMPORT_MAP_FILE_NAME: &str = "import_map.json";
+
+/// Options for [`vendor`](crate::vendor).
+#[derive(Debug, Default, Clone)]
+pub struct VendorOptions {
+  /// Path to the configuration file. When not set, a `deno.json` is
+  /// discovered from the current working directory.
+  pub config_path: Option<String>,
+  /// Path to an import map that is used instead of the configuration file's.
+  pub import_map_path: Option<String>,
+  /// Directory to write the modules to, `vendor` by default.
+  pub output: Option<String>,
+  /// Verify the directory instead of writing to it.
+  pub check: bool,
+  /// Replace the contents of a non-empty directory.
+  pub force: bool,
+}
+
+/// The outcome of vendoring the modules of a program.
+#[derive(Debug, Clone)]
+pub struct VendorReport {
+  pub output_dir: PathBuf,
+  /// The remote modules in the directory.
+  pub modules: Vec<ModuleSpecifier>,
+  /// The ways the directory differs from the sources and the lockfile,
+  /// only when checking.
+  pub problems: Vec<String>,
+}
+
+pub async fn vendor(
+  flags: Arc<Flags>,
+  vendor_flags: VendorFlags,
+) -> Result<(), AnyError> {
+  let factory = CliFactory::from_flags(flags);
+  let report = vendor_with_factory(
+    &factory,
+    &vendor_flags.files,
+    vendor_flags.output.as_deref(),
+    vendor_flags.check,
+    vendor_flags.force,
+  )
+  .await?;
+  let output_dir = report.output_dir.display();
+  if vendor_flags.check {
+    if !report.problems.is_empty() {
+      for problem in &report.problems {
+        log::error!("{} {}", colors::red("error:"), problem);
+      }
+      bail!(
+        "Found {} problem(s) in '{}'. Run deno vendor without --check to update it.",
+        report.problems.len(),
+        output_dir
+      );
+    }
+    log::info!(
+      "{} {} ({} modules)",
+      colors::green("Verified"),
+      output_dir,
+      report.modules.len()
+    );
+  } else {
+    log::info!(
+      "{} {} modules to {}",
+      colors::green("Vendored"),
+      report.modules.len(),
+      output_dir
+    );
+    log::info!(
+      "{}",
+      colors::gray(format!(
+        "To use them, set \"importMap\" to \"{}\" in the configuration file.",
+        report.output_dir.join(IMPORT_MAP_FILE_NAME).display()
+      ))
+    );
+  }
+  Ok(())
+}
+
+/// Vendors the remote modules of `roots` the same way `deno vendor` does,
+/// using the resolver of the workspace found in the current working
+/// directory.
+pub async fn vendor_modules(
+  roots: &[String],
+  options: VendorOptions,
+) -> Result<VendorReport, AnyError> {
+  let flags = Flags {
+    config_flag: match options.config_path {
+      Some(path) => ConfigFlag::Path(path),
+      None => ConfigFlag::Discover,
+    },
+    import_map_path: options.import_map_path,
+    ..Default::default()
+  };
+  let factory = CliFactory::from_flags(Arc::new(flags));
+  vendor_with_factory(
+    &factory,
+    roots,
+    options.output.as_deref(),
+    options.check,
+    options.force,
+  )
+  .await
+}
+
+async fn vendor_with_factory(
+  factory: &CliFactory,
+  roots: &[String],
+  output: Option<&str>,
+  check: bool,
+  force: bool,
+) -> Result<VendorReport, AnyError> {
+  let cli_options = factory.cli_options()?;
+  let output_dir =
+    normalize_path(cli_options.initial_cwd().join(output.unwrap_or("vendor")));
+  if cli_options.vendor_dir_path() == Some(&output_dir) {
+    bail!(
+      concat!(
+        "Could not vendor to '{}' because the \"vendor\" option of the ",
+        "configuration file uses it. Use --output to choose another directory."
+      ),
+      output_dir.display()
+    );
+  }
+
+  // the checksums in the lockfile before building the graph adds new ones
+  let maybe_locked_checksums = cli_options
+    .maybe_lockfile()
+    .map(|lockfile| lockfile.lock().remote().clone());
+  let resolver = factory.workspace_resolver().await?;
+  let roots = roots
+    .iter()
+    .map(|root| {
+      resolve_root_specifier(
+        root,
+        resolver.maybe_import_map(),
+        cli_options.initial_cwd(),
+      )
+    })
+    .collect::<Result<Vec<_>, _>>()?;
+  let module_graph_creator = factory.module_graph_creator().await?;
+  let graph = module_graph_creator
+    .create_graph(GraphKind::All, roots)
+    .await?;
+  module_graph_creator.graph_valid(&graph)?;
+
+  let file_fetcher = factory.file_fetcher()?;
+  let plan = VendorPlan::build(&graph, &|specifier| {
+    let maybe_file = file_fetcher.fetch_cached(specifier, 10)?;
+    Ok(maybe_file.map(|file| file.source))
+  })?;
+  let modules = plan.files.values().map(|f| f.specifier.clone()).collect();
+  if check {
+    if maybe_locked_checksums.is_none() {
+      log::warn!(
+        "{} No lockfile was found, so the modules are only compared to their sources.",
+        colors::yellow("Warning"),
+      );
+    }
+    let problems = plan.check(&output_dir, maybe_locked_checksums.as_ref())?;
+    return Ok(VendorReport {
+      output_dir,
+      modules,
+      problems,
+    });
+  }
+
+  if let Some(lockfile) = cli_options.maybe_lockfile() {
+    lockfile.write_if_changed()?;
+  }
+  if is_non_empty_dir(&output_dir)? {
+    if !force {
+      bail!(
+        "Directory '{}' is not empty. Use --force to replace its contents.",
+        output_dir.display()
+      );
+    }
+    ensure_replaceable(
+      &output_dir,
+      &[
+        cli_options.initial_cwd().to_path_buf(),
+        cli_options.workspace().root_dir_path(),
+        cli_options.start_dir.dir_path(),
+      ],
+    )?;
+    std::fs::remove_dir_all(&output_dir)
+      .with_context(|| format!("Failed removing '{}'", output_dir.display()))?;
+  }
+  plan.write(&output_dir)?;
+  Ok(VendorReport {
+    output_dir,
+    modules,
+    problems: Vec::new(),
+  })
+}
+
+/// Refuses to replace the output directory when it's one of the
+/// `protected_dirs` or contains one of them, as then it's not only vendored
+/// files that would be removed.
+fn ensure_replaceable(
+  output_dir: &Path,
+  protected_dirs: &[PathBuf],
+) -> Result<(), AnyError> {
+  let output_dir = canonicalize_path_maybe_not_exists(output_dir)?;
+  for dir in protected_dirs {
+    let dir = canonicalize_path_maybe_not_exists(dir)?;
+    if dir.starts_with(&output_dir) {
+      bail!(
+        "Refusing to replace '{}' because it contains '{}'. Choose another directory with --output.",
+        output_dir.display(),
+        dir.display()
+      );
+    }
+  }
+  Ok(())
+}
+
+struct VendoredFile {
+  specifier: ModuleSpecifier,
+  source: Arc<[u8]>,
+  /// For jsr modules, the checksum in the manifest of the package version,
+  /// which the lockfile has the checksum of instead of the module's.
+  package_checksum: Option<String>,
+}
+
+/// The files of the vendor directory and the import map that resolves the
+/// remote modules to them.
+#[derive(Default)]
+struct VendorPlan {
+  /// Keyed by the path relative to the vendor directory, in URL form.
+  files: BTreeMap<String, VendoredFile>,
+  paths: BTreeMap<ModuleSpecifier, String>,
+  imports: BTreeMap<String, String>,
+}
+
+impl VendorPlan {
+  /// Plans the vendor directory of the remote modules of `graph`, with
+  /// `fetch_cached` getting the cached bytes of a URL.
+  fn build(
+    graph: &ModuleGraph,
+    fetch_cached: &dyn Fn(
+      &ModuleSpecifier,
+    ) -> Result<Option<Arc<[u8]>>, AnyError>,
+  ) -> Result<Self, AnyError> {
+    let mut plan = Self::default();
+    let mut taken_paths = HashSet::new();
+    for module in graph.modules() {
+      let (media_type, source) = match module {
+        Module::Js(module) => (module.media_type, &module.source),
+        Module::Json(module) => (module.media_type, &module.source),
+        _ => continue,
+      };
+      let specifier = module.specifier();
+      if !is_remote(specifier) {
+        continue;
+      }
+      let mut path = url_to_vendor_path(specifier, media_type);
+      if !taken_paths.insert(path.to_lowercase()) {
+        // case insensitive file systems would also consider it taken
+        path = with_hash_suffix(&path, specifier.as_str());
+        taken_paths.insert(path.to_lowercase());
+      }
+      // prefer the bytes as they were downloaded, which the lockfile
+      // checksums are for
+      let source = match fetch_cached(specifier)? {
+        Some(source) => source,
+        None => Arc::from(source.as_bytes()),
+      };
+      let package_checksum = jsr_package_checksum(specifier, fetch_cached)?;
+      if path == naive_vendor_path(specifier) {
+        plan.imports.insert(
+          format!("{}/", specifier.origin().ascii_serialization()),
+          format!("./{}/", host_dir_name(specifier)),
+        );
+      } else {
+        plan
+          .imports
+          .insert(specifier.to_string(), format!("./{}", path));
+      }
+      plan.paths.insert(specifier.clone(), path.clone());
+      plan.files.insert(
+        path,
+        VendoredFile {
+          specifier: specifier.clone(),
+          source,
+          package_checksum,
+        },
+      );
+    }
+
+    // redirects, including the ones of jsr specifiers to jsr.io
+    for from in graph.redirects.keys() {
+      if let Some(path) = plan.paths.get(graph.resolve(from)) {
+        let path = format!("./{}", path);
+        plan.add_import(from.to_string(), path);
+      }
+    }
+
+    // bare specifiers, and relative ones that don't resolve to the vendored
+    // module from the vendored importer
+    for module in graph.modules() {
+      let Some(module) = module.js() else {
+        continue;
+      };
+      let dependencies = module
+        .dependencies
+        .iter()
+        .flat_map(|(text, dep)| {
+          [(text, &dep.maybe_code), (text, &dep.maybe_type)]
+        })
+        .chain(
+          module
+            .maybe_types_dependency
+            .iter()
+            .map(|dep| (&dep.specifier, &dep.dependency)),
+        );
+      for (text, resolution) in dependencies {
+        let Resolution::Ok(resolved) = resolution else {
+          continue;
+        };
+        let target = graph.resolve(&resolved.specifier);
+        let Some(path) = plan.paths.get(target) else {
+          continue;
+        };
+        if is_relative_specifier(text) {
+          let Some(importer_path) = plan.paths.get(&module.specifier) else {
+            continue;
+          };
+          let base = Url::parse("file:///").unwrap();
+          let naive = base.join(importer_path)?.join(text)?;
+          if naive != base.join(path)? {
+            let key = format!(".{}", naive.path());
+            plan.add_import(key, format!("./{}", path));
+          }
+        } else if text != target.as_str() {
+          plan.add_import(text.clone(), format!("./{}", path));
+        }
+      }
+    }
+    Ok(plan)
+  }
+
+  fn add_import(&mut self, key: String, path: String) {
+    if let Some(existing) = self.imports.get(&key) {
+      if existing != &path {
+        log::warn!(
+          "{} '{}' resolves to both '{}' and '{}'. Using the first.",
+          colors::yellow("Warning"),
+          key,
+          existing,
+          path
+        );
+      }
+      return;
+    }
+    self.imports.insert(key, path);
+  }
+
+  fn import_map_json(&self) -> serde_json::Value {
+    serde_json::json!({ "imports": self.imports })
+  }
+
+  fn write(&self, output_dir: &Path) -> Result<(), AnyError> {
+    for (path, file) in &self.files {
+      let file_path = vendor_file_path(output_dir, path)?;
+      std::fs::create_dir_all(file_path.parent().unwrap())?;
+      std::fs::write(&file_path, &file.source)
+        .with_context(|| format!("Failed writing '{}'", file_path.display()))?;
+    }
+    let import_map = serde_json::to_string_pretty(&self.import_map_json())?;
+    std::fs::create_dir_all(output_dir)?;
+    std::fs::write(
+      output_dir.join(IMPORT_MAP_FILE_NAME),
+      format!("{}\n", import_map),
+    )?;
+    Ok(())
+  }
+
+  /// Compares the vendor directory with the sources and the lockfile
+  /// checksums, returning the differences.
+  fn check(
+    &self,
+    output_dir: &Path,
+    maybe_locked_checksums: Option<&BTreeMap<String, String>>,
+  ) -> Result<Vec<String>, AnyError> {
+    if !output_dir.is_dir() {
+      return Ok(vec![format!("'{}' does not exist", output_dir.display())]);
+    }
+    let mut problems = Vec::new();
+    let mut expected_paths = BTreeSet::new();
+    for (path, file) in &self.files {
+      let file_path = vendor_file_path(output_dir, path)?;
+      expected_paths.insert(file_path.clone());
+      let Ok(data) = std::fs::read(&file_path) else {
+        problems.push(format!("{}: missing ({})", path, file.specifier));
+        continue;
+      };
+      if *data != *file.source {
+        problems.push(format!("{}: differs from {}", path, file.specifier));
+      }
+      if let Some(locked_checksums) = maybe_locked_checksums {
+        let expected = locked_checksums
+          .get(file.specifier.as_str())
+          .or(file.package_checksum.as_ref());
+        match expected {
+          Some(expected) => {
+            if &checksum::gen(&[&data]) != expected {
+              problems.push(format!(
+                "{}: does not match the lockfile checksum of {}",
+                path, file.specifier
+              ));
+            }
+          }
+          None => {
+            problems.push(format!(
+              "{}: {} is not in the lockfile",
+              path, file.specifier
+            ));
+          }
+        }
+      }
+    }
+
+    let import_map_path = output_dir.join(IMPORT_MAP_FILE_NAME);
+    expected_paths.insert(import_map_path.clone());
+    let import_map = std::fs::read_to_string(&import_map_path)
+      .ok()
+      .and_then(|text| serde_json::from_str::<serde_json::Value>(&text).ok());
+    if import_map.as_ref() != Some(&self.import_map_json()) {
+      problems.push(format!("{}: out of date", IMPORT_MAP_FILE_NAME));
+    }
+
+    for entry in walkdir::WalkDir::new(output_dir) {
+      let entry = entry?;
+      if entry.file_type().is_file() && !expected_paths.contains(entry.path()) {
+        let path = entry.path().strip_prefix(output_dir).unwrap();
+        problems
+          .push(format!("{}: not part of the module graph", path.display()));
+      }
+    }
+    Ok(problems)
+  }
+}
+
+/// Gets the sha256 checksum of a jsr module from the manifest of its package
+/// version, if it's cached.
+fn jsr_package_checksum(
+  specifier: &ModuleSpecifier,
+  fetch_cached: &dyn Fn(
+    &ModuleSpecifier,
+  ) -> Result<Option<Arc<[u8]>>, AnyError>,
+) -> Result<Option<String>, AnyError> {
+  let Some(path) = specifier.as_str().strip_prefix(jsr_url().as_str()) else {
+    return Ok(None);
+  };
+  let mut parts = path.splitn(4, '/');
+  let (Some(scope), Some(name), Some(version), Some(module_path)) =
+    (parts.next(), parts.next(), parts.next(), parts.next())
+  else {
+    return Ok(None);
+  };
+  let meta_url =
+    jsr_url().join(&format!("{}/{}/{}_meta.json", scope, name, version))?;
+  let Some(meta) = fetch_cached(&meta_url)? else {
+    return Ok(None);
+  };
+  let info = serde_json::from_slice::<JsrPackageVersionInfo>(&meta)
+    .with_context(|| format!("Failed parsing '{}'", meta_url))?;
+  Ok(
+    info
+      .manifest
+      .get(&format!("/{}", module_path))
+      .and_then(|entry| entry.checksum.strip_prefix("sha256-"))
+      .map(|checksum| checksum.to_string()),
+  )
+}
+
+fn is_remote(specifier: &ModuleSpecifier) -> bool {
+  matches!(specifier.scheme(), "http" | "https")
+}
+
+fn is_relative_specifier(text: &str) -> bool {
+  text.starts_with("./") || text.starts_with("../") || text.starts_with('/')
+}
+
+fn is_non_empty_dir(path: &Path) -> Result<bool, AnyError> {
+  match std::fs::read_dir(path) {
+    Ok(mut entries) => Ok(entries.next().is_some()),
+    Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(false),
+    Err(err) => Err(err.into()),
+  }
+}
+
+/// The directory of the modules of a host, with the port if any. Modules
+/// not served over https are kept apart.
+fn host_dir_name(specifier: &ModuleSpecifier) -> String {
+  let mut name = String::new();
+  if specifier.scheme() != "https" {
+    name.push_str(specifier.scheme());
+    name.push('_');
+  }
+  name.push_str(&sanitize_path_segment(
+    specifier.host_str().unwrap_or_default(),
+  ));
+  if let Some(port) = specifier.port() {
+    name.push_str(&format!("_{}", port));
+  }
+  name
+}
+
+/// The path the module would have if its URL was copied as is, which the
+/// import map can resolve by the URL's origin alone.
+fn naive_vendor_path(specifier: &ModuleSpecifier) -> String {
+  format!("{}{}", host_dir_name(specifier), specifier.path())
+}
+
+/// The path of a module relative to the vendor directory, in URL form.
+/// Segments that are invalid on some file systems are replaced, and the
+/// query and a missing or misleading extension are appended to the name.
+fn url_to_vendor_path(
+  specifier: &ModuleSpecifier,
+  media_type: MediaType,
+) -> String {
+  let mut path = host_dir_name(specifier);
+  let segments = specifier.path_segments().into_iter().flatten();
+  for segment in segments {
+    path.push('/');
+    path.push_str(&sanitize_path_segment(segment));
+  }
+  if path.ends_with('/') {
+    path.push_str("index");
+  }
+  if let Some(query) = specifier.query() {
+    path = with_hash_suffix(&path, query);
+  }
+  let file_url = Url::parse("file:///").unwrap().join(&path);
+  let has_extension = file_url
+    .map(|url| MediaType::from_specifier(&url) == media_type)
+    .unwrap_or(false);
+  if !has_extension {
+    path.push_str(media_type.as_ts_extension());
+  }
+  path
+}
+
+fn sanitize_path_segment(segment: &str) -> String {
+  segment
+    .chars()
+    .map(|c| match c {
+      ':' | '*' | '|' | '\\' | '"' | '<' | '>' | '?' => '_',
+      c => c,
+    })
+    .collect()
+}
+
+/// Adds a short hash of `value` to the name, before the extension.
+fn with_hash_suffix(path: &str, value: &str) -> String {
+  let hash = &checksum::gen(&[value.as_bytes()])[..8];
+  let name_start = path.rfind('/').map(|i| i + 1).unwrap_or(0);
+  match path[name_start..].find('.') {
+    Some(dot) => {
+      let dot = name_start + dot;
+      format!("{}_{}{}", &path[..dot], hash, &path[dot..])
+    }
+    None => format!("{}_{}", path, hash),
+  }
+}
+
+/// Converts a path in URL form to a file path, decoding it the same way
+/// resolving the URL through the import map does.
+fn vendor_file_path(
+  output_dir: &Path,
+  path: &str,
+) -> Result<PathBuf, AnyError> {
+  let dir_url = url_from_directory_path(output_dir)?;
+  Ok(url_to_file_path(&dir_url.join(path)?)?)
+}
+
+#[cfg(test)]
+mod test {
+  use deno_graph::source::MemoryLoader;
+  use deno_graph::source::Source;
+  use test_util::TempDir;
+
+  use super::*;
+
+  fn vendor_path(specifier: &str, media_type: MediaType) -> String {
+    url_to_vendor_path(&ModuleSpecifier::parse(specifier).unwrap(), media_type)
+  }
+
+  #[test]
+  fn maps_urls_to_vendor_paths() {
+    assert_eq!(
+      vendor_path("https://deno.land/std/path/mod.ts", MediaType::TypeScript),
+      "deno.land/std/path/mod.ts"
+    );
+    assert_eq!(
+      vendor_path(
+        "https://jsr.io/@std/path/1.0.8/mod.ts",
+        MediaType::TypeScript
+      ),
+      "jsr.io/@std/path/1.0.8/mod.ts"
+    );
+    assert_eq!(
+      vendor_path("http://localhost:4545/mod.ts", MediaType::TypeScript),
+      "http_localhost_4545/mod.ts"
+    );
+    // missing or misleading extensions
+    assert_eq!(
+      vendor_path("https://esm.sh/preact", MediaType::JavaScript),
+      "esm.sh/preact.js"
+    );
+    assert_eq!(
+      vendor_path("https://esm.sh/preact/", MediaType::Dts),
+      "esm.sh/preact/index.d.ts"
+    );
+    assert_eq!(
+      vendor_path("https://example.com/mod.ts", MediaType::JavaScript),
+      "example.com/mod.ts.js"
+    );
+    // queries and characters invalid on windows
+    let path =
+      vendor_path("https://esm.sh/a:b.js?target=es2022", MediaType::JavaScript);
+    assert!(path.starts_with("esm.sh/a_b_"));
+    assert!(path.ends_with(".js"));
+    assert_ne!(
+      path,
+      vendor_path("https://esm.sh/a:b.js?target=deno", MediaType::JavaScript)
+    );
+  }
+
+  #[test]
+  fn naive_paths_only_match_plain_urls() {
+    let specifier =
+      ModuleSpecifier::parse("https://deno.land/std/mod.ts").unwrap();
+    assert_eq!(
+      naive_vendor_path(&specifier),
+      url_to_vendor_path(&specifier, MediaType::TypeScript)
+    );
+    let specifier = ModuleSpecifier::parse("https://esm.sh/preact").unwrap();
+    assert_ne!(
+      naive_vendor_path(&specifier),
+      url_to_vendor_path(&specifier, MediaType::JavaScript)
+    );
+  }
+
+  #[test]
+  fn adds_hash_suffix_before_extension() {
+    let hash = &checksum::gen(&[b"x"])[..8];
+    assert_eq!(
+      with_hash_suffix("a.b/mod.d.ts", "x"),
+      format!("a.b/mod_{}.d.ts", hash)
+    );
+    assert_eq!(with_hash_suffix("a/mod", "x"), format!("a/mod_{}", hash));
+  }
+
+  async fn build_graph(modules: &[(&str, &str, Option<&str>)]) -> ModuleGraph {
+    let loader = MemoryLoader::new(
+      modules
+        .iter()
+        .map(|(specifier, content, content_type)| {
+          (
+            *specifier,
+            Source::Module {
+              specifier: *specifier,
+              maybe_headers: content_type
+                .map(|content_type| vec![("content-type", content_type)]),
+              content: *content,
+            },
+          )
+        })
+        .collect(),
+      Vec::new(),
+    );
+    let mut graph = ModuleGraph::new(GraphKind::All);
+    graph
+      .build(
+        vec![ModuleSpecifier::parse(modules[0].0).unwrap()],
+        &loader,
+        Default::default(),
+      )
+      .await;
+    graph.valid().unwrap();
+    graph
+  }
+
+  #[tokio::test]
+  async fn builds_and_checks_vendor_plan() {
+    let graph = build_graph(&[
+      (
+        "file:///main.ts",
+        "import 'https://deno.land/x/mod.ts'; import 'https://esm.sh/preact';",
+        None,
+      ),
+      ("https://deno.land/x/mod.ts", "import './dep.ts';", None),
+      ("https://deno.land/x/dep.ts", "export {};", None),
+      (
+        "https://esm.sh/preact",
+        "export default 1;",
+        Some("application/javascript"),
+      ),
+    ])
+    .await;
+    let plan = VendorPlan::build(&graph, &|_| Ok(None)).unwrap();
+    assert_eq!(
+      plan.files.keys().collect::<Vec<_>>(),
+      vec![
+        "deno.land/x/dep.ts",
+        "deno.land/x/mod.ts",
+        "esm.sh/preact.js"
+      ]
+    );
+    assert_eq!(
+      plan.import_map_json(),
+      serde_json::json!({
+        "imports": {
+          "https://deno.land/": "./deno.land/",
+          "https://esm.sh/preact": "./esm.sh/preact.js",
+        }
+      })
+    );
+
+    let temp_dir = TempDir::new();
+    let output_dir = temp_dir.path().join("vendor");
+    let locked_checksums = plan
+      .files
+      .values()
+      .map(|file| (file.specifier.to_string(), checksum::gen(&[&file.source])))
+      .collect::<BTreeMap<_, _>>();
+    assert_eq!(
+      plan
+        .check(output_dir.as_path(), Some(&locked_checksums))
+        .unwrap()
+        .len(),
+      1
+    );
+    plan.write(output_dir.as_path()).unwrap();
+    assert!(plan
+      .check(output_dir.as_path(), Some(&locked_checksums))
+      .unwrap()
+      .is_empty());
+
+    output_dir
+      .join("deno.land/x/dep.ts")
+      .write("export const a = 1;");
+    output_dir.join("other.ts").write("");
+    let mut locked_checksums = locked_checksums;
+    locked_checksums.remove("https://esm.sh/preact");
+    assert_eq!(
+      plan
+        .check(output_dir.as_path(), Some(&locked_checksums))
+        .unwrap(),
+      vec![
+        "deno.land/x/dep.ts: differs from https://deno.land/x/dep.ts",
+        "deno.land/x/dep.ts: does not match the lockfile checksum of https://deno.land/x/dep.ts",
+        "esm.sh/preact.js: https://esm.sh/preact is not in the lockfile",
+        "other.ts: not part of the module graph",
+      ]
+    );
+  }
+
+  #[test]
+  fn checks_jsr_modules_against_package_manifest() {
+    let source = "export {};";
+    let meta = serde_json::json!({
+      "manifest": {
+        "/mod.ts": {
+          "size": source.len(),
+          "checksum": format!("sha256-{}", checksum::gen(&[source.as_bytes()])),
+        },
+      },
+      "exports": { ".": "./mod.ts" },
+    });
+    let meta: Arc<[u8]> = serde_json::to_vec(&meta).unwrap().into();
+    let fetch_cached = |specifier: &ModuleSpecifier| -> Result<_, AnyError> {
+      Ok(
+        (specifier.as_str() == "https://jsr.io/@std/a/1.0.0_meta.json")
+          .then(|| meta.clone()),
+      )
+    };
+    let specifier =
+      ModuleSpecifier::parse("https://jsr.io/@std/a/1.0.0/mod.ts").unwrap();
+    let package_checksum =
+      jsr_package_checksum(&specifier, &fetch_cached).unwrap();
+    assert_eq!(package_checksum, Some(checksum::gen(&[source.as_bytes()])));
+    let other_specifier =
+      ModuleSpecifier::parse("https://deno.land/x/mod.ts").unwrap();
+    assert_eq!(
+      jsr_package_checksum(&other_specifier, &fetch_cached).unwrap(),
+      None
+    );
+
+    let module_path = "jsr.io/@std/a/1.0.0/mod.ts";
+    let mut plan = VendorPlan::default();
+    plan.files.insert(
+      module_path.to_string(),
+      VendoredFile {
+        specifier,
+        source: Arc::from(source.as_bytes()),
+        package_checksum,
+      },
+    );
+    let temp_dir = TempDir::new();
+    let output_dir = temp_dir.path().join("vendor");
+    plan.write(output_dir.as_path()).unwrap();
+    // jsr modules aren't in the remote checksums of the lockfile
+    let locked_checksums = BTreeMap::new();
+    assert!(plan
+      .check(output_dir.as_path(), Some(&locked_checksums))
+      .unwrap()
+      .is_empty());
+
+    output_dir.join(module_path).write("export const a = 1;");
+    let problems = plan
+      .check(output_dir.as_path(), Some(&locked_checksums))
+      .unwrap();
+    assert!(problems.contains(&format!(
+      "{}: does not match the lockfile checksum of https://{}",
+      module_path, module_path
+    )));
+  }
+
+  #[test]
+  fn refuses_to_replace_protected_dirs() {
+    let temp_dir = TempDir::new();
+    let project_dir = temp_dir.path().join("project");
+    project_dir.create_dir_all();
+    let protected_dirs = [project_dir.to_path_buf()];
+    ensure_replaceable(project_dir.join("vendor").as_path(), &protected_dirs)
+      .unwrap();
+    assert!(ensure_replaceable(project_dir.as_path(), &protected_dirs).is_err());
+    assert!(
+      ensure_replaceable(temp_dir.path().as_path(), &protected_dirs).is_err()
+    );
+  }
+}
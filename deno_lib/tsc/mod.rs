@@ -58,6 +58,7 @@ pub use self::diagnostics::DiagnosticCategory;
 pub use self::diagnostics::Diagnostics;
 pub use self::diagnostics::Position;
 
+#[cfg(feature = "type-check")]
 pub static COMPILER_SNAPSHOT: Lazy<Box<[u8]>> = Lazy::new(
   #[cold]
   #[inline(never)]
@@ -83,9 +84,8 @@ pub static COMPILER_SNAPSHOT: Lazy<Box<[u8]>> = Lazy::new(
   },
 );
 
-pub fn get_types_declaration_file_text() -> String {
-  let mut assets = get_asset_texts_from_new_runtime()
-    .unwrap()
+pub fn get_types_declaration_file_text() -> Result<String, AnyError> {
+  let mut assets = get_asset_texts_from_new_runtime()?
     .into_iter()
     .map(|a| (a.specifier, a.text))
     .collect::<HashMap<_, _>>();
@@ -109,14 +109,16 @@ pub fn get_types_declaration_file_text() -> String {
     "deno.unstable",
   ];
 
-  lib_names
-    .into_iter()
-    .map(|name| {
-      let asset_url = format!("asset:///lib.{name}.d.ts");
-      assets.remove(&asset_url).unwrap()
-    })
-    .collect::<Vec<_>>()
-    .join("\n")
+  Ok(
+    lib_names
+      .into_iter()
+      .map(|name| {
+        let asset_url = format!("asset:///lib.{name}.d.ts");
+        assets.remove(&asset_url).unwrap()
+      })
+      .collect::<Vec<_>>()
+      .join("\n"),
+  )
 }
 
 fn get_asset_texts_from_new_runtime() -> Result<Vec<AssetText>, AnyError> {
@@ -134,7 +136,7 @@ fn get_asset_texts_from_new_runtime() -> Result<Vec<AssetText>, AnyError> {
 
   // the assets are stored within the typescript isolate, so take them out of there
   let mut runtime = JsRuntime::new(RuntimeOptions {
-    startup_snapshot: Some(compiler_snapshot()),
+    startup_snapshot: Some(compiler_snapshot()?),
     extensions: vec![deno_cli_tsc::init_ops()],
     ..Default::default()
   });
@@ -145,8 +147,16 @@ fn get_asset_texts_from_new_runtime() -> Result<Vec<AssetText>, AnyError> {
   Ok(serde_v8::from_v8::<Vec<AssetText>>(scope, local)?)
 }
 
-pub fn compiler_snapshot() -> &'static [u8] {
-  &COMPILER_SNAPSHOT
+/// Returns the TypeScript compiler snapshot, or an error when this crate was
+/// built without the `type-check` feature.
+pub fn compiler_snapshot() -> Result<&'static [u8], AnyError> {
+  #[cfg(feature = "type-check")]
+  return Ok(&COMPILER_SNAPSHOT);
+
+  #[cfg(not(feature = "type-check"))]
+  Err(anyhow!(
+    "Type checking is not available because deno_lib was built without the \"type-check\" feature."
+  ))
 }
 
 macro_rules! inc {
@@ -1082,7 +1092,7 @@ pub fn exec(request: Request) -> Result<Response, AnyError> {
   let exec_source = format!("globalThis.exec({request_value})");
 
   let mut runtime = JsRuntime::new(RuntimeOptions {
-    startup_snapshot: Some(compiler_snapshot()),
+    startup_snapshot: Some(compiler_snapshot()?),
     extensions: vec![deno_cli_tsc::init_ops(
       request,
       root_map,
@@ -1233,7 +1243,7 @@ mod tests {
   #[tokio::test]
   async fn test_compiler_snapshot() {
     let mut js_runtime = JsRuntime::new(RuntimeOptions {
-      startup_snapshot: Some(compiler_snapshot()),
+      startup_snapshot: Some(compiler_snapshot().unwrap()),
       ..Default::default()
     });
     js_runtime
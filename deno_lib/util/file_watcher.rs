@@ -73,8 +73,9 @@ impl DebouncedReceiver {
   }
 }
 
+/// Awaits the operation and returns the formatted error if it failed.
 #[allow(clippy::print_stderr)]
-async fn error_handler<F>(watch_future: F) -> bool
+async fn error_handler<F>(watch_future: F, quiet: bool) -> Option<String>
 where
   F: Future<Output = Result<(), AnyError>>,
 {
@@ -84,14 +85,13 @@ where
       Some(e) => format_js_error(e),
       None => format!("{err:?}"),
     };
-    eprintln!(
-      "{}: {}",
-      colors::red_bold("error"),
-      error_string.trim_start_matches("error: ")
-    );
-    false
+    let error_string = error_string.trim_start_matches("error: ").to_string();
+    if !quiet {
+      eprintln!("{}: {}", colors::red_bold("error"), error_string);
+    }
+    Some(error_string)
   } else {
-    true
+    None
   }
 }
 
@@ -101,6 +101,8 @@ pub struct PrintConfig {
   job_name: &'static str,
   /// Determine whether to clear the terminal screen; applicable to TTY environments only.
   clear_screen: bool,
+  /// Suppress all terminal output, including operation errors.
+  quiet: bool,
 }
 
 impl PrintConfig {
@@ -112,6 +114,7 @@ impl PrintConfig {
       banner: "Watcher",
       job_name,
       clear_screen,
+      quiet: false,
     }
   }
 
@@ -124,10 +127,51 @@ impl PrintConfig {
       banner,
       job_name,
       clear_screen,
+      quiet: false,
     }
   }
+
+  /// A config that never writes to the terminal. Used when the watcher is
+  /// driven by an embedder through [`WatcherEvent`]s instead.
+  pub fn quiet() -> Self {
+    Self {
+      banner: "Watcher",
+      job_name: "Process",
+      clear_screen: false,
+      quiet: true,
+    }
+  }
+}
+
+/// Events published to subscribers of [`WatcherCommunicator::subscribe`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum WatcherEvent {
+  /// Watched paths changed on disk.
+  PathsChanged(Vec<PathBuf>),
+  /// The operation is about to be restarted.
+  Restarting,
+  /// The operation finished; `error` is set if it failed.
+  Finished { error: Option<String> },
+  /// The watcher was stopped and will not run the operation again.
+  Stopped,
+}
+
+/// What the watcher should do with a batch of changed paths, as decided by
+/// a handler installed with [`WatcherCommunicator::set_change_handler`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WatcherChangeAction {
+  /// Restart the operation from scratch.
+  Restart,
+  /// Pass the paths to the HMR runner so changed modules are hot-replaced.
+  /// Falls back to a restart when no HMR runner is active.
+  HotReplace,
+  /// Do nothing.
+  Ignore,
 }
 
+type WatcherChangeHandler =
+  Arc<dyn Fn(&[PathBuf]) -> WatcherChangeAction + Send + Sync>;
+
 fn create_print_after_restart_fn(
   banner: &'static str,
   clear_screen: bool,
@@ -145,7 +189,6 @@ fn create_print_after_restart_fn(
 }
 
 /// An interface to interact with Deno's CLI file watcher.
-#[derive(Debug)]
 pub struct WatcherCommunicator {
   /// Send a list of paths that should be watched for changes.
   paths_to_watch_tx: tokio::sync::mpsc::UnboundedSender<Vec<PathBuf>>,
@@ -156,12 +199,71 @@ pub struct WatcherCommunicator {
   /// Send a message to force a restart.
   restart_tx: tokio::sync::mpsc::UnboundedSender<()>,
 
+  /// Send a message to stop watching.
+  stop_tx: tokio::sync::mpsc::UnboundedSender<()>,
+
+  /// Publish watcher events to subscribers.
+  events_tx: tokio::sync::broadcast::Sender<WatcherEvent>,
+
+  change_handler: Mutex<Option<WatcherChangeHandler>>,
+
   restart_mode: Mutex<WatcherRestartMode>,
 
   banner: String,
+
+  quiet: bool,
+}
+
+impl std::fmt::Debug for WatcherCommunicator {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("WatcherCommunicator")
+      .field("restart_mode", &self.restart_mode)
+      .field("banner", &self.banner)
+      .field("quiet", &self.quiet)
+      .finish_non_exhaustive()
+  }
+}
+
+/// The receiving ends of a [`WatcherCommunicator`], consumed by
+/// [`watch_recv_with_communicator`].
+pub struct WatcherChannels {
+  paths_to_watch_rx: UnboundedReceiver<Vec<PathBuf>>,
+  changed_paths_tx: tokio::sync::broadcast::Sender<Option<Vec<PathBuf>>>,
+  restart_rx: UnboundedReceiver<()>,
+  stop_rx: UnboundedReceiver<()>,
 }
 
 impl WatcherCommunicator {
+  pub fn new(
+    print_config: &PrintConfig,
+    restart_mode: WatcherRestartMode,
+  ) -> (Arc<Self>, WatcherChannels) {
+    let (paths_to_watch_tx, paths_to_watch_rx) = mpsc::unbounded_channel();
+    let (restart_tx, restart_rx) = mpsc::unbounded_channel();
+    let (stop_tx, stop_rx) = mpsc::unbounded_channel();
+    let (changed_paths_tx, changed_paths_rx) =
+      tokio::sync::broadcast::channel(4);
+    let (events_tx, _) = tokio::sync::broadcast::channel(64);
+    let communicator = Arc::new(Self {
+      paths_to_watch_tx,
+      changed_paths_rx,
+      restart_tx,
+      stop_tx,
+      events_tx,
+      change_handler: Mutex::new(None),
+      restart_mode: Mutex::new(restart_mode),
+      banner: colors::intense_blue(print_config.banner).to_string(),
+      quiet: print_config.quiet,
+    });
+    let channels = WatcherChannels {
+      paths_to_watch_rx,
+      changed_paths_tx,
+      restart_rx,
+      stop_rx,
+    };
+    (communicator, channels)
+  }
+
   pub fn watch_paths(&self, paths: Vec<PathBuf>) -> Result<(), AnyError> {
     if paths.is_empty() {
       return Ok(());
@@ -187,7 +289,51 @@ impl WatcherCommunicator {
     *self.restart_mode.lock() = restart_mode;
   }
 
+  /// Stops the watcher; the running operation is dropped and `watch_recv`
+  /// returns.
+  pub fn stop(&self) -> Result<(), AnyError> {
+    self.stop_tx.send(()).map_err(AnyError::from)
+  }
+
+  /// Subscribes to events about changed paths and the operation lifecycle.
+  pub fn subscribe(&self) -> tokio::sync::broadcast::Receiver<WatcherEvent> {
+    self.events_tx.subscribe()
+  }
+
+  /// Installs a handler that decides what to do with each batch of changed
+  /// paths, overriding the restart mode.
+  pub fn set_change_handler(
+    &self,
+    handler: impl Fn(&[PathBuf]) -> WatcherChangeAction + Send + Sync + 'static,
+  ) {
+    *self.change_handler.lock() = Some(Arc::new(handler));
+  }
+
+  fn change_action(&self, paths: &[PathBuf]) -> WatcherChangeAction {
+    // the handler may call back into the communicator, so it runs without
+    // holding the lock
+    let maybe_handler = self.change_handler.lock().clone();
+    if let Some(handler) = maybe_handler {
+      let action = handler(paths);
+      if action != WatcherChangeAction::HotReplace {
+        return action;
+      }
+    }
+    match *self.restart_mode.lock() {
+      WatcherRestartMode::Automatic => WatcherChangeAction::Restart,
+      WatcherRestartMode::Manual => WatcherChangeAction::HotReplace,
+    }
+  }
+
+  fn send_event(&self, event: WatcherEvent) {
+    // there may be no subscribers
+    let _ = self.events_tx.send(event);
+  }
+
   pub fn print(&self, msg: String) {
+    if self.quiet {
+      return;
+    }
     log::info!("{} {}", self.banner, msg);
   }
 }
@@ -237,9 +383,39 @@ pub enum WatcherRestartMode {
 ///    changes. For example, in the case where we would like to bundle, then `operation` would
 ///    have the logic for it like bundling the code.
 pub async fn watch_recv<O, F>(
-  mut flags: Arc<Flags>,
+  flags: Arc<Flags>,
   print_config: PrintConfig,
   restart_mode: WatcherRestartMode,
+  operation: O,
+) -> Result<(), AnyError>
+where
+  O: FnMut(
+    Arc<Flags>,
+    Arc<WatcherCommunicator>,
+    Option<Vec<PathBuf>>,
+  ) -> Result<F, AnyError>,
+  F: Future<Output = Result<(), AnyError>>,
+{
+  let (watcher_communicator, channels) =
+    WatcherCommunicator::new(&print_config, restart_mode);
+  watch_recv_with_communicator(
+    flags,
+    print_config,
+    watcher_communicator,
+    channels,
+    operation,
+  )
+  .await
+}
+
+/// Same as [`watch_recv`], but drives a communicator created up front with
+/// [`WatcherCommunicator::new`], so the caller can subscribe to events or
+/// stop the watcher before the first run. Returns once the watcher is stopped.
+pub async fn watch_recv_with_communicator<O, F>(
+  mut flags: Arc<Flags>,
+  print_config: PrintConfig,
+  watcher_communicator: Arc<WatcherCommunicator>,
+  channels: WatcherChannels,
   mut operation: O,
 ) -> Result<(), AnyError>
 where
@@ -251,10 +427,12 @@ where
   F: Future<Output = Result<(), AnyError>>,
 {
   let exclude_set = flags.resolve_watch_exclude_set()?;
-  let (paths_to_watch_tx, mut paths_to_watch_rx) =
-    tokio::sync::mpsc::unbounded_channel();
-  let (restart_tx, mut restart_rx) = tokio::sync::mpsc::unbounded_channel();
-  let (changed_paths_tx, changed_paths_rx) = tokio::sync::broadcast::channel(4);
+  let WatcherChannels {
+    mut paths_to_watch_rx,
+    changed_paths_tx,
+    mut restart_rx,
+    mut stop_rx,
+  } = channels;
   let (watcher_sender, mut watcher_receiver) =
     DebouncedReceiver::new_with_sender();
 
@@ -262,42 +440,44 @@ where
     banner,
     job_name,
     clear_screen,
+    quiet,
   } = print_config;
 
   let print_after_restart = create_print_after_restart_fn(banner, clear_screen);
-  let watcher_communicator = Arc::new(WatcherCommunicator {
-    paths_to_watch_tx: paths_to_watch_tx.clone(),
-    changed_paths_rx: changed_paths_rx.resubscribe(),
-    restart_tx: restart_tx.clone(),
-    restart_mode: Mutex::new(restart_mode),
-    banner: colors::intense_blue(banner).to_string(),
-  });
-  info!("{} {} started.", colors::intense_blue(banner), job_name);
+  let after_restart = || {
+    if !quiet {
+      print_after_restart();
+    }
+    watcher_communicator.send_event(WatcherEvent::Restarting);
+  };
+  if !quiet {
+    info!("{} {} started.", colors::intense_blue(banner), job_name);
+  }
 
   let changed_paths = Rc::new(RefCell::new(None));
   let changed_paths_ = changed_paths.clone();
   let watcher_ = watcher_communicator.clone();
 
   deno_core::unsync::spawn(async move {
-    loop {
-      let received_changed_paths = watcher_receiver.recv().await;
-      changed_paths_
-        .borrow_mut()
-        .clone_from(&received_changed_paths);
-
-      match *watcher_.restart_mode.lock() {
-        WatcherRestartMode::Automatic => {
-          let _ = restart_tx.send(());
+    // the senders are dropped once the watcher is stopped
+    while let Some(paths) = watcher_receiver.recv().await {
+      changed_paths_.borrow_mut().replace(paths.clone());
+
+      watcher_.send_event(WatcherEvent::PathsChanged(paths.clone()));
+      match watcher_.change_action(&paths) {
+        WatcherChangeAction::Restart => {
+          let _ = watcher_.restart_tx.send(());
         }
-        WatcherRestartMode::Manual => {
+        WatcherChangeAction::HotReplace => {
           // TODO(bartlomieju): should we fail on sending changed paths?
-          let _ = changed_paths_tx.send(received_changed_paths);
+          let _ = changed_paths_tx.send(Some(paths));
         }
+        WatcherChangeAction::Ignore => {}
       }
     }
   });
 
-  loop {
+  let result = loop {
     // We may need to give the runtime a tick to settle, as cancellations may need to propagate
     // to tasks. We choose yielding 10 times to the runtime as a decent heuristic. If watch tests
     // start to fail, this may need to be increased.
@@ -314,11 +494,14 @@ where
         add_paths_to_watcher(&mut watcher, &maybe_paths.unwrap(), &exclude_set);
       }
     };
-    let operation_future = error_handler(operation(
-      flags.clone(),
-      watcher_communicator.clone(),
-      changed_paths.borrow_mut().take(),
-    )?);
+    let operation_future = error_handler(
+      operation(
+        flags.clone(),
+        watcher_communicator.clone(),
+        changed_paths.borrow_mut().take(),
+      )?,
+      quiet,
+    );
 
     // don't reload dependencies after the first run
     if flags.reload {
@@ -331,22 +514,26 @@ where
     select! {
       _ = receiver_future => {},
       _ = restart_rx.recv() => {
-        print_after_restart();
+        after_restart();
         continue;
       },
-      success = operation_future => {
+      _ = stop_rx.recv() => break Ok(()),
+      error = operation_future => {
         consume_paths_to_watch(&mut watcher, &mut paths_to_watch_rx, &exclude_set);
-        // TODO(bartlomieju): print exit code here?
-        info!(
-          "{} {} {}. Restarting on file change...",
-          colors::intense_blue(banner),
-          job_name,
-          if success {
-            "finished"
-          } else {
-            "failed"
-          }
-        );
+        if !quiet {
+          // TODO(bartlomieju): print exit code here?
+          info!(
+            "{} {} {}. Restarting on file change...",
+            colors::intense_blue(banner),
+            job_name,
+            if error.is_none() {
+              "finished"
+            } else {
+              "failed"
+            }
+          );
+        }
+        watcher_communicator.send_event(WatcherEvent::Finished { error });
       },
     }
     let receiver_future = async {
@@ -362,11 +549,16 @@ where
     select! {
       _ = receiver_future => {},
       _ = restart_rx.recv() => {
-        print_after_restart();
+        after_restart();
         continue;
       },
+      _ = stop_rx.recv() => break Ok(()),
     }
-  }
+  };
+
+  drop(watcher_sender);
+  watcher_communicator.send_event(WatcherEvent::Stopped);
+  result
 }
 
 fn new_watcher(
@@ -436,3 +628,74 @@ fn consume_paths_to_watch(
     }
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn change_action_follows_handler_and_restart_mode() {
+    let (communicator, _channels) = WatcherCommunicator::new(
+      &PrintConfig::quiet(),
+      WatcherRestartMode::Automatic,
+    );
+    let paths = vec![PathBuf::from("/a.ts")];
+    assert_eq!(
+      communicator.change_action(&paths),
+      WatcherChangeAction::Restart
+    );
+    communicator.change_restart_mode(WatcherRestartMode::Manual);
+    assert_eq!(
+      communicator.change_action(&paths),
+      WatcherChangeAction::HotReplace
+    );
+
+    communicator.set_change_handler(|paths| {
+      if paths.iter().any(|p| p.ends_with("a.ts")) {
+        WatcherChangeAction::Ignore
+      } else {
+        WatcherChangeAction::Restart
+      }
+    });
+    assert_eq!(
+      communicator.change_action(&paths),
+      WatcherChangeAction::Ignore
+    );
+    assert_eq!(
+      communicator.change_action(&[PathBuf::from("/b.ts")]),
+      WatcherChangeAction::Restart
+    );
+
+    // hot replacement falls back to a restart without an HMR runner
+    communicator.set_change_handler(|_| WatcherChangeAction::HotReplace);
+    communicator.change_restart_mode(WatcherRestartMode::Automatic);
+    assert_eq!(
+      communicator.change_action(&paths),
+      WatcherChangeAction::Restart
+    );
+  }
+
+  #[test]
+  fn change_handler_can_call_back_into_the_communicator() {
+    let (communicator, _channels) = WatcherCommunicator::new(
+      &PrintConfig::quiet(),
+      WatcherRestartMode::Automatic,
+    );
+    let weak_communicator = Arc::downgrade(&communicator);
+    communicator.set_change_handler(move |_| {
+      let communicator = weak_communicator.upgrade().unwrap();
+      communicator.change_restart_mode(WatcherRestartMode::Manual);
+      communicator.set_change_handler(|_| WatcherChangeAction::Restart);
+      WatcherChangeAction::Ignore
+    });
+    let paths = vec![PathBuf::from("/a.ts")];
+    assert_eq!(
+      communicator.change_action(&paths),
+      WatcherChangeAction::Ignore
+    );
+    assert_eq!(
+      communicator.change_action(&paths),
+      WatcherChangeAction::Restart
+    );
+  }
+}
@@ -0,0 +1,597 @@
+// Copyright 2018-2024 the Deno authors. All rights reserved. MIT license.
+
+//! Structured op tracing and aggregated op metrics.
+//!
+//! `--strace-ops` and `--enable-op-summary-metrics` only print text to
+//! stderr. The types in this module expose the same information as data so
+//! that embedders can inspect it programmatically or export it as a Chrome
+//! trace-event file (loadable in Perfetto or `chrome://tracing`).
+
+use std::cell::OnceCell;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::collections::VecDeque;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::Instant;
+
+use deno_core::parking_lot::Mutex;
+use deno_core::serde::Serialize;
+use deno_core::serde_json;
+use deno_core::serde_json::json;
+use deno_core::OpDecl;
+use deno_core::OpMetricsEvent;
+use deno_core::OpMetricsFactoryFn;
+use deno_core::OpMetricsFn;
+use deno_core::OpMetricsSource;
+use deno_core::OpMetricsSummaryTracker;
+use deno_core::OpState;
+use deno_core::ResourceId;
+use tokio::sync::mpsc::UnboundedReceiver;
+use tokio::sync::mpsc::UnboundedSender;
+
+/// How an op was invoked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum OpCallKind {
+  Sync,
+  Fast,
+  Async,
+}
+
+impl From<OpMetricsSource> for OpCallKind {
+  fn from(source: OpMetricsSource) -> Self {
+    match source {
+      OpMetricsSource::Slow => OpCallKind::Sync,
+      OpMetricsSource::Fast => OpCallKind::Fast,
+      OpMetricsSource::Async => OpCallKind::Async,
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum OpTraceResult {
+  Ok,
+  Error,
+}
+
+/// A single completed op call.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OpTraceSpan {
+  pub op_name: &'static str,
+  pub kind: OpCallKind,
+  /// Microseconds since the recorder was created.
+  pub start_us: u64,
+  /// Microseconds since the recorder was created.
+  pub end_us: u64,
+  pub result: OpTraceResult,
+  /// Resources that were added to the resource table by this call. For
+  /// async calls, these also include the resources that other ops added
+  /// while the call was running.
+  pub resource_ids: Vec<ResourceId>,
+  /// Whether the start (and so the duration) may belong to another call of
+  /// the same op. The completion of an async call doesn't identify it, so
+  /// when several calls of an op are running they're paired in dispatch
+  /// order, which is wrong for calls that complete out of order.
+  pub approximate: bool,
+}
+
+impl OpTraceSpan {
+  pub fn duration_us(&self) -> u64 {
+    self.end_us.saturating_sub(self.start_us)
+  }
+}
+
+/// Aggregated metrics for a single op.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OpMetricsEntry {
+  pub name: String,
+  pub dispatched_sync: u64,
+  pub dispatched_fast: u64,
+  pub dispatched_async: u64,
+  pub completed_async: u64,
+  /// `None` when the metrics were built from the runtime's summary tracker,
+  /// which doesn't record results.
+  pub errors: Option<u64>,
+  /// Sum of the durations of all recorded calls. `None` when the metrics were
+  /// built from the runtime's summary tracker, which doesn't record timings.
+  pub total_duration_us: Option<u64>,
+  /// Number of the recorded calls whose duration is approximate, see
+  /// [`OpTraceSpan::approximate`].
+  pub approximate_durations: u64,
+}
+
+/// Aggregated op metrics for a run, sorted by op name.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OpMetricsReport {
+  pub ops: Vec<OpMetricsEntry>,
+}
+
+impl OpMetricsReport {
+  /// Builds a report from the summary tracker that the runtime installs when
+  /// `enable_op_summary_metrics` is set. `op_names` must be indexed by op id,
+  /// as returned by `JsRuntime::op_names()`.
+  pub fn from_summary_tracker(
+    op_names: &[&'static str],
+    tracker: &OpMetricsSummaryTracker,
+  ) -> Self {
+    let per_op = tracker.per_op();
+    let mut ops = op_names
+      .iter()
+      .zip(per_op.iter())
+      .filter(|(_, summary)| {
+        summary.ops_dispatched_sync > 0
+          || summary.ops_dispatched_fast > 0
+          || summary.ops_dispatched_async > 0
+      })
+      .map(|(name, summary)| OpMetricsEntry {
+        name: name.to_string(),
+        dispatched_sync: summary.ops_dispatched_sync,
+        dispatched_fast: summary.ops_dispatched_fast,
+        dispatched_async: summary.ops_dispatched_async,
+        completed_async: summary.ops_completed_async,
+        errors: None,
+        total_duration_us: None,
+        approximate_durations: 0,
+      })
+      .collect::<Vec<_>>();
+    ops.sort_by(|a, b| a.name.cmp(&b.name));
+    Self { ops }
+  }
+
+  pub fn from_spans<'a>(
+    spans: impl IntoIterator<Item = &'a OpTraceSpan>,
+  ) -> Self {
+    let mut entries: HashMap<&'static str, OpMetricsEntry> = HashMap::new();
+    for span in spans {
+      let entry =
+        entries
+          .entry(span.op_name)
+          .or_insert_with(|| OpMetricsEntry {
+            name: span.op_name.to_string(),
+            errors: Some(0),
+            total_duration_us: Some(0),
+            ..Default::default()
+          });
+      match span.kind {
+        OpCallKind::Sync => entry.dispatched_sync += 1,
+        OpCallKind::Fast => entry.dispatched_fast += 1,
+        OpCallKind::Async => {
+          entry.dispatched_async += 1;
+          entry.completed_async += 1;
+        }
+      }
+      if span.result == OpTraceResult::Error {
+        *entry.errors.get_or_insert(0) += 1;
+      }
+      *entry.total_duration_us.get_or_insert(0) += span.duration_us();
+      if span.approximate {
+        entry.approximate_durations += 1;
+      }
+    }
+    let mut ops = entries.into_values().collect::<Vec<_>>();
+    ops.sort_by(|a, b| a.name.cmp(&b.name));
+    Self { ops }
+  }
+}
+
+/// Converts spans to the Chrome trace-event JSON format using "complete"
+/// (`ph: "X"`) events. Synchronous and async calls are put on separate
+/// tracks so that overlapping async ops remain readable.
+pub fn to_chrome_trace<'a>(
+  spans: impl IntoIterator<Item = &'a OpTraceSpan>,
+) -> serde_json::Value {
+  let events = spans
+    .into_iter()
+    .map(|span| {
+      json!({
+        "name": span.op_name,
+        "cat": "op",
+        "ph": "X",
+        "ts": span.start_us,
+        "dur": span.duration_us(),
+        "pid": 1,
+        "tid": match span.kind {
+          OpCallKind::Sync | OpCallKind::Fast => 1,
+          OpCallKind::Async => 2,
+        },
+        "args": {
+          "kind": span.kind,
+          "result": span.result,
+          "resourceIds": span.resource_ids,
+          "approximate": span.approximate,
+        },
+      })
+    })
+    .collect::<Vec<_>>();
+  json!({
+    "traceEvents": events,
+    "displayTimeUnit": "ms",
+  })
+}
+
+/// Match an op name against a list of `--strace-ops` style patterns. An empty
+/// list matches every op.
+fn matches_pattern(patterns: &[String], name: &str) -> bool {
+  patterns.is_empty() || patterns.iter().any(|pattern| name.contains(pattern))
+}
+
+#[derive(Default)]
+struct OpTraceRecorderInner {
+  spans: Vec<OpTraceSpan>,
+  listeners: Vec<UnboundedSender<OpTraceSpan>>,
+}
+
+/// Records op calls as [`OpTraceSpan`]s.
+///
+/// The recorder plugs into a `JsRuntime` through
+/// `RuntimeOptions::op_metrics_factory_fn` with the callback returned by
+/// [`OpTraceRecorder::op_metrics_factory_fn`]. `MainWorker` (deno_runtime)
+/// creates its runtime with its own op metrics factory and has no option to
+/// pass another one, so the recorder can't be attached to workers created by
+/// this crate. They report the runtime's summary metrics instead, which only
+/// count the calls, see [`crate::CliMainWorker::op_metrics_report`].
+pub struct OpTraceRecorder {
+  start: Instant,
+  patterns: Vec<String>,
+  /// Stop keeping spans in memory once this many were recorded. Listeners
+  /// still receive every span.
+  max_spans: usize,
+  inner: Mutex<OpTraceRecorderInner>,
+}
+
+impl OpTraceRecorder {
+  /// Creates a recorder for the ops matching `patterns` (same semantics as
+  /// `--strace-ops`, an empty list traces every op).
+  pub fn new(patterns: Vec<String>) -> Self {
+    Self {
+      start: Instant::now(),
+      patterns,
+      max_spans: 1_000_000,
+      inner: Default::default(),
+    }
+  }
+
+  /// Subscribes to spans as they complete.
+  pub fn subscribe(&self) -> UnboundedReceiver<OpTraceSpan> {
+    let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
+    self.inner.lock().listeners.push(sender);
+    receiver
+  }
+
+  /// Returns a copy of the spans recorded so far.
+  pub fn spans(&self) -> Vec<OpTraceSpan> {
+    self.inner.lock().spans.clone()
+  }
+
+  /// Removes and returns the spans recorded so far.
+  pub fn take_spans(&self) -> Vec<OpTraceSpan> {
+    std::mem::take(&mut self.inner.lock().spans)
+  }
+
+  pub fn metrics(&self) -> OpMetricsReport {
+    OpMetricsReport::from_spans(self.inner.lock().spans.iter())
+  }
+
+  pub fn to_chrome_trace(&self) -> serde_json::Value {
+    to_chrome_trace(self.inner.lock().spans.iter())
+  }
+
+  fn elapsed_us(&self) -> u64 {
+    self.start.elapsed().as_micros() as u64
+  }
+
+  fn record(&self, span: OpTraceSpan) {
+    let mut inner = self.inner.lock();
+    inner
+      .listeners
+      .retain(|listener| listener.send(span.clone()).is_ok());
+    if inner.spans.len() < self.max_spans {
+      inner.spans.push(span);
+    }
+  }
+
+  /// Creates the metrics callbacks for the ops matching the patterns. Each call
+  /// is timed from its dispatch to its completion.
+  ///
+  /// The resources opened by synchronous calls are only tracked once
+  /// `op_state` is set to the `JsRuntime::op_state()` of the runtime, which
+  /// doesn't exist yet when the callback is created.
+  pub fn op_metrics_factory_fn(
+    self: Arc<Self>,
+    op_state: Rc<OnceCell<Rc<RefCell<OpState>>>>,
+  ) -> OpMetricsFactoryFn {
+    Box::new(move |_op_id, _op_count, decl: &OpDecl| {
+      if !matches_pattern(&self.patterns, decl.name) {
+        return None;
+      }
+      let op_name = decl.name;
+      let recorder = self.clone();
+      let op_state = op_state.clone();
+      let calls = RefCell::new(PendingCalls::default());
+      let metrics_fn: OpMetricsFn = Rc::new(move |_ctx, event, source| {
+        let now = recorder.elapsed_us();
+        let mut calls = calls.borrow_mut();
+        match event {
+          OpMetricsEvent::Dispatched => {
+            let resources = op_state.get().and_then(resource_ids);
+            calls.dispatched(source.into(), now, resources);
+          }
+          OpMetricsEvent::Completed
+          | OpMetricsEvent::CompletedAsync
+          | OpMetricsEvent::Error
+          | OpMetricsEvent::ErrorAsync => {
+            let result = match event {
+              OpMetricsEvent::Error | OpMetricsEvent::ErrorAsync => {
+                OpTraceResult::Error
+              }
+              _ => OpTraceResult::Ok,
+            };
+            let kind = OpCallKind::from(source);
+            let Some((call, approximate)) = calls.completed(kind) else {
+              return;
+            };
+            let resource_ids = match call.resources_before {
+              Some(before) => op_state
+                .get()
+                .and_then(resource_ids)
+                .map(|after| {
+                  let mut opened =
+                    after.difference(&before).copied().collect::<Vec<_>>();
+                  opened.sort_unstable();
+                  opened
+                })
+                .unwrap_or_default(),
+              None => Vec::new(),
+            };
+            recorder.record(OpTraceSpan {
+              op_name,
+              kind,
+              start_us: call.start_us,
+              end_us: now,
+              result,
+              resource_ids,
+              approximate,
+            });
+          }
+        }
+      });
+      Some(metrics_fn)
+    })
+  }
+}
+
+struct PendingCall {
+  start_us: u64,
+  resources_before: Option<HashSet<ResourceId>>,
+}
+
+/// Calls of a single op that were dispatched but haven't completed yet.
+///
+/// Synchronous calls complete before the next one is dispatched. Async calls
+/// of the same op may complete in any order and the metrics callback doesn't
+/// identify the promise, so they're paired in dispatch order. The pairing is
+/// only certain when a single call was running.
+#[derive(Default)]
+struct PendingCalls {
+  sync: Option<PendingCall>,
+  r#async: VecDeque<PendingCall>,
+}
+
+impl PendingCalls {
+  fn dispatched(
+    &mut self,
+    kind: OpCallKind,
+    start_us: u64,
+    resources_before: Option<HashSet<ResourceId>>,
+  ) {
+    let call = PendingCall {
+      start_us,
+      resources_before,
+    };
+    match kind {
+      OpCallKind::Sync | OpCallKind::Fast => self.sync = Some(call),
+      OpCallKind::Async => self.r#async.push_back(call),
+    }
+  }
+
+  /// Takes the call that completed and whether it's only approximately the
+  /// right one.
+  fn completed(&mut self, kind: OpCallKind) -> Option<(PendingCall, bool)> {
+    match kind {
+      OpCallKind::Sync | OpCallKind::Fast => {
+        self.sync.take().map(|call| (call, false))
+      }
+      OpCallKind::Async => {
+        let approximate = self.r#async.len() > 1;
+        self.r#async.pop_front().map(|call| (call, approximate))
+      }
+    }
+  }
+}
+
+fn resource_ids(state: &Rc<RefCell<OpState>>) -> Option<HashSet<ResourceId>> {
+  // the op may currently hold a borrow of the state
+  let state = state.try_borrow().ok()?;
+  Some(state.resource_table.names().map(|(rid, _)| rid).collect())
+}
+
+#[cfg(test)]
+mod test {
+  use deno_core::error::AnyError;
+  use deno_core::op2;
+  use deno_core::JsRuntime;
+  use deno_core::RuntimeOptions;
+
+  use super::*;
+
+  deno_core::extension!(
+    op_trace_test,
+    ops = [op_trace_test_ok, op_trace_test_error]
+  );
+
+  #[op2(fast)]
+  fn op_trace_test_ok() {}
+
+  #[op2(fast)]
+  fn op_trace_test_error() -> Result<(), AnyError> {
+    Err(deno_core::anyhow::anyhow!("failed"))
+  }
+
+  fn span(
+    op_name: &'static str,
+    kind: OpCallKind,
+    start_us: u64,
+    end_us: u64,
+    result: OpTraceResult,
+  ) -> OpTraceSpan {
+    OpTraceSpan {
+      op_name,
+      kind,
+      start_us,
+      end_us,
+      result,
+      resource_ids: vec![],
+      approximate: false,
+    }
+  }
+
+  #[test]
+  fn metrics_from_spans() {
+    let mut approximate_read =
+      span("op_read", OpCallKind::Async, 0, 10, OpTraceResult::Ok);
+    approximate_read.approximate = true;
+    let spans = [
+      approximate_read,
+      span("op_read", OpCallKind::Async, 2, 5, OpTraceResult::Error),
+      span("op_now", OpCallKind::Fast, 3, 4, OpTraceResult::Ok),
+      span("op_open_sync", OpCallKind::Sync, 6, 9, OpTraceResult::Ok),
+    ];
+    let report = OpMetricsReport::from_spans(spans.iter());
+    assert_eq!(
+      report.ops,
+      vec![
+        OpMetricsEntry {
+          name: "op_now".to_string(),
+          dispatched_fast: 1,
+          errors: Some(0),
+          total_duration_us: Some(1),
+          ..Default::default()
+        },
+        OpMetricsEntry {
+          name: "op_open_sync".to_string(),
+          dispatched_sync: 1,
+          errors: Some(0),
+          total_duration_us: Some(3),
+          ..Default::default()
+        },
+        OpMetricsEntry {
+          name: "op_read".to_string(),
+          dispatched_async: 2,
+          completed_async: 2,
+          errors: Some(1),
+          total_duration_us: Some(13),
+          approximate_durations: 1,
+          ..Default::default()
+        },
+      ]
+    );
+  }
+
+  #[test]
+  fn chrome_trace_events() {
+    let mut open =
+      span("op_open_sync", OpCallKind::Sync, 6, 9, OpTraceResult::Ok);
+    open.resource_ids = vec![4];
+    let trace = to_chrome_trace([&open]);
+    assert_eq!(
+      trace,
+      json!({
+        "traceEvents": [{
+          "name": "op_open_sync",
+          "cat": "op",
+          "ph": "X",
+          "ts": 6,
+          "dur": 3,
+          "pid": 1,
+          "tid": 1,
+          "args": {
+            "kind": "sync",
+            "result": "ok",
+            "resourceIds": [4],
+            "approximate": false,
+          },
+        }],
+        "displayTimeUnit": "ms",
+      })
+    );
+  }
+
+  #[test]
+  fn pending_async_calls_pair_in_dispatch_order() {
+    let mut calls = PendingCalls::default();
+    calls.dispatched(OpCallKind::Async, 1, None);
+    calls.dispatched(OpCallKind::Async, 2, None);
+    calls.dispatched(OpCallKind::Sync, 3, None);
+    let (call, approximate) = calls.completed(OpCallKind::Sync).unwrap();
+    assert_eq!((call.start_us, approximate), (3, false));
+    assert!(calls.completed(OpCallKind::Sync).is_none());
+    // two calls were running, so either could have completed first
+    let (call, approximate) = calls.completed(OpCallKind::Async).unwrap();
+    assert_eq!((call.start_us, approximate), (1, true));
+    let (call, approximate) = calls.completed(OpCallKind::Async).unwrap();
+    assert_eq!((call.start_us, approximate), (2, false));
+    assert!(calls.completed(OpCallKind::Async).is_none());
+  }
+
+  #[test]
+  fn records_op_calls() {
+    let recorder =
+      Arc::new(OpTraceRecorder::new(vec!["op_trace_test".to_string()]));
+    let op_state = Rc::new(OnceCell::new());
+    let mut runtime = JsRuntime::new(RuntimeOptions {
+      extensions: vec![op_trace_test::init_ops()],
+      op_metrics_factory_fn: Some(
+        recorder.clone().op_metrics_factory_fn(op_state.clone()),
+      ),
+      ..Default::default()
+    });
+    op_state.set(runtime.op_state()).unwrap();
+    runtime
+      .execute_script(
+        "op_trace_test.js",
+        "Deno.core.ops.op_trace_test_ok();
+        try { Deno.core.ops.op_trace_test_error(); } catch {}",
+      )
+      .unwrap();
+
+    let spans = recorder.spans();
+    assert_eq!(
+      spans
+        .iter()
+        .map(|span| (span.op_name, span.result))
+        .collect::<Vec<_>>(),
+      vec![
+        ("op_trace_test_ok", OpTraceResult::Ok),
+        ("op_trace_test_error", OpTraceResult::Error),
+      ]
+    );
+    assert!(spans.iter().all(|span| span.end_us >= span.start_us));
+    let report = recorder.metrics();
+    assert_eq!(report.ops[0].name, "op_trace_test_error");
+    assert_eq!(report.ops[0].errors, Some(1));
+    assert!(report.ops[0].total_duration_us.is_some());
+  }
+
+  #[test]
+  fn pattern_matching() {
+    assert!(matches_pattern(&[], "op_read"));
+    assert!(matches_pattern(&["read".to_string()], "op_read"));
+    assert!(!matches_pattern(&["write".to_string()], "op_read"));
+  }
+}
@@ -3,6 +3,44 @@
 use std::future::Future;
 use std::time::Duration;
 
+/// How many times to attempt an operation and how long to wait in between,
+/// when configured. Otherwise each kind of download keeps its own waits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+  /// The number of attempts, including the first one.
+  pub max_attempts: u32,
+  /// The wait before the first retry, which doubles for every retry after.
+  pub initial_backoff: Duration,
+  /// The upper bound of the wait between attempts.
+  pub max_backoff: Duration,
+}
+
+/// The settings of a configured policy that aren't given.
+impl Default for RetryPolicy {
+  fn default() -> Self {
+    Self {
+      max_attempts: 4,
+      initial_backoff: Duration::from_millis(100),
+      max_backoff: Duration::from_secs(10),
+    }
+  }
+}
+
+impl RetryPolicy {
+  /// The wait after the given failed attempt (starting at 1). Up to half of
+  /// it is random, so that clients that failed together don't retry in
+  /// lockstep.
+  pub fn backoff(&self, attempt: u32) -> Duration {
+    let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+    let backoff = self
+      .initial_backoff
+      .saturating_mul(factor)
+      .min(self.max_backoff);
+    let half = backoff / 2;
+    half + half.mul_f64(rand::random::<f64>())
+  }
+}
+
 pub fn retry<
   F: FnMut() -> Fut,
   T,
@@ -10,6 +48,7 @@ pub fn retry<
   Fut: Future<Output = Result<T, E>>,
   ShouldRetry: FnMut(&E) -> bool,
 >(
+  maybe_policy: Option<RetryPolicy>,
   mut f: F,
   mut should_retry: ShouldRetry,
 ) -> impl Future<Output = Result<T, E>> {
@@ -22,6 +61,7 @@ pub fn retry<
   let mut waits = WAITS.into_iter();
   async move {
     let mut first_result = None;
+    let mut attempt = 1;
     loop {
       let result = f().await;
       match result {
@@ -32,10 +72,85 @@ pub fn retry<
       if first_result.is_none() {
         first_result = Some(result);
       }
-      let Some(wait) = waits.next() else {
+      let wait = match &maybe_policy {
+        Some(policy) if attempt < policy.max_attempts => {
+          Some(policy.backoff(attempt))
+        }
+        Some(_) => None,
+        None => waits.next(),
+      };
+      let Some(wait) = wait else {
         return first_result.unwrap();
       };
       tokio::time::sleep(wait).await;
+      attempt += 1;
     }
   }
 }
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn backs_off_exponentially_with_jitter() {
+    let policy = RetryPolicy {
+      max_attempts: 10,
+      initial_backoff: Duration::from_millis(100),
+      max_backoff: Duration::from_millis(1000),
+    };
+    for (attempt, expected_max) in [(1, 100), (2, 200), (3, 400), (4, 800)] {
+      let backoff = policy.backoff(attempt);
+      assert!(backoff >= Duration::from_millis(expected_max / 2));
+      assert!(backoff <= Duration::from_millis(expected_max));
+    }
+    for attempt in [5, 31, u32::MAX] {
+      let backoff = policy.backoff(attempt);
+      assert!(backoff >= Duration::from_millis(500));
+      assert!(backoff <= Duration::from_millis(1000));
+    }
+  }
+
+  #[tokio::test]
+  async fn retries_up_to_max_attempts() {
+    let policy = RetryPolicy {
+      max_attempts: 3,
+      initial_backoff: Duration::from_millis(1),
+      max_backoff: Duration::from_millis(1),
+    };
+    let mut attempts = 0;
+    let result: Result<(), usize> = retry(
+      Some(policy),
+      || {
+        attempts += 1;
+        let attempt = attempts;
+        async move { Err(attempt) }
+      },
+      |_| true,
+    )
+    .await;
+    // the first error is returned
+    assert_eq!(result, Err(1));
+    assert_eq!(attempts, 3);
+  }
+
+  #[tokio::test]
+  async fn retries_with_fixed_waits_without_policy() {
+    let mut attempts = 0;
+    let start = std::time::Instant::now();
+    let result: Result<(), usize> = retry(
+      None,
+      || {
+        attempts += 1;
+        let attempt = attempts;
+        async move { Err(attempt) }
+      },
+      |_| true,
+    )
+    .await;
+    assert_eq!(result, Err(1));
+    assert_eq!(attempts, 4);
+    // waited 100, 250 and 500ms
+    assert!(start.elapsed() >= Duration::from_millis(850));
+  }
+}
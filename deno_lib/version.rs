@@ -13,18 +13,15 @@ const IS_CANARY: bool = option_env!("DENO_CANARY").is_some();
 const IS_RC: bool = option_env!("DENO_RC").is_some();
 
 pub static DENO_VERSION_INFO: Lazy<DenoVersionInfo> = Lazy::new(|| {
-  let release_channel = libsui::find_section("denover")
-    .and_then(|buf| std::str::from_utf8(buf).ok())
-    .and_then(|str_| ReleaseChannel::deserialize(str_).ok())
-    .unwrap_or({
-      if IS_CANARY {
-        ReleaseChannel::Canary
-      } else if IS_RC {
-        ReleaseChannel::Rc
-      } else {
-        ReleaseChannel::Stable
-      }
-    });
+  // The deno release pipeline stamps the channel into a "denover" section of
+  // its binaries, which binaries embedding this crate never have.
+  let release_channel = if IS_CANARY {
+    ReleaseChannel::Canary
+  } else if IS_RC {
+    ReleaseChannel::Rc
+  } else {
+    ReleaseChannel::Stable
+  };
 
   DenoVersionInfo {
     deno: if release_channel == ReleaseChannel::Canary {
@@ -7,6 +7,7 @@ use std::sync::Arc;
 
 use deno_ast::ModuleSpecifier;
 use deno_core::anyhow::bail;
+use deno_core::error::generic_error;
 use deno_core::error::AnyError;
 use deno_core::futures::FutureExt;
 use deno_core::url::Url;
@@ -15,6 +16,7 @@ use deno_core::CompiledWasmModuleStore;
 use deno_core::Extension;
 use deno_core::FeatureChecker;
 use deno_core::ModuleLoader;
+use deno_core::OpMetricsSummaryTracker;
 use deno_core::PollEventLoopOptions;
 use deno_core::SharedArrayBufferStore;
 use deno_runtime::code_cache;
@@ -54,6 +56,7 @@ use crate::npm::CliNpmResolver;
 use crate::util::checksum;
 use crate::util::file_watcher::WatcherCommunicator;
 use crate::util::file_watcher::WatcherRestartMode;
+use crate::util::op_trace::OpMetricsReport;
 use crate::version;
 
 pub struct CreateModuleLoaderResult {
@@ -81,6 +84,55 @@ pub trait HmrRunner: Send + Sync {
   async fn run(&mut self) -> Result<(), AnyError>;
 }
 
+/// Why V8 refused to hot-replace a module.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HmrRejectReason {
+  CompileError,
+  BlockedByActiveGenerator,
+  BlockedByActiveFunction,
+  BlockedByTopLevelEsModuleChange,
+}
+
+impl std::fmt::Display for HmrRejectReason {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.write_str(match self {
+      HmrRejectReason::CompileError => "compile error",
+      HmrRejectReason::BlockedByActiveGenerator => {
+        "blocked by active generator"
+      }
+      HmrRejectReason::BlockedByActiveFunction => "blocked by active function",
+      HmrRejectReason::BlockedByTopLevelEsModuleChange => {
+        "blocked by top-level ES module change"
+      }
+    })
+  }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ReplaceModuleError {
+  #[error("Module {0} is not loaded in this worker.")]
+  NotLoaded(ModuleSpecifier),
+  #[error("Failed to replace module {specifier}: {reason}.")]
+  Rejected {
+    specifier: ModuleSpecifier,
+    reason: HmrRejectReason,
+  },
+  #[error(transparent)]
+  Other(AnyError),
+}
+
+/// Hot-replaces modules of a running worker on request, as opposed to the
+/// [`HmrRunner`] which reacts to file watcher notifications.
+#[async_trait::async_trait(?Send)]
+pub trait ModuleReplacer: Send + Sync {
+  async fn start(&mut self) -> Result<(), AnyError>;
+  async fn replace_module(
+    &mut self,
+    specifier: &ModuleSpecifier,
+    source: String,
+  ) -> Result<(), ReplaceModuleError>;
+}
+
 #[async_trait::async_trait(?Send)]
 pub trait CoverageCollector: Send + Sync {
   async fn start_collecting(&mut self) -> Result<(), AnyError>;
@@ -91,6 +143,12 @@ pub type CreateHmrRunnerCb = Box<
   dyn Fn(deno_core::LocalInspectorSession) -> Box<dyn HmrRunner> + Send + Sync,
 >;
 
+pub type CreateModuleReplacerCb = Box<
+  dyn Fn(deno_core::LocalInspectorSession) -> Box<dyn ModuleReplacer>
+    + Send
+    + Sync,
+>;
+
 pub type CreateCoverageCollectorCb = Box<
   dyn Fn(deno_core::LocalInspectorSession) -> Box<dyn CoverageCollector>
     + Send
@@ -116,6 +174,7 @@ pub struct CliMainWorkerOptions {
   pub unsafely_ignore_certificate_errors: Option<Vec<String>>,
   pub skip_op_registration: bool,
   pub create_hmr_runner: Option<CreateHmrRunnerCb>,
+  pub create_module_replacer: Option<CreateModuleReplacerCb>,
   pub create_coverage_collector: Option<CreateCoverageCollectorCb>,
   pub node_ipc: Option<i64>,
   pub serve_port: Option<u16>,
@@ -166,6 +225,7 @@ pub struct CliMainWorker {
   main_module: ModuleSpecifier,
   worker: MainWorker,
   shared: Arc<SharedWorkerState>,
+  maybe_module_replacer: Option<Box<dyn ModuleReplacer>>,
 }
 
 impl CliMainWorker {
@@ -173,6 +233,11 @@ impl CliMainWorker {
     self.worker
   }
 
+  /// Runs the event loop until there is no more pending work.
+  pub async fn run_event_loop(&mut self) -> Result<(), AnyError> {
+    self.worker.run_event_loop(false).await
+  }
+
   pub async fn setup_repl(&mut self) -> Result<(), AnyError> {
     self.worker.run_event_loop(false).await?;
     Ok(())
@@ -350,6 +415,53 @@ impl CliMainWorker {
     Ok(Some(hmr_runner))
   }
 
+  /// Hot-replaces the already loaded module at `specifier` with `source`,
+  /// emitting it first if needed, and dispatches the `hmr` event on success.
+  pub async fn replace_module(
+    &mut self,
+    specifier: &ModuleSpecifier,
+    source: String,
+  ) -> Result<(), ReplaceModuleError> {
+    if self.maybe_module_replacer.is_none() {
+      let Some(create_module_replacer) =
+        self.shared.options.create_module_replacer.as_ref()
+      else {
+        return Err(ReplaceModuleError::Other(generic_error(
+          "Hot module replacement is not available for this worker.",
+        )));
+      };
+      let session = self.worker.create_inspector_session();
+      let mut module_replacer = create_module_replacer(session);
+      self
+        .worker
+        .js_runtime
+        .with_event_loop_future(
+          module_replacer.start().boxed_local(),
+          PollEventLoopOptions::default(),
+        )
+        .await
+        .map_err(ReplaceModuleError::Other)?;
+      self.maybe_module_replacer = Some(module_replacer);
+    }
+
+    let module_replacer = self.maybe_module_replacer.as_mut().unwrap();
+    self
+      .worker
+      .js_runtime
+      .with_event_loop_future(
+        module_replacer
+          .replace_module(specifier, source)
+          .boxed_local(),
+        PollEventLoopOptions::default(),
+      )
+      .await
+      .map_err(|err| {
+        err
+          .downcast::<ReplaceModuleError>()
+          .unwrap_or_else(ReplaceModuleError::Other)
+      })
+  }
+
   pub async fn maybe_setup_coverage_collector(
     &mut self,
   ) -> Result<Option<Box<dyn CoverageCollector>>, AnyError> {
@@ -372,6 +484,19 @@ impl CliMainWorker {
     Ok(Some(coverage_collector))
   }
 
+  /// Returns the aggregated op metrics collected so far. Only available when
+  /// the worker was created with `enable_op_summary_metrics`, for example by
+  /// [`crate::create_main_worker_with_op_metrics`]. The runtime only counts
+  /// the calls, so the report has no durations or errors. Those need an
+  /// [`crate::OpTraceRecorder`], which can't be attached to a `MainWorker`.
+  pub fn op_metrics_report(&self) -> Option<OpMetricsReport> {
+    let op_names = self.worker.js_runtime.op_names();
+    let op_state = self.worker.js_runtime.op_state();
+    let op_state = op_state.borrow();
+    let tracker = op_state.try_borrow::<Rc<OpMetricsSummaryTracker>>()?;
+    Some(OpMetricsReport::from_summary_tracker(&op_names, tracker))
+  }
+
   pub fn execute_script_static(
     &mut self,
     name: &'static str,
@@ -625,6 +750,7 @@ impl CliMainWorkerFactory {
       main_module,
       worker,
       shared: shared.clone(),
+      maybe_module_replacer: None,
     })
   }
 
@@ -885,6 +1011,84 @@ mod tests {
     assert!(result.is_err());
   }
 
+  async fn create_replaceable_worker(
+    temp_dir: &test_util::TempDir,
+  ) -> (CliMainWorker, ModuleSpecifier) {
+    let dep = temp_dir.path().join("dep.js");
+    dep.write("export function value() {\n  return 1;\n}\n");
+    let main = temp_dir.path().join("main.js");
+    main.write(
+      "import { value } from \"./dep.js\";\nglobalThis.readValue = value;\n",
+    );
+    let mut worker = crate::create_main_worker(&main.to_string_lossy())
+      .await
+      .unwrap();
+    worker.execute_main_module().await.unwrap();
+    (
+      worker,
+      ModuleSpecifier::from_file_path(dep.as_path()).unwrap(),
+    )
+  }
+
+  #[tokio::test]
+  async fn replace_module_updates_loaded_module() {
+    let temp_dir = test_util::TempDir::new();
+    let (mut worker, dep) = create_replaceable_worker(&temp_dir).await;
+    worker
+      .execute_script_static(
+        "before.js",
+        "if (globalThis.readValue() !== 1) throw new Error('unexpected');",
+      )
+      .unwrap();
+    worker
+      .replace_module(
+        &dep,
+        "export function value() {\n  return 2;\n}\n".to_string(),
+      )
+      .await
+      .unwrap();
+    worker
+      .execute_script_static(
+        "after.js",
+        "if (globalThis.readValue() !== 2) throw new Error('not replaced');",
+      )
+      .unwrap();
+  }
+
+  #[tokio::test]
+  async fn replace_module_errors() {
+    let temp_dir = test_util::TempDir::new();
+    let (mut worker, dep) = create_replaceable_worker(&temp_dir).await;
+    let err = worker
+      .replace_module(&dep, "export function value( {".to_string())
+      .await
+      .unwrap_err();
+    assert!(matches!(
+      err,
+      ReplaceModuleError::Rejected {
+        reason: HmrRejectReason::CompileError,
+        ..
+      }
+    ));
+
+    let not_loaded = ModuleSpecifier::from_file_path(
+      temp_dir.path().join("other.js").as_path(),
+    )
+    .unwrap();
+    let err = worker
+      .replace_module(&not_loaded, "export {};".to_string())
+      .await
+      .unwrap_err();
+    assert!(matches!(err, ReplaceModuleError::NotLoaded(_)));
+    // the module keeps its previous source
+    worker
+      .execute_script_static(
+        "after.js",
+        "if (globalThis.readValue() !== 1) throw new Error('replaced');",
+      )
+      .unwrap();
+  }
+
   #[tokio::test]
   async fn execute_mod_002_hello() {
     // This assumes cwd is project root (an assumption made throughout the